@@ -1,58 +1,242 @@
-use std::fs::File;
-use std::io::Write;
+use std::fmt;
 
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     get_argument_size_by_im, get_available_im_names, get_im_name, get_instruction_by_name,
-    microasm::COMMENT_IDENT, read_file, AssemblerError, SyntaxError, IM_CONSTANT, IM_IMPLIED,
+    isa::isa, microasm::COMMENT_IDENT, read_file, write_file, AssemblerError, InstructionMode,
+    SyntaxError, ADDRESS_SPACE_SIZE, DATA_END, DATA_START, IM_ABSOLUTE, IM_ACCUMULATOR,
+    IM_CONSTANT, IM_IMMEDIATE, IM_IMPLIED, IM_INDIRECT, IM_ZEROPAGE, ZERO_PAGE_SIZE,
+    ZERO_PAGE_START,
 };
 
-use super::{analyze_arg, parse_arg, Argument, Instruction, Label, MacroDef, Token, TokenizedLine};
+use super::cfg::{flow, instruction_addresses, Flow};
+use super::loader::{FileLoader, Includer, SourceLoader};
+use super::pseudo::{pseudo_instruction_by_name, PseudoInstruction};
+use super::stack::analyze_stack;
+use super::{
+    analyze_arg, char_literals, parse_arg, split_args, AddressPart, Argument, Instruction, Label,
+    LabelRef, MacroDef, Section, Token, TokenizedLine,
+};
 
-pub fn assembler(file_in: &str, file_out: &str) -> Result<(), AssemblerError> {
+/// Assembles a source file, files included by it are loaded relative to its directory.
+/// With `zero_page` set, operands in the zero page use the zero page mode where possible.
+/// Returns the assembled program with the warnings produced along the way.
+pub fn assembler(
+    file_in: &str,
+    file_out: &str,
+    zero_page: bool,
+) -> Result<AssembledProgram, AssemblerError> {
     let input = read_file(file_in)?;
-    // tokenize
-    let tokens = match tokenize(input) {
-        Ok(t) => t,
-        Err(e) => {
-            return Err(AssemblerError::new(
-                String::from("Assembly failed at tokenization step."),
-                Some(e),
-            ))
-        }
+    let options = AssembleOptions {
+        file_name: file_in.to_string(),
+        loader: Some(&FileLoader),
+        zero_page,
     };
+    let (instructions, labels, zero_page_saved) = parse_source(&input, &options)?;
+
+    // assemble
+    let mut program = build_program(&instructions, labels)?;
+    program.warnings = analyze_stack(&instructions, &program.symbols).warnings;
+    program.zero_page_saved = zero_page_saved;
+
+    // write to output file
+    write_file(file_out, &program.bytes)?;
+    Ok(program)
+}
+
+/// Options of an assembly from memory.
+pub struct AssembleOptions<'a> {
+    /// Name of the source, used by diagnostics and the source map. Includes are resolved relative to it.
+    pub file_name: String,
+    /// Loads the files included with `#include`. Including a file is an error without a loader.
+    pub loader: Option<&'a dyn SourceLoader>,
+    /// Switches operands whose address lies in the zero page from the absolute to the zero page mode.
+    pub zero_page: bool,
+}
+
+impl Default for AssembleOptions<'_> {
+    fn default() -> Self {
+        AssembleOptions {
+            file_name: String::from("<input>"),
+            loader: None,
+            zero_page: false,
+        }
+    }
+}
+
+/// Source location of the machine code of one instruction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceMapEntry {
+    pub address: u32,
+    /// Number of bytes the instruction takes.
+    pub size: u32,
+    pub file: String,
+    pub line: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub file: String,
+    /// Line in the file, 0 if the diagnostic is about the whole file.
+    pub line: u32,
+    pub message: String,
+}
 
-    // parse
-    let parsed = match parse(tokens) {
-        Ok(idf) => idf,
-        Err(e) => {
-            return Err(AssemblerError::new(
-                String::from("Assembly failed at parsing step"),
-                Some(e),
-            ))
+impl Diagnostic {
+    fn from_error(e: &AssemblerError, file_name: &str) -> Self {
+        // an included file which cannot be loaded
+        if let AssemblerError::Io { path, source } = e {
+            return Diagnostic {
+                file: path.clone(),
+                line: 0,
+                message: source.to_string(),
+            };
+        }
+        match e.syntax_error() {
+            Some(serr) => Diagnostic {
+                file: serr.file.clone().unwrap_or_else(|| file_name.to_string()),
+                line: serr.line,
+                message: serr.message.clone(),
+            },
+            None => Diagnostic {
+                file: file_name.to_string(),
+                line: 0,
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.file, self.message)
+        } else {
+            write!(f, "{}:{}: {}", self.file, self.line, self.message)
         }
+    }
+}
+
+/// Everything reported by a failed assembly.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diagnostics {
+    pub errors: Vec<Diagnostic>,
+    pub warnings: Vec<Diagnostic>,
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self.errors.iter().map(|e| e.to_string()).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AssembledProgram {
+    pub bytes: Vec<u8>,
+    /// All labels with their addresses.
+    pub symbols: Vec<Label>,
+    /// Source location of every instruction, ordered by address.
+    pub source_map: Vec<SourceMapEntry>,
+    pub warnings: Vec<Diagnostic>,
+    /// Bytes saved by switching operands to the zero page mode.
+    pub zero_page_saved: u32,
+}
+
+/// Assembles a program without touching the file system. Included files are provided by the loader in `options`.
+pub fn assemble_str(
+    source: &str,
+    options: &AssembleOptions,
+) -> Result<AssembledProgram, Diagnostics> {
+    parse_source(source, options)
+        .and_then(|(instructions, labels, zero_page_saved)| {
+            let mut program = build_program(&instructions, labels)?;
+            program.warnings = analyze_stack(&instructions, &program.symbols).warnings;
+            program.zero_page_saved = zero_page_saved;
+            Ok(program)
+        })
+        .map_err(|e| Diagnostics {
+            errors: vec![Diagnostic::from_error(&e, &options.file_name)],
+            warnings: Vec::new(),
+        })
+}
+
+/// Reads a source file and runs it through the tokenizer and the parser.
+pub(crate) fn parse_file(
+    file_in: &str,
+    zero_page: bool,
+) -> Result<(Vec<Instruction>, Vec<Label>), AssemblerError> {
+    let input = read_file(file_in)?;
+    let options = AssembleOptions {
+        file_name: file_in.to_string(),
+        loader: Some(&FileLoader),
+        zero_page,
     };
-    // println!("parsed {:?}", parsed);
+    let (instructions, labels, _) = parse_source(&input, &options)?;
+    Ok((instructions, labels))
+}
 
-    // assemble
-    let output = assemble(parsed);
+/// Runs the source through the tokenizer, resolving includes, and the parser.
+/// Also returns the bytes saved by the zero page mode.
+fn parse_source(
+    source: &str,
+    options: &AssembleOptions,
+) -> Result<(Vec<Instruction>, Vec<Label>, u32), AssemblerError> {
+    let mut module = parse_module(tokenize_source(source, options)?)?;
+    let zero_page_saved = match options.zero_page {
+        true => select_zero_page(&mut module),
+        false => 0,
+    };
 
-    // write to output file
-    let mut file = File::create(file_out).unwrap();
-    file.write_all(&output).unwrap();
-    Ok(())
+    let (instructions, labels) = resolve_labels(module)?;
+    Ok((instructions, labels, zero_page_saved))
 }
 
-fn tokenize(code: String) -> Result<Vec<TokenizedLine>, SyntaxError> {
+/// Runs the source through the tokenizer, resolving includes.
+pub(crate) fn tokenize_source(
+    source: &str,
+    options: &AssembleOptions,
+) -> Result<Vec<TokenizedLine>, AssemblerError> {
+    let file_name = &options.file_name;
+
+    // tokenize, replacing the includes with the tokens of the included files
+    let includer = Includer {
+        loader: options.loader,
+        libraries: true,
+        tokenize: &|code: &str, file: &str| tokenize(code, file).map_err(AssemblerError::Lex),
+        include: &|t: &TokenizedLine| match &t.1 {
+            Token::Marker(name, args) if name == "include" => Some((t.0, args.clone())),
+            _ => None,
+        },
+    };
+    let tokens = includer.resolve(source, file_name)?;
+    if tokens.is_empty() {
+        return Err(AssemblerError::Parse(SyntaxError::in_file(
+            file_name,
+            0,
+            String::from("No code was found."),
+        )));
+    }
+    Ok(tokens)
+}
+
+pub(crate) fn tokenize(code: &str, file: &str) -> Result<Vec<TokenizedLine>, SyntaxError> {
     let mut tokenized_lines: Vec<TokenizedLine> = Vec::new();
 
+    let label_re = Regex::new(r"[^a-zA-Z0-9_]").unwrap();
+
     for (line_idx, line) in code.lines().enumerate() {
         let real_line = line_idx as u32 + 1;
         let line = line.trim();
 
-        // check for comments and remove if
-        let comment_idx = line.chars().position(|c| c == COMMENT_IDENT);
+        // check for comments and remove if, character literals can contain the comment identifier
+        let comment_idx = line
+            .char_indices()
+            .zip(char_literals(line))
+            .find(|((_, c), in_literal)| *c == COMMENT_IDENT && !in_literal)
+            .map(|((idx, _), _)| idx);
         let line = if let Some(idx) = comment_idx {
             line[0..idx].trim().to_string()
         } else {
@@ -66,14 +250,15 @@ fn tokenize(code: String) -> Result<Vec<TokenizedLine>, SyntaxError> {
             .collect();
 
         // skip empty lines
-        if words.len() == 0 {
+        if words.is_empty() {
             continue;
         }
 
         // the line is a marker
-        let tokenized = if let Some('#') = line.chars().nth(0) {
+        let tokenized = if let Some('#') = line.chars().next() {
             if line.chars().count() == 1 {
-                return Err(SyntaxError::new(
+                return Err(SyntaxError::in_file(
+                    file,
                     real_line,
                     String::from("No keyword was specified."),
                 ));
@@ -82,12 +267,14 @@ fn tokenize(code: String) -> Result<Vec<TokenizedLine>, SyntaxError> {
             TokenizedLine(
                 real_line,
                 Token::Marker(words[0][1..].to_lowercase(), words[1..].to_vec()),
+                file.to_string(),
             )
         }
         // the line is a label
         else if let Some(':') = words.last().unwrap().chars().last() {
             if words.len() > 1 {
-                return Err(SyntaxError::new(
+                return Err(SyntaxError::in_file(
+                    file,
                     real_line,
                     String::from("Invalid label definition, a label can only be one word."),
                 ));
@@ -98,15 +285,16 @@ fn tokenize(code: String) -> Result<Vec<TokenizedLine>, SyntaxError> {
             label_name.pop();
 
             if label_name.chars().count() == 0 {
-                return Err(SyntaxError::new(
+                return Err(SyntaxError::in_file(
+                    file,
                     real_line,
                     String::from("Invalid label name, name cannot be empty."),
                 ));
             }
 
-            let re = Regex::new(r"[^a-zA-Z0-9_]").unwrap();
-            if re.is_match(&label_name) {
-                return Err(SyntaxError::new(
+            if label_re.is_match(&label_name) {
+                return Err(SyntaxError::in_file(
+                    file,
                     real_line,
                     format!("Invalid label name '{}'. Label name can only contain characters a-Z, numbers or the '_' symbol.", label_name),
                 ));
@@ -114,8 +302,9 @@ fn tokenize(code: String) -> Result<Vec<TokenizedLine>, SyntaxError> {
 
             // check if first char is not a number
             let first_char = label_name.chars().next().unwrap();
-            if first_char.is_digit(10) {
-                return Err(SyntaxError::new(
+            if first_char.is_ascii_digit() {
+                return Err(SyntaxError::in_file(
+                    file,
                     real_line,
                     format!(
                         "Invalid label name '{}', label name has to start with a letter.",
@@ -124,27 +313,41 @@ fn tokenize(code: String) -> Result<Vec<TokenizedLine>, SyntaxError> {
                 ));
             }
 
-            TokenizedLine(real_line, Token::Label(label_name))
+            TokenizedLine(real_line, Token::Label(label_name), file.to_string())
         }
         // the line is an instruction
         else {
             let args = if words.len() > 1 {
                 let arg_str = words[1..].join(" ");
-                let re = Regex::new(r"\s*,\s*").unwrap();
-                let args: Vec<String> = re
-                    .split(&arg_str)
-                    .map(|s| s.trim().to_lowercase())
+                // character literals keep their case
+                let args: Vec<String> = split_args(&arg_str)
+                    .iter()
+                    .map(|a| {
+                        a.chars()
+                            .zip(char_literals(a))
+                            .map(|(c, in_literal)| match in_literal {
+                                true => c,
+                                false => c.to_ascii_lowercase(),
+                            })
+                            .collect()
+                    })
                     .collect();
 
                 for a in &args {
                     if a.chars().count() == 0 {
-                        return Err(SyntaxError::new(
+                        return Err(SyntaxError::in_file(
+                            file,
                             real_line,
                             String::from("Invalid argument, argument cannot be emty."),
                         ));
                     }
-                    if a.contains(" ") {
-                        return Err(SyntaxError::new(
+                    let has_space = a
+                        .chars()
+                        .zip(char_literals(a))
+                        .any(|(c, in_literal)| c == ' ' && !in_literal);
+                    if has_space {
+                        return Err(SyntaxError::in_file(
+								file,
 								real_line,
 								format!("Invalid argument '{}', arguments can only be one word. If you want to specify multiple arguments separate them by a comma.", a),
 							));
@@ -155,60 +358,161 @@ fn tokenize(code: String) -> Result<Vec<TokenizedLine>, SyntaxError> {
                 Vec::new()
             };
 
-            TokenizedLine(real_line, Token::Instruction(words[0].to_owned(), args))
+            TokenizedLine(
+                real_line,
+                Token::Instruction(words[0].to_owned(), args),
+                file.to_string(),
+            )
         };
 
         tokenized_lines.push(tokenized);
     }
 
-    if tokenized_lines.len() == 0 {
-        return Err(SyntaxError::new(0, String::from("No code was found.")));
-    }
-
     Ok(tokenized_lines)
 }
 
-pub fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<Instruction>, SyntaxError> {
+/// A parsed program whose labels are not resolved yet.
+pub(crate) struct Module {
+    pub instructions: Vec<Instruction>,
+    /// Labels of both sections, data labels are placed at `DATA_START`.
+    pub labels: Vec<Label>,
+    /// Instructions using labels: (index, line, file).
+    pub references: Vec<(usize, u32, String)>,
+    /// Labels exported with `#export`.
+    pub exports: Vec<String>,
+    /// Labels imported with `#import`, they are defined by other modules.
+    pub imports: Vec<String>,
+    /// Number of bytes reserved in the data section.
+    pub data_size: u32,
+    /// Number of bytes allocated in the zero page.
+    pub zero_page_size: u32,
+}
+
+/// Takes the tokens produced by the tokenizer, expands macros and resolves labels.
+/// Returns the instructions together with all the defined labels.
+pub fn parse(tokens: Vec<TokenizedLine>) -> Result<(Vec<Instruction>, Vec<Label>), AssemblerError> {
+    resolve_labels(parse_module(tokens)?)
+}
+
+/// Replaces the labels used by the instructions of a module with their values.
+fn resolve_labels(mut module: Module) -> Result<(Vec<Instruction>, Vec<Label>), AssemblerError> {
+    for (idx, line, file) in &module.references {
+        let ins = module.instructions.get_mut(*idx).unwrap();
+
+        if let Some(Argument::Label(label_ref)) = &ins.argument {
+            let label = module.labels.iter().find(|&l| l.name == label_ref.name);
+
+            if label.is_none() && module.imports.contains(&label_ref.name) {
+                return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                    file,
+                    *line,
+                    format!("Label '{}' is imported, the module has to be assembled with the 'object' subcommand and linked with tower-link.", label_ref.name),
+                )));
+            }
+            if label.is_none() {
+                return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                    file,
+                    *line,
+                    format!("Label '{}' is not defined.", label_ref.name),
+                )));
+            }
+
+            ins.argument = Some(Argument::Explicit(label_ref.value(label.unwrap().address)));
+        }
+    }
+    Ok((module.instructions, module.labels))
+}
+
+/// Takes the tokens produced by the tokenizer and expands macros, leaving the labels used by instructions unresolved.
+pub(crate) fn parse_module(tokens: Vec<TokenizedLine>) -> Result<Module, AssemblerError> {
     let mut instructions: Vec<Instruction> = Vec::new();
     let mut macros: Vec<MacroDef> = Vec::new();
     let mut is_defining_macro = false;
     let mut current_macro: Option<MacroDef> = None;
 
     let mut labels: Vec<Label> = Vec::new();
-    let mut instructions_using_labels: Vec<(usize, u32)> = Vec::new();
+    let mut instructions_using_labels: Vec<(usize, u32, String)> = Vec::new();
 
     let mut current_address = 0;
 
+    let mut section = Section::Code;
+    let mut data_size = 0;
+    let mut zero_page_size = 0;
+    // names allocated with `#zp` and `#var`
+    let mut variables: Vec<String> = Vec::new();
+    let mut exports: Vec<(String, u32, String)> = Vec::new();
+    let mut imports: Vec<(String, u32, String)> = Vec::new();
+    // arguments written like a register without the '%', only an error if no label has their name
+    let mut bare_registers: Vec<(String, u32, String)> = Vec::new();
+
+    let label_re = Regex::new(r"[^a-zA-Z0-9_]").unwrap();
+
     // TODO: move argument parsing into tokenizer
     for t in tokens {
-        let (real_line, token) = (t.0, t.1);
+        let (real_line, token, file) = (t.0, t.1, t.2);
 
         match token {
             Token::Instruction(name, args) => {
+                if section == Section::Data && !is_defining_macro {
+                    return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                        &file,
+                        real_line,
+                        format!("Instruction '{}' cannot be placed in the data section, it only reserves memory.", name),
+                    )));
+                }
+
                 // parse raw args to nice structures
                 let mut parsed_args = Vec::new();
 
                 for arg in &args {
                     let parsed_arg = match parse_arg(arg) {
                         Ok(arg) => arg,
-                        Err(e) => return Err(SyntaxError::new(real_line, e)),
+                        Err(e) => {
+                            return Err(AssemblerError::Parse(SyntaxError::in_file(
+                                &file, real_line, e,
+                            )))
+                        }
                     };
 
-                    let im = if let Some(Argument::Label(name)) = &parsed_arg {
-                        instructions_using_labels.push((instructions.len(), real_line));
+                    let im = if let Some(Argument::Label(label)) = &parsed_arg {
+                        instructions_using_labels.push((
+                            instructions.len(),
+                            real_line,
+                            file.clone(),
+                        ));
 
-                        let re = Regex::new(r"[^a-zA-Z0-9_]").unwrap();
-                        if re.is_match(&name) {
-                            return Err(SyntaxError::new(
+                        if label_re.is_match(&label.name) {
+                            return Err(AssemblerError::Parse(SyntaxError::in_file(
+								&file,
 								real_line,
-								format!("Invalid label name '{}'. Label name can only contain characters a-Z, numbers or the '_' symbol.", name),
-							));
+								format!("Invalid label name '{}'. Label name can only contain characters a-Z, numbers or the '_' symbol.", label.name),
+							)));
+                        }
+
+                        // the mode of unprefixed labels is inferred once the instruction is known
+                        match analyze_arg(arg) {
+                            Ok(IM_IMMEDIATE) if label.part == AddressPart::Full => {
+                                return Err(AssemblerError::Parse(SyntaxError::in_file(
+                                    &file,
+                                    real_line,
+                                    format!("Label '{}' does not fit into an immediate value, use '#<{}' or '#>{}' for its low or high byte.", label.name, label.name, label.name),
+                                )))
+                            }
+                            Ok(im) => im,
+                            Err(e) => {
+                                return Err(AssemblerError::Parse(SyntaxError::in_file(
+                                    &file, real_line, e,
+                                )))
+                            }
                         }
-                        IM_CONSTANT
                     } else {
                         match analyze_arg(arg) {
                             Ok(im) => im,
-                            Err(e) => return Err(SyntaxError::new(real_line, e)),
+                            Err(e) => {
+                                return Err(AssemblerError::Parse(SyntaxError::in_file(
+                                    &file, real_line, e,
+                                )))
+                            }
                         }
                     };
 
@@ -232,31 +536,36 @@ pub fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<Instruction>, SyntaxError
                         }
                     }
                 } else {
-                    let found_placeholder = parsed_args
-                        .iter()
-                        .find(|&a| matches!(a, (_, Some(Argument::Implicit(arg_idx)))));
-
-                    if found_placeholder.is_some() {
-                        if let Argument::Implicit(arg_idx) =
-                            found_placeholder.as_ref().unwrap().1.as_ref().unwrap()
-                        {
-                            return Err(SyntaxError::new(
-                                real_line,
-                                format!("Wrong usage of argument placeholder '${}'. Argument placeholders can only be used inside a macro.", arg_idx),
-                            ));
-                        }
+                    let found_placeholder = parsed_args.iter().find_map(|a| match a {
+                        (_, Some(Argument::Implicit(arg_idx))) => Some(arg_idx),
+                        _ => None,
+                    });
+
+                    if let Some(arg_idx) = found_placeholder {
+                        return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                            &file,
+                            real_line,
+                            format!("Wrong usage of argument placeholder '${}'. Argument placeholders can only be used inside a macro.", arg_idx),
+                        )));
                     }
                 }
 
-                let ins = get_instruction_by_name(&name);
+                // pseudo-instructions are checked like instructions and expanded when they are placed,
+                // macros with the same name take precedence
+                let pseudo = pseudo_instruction_by_name(&name)
+                    .filter(|_| !macros.iter().any(|m| m.name == name));
+                let available_modes = get_instruction_by_name(&name)
+                    .map(|ins| ins.2)
+                    .or(pseudo.map(|p| p.modes));
 
                 // check if this instruction exists
-                if ins.is_some() {
+                if let Some(available_modes_val) = available_modes {
                     if args.len() > 1 {
-                        return Err(SyntaxError::new(
+                        return Err(AssemblerError::Parse(SyntaxError::in_file(
+                            &file,
                             real_line,
                             String::from("Instructions can only have one argument."),
-                        ));
+                        )));
                     }
 
                     let (instruction_mode, argument) = if args.len() == 1 {
@@ -265,10 +574,43 @@ pub fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<Instruction>, SyntaxError
                         (IM_IMPLIED, None)
                     };
 
+                    if let (0, Some(Argument::Label(label))) = (instruction_mode, &argument) {
+                        if is_bare_register(&label.name)
+                            && available_modes_val & IM_ACCUMULATOR != 0
+                        {
+                            bare_registers.push((label.name.clone(), real_line, file.clone()));
+                        }
+                    }
+
+                    let (instruction_mode, argument) = match argument {
+                        Some(arg @ (Argument::Explicit(_) | Argument::Label(_)))
+                            if instruction_mode == 0 =>
+                        {
+                            match infer_mode(arg, available_modes_val, &labels) {
+                                Some((im, arg)) => (im, Some(arg)),
+                                None => {
+                                    return Err(AssemblerError::Parse(SyntaxError::in_file(
+                                        &file,
+                                        real_line,
+                                        ambiguous_mode_message(
+                                            &args[0],
+                                            &name,
+                                            available_modes_val,
+                                        ),
+                                    )))
+                                }
+                            }
+                        }
+                        argument => (instruction_mode, argument),
+                    };
+
                     let new_instruction = Instruction {
                         name: name.clone(),
                         argument: argument.clone(),
-                        instruction_mode: instruction_mode.clone(),
+                        instruction_mode,
+                        line: real_line,
+                        file: file.clone(),
+                        pseudo: None,
                     };
 
                     if is_defining_macro {
@@ -290,59 +632,121 @@ pub fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<Instruction>, SyntaxError
                             .instructions
                             .push((new_instruction, real_line, Vec::new()));
                     } else {
-                        if instruction_mode == 0 {
-                            return Err(SyntaxError::new(
-								real_line,
-								format!("No mode identifier specified for argument '{}' of instruction '{}'.", args[0], name),
-							));
-                        } else {
-                            let available_modes_val = ins.unwrap().2;
-                            if (available_modes_val & instruction_mode) == 0 {
-                                let available_modes = get_available_im_names(available_modes_val);
-                                let this_mode =
-                                    get_im_name((instruction_mode as f32).log2() as u32).unwrap();
-                                return Err(SyntaxError::new(
-									real_line,
-									format!("Instruction '{}' cannot take an argument in '{}' instruction mode. Available modes are: {}", name, this_mode, available_modes.join(","))
-								));
-                            }
+                        if (available_modes_val & instruction_mode) == 0 {
+                            return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                                &file,
+                                real_line,
+                                unavailable_mode_message(
+                                    &name,
+                                    instruction_mode,
+                                    available_modes_val,
+                                ),
+                            )));
                         }
 
-                        instructions.push(new_instruction);
-                        current_address += 1 + get_argument_size_by_im(instruction_mode);
+                        if let Some(pseudo) = pseudo {
+                            for ins in expand_pseudo(pseudo, &new_instruction, current_address)? {
+                                if let Some(Argument::Label(_)) = &ins.argument {
+                                    instructions_using_labels.push((
+                                        instructions.len(),
+                                        real_line,
+                                        file.clone(),
+                                    ));
+                                }
+                                current_address +=
+                                    1 + get_argument_size_by_im(ins.instruction_mode);
+                                instructions.push(ins);
+                            }
+                        } else {
+                            instructions.push(new_instruction);
+                            current_address += 1 + get_argument_size_by_im(instruction_mode);
+                        }
                     }
                 } else {
                     let macro_def = macros.iter_mut().find(|m| m.name == name);
 
                     if let Some(macro_def) = macro_def {
                         if args.len() != macro_def.args.len() {
-                            return Err(SyntaxError::new(
+                            return Err(AssemblerError::Semantic(SyntaxError::in_file(
+							    &file,
 							    real_line,
 							    format!("Wrong number of arguments for macro '{}'. This macro requires {} arguments.", macro_def.name, macro_def.args.len()),
-							));
+							)));
                         }
 
                         let mut new_instructions = Vec::new();
                         for ins in &macro_def.instructions {
                             let analyzed = if let Some(arg) = ins.0.argument.clone() {
-                                let analyzed = if let Argument::Implicit(idx) = arg {
+                                if let Argument::Implicit(idx) = arg {
                                     let upstream_arg = &args[idx as usize - 1];
                                     let im = match analyze_arg(upstream_arg) {
                                         Ok(im) => im,
-                                        Err(e) => return Err(SyntaxError::new(real_line, e)),
+                                        Err(e) => {
+                                            return Err(AssemblerError::Parse(
+                                                SyntaxError::in_file(&file, real_line, e),
+                                            ))
+                                        }
                                     };
 
                                     let argument = match parse_arg(upstream_arg) {
                                         Ok(arg) => arg,
-                                        Err(e) => return Err(SyntaxError::new(real_line, e)),
+                                        Err(e) => {
+                                            return Err(AssemblerError::Parse(
+                                                SyntaxError::in_file(&file, real_line, e),
+                                            ))
+                                        }
                                     };
 
-                                    (im, argument)
+                                    // unprefixed arguments take the mode from the instruction using them
+                                    let modes = get_instruction_by_name(&ins.0.name)
+                                        .map(|i| i.2)
+                                        .or(pseudo_instruction_by_name(&ins.0.name)
+                                            .map(|p| p.modes))
+                                        .unwrap_or(0);
+                                    if let (0, Some(Argument::Label(label))) = (im, &argument) {
+                                        if is_bare_register(&label.name)
+                                            && modes & IM_ACCUMULATOR != 0
+                                        {
+                                            bare_registers.push((
+                                                label.name.clone(),
+                                                real_line,
+                                                file.clone(),
+                                            ));
+                                        }
+                                    }
+                                    match argument {
+                                        Some(
+                                            arg @ (Argument::Explicit(_) | Argument::Label(_)),
+                                        ) if im == 0 && ins.0.instruction_mode == 0 => {
+                                            match infer_mode(arg, modes, &labels) {
+                                                Some((im, arg)) => (im, Some(arg)),
+                                                None => {
+                                                    let mut trace = ins.2.clone();
+                                                    trace.push(macro_def.name.clone());
+                                                    trace.reverse();
+                                                    return Err(AssemblerError::Parse(
+                                                        SyntaxError::in_file(
+                                                            &file,
+                                                            real_line,
+                                                            format!(
+                                                                "{} (macro trace: {})",
+                                                                ambiguous_mode_message(
+                                                                    upstream_arg,
+                                                                    &ins.0.name,
+                                                                    modes
+                                                                ),
+                                                                trace.join("->")
+                                                            ),
+                                                        ),
+                                                    ));
+                                                }
+                                            }
+                                        }
+                                        argument => (im, argument),
+                                    }
                                 } else {
                                     (ins.0.instruction_mode, Some(arg))
-                                };
-
-                                analyzed
+                                }
                             } else {
                                 (IM_IMPLIED, None)
                             };
@@ -352,28 +756,33 @@ pub fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<Instruction>, SyntaxError
                             new_instruction.2.push(macro_def.name.clone());
                             new_instruction.0.argument = analyzed.1;
 
-                            if !is_defining_macro {
-                                if new_instruction.0.instruction_mode == 0 && analyzed.0 == 0 {
-                                    let mut trace = new_instruction.2.clone();
-                                    trace.reverse();
-                                    return Err(SyntaxError::new(
-                                        real_line,
-                                        format!(
-											"No mode identifier specified for '{}' on line {}. (macro trace: {})",
-											new_instruction.0.name,
-											new_instruction.1,
-											trace.join("->")
-										),
-                                    ));
-                                }
-
-                                current_address += 1 + get_argument_size_by_im(analyzed.0);
-                            }
                             if new_instruction.0.instruction_mode == 0 {
                                 new_instruction.0.instruction_mode = analyzed.0;
                             }
-
-                            new_instructions.push(new_instruction);
+                            new_instruction.0.line = real_line;
+                            new_instruction.0.file = file.clone();
+
+                            match pseudo_instruction_by_name(&new_instruction.0.name) {
+                                Some(pseudo) if !is_defining_macro => {
+                                    for ins in
+                                        expand_pseudo(pseudo, &new_instruction.0, current_address)?
+                                    {
+                                        current_address +=
+                                            1 + get_argument_size_by_im(ins.instruction_mode);
+                                        new_instructions.push((
+                                            ins,
+                                            new_instruction.1,
+                                            new_instruction.2.clone(),
+                                        ));
+                                    }
+                                }
+                                _ => {
+                                    if !is_defining_macro {
+                                        current_address += 1 + get_argument_size_by_im(analyzed.0);
+                                    }
+                                    new_instructions.push(new_instruction);
+                                }
+                            }
                         }
 
                         if is_defining_macro {
@@ -391,15 +800,23 @@ pub fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<Instruction>, SyntaxError
                                 }
                             }
                         } else {
-                            let only_instructions: Vec<Instruction> =
-                                new_instructions.iter().map(|i| i.0.clone()).collect();
-                            instructions.extend(only_instructions);
+                            for (ins, _, _) in new_instructions {
+                                if let Some(Argument::Label(_)) = &ins.argument {
+                                    instructions_using_labels.push((
+                                        instructions.len(),
+                                        real_line,
+                                        file.clone(),
+                                    ));
+                                }
+                                instructions.push(ins);
+                            }
                         }
                     } else {
-                        return Err(SyntaxError::new(
+                        return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                            &file,
                             real_line,
                             format!("Unknown instruction '{}'.", name),
-                        ));
+                        )));
                     }
                 }
             }
@@ -408,41 +825,51 @@ pub fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<Instruction>, SyntaxError
                 let exists = labels.iter().find(|&l| l.name == name);
 
                 if exists.is_some() {
-                    return Err(SyntaxError::new(
+                    return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                        &file,
                         real_line,
                         format!("Label with name '{}' already exists.", name),
-                    ));
+                    )));
                 }
 
+                let address = match section {
+                    Section::Code => current_address,
+                    Section::Data => DATA_START as u32 + data_size,
+                    Section::ZeroPage => ZERO_PAGE_START as u32 + zero_page_size,
+                };
                 let new_label = Label {
                     name,
-                    address: current_address,
+                    address,
+                    section,
                 };
                 labels.push(new_label);
             }
             Token::Marker(name, args) => match name.as_ref() {
                 "macro" => {
                     if is_defining_macro {
-                        return Err(SyntaxError::new(
+                        return Err(AssemblerError::Parse(SyntaxError::in_file(
+                            &file,
                             real_line,
                             String::from("Invalid placement of macro marker."),
-                        ));
+                        )));
                     }
 
-                    if args.len() == 0 || args[0].chars().count() == 0 {
-                        return Err(SyntaxError::new(
+                    if args.is_empty() || args[0].chars().count() == 0 {
+                        return Err(AssemblerError::Parse(SyntaxError::in_file(
+                            &file,
                             real_line,
                             String::from("Missing macro name."),
-                        ));
+                        )));
                     }
                     if args.len() > 1 {
-                        return Err(SyntaxError::new(
+                        return Err(AssemblerError::Parse(SyntaxError::in_file(
+                            &file,
                             real_line,
                             format!(
                                 "Invalid macro name '{}', it has to be one word.",
                                 args[0..].join(" ")
                             ),
-                        ));
+                        )));
                     }
 
                     let name = args[0].to_owned();
@@ -458,87 +885,618 @@ pub fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<Instruction>, SyntaxError
                 "end" => {
                     if let Some(current_macro) = &current_macro {
                         let mut ordered = current_macro.args.clone();
-                        ordered.sort_by(|a, b| a.cmp(&b));
+                        ordered.sort();
 
-                        let mut prev_idx = 0;
-                        for o in ordered {
-                            if o != prev_idx + 1 {
-                                return Err(SyntaxError::new(
+                        for (prev_idx, o) in ordered.into_iter().enumerate() {
+                            if o != prev_idx as u32 + 1 {
+                                return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                                    &file,
                                     real_line,
                                     format!(
                                         "Invalid argument index '{}'. Argument indexes have to be in order.",
                                         o
                                     ),
-                                ));
+                                )));
                             }
-                            prev_idx += 1;
                         }
 
                         macros.push(current_macro.clone());
                         is_defining_macro = false;
                     } else {
-                        return Err(SyntaxError::new(
+                        return Err(AssemblerError::Parse(SyntaxError::in_file(
+                            &file,
                             real_line,
                             String::from("Invalid usage of '#end', there is no scope to be ended."),
-                        ));
+                        )));
                     }
                 }
                 "include" => {}
+                "section" => {
+                    section = match args.join(" ").to_lowercase().as_str() {
+                        "code" => Section::Code,
+                        "data" => Section::Data,
+                        other => {
+                            return Err(AssemblerError::Parse(SyntaxError::in_file(
+                                &file,
+                                real_line,
+                                format!(
+                                    "Invalid section '{}', sections are 'code' and 'data'.",
+                                    other
+                                ),
+                            )))
+                        }
+                    };
+                }
+                "reserve" => {
+                    if section != Section::Data {
+                        return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                            &file,
+                            real_line,
+                            String::from("Memory can only be reserved in the data section, switch to it with '#section data'."),
+                        )));
+                    }
+
+                    let size = match args.as_slice() {
+                        [size] => match parse_arg(size) {
+                            Ok(Some(Argument::Explicit(size)))
+                                if size <= ADDRESS_SPACE_SIZE as u32 =>
+                            {
+                                size
+                            }
+                            _ => {
+                                return Err(AssemblerError::Parse(SyntaxError::in_file(
+                                    &file,
+                                    real_line,
+                                    format!("Invalid size '{}', it has to be a number.", size),
+                                )))
+                            }
+                        },
+                        _ => {
+                            return Err(AssemblerError::Parse(SyntaxError::in_file(
+                                &file,
+                                real_line,
+                                String::from("'#reserve' takes the number of bytes to reserve."),
+                            )))
+                        }
+                    };
+
+                    data_size += size;
+                    if DATA_START as u32 + data_size > DATA_END as u32 {
+                        return Err(AssemblerError::Range(SyntaxError::in_file(
+                            &file,
+                            real_line,
+                            format!(
+                                "The data section does not fit into the RAM (0x{:04x}-0x{:04x}).",
+                                DATA_START,
+                                DATA_END - 1
+                            ),
+                        )));
+                    }
+                }
+                "zp" | "var" => {
+                    let args = args.join(" ");
+                    let args: Vec<&str> = args
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .filter(|a| !a.is_empty())
+                        .collect();
+
+                    let (var_name, size) = match args.as_slice() {
+                        [var_name] => (var_name.to_lowercase(), 1),
+                        [var_name, size] => match parse_arg(size) {
+                            Ok(Some(Argument::Explicit(size)))
+                                if size > 0 && size <= ADDRESS_SPACE_SIZE as u32 =>
+                            {
+                                (var_name.to_lowercase(), size)
+                            }
+                            _ => {
+                                return Err(AssemblerError::Parse(SyntaxError::in_file(
+                                    &file,
+                                    real_line,
+                                    format!("Invalid size '{}', it has to be a number of bytes greater than 0.", size),
+                                )))
+                            }
+                        },
+                        _ => {
+                            return Err(AssemblerError::Parse(SyntaxError::in_file(
+                                &file,
+                                real_line,
+                                format!("'#{}' takes the name of the variable and its size in bytes, e.g. '#{} counter 1'.", name, name),
+                            )))
+                        }
+                    };
+
+                    if label_re.is_match(&var_name) {
+                        return Err(AssemblerError::Parse(SyntaxError::in_file(
+                            &file,
+                            real_line,
+                            format!("Invalid variable name '{}'. Variable name can only contain characters a-Z, numbers or the '_' symbol.", var_name),
+                        )));
+                    }
+                    if labels.iter().any(|l| l.name == var_name) {
+                        return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                            &file,
+                            real_line,
+                            format!("Label with name '{}' already exists.", var_name),
+                        )));
+                    }
+
+                    // the mode of an operand depends on the variable, so it has to be known first
+                    let is_used = instructions_using_labels.iter().any(|(idx, _, _)| {
+                        matches!(&instructions[*idx].argument, Some(Argument::Label(l)) if l.name == var_name)
+                    });
+                    if is_used {
+                        return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                            &file,
+                            real_line,
+                            format!("Variable '{}' is used before it is declared, variables have to be declared before they are used.", var_name),
+                        )));
+                    }
+
+                    let (var_section, address) = match name.as_ref() {
+                        "zp" => {
+                            zero_page_size += size;
+                            if zero_page_size > ZERO_PAGE_SIZE as u32 {
+                                return Err(AssemblerError::Range(SyntaxError::in_file(
+                                    &file,
+                                    real_line,
+                                    format!(
+                                        "Variable '{}' does not fit into the zero page (0x{:04x}-0x{:04x}), {} of its {} bytes are left.",
+                                        var_name,
+                                        ZERO_PAGE_START,
+                                        ZERO_PAGE_START + ZERO_PAGE_SIZE - 1,
+                                        ZERO_PAGE_SIZE as u32 + size - zero_page_size,
+                                        ZERO_PAGE_SIZE
+                                    ),
+                                )));
+                            }
+                            (
+                                Section::ZeroPage,
+                                ZERO_PAGE_START as u32 + zero_page_size - size,
+                            )
+                        }
+                        _ => {
+                            data_size += size;
+                            if DATA_START as u32 + data_size > DATA_END as u32 {
+                                return Err(AssemblerError::Range(SyntaxError::in_file(
+                                    &file,
+                                    real_line,
+                                    format!(
+                                        "Variable '{}' does not fit into the RAM (0x{:04x}-0x{:04x}).",
+                                        var_name,
+                                        DATA_START,
+                                        DATA_END - 1
+                                    ),
+                                )));
+                            }
+                            (Section::Data, DATA_START as u32 + data_size - size)
+                        }
+                    };
+
+                    variables.push(var_name.clone());
+                    labels.push(Label {
+                        name: var_name,
+                        address,
+                        section: var_section,
+                    });
+                }
+                "export" | "import" => {
+                    let names = args.join(" ");
+                    let names = names
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .filter(|n| !n.is_empty());
+
+                    let mut is_empty = true;
+                    for n in names {
+                        is_empty = false;
+                        if label_re.is_match(n) {
+                            return Err(AssemblerError::Parse(SyntaxError::in_file(
+                                &file,
+                                real_line,
+                                format!("Invalid label name '{}'. Label name can only contain characters a-Z, numbers or the '_' symbol.", n),
+                            )));
+                        }
+
+                        let entry = (n.to_string(), real_line, file.clone());
+                        match name.as_ref() {
+                            "export" => exports.push(entry),
+                            _ => imports.push(entry),
+                        }
+                    }
+
+                    if is_empty {
+                        return Err(AssemblerError::Parse(SyntaxError::in_file(
+                            &file,
+                            real_line,
+                            format!("Missing label names after '#{}'.", name),
+                        )));
+                    }
+                }
                 _ => {
-                    return Err(SyntaxError::new(
+                    return Err(AssemblerError::Parse(SyntaxError::in_file(
+                        &file,
                         real_line,
                         format!("Invalid keyword '{}'.", name),
-                    ));
+                    )));
                 }
             },
         }
     }
 
-    for (idx, line) in instructions_using_labels {
-        let ins = instructions.get_mut(idx).unwrap();
+    for (name, line, file) in &exports {
+        if !labels.iter().any(|l| l.name == *name) {
+            return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                file,
+                *line,
+                format!("Exported label '{}' is not defined.", name),
+            )));
+        }
+    }
+    for (name, line, file) in &bare_registers {
+        if !labels.iter().any(|l| l.name == *name) && !imports.iter().any(|i| i.0 == *name) {
+            return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                file,
+                *line,
+                bare_register_message(name),
+            )));
+        }
+    }
+    for (name, line, file) in &imports {
+        if labels.iter().any(|l| l.name == *name) {
+            return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                file,
+                *line,
+                format!(
+                    "Label '{}' is imported, but it is also defined in this module.",
+                    name
+                ),
+            )));
+        }
+    }
 
-        if let Some(Argument::Label(name)) = &ins.argument {
-            let label = labels.iter().find(|&l| l.name == *name);
+    Ok(Module {
+        instructions,
+        labels,
+        references: instructions_using_labels,
+        exports: exports.into_iter().map(|e| e.0).collect(),
+        imports: imports.into_iter().map(|i| i.0).collect(),
+        data_size,
+        zero_page_size,
+    })
+}
 
-            if label.is_none() {
-                return Err(SyntaxError::new(
-                    line,
-                    format!("Label '{}' is not defined.", name),
-                ));
-            }
+/// Picks the mode of an argument written without a mode identifier from the modes the instruction allows.
+/// The first rule which applies is used:
+/// 1. labels of zero page variables use the zero page mode with the low byte of their address,
+/// 2. the constant mode, so jumps go to the address and stores write to it,
+/// 3. labels use the absolute mode, so data is read from the address of the label,
+/// 4. the only mode of the instruction which takes an argument.
+///
+/// Returns `None` if the mode cannot be inferred.
+fn infer_mode(
+    argument: Argument,
+    modes: InstructionMode,
+    labels: &[Label],
+) -> Option<(InstructionMode, Argument)> {
+    let label = match &argument {
+        Argument::Label(label) => Some(label),
+        _ => None,
+    };
+    let is_zero_page = label.is_some_and(|label| {
+        labels
+            .iter()
+            .any(|l| l.name == label.name && l.section == Section::ZeroPage)
+    });
+
+    if is_zero_page && modes & IM_ZEROPAGE != 0 {
+        // the zero page starts at a page boundary, so the offset is the low byte of the address
+        let label = LabelRef {
+            part: AddressPart::Low,
+            ..label.unwrap().clone()
+        };
+        return Some((IM_ZEROPAGE, Argument::Label(label)));
+    }
+    if modes & IM_CONSTANT != 0 {
+        return Some((IM_CONSTANT, argument));
+    }
+    if label.is_some() && modes & IM_ABSOLUTE != 0 {
+        return Some((IM_ABSOLUTE, argument));
+    }
 
-            ins.argument = Some(Argument::Explicit(label.unwrap().address));
-        }
+    let argument_modes = modes & !(IM_IMPLIED | IM_ACCUMULATOR);
+    match argument_modes.count_ones() {
+        1 => Some((argument_modes, argument)),
+        _ => None,
     }
-    Ok(instructions)
 }
 
-/// Takes a vector of instructions and converts them to a vector of bytes which can be executed by the Tower architecture
-fn assemble(instructions: Vec<Instruction>) -> Vec<u8> {
-    let mut raw_bytes: Vec<u8> = Vec::new();
+/// Whether a label name is also the name of a register, written without the '%'.
+fn is_bare_register(name: &str) -> bool {
+    name.eq_ignore_ascii_case("a") || name.eq_ignore_ascii_case("b")
+}
 
-    for ins in instructions {
-        let instruction = get_instruction_by_name(&ins.name).unwrap();
-        let opcode = instruction.0;
-        // convert to 0-7
-        let im = (ins.instruction_mode as f32).log2() as u32;
+/// Explains that an argument which is not a label was probably meant to be a register.
+fn bare_register_message(name: &str) -> String {
+    if name.eq_ignore_ascii_case("a") {
+        return format!(
+            "Label '{}' is not defined. Registers are prefixed with '%', use '%A' for register A.",
+            name
+        );
+    }
+    format!(
+        "Label '{}' is not defined. Registers are prefixed with '%', but only register A ('%A') can be used as an argument.",
+        name
+    )
+}
+
+/// Explains that an instruction cannot take its argument in the mode it was given in.
+fn unavailable_mode_message(name: &str, im: InstructionMode, modes: InstructionMode) -> String {
+    let available_modes = get_available_im_names(modes).join(",");
+    if im == IM_ACCUMULATOR {
+        return format!(
+            "Instruction '{}' cannot take register A as its argument. Available modes are: {}",
+            name, available_modes
+        );
+    }
+    format!(
+        "Instruction '{}' cannot take an argument in '{}' instruction mode. Available modes are: {}",
+        name,
+        get_im_name(im.trailing_zeros()).unwrap_or("unknown"),
+        available_modes
+    )
+}
+
+/// Explains why the mode of an argument without a mode identifier cannot be inferred.
+fn ambiguous_mode_message(arg: &str, name: &str, modes: InstructionMode) -> String {
+    let identifiers: Vec<String> = [
+        (IM_IMMEDIATE, '#'),
+        (IM_ABSOLUTE, '*'),
+        (IM_INDIRECT, '@'),
+        (IM_CONSTANT, '&'),
+    ]
+    .iter()
+    .filter(|(im, _)| modes & im != 0)
+    .map(|(im, prefix)| {
+        let mode = get_im_name(im.trailing_zeros()).unwrap_or("unknown");
+        format!("'{}{}' ({})", prefix, arg, mode)
+    })
+    .collect();
+
+    if identifiers.is_empty() {
+        return format!("Instruction '{}' does not take an argument.", name);
+    }
+    format!(
+        "Cannot infer the mode of argument '{}' of instruction '{}', add a mode identifier: {}.",
+        arg,
+        name,
+        identifiers.join(", ")
+    )
+}
 
-        let instruction_byte = ((opcode << 3) | im) as u8;
-        raw_bytes.push(instruction_byte);
+/// Switches operands of instructions which have both the absolute and the zero page mode to the zero page mode
+/// if their address lies in the zero page. The shorter instructions move the code labels, so the labels are laid
+/// out again until no more operands change. Returns the number of bytes saved.
+pub(crate) fn select_zero_page(module: &mut Module) -> u32 {
+    let zero_page = ZERO_PAGE_START as u32..(ZERO_PAGE_START + ZERO_PAGE_SIZE) as u32;
+    let mut saved = 0;
+
+    loop {
+        let old_layout = layout(&module.instructions);
+        let mut changed = false;
+
+        for ins in module.instructions.iter_mut() {
+            let has_zero_page = get_instruction_by_name(&ins.name)
+                .is_some_and(|native| native.2 & IM_ZEROPAGE != 0);
+            if ins.instruction_mode != IM_ABSOLUTE || !has_zero_page {
+                continue;
+            }
 
-        if let Some(arg) = ins.argument {
-            if let Argument::Explicit(arg_val) = arg {
-                let size = get_argument_size_by_im(ins.instruction_mode);
-                if size == 0 {
-                    continue;
+            let argument = match &ins.argument {
+                Some(Argument::Explicit(address)) if zero_page.contains(address) => {
+                    Argument::Explicit(address - ZERO_PAGE_START as u32)
                 }
+                // labels of other modules are not known yet
+                Some(Argument::Label(label)) if label.part == AddressPart::Full => {
+                    match module.labels.iter().find(|l| l.name == label.name) {
+                        Some(l) if zero_page.contains(&label.value(l.address)) => {
+                            Argument::Label(LabelRef {
+                                part: AddressPart::Low,
+                                ..label.clone()
+                            })
+                        }
+                        _ => continue,
+                    }
+                }
+                _ => continue,
+            };
 
-                if size == 2 {
-                    raw_bytes.push(((arg_val >> 8) & 0xFF) as u8);
+            ins.instruction_mode = IM_ZEROPAGE;
+            ins.argument = Some(argument);
+            changed = true;
+            saved += 1;
+        }
+
+        if !changed {
+            return saved;
+        }
+        move_code_addresses(module, &old_layout);
+    }
+}
+
+/// Addresses of the instructions followed by the address after the last instruction.
+pub(crate) fn layout(instructions: &[Instruction]) -> Vec<u32> {
+    let mut addresses = instruction_addresses(instructions);
+    let end = match (addresses.last(), instructions.last()) {
+        (Some(address), Some(ins)) => address + 1 + get_argument_size_by_im(ins.instruction_mode),
+        _ => 0,
+    };
+    addresses.push(end);
+    addresses
+}
+
+/// Finds the jumps of pseudo-instruction expansions to the end of their expansion, like `JW next` of `JNW`.
+/// Returns the index of every jump together with the index of the instruction after its expansion,
+/// `layout` holds the addresses the jumps were expanded with.
+pub(crate) fn expansion_end_jumps(
+    instructions: &[Instruction],
+    layout: &[u32],
+) -> Vec<(usize, usize)> {
+    let mut jumps = Vec::new();
+    let mut start = 0;
+    while start < instructions.len() {
+        // index after the expansion starting at `start`
+        let end = match instructions[start].pseudo {
+            Some(_) => {
+                start
+                    + 1
+                    + instructions[start + 1..]
+                        .iter()
+                        .take_while(|i| i.pseudo.as_ref().is_some_and(|p| !p.is_first))
+                        .count()
+            }
+            None => start + 1,
+        };
+
+        for (idx, ins) in instructions.iter().enumerate().take(end).skip(start) {
+            let is_jump = matches!(flow(ins), Flow::Jump(_) | Flow::Branch(_));
+            if let Some(Argument::Explicit(target)) = ins.argument {
+                if ins.pseudo.is_some() && is_jump && target == layout[end] {
+                    jumps.push((idx, end));
                 }
-                raw_bytes.push((arg_val & 0xFF) as u8);
             }
         }
+        start = end;
+    }
+    jumps
+}
+
+/// Moves the code labels and the jumps to the end of pseudo-instruction expansions to the new addresses of
+/// their instructions, `old_layout` is the layout before the sizes of the instructions changed.
+fn move_code_addresses(module: &mut Module, old_layout: &[u32]) {
+    let new_layout = layout(&module.instructions);
+    let moved = |address: u32| {
+        old_layout
+            .binary_search(&address)
+            .map_or(address, |idx| new_layout[idx])
+    };
+
+    for label in module.labels.iter_mut() {
+        if label.section == Section::Code {
+            label.address = moved(label.address);
+        }
+    }
+
+    for (idx, end) in expansion_end_jumps(&module.instructions, old_layout) {
+        module.instructions[idx].argument = Some(Argument::Explicit(new_layout[end]));
+    }
+}
+
+/// Expands a pseudo-instruction placed at `address` to the native instructions of the active ISA.
+fn expand_pseudo(
+    pseudo: &PseudoInstruction,
+    ins: &Instruction,
+    address: u32,
+) -> Result<Vec<Instruction>, AssemblerError> {
+    // arguments of macros are only known once the macro is used
+    if pseudo.modes & ins.instruction_mode == 0 {
+        return Err(AssemblerError::Semantic(SyntaxError::in_file(
+            &ins.file,
+            ins.line,
+            unavailable_mode_message(pseudo.name, ins.instruction_mode, pseudo.modes),
+        )));
+    }
+
+    pseudo.expand(ins, address).map_err(|native| {
+        AssemblerError::Semantic(SyntaxError::in_file(
+            &ins.file,
+            ins.line,
+            format!(
+                "Pseudo-instruction '{}' cannot be used, the ISA has no '{}' instruction in the mode it needs.",
+                pseudo.name, native
+            ),
+        ))
+    })
+}
+
+/// Takes the parsed instructions and converts them to bytes which can be executed by the Tower architecture,
+/// keeping track of where every instruction came from.
+pub(crate) fn build_program(
+    instructions: &[Instruction],
+    labels: Vec<Label>,
+) -> Result<AssembledProgram, AssemblerError> {
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut source_map = Vec::new();
+
+    for (ins, address) in instructions.iter().zip(instruction_addresses(instructions)) {
+        if let Some(Argument::Explicit(val)) = ins.argument {
+            let size = get_argument_size_by_im(ins.instruction_mode);
+            let bits = size * 8;
+            // negative values fit if they are in the range of a signed value of the size
+            let fits = val < (1 << bits) || (val as i32) < 0 && (val as i32) >= -(1 << (bits - 1));
+            if size > 0 && !fits {
+                let val = match val as i32 {
+                    negative if negative < 0 => negative.to_string(),
+                    _ => format!("0x{:x}", val),
+                };
+                return Err(AssemblerError::Range(SyntaxError::in_file(
+                    &ins.file,
+                    ins.line,
+                    format!("Value {} does not fit into {} byte(s).", val, size),
+                )));
+            }
+        }
+
+        let encoded = encode_instruction(ins);
+        if address as usize + encoded.len() > ADDRESS_SPACE_SIZE {
+            return Err(AssemblerError::Range(SyntaxError::in_file(
+                &ins.file,
+                ins.line,
+                format!(
+                    "The program does not fit into the address space of 0x{:x} bytes.",
+                    ADDRESS_SPACE_SIZE
+                ),
+            )));
+        }
+
+        source_map.push(SourceMapEntry {
+            address,
+            size: encoded.len() as u32,
+            file: ins.file.clone(),
+            line: ins.line,
+        });
+        bytes.extend(encoded);
+    }
+
+    Ok(AssembledProgram {
+        bytes,
+        symbols: labels,
+        source_map,
+        warnings: Vec::new(),
+        zero_page_saved: 0,
+    })
+}
+
+/// Converts a single instruction to its machine code.
+pub(crate) fn encode_instruction(ins: &Instruction) -> Vec<u8> {
+    let mut raw_bytes: Vec<u8> = Vec::new();
+
+    let instruction = get_instruction_by_name(&ins.name).unwrap();
+    let opcode = instruction.0;
+    // convert to 0-7
+    let im = ins.instruction_mode.trailing_zeros();
+
+    // the instruction byte is the upper part of the microcode ROM address, Isa::validate checks it fits into 8 bits
+    let instruction_byte = (opcode << isa().address.mode_bits) | im;
+    raw_bytes.push(u8::try_from(instruction_byte).expect("the ISA is validated"));
+
+    if let Some(Argument::Explicit(arg_val)) = ins.argument {
+        let size = get_argument_size_by_im(ins.instruction_mode);
+
+        if size == 2 {
+            raw_bytes.push(((arg_val >> 8) & 0xFF) as u8);
+        }
+        if size > 0 {
+            raw_bytes.push((arg_val & 0xFF) as u8);
+        }
     }
     raw_bytes
 }
@@ -0,0 +1,110 @@
+use crate::{get_argument_size_by_im, IM_CONSTANT};
+
+use super::{Argument, Instruction, Label};
+
+/// Instructions after which the execution does not simply continue with the next instruction.
+pub const CONTROL_FLOW_INSTRUCTIONS: &[&str] = &["JMP", "JW", "JZ", "JNZ", "JSR", "RTS", "HLT"];
+
+/// A run of instructions which is only entered at the top and only left at the bottom.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+    /// Label at the start of the block, if there is one.
+    pub label: Option<String>,
+    /// Index of the first instruction.
+    pub start: usize,
+    /// Index one past the last instruction.
+    pub end: usize,
+}
+
+/// Where the execution continues after an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Continues with the next instruction.
+    Next,
+    /// Continues at the address.
+    Jump(u32),
+    /// Continues at the address or with the next instruction.
+    Branch(u32),
+    /// Calls the subroutine at the address, `None` for indirect calls, and continues with the next instruction.
+    Call(Option<u32>),
+    /// Returns from a subroutine.
+    Return,
+    /// Jumps to an address only known at runtime.
+    IndirectJump,
+    /// Continues at an address only known at runtime or with the next instruction.
+    IndirectBranch,
+    Halt,
+}
+
+/// Computes where the execution continues after an instruction with resolved arguments.
+pub fn flow(ins: &Instruction) -> Flow {
+    let target = match (&ins.argument, ins.instruction_mode) {
+        (Some(Argument::Explicit(address)), IM_CONSTANT) => Some(*address),
+        _ => None,
+    };
+
+    match (ins.name.to_uppercase().as_str(), target) {
+        ("JMP", Some(address)) => Flow::Jump(address),
+        ("JMP", None) => Flow::IndirectJump,
+        ("JW" | "JZ" | "JNZ", Some(address)) => Flow::Branch(address),
+        ("JW" | "JZ" | "JNZ", None) => Flow::IndirectBranch,
+        ("JSR", target) => Flow::Call(target),
+        ("RTS", _) => Flow::Return,
+        ("HLT", _) => Flow::Halt,
+        _ => Flow::Next,
+    }
+}
+
+pub fn is_control_flow(name: &str) -> bool {
+    CONTROL_FLOW_INSTRUCTIONS
+        .iter()
+        .any(|&i| i.to_lowercase() == name.to_lowercase())
+}
+
+/// Computes the address of every instruction.
+pub fn instruction_addresses(instructions: &[Instruction]) -> Vec<u32> {
+    let mut current_address = 0;
+    instructions
+        .iter()
+        .map(|ins| {
+            let address = current_address;
+            current_address += 1 + get_argument_size_by_im(ins.instruction_mode);
+            address
+        })
+        .collect()
+}
+
+/// Splits the instructions into basic blocks. A new block starts at every label and after every control flow instruction.
+pub fn basic_blocks(instructions: &[Instruction], labels: &[Label]) -> Vec<BasicBlock> {
+    let addresses = instruction_addresses(instructions);
+
+    let mut blocks: Vec<BasicBlock> = Vec::new();
+    let mut current: Option<BasicBlock> = None;
+
+    for (idx, ins) in instructions.iter().enumerate() {
+        let label = labels.iter().find(|l| l.address == addresses[idx]);
+
+        // a label is a possible jump target, so it always starts a new block
+        if label.is_some() {
+            if let Some(block) = current.take() {
+                blocks.push(block);
+            }
+        }
+
+        let block = current.get_or_insert_with(|| BasicBlock {
+            label: label.map(|l| l.name.clone()),
+            start: idx,
+            end: idx,
+        });
+        block.end = idx + 1;
+
+        if is_control_flow(&ins.name) {
+            blocks.push(current.take().unwrap());
+        }
+    }
+
+    if let Some(block) = current {
+        blocks.push(block);
+    }
+    blocks
+}
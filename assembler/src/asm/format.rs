@@ -0,0 +1,102 @@
+use crate::format::{format_file, indent_width, render, split_comment, Line};
+use crate::isa::isa;
+use crate::AssemblerError;
+
+use super::pseudo::pseudo_instruction_by_name;
+use super::split_args;
+
+/// Formats a program source.
+///
+/// Mnemonics are upper-cased and arguments separated by `, `. Markers start at the first column, labels keep the nesting
+/// given by their indentation and the code after a label is indented one level deeper than the label.
+pub fn format_source(source: &str) -> String {
+    let mut lines = Vec::new();
+
+    // indentation of the labels the current line is nested in, innermost last
+    let mut labels: Vec<usize> = Vec::new();
+    let mut is_in_macro = false;
+
+    for raw in source.lines() {
+        let (code, comment) = split_comment(raw);
+        let width = indent_width(raw);
+        let base = is_in_macro as usize;
+        let comment = comment.map(str::to_string);
+
+        let words: Vec<&str> = code.split_whitespace().collect();
+        let Some(first) = words.first() else {
+            // comments are indented like the code they are nested in
+            let depth = labels.iter().filter(|w| **w < width).count();
+            let indent = if comment.is_some() { base + depth } else { 0 };
+            lines.push(Line {
+                indent,
+                code: String::new(),
+                comment,
+            });
+            continue;
+        };
+
+        let line = if let Some(keyword) = first.strip_prefix('#') {
+            let keyword = keyword.to_lowercase();
+            match keyword.as_str() {
+                "macro" => is_in_macro = true,
+                "end" => is_in_macro = false,
+                _ => {}
+            }
+            if keyword == "macro" || keyword == "end" {
+                labels.clear();
+            }
+
+            let mut code = format!("#{}", keyword);
+            for w in &words[1..] {
+                code += &format!(" {}", w);
+            }
+            Line {
+                indent: 0,
+                code,
+                comment,
+            }
+        } else if code.ends_with(':') {
+            while labels.last().is_some_and(|w| *w >= width) {
+                labels.pop();
+            }
+            let indent = base + labels.len();
+            labels.push(width);
+
+            Line {
+                indent,
+                code: words.join(" "),
+                comment,
+            }
+        } else {
+            // the code belongs to the last label until a label of the same or a lower level follows
+            let name = match isa().instruction_by_name(first) {
+                Some(ins) => ins.name.to_uppercase(),
+                None => match pseudo_instruction_by_name(first) {
+                    Some(pseudo) => pseudo.name.to_string(),
+                    // macros are case sensitive
+                    None => first.to_string(),
+                },
+            };
+            let args = split_args(&code[first.len()..]);
+
+            let code = match args.as_slice() {
+                [""] => name,
+                _ => format!("{} {}", name, args.join(", ")),
+            };
+            Line {
+                indent: base + labels.len(),
+                code,
+                comment,
+            }
+        };
+        lines.push(line);
+    }
+
+    render(&lines)
+}
+
+/// Formats a program source file in place, or only checks that it is formatted.
+/// Returns the first line which is not formatted, `None` if the file is formatted.
+pub fn formatter(input_file_path: &str, check: bool) -> Result<Option<u32>, AssemblerError> {
+    format_file(input_file_path, check, format_source)
+}
@@ -0,0 +1,243 @@
+//! Export of the control-flow graph and the call graph of a program to Graphviz DOT files.
+
+use std::collections::HashMap;
+
+use crate::{read_file, write_file, AssemblerError};
+
+use super::asm::parse_file;
+use super::cfg::{basic_blocks, flow, instruction_addresses, BasicBlock, Flow};
+use super::listing::format_instruction;
+use super::stack::{analyze_stack, Depth, StackAnalysis};
+use super::stdlib;
+use super::{Instruction, Label};
+
+/// Writes the control-flow graph of a source file and optionally its call graph.
+pub fn grapher(
+    file_in: &str,
+    file_out: &str,
+    calls_out: Option<&str>,
+) -> Result<(), AssemblerError> {
+    let (instructions, labels) = parse_file(file_in, false)?;
+
+    // source files of the instructions, read once
+    let mut sources: HashMap<String, Vec<String>> = HashMap::new();
+    for ins in &instructions {
+        if !sources.contains_key(&ins.file) {
+            let source = read_file(&ins.file).ok().or_else(|| {
+                let name = ins.file.strip_prefix("stdlib/")?.strip_suffix(".asm")?;
+                stdlib::library(name).map(String::from)
+            });
+            let lines = source.map_or_else(Vec::new, |s| s.lines().map(String::from).collect());
+            sources.insert(ins.file.clone(), lines);
+        }
+    }
+    let source_line = |file: &str, line: u32| -> Option<String> {
+        sources
+            .get(file)?
+            .get(line.checked_sub(1)? as usize)
+            .cloned()
+    };
+
+    let output = cfg_dot(&instructions, &labels, &source_line);
+    write_file(file_out, output.as_bytes())?;
+
+    if let Some(calls_out) = calls_out {
+        let output = call_graph_dot(&analyze_stack(&instructions, &labels));
+        write_file(calls_out, output.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Escapes a string for a quoted DOT identifier.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Generates the control-flow graph of the basic blocks. Every block lists its source lines, `source_line` returns
+/// the text of a line of a file, the instructions are formatted if it is not known.
+/// Blocks which cannot be reached from the start of the program are filled red.
+pub fn cfg_dot(
+    instructions: &[Instruction],
+    labels: &[Label],
+    source_line: &dyn Fn(&str, u32) -> Option<String>,
+) -> String {
+    let addresses = instruction_addresses(instructions);
+    let blocks = basic_blocks(instructions, labels);
+
+    // index of the block holding the address
+    let block_at = |address: u32| -> Option<usize> {
+        let idx = addresses.binary_search(&address).ok()?;
+        blocks.iter().position(|b| b.start <= idx && idx < b.end)
+    };
+    let label_at = |address: u32| -> String {
+        labels
+            .iter()
+            .find(|l| l.address == address)
+            .map_or_else(|| format!("0x{:04x}", address), |l| l.name.clone())
+    };
+
+    // (from, to, label, is a call)
+    let mut edges: Vec<(usize, usize, String, bool)> = Vec::new();
+    for (idx, block) in blocks.iter().enumerate() {
+        let last = &instructions[block.end - 1];
+        let next = Some(idx + 1).filter(|&n| n < blocks.len());
+        let name = last.name.to_uppercase();
+
+        let (taken, not_taken) = match name.as_str() {
+            "JZ" => ("ZERO", "!ZERO"),
+            "JNZ" => ("!ZERO", "ZERO"),
+            "JW" => ("WRAP", "!WRAP"),
+            _ => ("", ""),
+        };
+
+        match flow(last) {
+            Flow::Next => edges.extend(next.map(|n| (idx, n, String::new(), false))),
+            Flow::Jump(target) => {
+                edges.extend(block_at(target).map(|t| (idx, t, String::new(), false)))
+            }
+            Flow::Branch(target) => {
+                edges.extend(block_at(target).map(|t| (idx, t, taken.to_string(), false)));
+                edges.extend(next.map(|n| (idx, n, not_taken.to_string(), false)));
+            }
+            Flow::IndirectBranch => {
+                edges.extend(next.map(|n| (idx, n, not_taken.to_string(), false)))
+            }
+            Flow::Call(target) => {
+                if let Some(target) = target {
+                    edges.extend(block_at(target).map(|t| (idx, t, String::from("call"), true)));
+                }
+                let label = match target {
+                    Some(target) => format!("after {}", label_at(target)),
+                    None => String::from("after call"),
+                };
+                edges.extend(next.map(|n| (idx, n, label, false)));
+            }
+            Flow::Return | Flow::IndirectJump | Flow::Halt => {}
+        }
+    }
+
+    // blocks reachable from the start of the program
+    let mut reachable = vec![false; blocks.len()];
+    let mut work = vec![0];
+    while let Some(idx) = work.pop() {
+        if blocks.is_empty() || reachable[idx] {
+            continue;
+        }
+        reachable[idx] = true;
+        work.extend(edges.iter().filter(|e| e.0 == idx).map(|e| e.1));
+    }
+
+    let mut output = String::from("digraph cfg {\n");
+    output += "\tnode [shape=box, fontname=\"monospace\"];\n";
+
+    for (idx, block) in blocks.iter().enumerate() {
+        let text = block_text(block, instructions, source_line);
+        let style = if reachable[idx] {
+            ""
+        } else {
+            ", style=filled, fillcolor=\"#f4cccc\", xlabel=\"unreachable\""
+        };
+        output += &format!(
+            "\tb{} [label=\"{}\"{}];\n",
+            idx,
+            escape(&text).replace('\n', "\\l"),
+            style
+        );
+    }
+
+    for (from, to, label, is_call) in edges {
+        let mut attributes = Vec::new();
+        if !label.is_empty() {
+            attributes.push(format!("label=\"{}\"", escape(&label)));
+        }
+        if is_call {
+            attributes.push(String::from("style=dashed"));
+        }
+
+        match attributes.is_empty() {
+            true => output += &format!("\tb{} -> b{};\n", from, to),
+            false => output += &format!("\tb{} -> b{} [{}];\n", from, to, attributes.join(", ")),
+        }
+    }
+
+    output += "}\n";
+    output
+}
+
+/// The label of a block followed by its source lines, every line is only listed once even if
+/// it was expanded to several instructions.
+fn block_text(
+    block: &BasicBlock,
+    instructions: &[Instruction],
+    source_line: &dyn Fn(&str, u32) -> Option<String>,
+) -> String {
+    let mut text = String::new();
+    if let Some(label) = &block.label {
+        text += &format!("{}:\n", label);
+    }
+
+    let mut last: Option<(&str, u32)> = None;
+    for ins in &instructions[block.start..block.end] {
+        if last == Some((&ins.file, ins.line)) {
+            continue;
+        }
+        last = Some((&ins.file, ins.line));
+
+        let line = match source_line(&ins.file, ins.line) {
+            Some(line) => line.trim().to_string(),
+            None => match &ins.pseudo {
+                Some(pseudo) => pseudo.text.clone(),
+                None => format_instruction(ins),
+            },
+        };
+        text += &format!("{: >4}  {}\n", ins.line, line);
+    }
+    text
+}
+
+/// Generates the call graph, every routine is shown with its worst-case stack depth.
+pub fn call_graph_dot(analysis: &StackAnalysis) -> String {
+    let mut output = String::from("digraph calls {\n");
+    output += "\tnode [shape=box, fontname=\"monospace\"];\n";
+
+    let mut has_indirect = false;
+    for (idx, routine) in analysis.routines.iter().enumerate() {
+        let depth = match routine.depth {
+            Depth::Bounded(d) => format!("{} bytes", d),
+            Depth::Unbounded => String::from("unbounded"),
+            Depth::Unknown => String::from("unknown"),
+        };
+        output += &format!(
+            "\tr{} [label=\"{}\\nstack: {}\"];\n",
+            idx,
+            escape(&routine.name),
+            depth
+        );
+    }
+
+    let mut edges: Vec<String> = Vec::new();
+    for (idx, routine) in analysis.routines.iter().enumerate() {
+        for (_, _, target) in &routine.calls {
+            let callee = target.and_then(|t| analysis.routines.iter().position(|r| r.address == t));
+            let edge = match callee {
+                Some(callee) => format!("\tr{} -> r{};\n", idx, callee),
+                None => {
+                    has_indirect = true;
+                    format!("\tr{} -> indirect [style=dashed];\n", idx)
+                }
+            };
+            if !edges.contains(&edge) {
+                edges.push(edge);
+            }
+        }
+    }
+
+    if has_indirect {
+        output += "\tindirect [label=\"(indirect)\", style=dashed];\n";
+    }
+    for edge in edges {
+        output += &edge;
+    }
+    output += "}\n";
+    output
+}
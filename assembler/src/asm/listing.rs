@@ -0,0 +1,158 @@
+use crate::microasm::timing::{find_timing, format_cycles, timing_report, InstructionTiming};
+use crate::{
+    write_file, AssemblerError, IM_ABSOLUTE, IM_ACCUMULATOR, IM_CONSTANT, IM_IMMEDIATE,
+    IM_INDIRECT, IM_ZEROPAGE,
+};
+
+use super::asm::{encode_instruction, parse_file};
+use super::cfg::{basic_blocks, instruction_addresses};
+use super::{AddressPart, Argument, Instruction, Label, Section};
+
+/// Assembles a source file and writes its listing to `file_out`.
+/// If a microcode source file is provided, every instruction and basic block is annotated with its cycle count.
+/// `zero_page` has to match the assembly the listing is made for.
+pub fn lister(
+    file_in: &str,
+    file_out: &str,
+    microcode_in: Option<&str>,
+    zero_page: bool,
+) -> Result<(), AssemblerError> {
+    let (instructions, labels) = parse_file(file_in, zero_page)?;
+
+    let timing_table = match microcode_in {
+        Some(microcode_in) => Some(timing_report(microcode_in)?),
+        None => None,
+    };
+
+    let output = listing(&instructions, &labels, timing_table.as_deref());
+
+    write_file(file_out, output.as_bytes())
+}
+
+/// Formats an instruction the way it would be written in the source code.
+pub fn format_instruction(ins: &Instruction) -> String {
+    let name = ins.name.to_uppercase();
+
+    let value = match &ins.argument {
+        // negative values are shown like they are encoded
+        Some(Argument::Explicit(val)) => match ins.instruction_mode {
+            IM_IMMEDIATE => format!("#0x{:02x}", val & 0xFF),
+            IM_ZEROPAGE => format!("*0x{:02x}", val & 0xFF),
+            IM_ABSOLUTE => format!("*0x{:04x}", val & 0xFFFF),
+            IM_INDIRECT => format!("@0x{:04x}", val & 0xFFFF),
+            IM_CONSTANT => format!("&0x{:04x}", val & 0xFFFF),
+            _ => format!("0x{:x}", val),
+        },
+        Some(Argument::Label(label)) => {
+            let prefix = match (ins.instruction_mode, label.part) {
+                (IM_IMMEDIATE, AddressPart::Low) => "#<",
+                (IM_IMMEDIATE, AddressPart::High) => "#>",
+                (IM_ABSOLUTE, _) => "*",
+                (IM_INDIRECT, _) => "@",
+                _ => "",
+            };
+            match label.offset {
+                0 => format!("{}{}", prefix, label.name),
+                offset => format!("{}{}+{}", prefix, label.name, offset),
+            }
+        }
+        Some(Argument::Implicit(idx)) => format!("${}", idx),
+        None if ins.instruction_mode == IM_ACCUMULATOR => String::from("%A"),
+        None => return name,
+    };
+
+    format!("{} {}", name, value)
+}
+
+/// Generates a listing with the address, machine code and source line of every instruction.
+pub fn listing(
+    instructions: &[Instruction],
+    labels: &[Label],
+    timing_table: Option<&[InstructionTiming]>,
+) -> String {
+    let addresses = instruction_addresses(instructions);
+    let indent = " ".repeat(34);
+
+    let mut output = String::from("ADDR  BYTES     LINE  CYCLES  INSTRUCTION\n");
+
+    for block in basic_blocks(instructions, labels) {
+        if let Some(first) = addresses.get(block.start) {
+            for l in labels.iter().filter(|l| l.address == *first) {
+                output += &format!("\n{}{}:\n", indent, l.name);
+            }
+        }
+
+        // (min, max) cycles of the whole block
+        let mut block_cycles = (0, 0);
+        let mut is_block_timed = true;
+
+        for idx in block.start..block.end {
+            let ins = &instructions[idx];
+
+            let bytes = encode_instruction(ins)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<String>>()
+                .join(" ");
+
+            let timing =
+                timing_table.and_then(|table| find_timing(table, &ins.name, ins.instruction_mode));
+
+            let cycles = match timing {
+                Some(t) => {
+                    block_cycles.0 += t.min;
+                    block_cycles.1 += t.max;
+                    format_cycles(t.min, t.max)
+                }
+                None => {
+                    is_block_timed = false;
+                    String::new()
+                }
+            };
+
+            // pseudo-instructions are listed above their expansion, which is indented
+            let text = match &ins.pseudo {
+                Some(pseudo) => {
+                    if pseudo.is_first {
+                        output += &format!(
+                            "{:04x}  {: <8}  {: >4}  {: >6}  {}\n",
+                            addresses[idx], "", ins.line, "", pseudo.text
+                        );
+                    }
+                    format!("  {}", format_instruction(ins))
+                }
+                None => format_instruction(ins),
+            };
+
+            output += &format!(
+                "{:04x}  {: <8}  {: >4}  {: >6}  {}\n",
+                addresses[idx], bytes, ins.line, cycles, text
+            );
+        }
+
+        if timing_table.is_some() {
+            let total = format_cycles(block_cycles.0, block_cycles.1);
+            let total = if is_block_timed {
+                total
+            } else {
+                // some of the instructions are not defined in the microcode
+                format!("{} + unknown", total)
+            };
+            output += &format!("{}; block: {} cycles\n", indent, total);
+        }
+    }
+
+    // labels pointing past the last instruction
+    for l in labels
+        .iter()
+        .filter(|l| l.section == Section::Code && !addresses.contains(&l.address))
+    {
+        output += &format!("\n{}{}:\n", indent, l.name);
+    }
+
+    // variables and labels of the data section with their addresses in the RAM
+    for l in labels.iter().filter(|l| l.section != Section::Code) {
+        output += &format!("\n{:04x}{}{}:\n", l.address, &indent[4..], l.name);
+    }
+    output
+}
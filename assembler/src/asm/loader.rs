@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::{AssemblerError, SyntaxError};
+
+use super::stdlib;
+
+/// Provides the contents of files included with `#include`.
+pub trait SourceLoader {
+    /// Loads `path` included from the file named `from`.
+    /// Returns the name the loaded file is known by (used by diagnostics and nested includes) and its contents.
+    /// A file which cannot be loaded is an `AssemblerError::Io` naming the file.
+    fn load(&self, path: &str, from: &str) -> Result<(String, String), AssemblerError>;
+}
+
+/// Loads included files from the file system, relative to the directory of the including file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileLoader;
+
+impl SourceLoader for FileLoader {
+    fn load(&self, path: &str, from: &str) -> Result<(String, String), AssemblerError> {
+        let resolved = resolve(path, from);
+        let name = resolved.to_string_lossy().to_string();
+
+        match fs::read_to_string(&resolved) {
+            Ok(contents) => Ok((name, contents)),
+            Err(e) => Err(AssemblerError::io(&name, e)),
+        }
+    }
+}
+
+/// Resolves `path` relative to the directory of the file named `from`.
+pub(crate) fn resolve(path: &str, from: &str) -> PathBuf {
+    match Path::new(from).parent() {
+        Some(dir) => normalize(&dir.join(path)),
+        None => normalize(Path::new(path)),
+    }
+}
+
+/// Removes `.` and `..` components without touching the file system, so every file is known by a single name.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir) => {}
+                _ => normalized.push(".."),
+            },
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+/// Loads included files from memory, the path of an `#include` has to match the name of a file exactly.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryLoader {
+    files: HashMap<String, String>,
+}
+
+impl MemoryLoader {
+    pub fn new() -> Self {
+        MemoryLoader::default()
+    }
+
+    pub fn add(&mut self, name: &str, contents: &str) {
+        self.files.insert(name.to_string(), contents.to_string());
+    }
+}
+
+impl SourceLoader for MemoryLoader {
+    fn load(&self, path: &str, _from: &str) -> Result<(String, String), AssemblerError> {
+        match self.files.get(path) {
+            Some(contents) => Ok((path.to_string(), contents.clone())),
+            None => Err(AssemblerError::io(
+                path,
+                io::Error::new(io::ErrorKind::NotFound, "file does not exist"),
+            )),
+        }
+    }
+}
+
+/// Replaces every `#include` of a source with the tokens of the included file. Shared by the assembler and the
+/// microassembler, which bring their own tokenizer.
+pub(crate) struct Includer<'a, T> {
+    pub loader: Option<&'a dyn SourceLoader>,
+    /// Whether a path in angle brackets (`#include <math16>`) names a file of the standard library.
+    pub libraries: bool,
+    /// Tokenizes the source of a file, given its contents and its name.
+    pub tokenize: &'a TokenizeFn<T>,
+    /// Returns the line and the arguments of a token which is an `#include`.
+    pub include: &'a IncludeFn<T>,
+}
+
+pub(crate) type TokenizeFn<T> = dyn Fn(&str, &str) -> Result<Vec<T>, AssemblerError>;
+pub(crate) type IncludeFn<T> = dyn Fn(&T) -> Option<(u32, Vec<String>)>;
+
+/// Files included while tokenizing a source.
+struct Includes {
+    /// files currently being included, used to detect recursive includes
+    stack: Vec<String>,
+    /// library files already included, each one is only included once
+    libraries: Vec<String>,
+}
+
+impl<T> Includer<'_, T> {
+    /// Tokenizes the source named `file_name` together with the files it includes.
+    pub fn resolve(&self, source: &str, file_name: &str) -> Result<Vec<T>, AssemblerError> {
+        let mut includes = Includes {
+            stack: vec![file_name.to_string()],
+            libraries: Vec::new(),
+        };
+        self.resolve_file(source, file_name, &mut includes)
+    }
+
+    fn resolve_file(
+        &self,
+        code: &str,
+        file: &str,
+        includes: &mut Includes,
+    ) -> Result<Vec<T>, AssemblerError> {
+        let mut tokens = Vec::new();
+
+        for t in (self.tokenize)(code, file)? {
+            let (real_line, args) = match (self.include)(&t) {
+                Some(include) => include,
+                None => {
+                    tokens.push(t);
+                    continue;
+                }
+            };
+
+            if args.is_empty() {
+                return Err(AssemblerError::Parse(SyntaxError::in_file(
+                    file,
+                    real_line,
+                    String::from("Missing file to include."),
+                )));
+            }
+            let path = args.join(" ");
+            let path = path.trim_matches('"');
+
+            if let Some(library) = stdlib::library_name(path).filter(|_| self.libraries) {
+                let contents = match stdlib::library(library) {
+                    Some(contents) => contents,
+                    None => {
+                        return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                            file,
+                            real_line,
+                            format!(
+                                "Failed to include '{}', there is no library '{}'.",
+                                path, library
+                            ),
+                        )))
+                    }
+                };
+
+                let name = stdlib::file_name(library);
+                if includes.libraries.contains(&name) {
+                    continue;
+                }
+                includes.libraries.push(name.clone());
+                includes.stack.push(name.clone());
+                tokens.extend(self.resolve_file(contents, &name, includes)?);
+                includes.stack.pop();
+                continue;
+            }
+
+            let loader = match self.loader {
+                Some(l) => l,
+                None => {
+                    return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                        file,
+                        real_line,
+                        format!("Cannot include '{}', no source loader is available.", path),
+                    )))
+                }
+            };
+
+            let (name, contents) = loader.load(path, file)?;
+
+            if includes.stack.contains(&name) {
+                return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                    file,
+                    real_line,
+                    format!(
+                        "'{}' is already being included, includes cannot be recursive.",
+                        name
+                    ),
+                )));
+            }
+
+            includes.stack.push(name.clone());
+            tokens.extend(self.resolve_file(&contents, &name, includes)?);
+            includes.stack.pop();
+        }
+
+        Ok(tokens)
+    }
+}
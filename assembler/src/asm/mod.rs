@@ -1,6 +1,18 @@
+use serde::{Deserialize, Serialize};
+
 use crate::{InstructionMode, IM_ABSOLUTE, IM_ACCUMULATOR, IM_CONSTANT, IM_IMMEDIATE, IM_INDIRECT};
 
+#[allow(clippy::module_inception)]
 pub mod asm;
+pub mod cfg;
+pub mod format;
+pub mod graph;
+pub mod listing;
+pub mod loader;
+pub mod object;
+pub mod pseudo;
+pub mod stack;
+pub mod stdlib;
 
 // ==============================================
 // =             SHARED DEFINITIONS             =
@@ -16,14 +28,29 @@ pub enum Token {
     Marker(String, Vec<String>),
 }
 
+///                     (line, token, file)
 #[derive(Debug, PartialEq)]
-pub struct TokenizedLine(u32, Token);
+pub struct TokenizedLine(pub(crate) u32, pub(crate) Token, pub(crate) String);
 
 #[derive(Debug, Clone)]
 pub struct Instruction {
     pub name: String,
     pub argument: Option<Argument>,
     pub instruction_mode: u32,
+    /// line in the source file, macro expansions use the line of the macro call
+    pub line: u32,
+    /// source file the instruction comes from
+    pub file: String,
+    /// pseudo-instruction the instruction was expanded from
+    pub pseudo: Option<PseudoSource>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PseudoSource {
+    /// the pseudo-instruction as written in the source, e.g. `JNW _loop`
+    pub text: String,
+    /// whether the instruction is the first one of the expansion
+    pub is_first: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -34,9 +61,61 @@ pub struct MacroDef {
     pub instructions: Vec<(Instruction, u32, Vec<String>)>,
 }
 
+/// Part of a program: code is placed in the ROM, data is reserved in the RAM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Section {
+    Code,
+    Data,
+    /// Variables allocated with `#zp`, accessed with one-byte offsets.
+    ZeroPage,
+}
+
+impl Section {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Section::Code => "code",
+            Section::Data => "data",
+            Section::ZeroPage => "zpage",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Label {
     pub name: String,
     pub address: u32,
+    pub section: Section,
+}
+
+/// Part of the address of a label an argument uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressPart {
+    Full,
+    /// `#<label`
+    Low,
+    /// `#>label`
+    High,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelRef {
+    pub name: String,
+    /// added to the address of the label, used by pseudo-instructions accessing the bytes of a word
+    pub offset: u32,
+    pub part: AddressPart,
+}
+
+impl LabelRef {
+    /// Computes the value of the argument from the address of the label.
+    pub fn value(&self, address: u32) -> u32 {
+        let address = address + self.offset;
+        match self.part {
+            AddressPart::Full => address,
+            AddressPart::Low => address & 0xFF,
+            AddressPart::High => (address >> 8) & 0xFF,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -45,8 +124,8 @@ pub enum Argument {
     Explicit(u32),
     /// argument index
     Implicit(u32),
-    /// label name
-    Label(String),
+    /// label reference
+    Label(LabelRef),
 }
 
 pub struct GenericInstruction {
@@ -65,13 +144,20 @@ pub fn analyze_arg(arg: &str) -> Result<InstructionMode, String> {
         '#' => IM_IMMEDIATE,
         '*' => IM_ABSOLUTE,
         '@' => IM_INDIRECT,
-        'a' => IM_ACCUMULATOR,
-        // let reg = arg.chars().nth(1).unwrap();
-        // match reg {
-        //     'a' => IM_REGA,
-        //     'b' => IM_REGB,
-        //     _ => return Err(format!("Invalid register '{}'.", reg)),
-        // }
+        '%' => match arg[1..].to_lowercase().as_str() {
+            "a" => IM_ACCUMULATOR,
+            "b" => {
+                return Err(String::from(
+                    "Register B cannot be used as an argument, only register A ('%A') can. Copy B to A with 'TBA' first.",
+                ))
+            }
+            _ => {
+                return Err(format!(
+                    "Invalid register '{}', the registers are '%A' and '%B'.",
+                    arg.to_uppercase()
+                ))
+            }
+        },
         '&' => IM_CONSTANT,
         _ => 0,
     };
@@ -83,7 +169,12 @@ pub fn parse_arg(arg: &str) -> Result<Option<Argument>, String> {
     if arg.chars().count() == 0 {
         return Err(String::from("Invalid argument, argument cannot be empty."));
     }
-    let in_place_argument_idx = arg.find('$');
+    // a `$` in a character literal is not an argument index
+    let in_place_argument_idx = arg
+        .char_indices()
+        .zip(char_literals(arg))
+        .find(|((_, c), in_literal)| *c == '$' && !in_literal)
+        .map(|((idx, _), _)| idx);
     let arg = if let Some(ipa_idx) = in_place_argument_idx {
         let argument_index_str = &arg[(ipa_idx + 1)..];
         if let Ok(argument_index) = argument_index_str.parse() {
@@ -100,30 +191,139 @@ pub fn parse_arg(arg: &str) -> Result<Option<Argument>, String> {
             _ => &arg[1..],
         };
 
-        let first_char = arg.chars().next().unwrap();
+        // the low or high byte of a label
+        let (str_val, part) = match (im, str_val.chars().next()) {
+            (IM_IMMEDIATE, Some('<')) => (&str_val[1..], AddressPart::Low),
+            (IM_IMMEDIATE, Some('>')) => (&str_val[1..], AddressPart::High),
+            _ => (str_val, AddressPart::Full),
+        };
 
-        if !first_char.is_digit(10) && im == 0 {
-            return Ok(Some(Argument::Label(arg.to_string())));
+        match str_val.chars().next() {
+            Some(c) if !c.is_ascii_digit() && c != '-' && c != '\'' => {
+                return Ok(Some(Argument::Label(LabelRef {
+                    name: str_val.to_string(),
+                    offset: 0,
+                    part,
+                })));
+            }
+            _ if part != AddressPart::Full => {
+                return Err(format!(
+                    "Invalid argument '{}', '<' and '>' can only be used with labels.",
+                    arg
+                ));
+            }
+            _ => {}
         }
 
-        // hex
-        let val = if str_val.starts_with("0x") {
-            u32::from_str_radix(&str_val[2..], 16)
-        }
-        // binary
-        else if str_val.starts_with("0b") {
-            u32::from_str_radix(&str_val[2..], 2)
-        }
-        // decimal
-        else {
-            str_val.parse::<u32>()
+        Argument::Explicit(parse_number(str_val)?)
+    };
+
+    Ok(Some(arg))
+}
+
+/// Parses a number: decimal, hex (`0x`), binary (`0b`), octal (`0o`) or a character in single quotes.
+/// Digits can be separated by `_`. Negative numbers are returned as their two's complement, so they can be
+/// cut to the size of the argument.
+pub fn parse_number(s: &str) -> Result<u32, String> {
+    if let Some(magnitude) = s.strip_prefix('-') {
+        return match parse_number(magnitude)? {
+            val if val <= 1 << 31 => Ok(val.wrapping_neg()),
+            _ => Err(format!("Value '{}' is out of range.", s)),
         };
+    }
+    if s.starts_with('\'') {
+        return parse_char(s);
+    }
 
-        if let Err(_) = val {
-            return Err(format!("Failed to parse value '{}'.", str_val));
+    let (digits, radix) = match s.get(..2) {
+        Some("0x") => (&s[2..], 16),
+        Some("0b") => (&s[2..], 2),
+        Some("0o") => (&s[2..], 8),
+        _ => (s, 10),
+    };
+    let digits = digits.replace('_', "");
+    if digits.is_empty() || digits.starts_with('+') {
+        return Err(format!("Failed to parse value '{}'.", s));
+    }
+    u32::from_str_radix(&digits, radix).map_err(|_| format!("Failed to parse value '{}'.", s))
+}
+
+/// Parses a character literal like `'A'` or `'\n'` to its ASCII code.
+fn parse_char(s: &str) -> Result<u32, String> {
+    let invalid = || {
+        format!(
+            "Invalid character literal {}, it has to be one character in single quotes.",
+            s
+        )
+    };
+    let inner = s
+        .strip_prefix('\'')
+        .and_then(|s| s.strip_suffix('\''))
+        .ok_or_else(invalid)?;
+
+    let c = match inner.strip_prefix('\\') {
+        Some("n") => '\n',
+        Some("r") => '\r',
+        Some("t") => '\t',
+        Some("0") => '\0',
+        Some("\\") => '\\',
+        Some("'") => '\'',
+        Some("\"") => '"',
+        Some(hex)
+            if hex.len() == 3
+                && hex.starts_with('x')
+                && hex[1..].chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            return Ok(u32::from_str_radix(&hex[1..], 16).unwrap());
+        }
+        Some(escape) => return Err(format!("Invalid escape sequence '\\{}' in {}.", escape, s)),
+        None => {
+            let mut chars = inner.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return Err(invalid()),
+            }
         }
-        Argument::Explicit(val.unwrap())
     };
 
-    Ok(Some(arg))
+    if !c.is_ascii() {
+        return Err(format!("Character '{}' is not an ASCII character.", c));
+    }
+    Ok(c as u32)
+}
+
+/// Marks the characters of `s` which belong to a character literal, including its quotes.
+pub fn char_literals(s: &str) -> Vec<bool> {
+    let mut marks = Vec::new();
+    let mut in_literal = false;
+    let mut escaped = false;
+
+    for c in s.chars() {
+        if in_literal {
+            marks.push(true);
+            match (escaped, c) {
+                (false, '\\') => escaped = true,
+                (false, '\'') => in_literal = false,
+                _ => escaped = false,
+            }
+        } else {
+            in_literal = c == '\'';
+            marks.push(in_literal);
+        }
+    }
+    marks
+}
+
+/// Splits the arguments of an instruction at the commas which are not part of a character literal.
+pub fn split_args(args: &str) -> Vec<&str> {
+    let mut split = Vec::new();
+    let mut start = 0;
+    for ((idx, c), in_literal) in args.char_indices().zip(char_literals(args)) {
+        if c == ',' && !in_literal {
+            split.push(args[start..idx].trim());
+            start = idx + 1;
+        }
+    }
+    split.push(args[start..].trim());
+    split
 }
@@ -0,0 +1,209 @@
+//! Relocatable object files, assembled from a single module and combined by the linker.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    get_argument_size_by_im, read_file, write_file, AssemblerError, SyntaxError, DATA_START,
+    ZERO_PAGE_START,
+};
+
+use super::asm::{
+    build_program, expansion_end_jumps, layout, parse_module, select_zero_page, tokenize_source,
+    AssembleOptions, SourceMapEntry,
+};
+use super::loader::FileLoader;
+use super::{AddressPart, Argument, Section};
+
+/// A module assembled as if its code started at `0x0000` and its data and variables at the start of their sections.
+/// The linker moves the sections and patches every place a label is used.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectFile {
+    /// Source file the object was assembled from.
+    pub file: String,
+    pub code: Vec<u8>,
+    /// Number of bytes reserved in the data section.
+    pub data_size: u32,
+    /// Number of bytes allocated in the zero page.
+    pub zero_page_size: u32,
+    /// Labels defined by the module.
+    pub symbols: Vec<Symbol>,
+    /// Labels defined by other modules.
+    pub imports: Vec<String>,
+    pub relocations: Vec<Relocation>,
+    /// Offsets of the 16-bit addresses pointing into the code itself, like the jumps of pseudo-instructions.
+    /// The linker adds the start of the code to them.
+    pub code_addresses: Vec<u32>,
+    /// Source location of every instruction, relative to the start of the code.
+    pub source_map: Vec<SourceMapEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Symbol {
+    pub name: String,
+    pub section: Section,
+    /// Offset from the start of the section.
+    pub offset: u32,
+    /// Whether other modules can use the label.
+    pub exported: bool,
+}
+
+/// Size and content of the value a relocation writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RelocationKind {
+    /// 16-bit address, high byte first.
+    Abs16,
+    /// Low byte of the address (`#<label`).
+    Low8,
+    /// High byte of the address (`#>label`).
+    High8,
+}
+
+/// A place in the code which holds the address of a label.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Relocation {
+    /// Offset of the patched bytes from the start of the code.
+    pub offset: u32,
+    pub symbol: String,
+    /// Added to the address of the label.
+    pub addend: u32,
+    pub kind: RelocationKind,
+    /// Source location of the instruction using the label.
+    pub file: String,
+    pub line: u32,
+}
+
+impl ObjectFile {
+    pub fn read(path: &str) -> Result<ObjectFile, AssemblerError> {
+        let contents = read_file(path)?;
+        serde_json::from_str(&contents).map_err(|e| {
+            AssemblerError::Parse(SyntaxError::in_file(
+                path,
+                0,
+                format!("Invalid object file: {}", e),
+            ))
+        })
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), AssemblerError> {
+        let contents = serde_json::to_string_pretty(self).unwrap();
+        write_file(path, contents.as_bytes())
+    }
+}
+
+/// Assembles a source file to a relocatable object file.
+/// With `zero_page` set, operands in the zero page use the zero page mode where possible.
+pub fn object_assembler(
+    file_in: &str,
+    file_out: &str,
+    zero_page: bool,
+) -> Result<(), AssemblerError> {
+    let input = read_file(file_in)?;
+    let options = AssembleOptions {
+        file_name: file_in.to_string(),
+        loader: Some(&FileLoader),
+        zero_page,
+    };
+    assemble_object(&input, &options)?.write(file_out)
+}
+
+/// Assembles a module to a relocatable object file without touching the file system.
+pub fn assemble_object(
+    source: &str,
+    options: &AssembleOptions,
+) -> Result<ObjectFile, AssemblerError> {
+    let mut module = parse_module(tokenize_source(source, options)?)?;
+    if options.zero_page {
+        // only labels of the module itself are known
+        select_zero_page(&mut module);
+    }
+
+    let mut instructions = module.instructions;
+    let addresses = layout(&instructions);
+    let mut relocations = Vec::new();
+
+    for (idx, line, file) in module.references {
+        let ins = &mut instructions[idx];
+        let label = match &ins.argument {
+            Some(Argument::Label(label)) => label.clone(),
+            _ => continue,
+        };
+
+        if !module.labels.iter().any(|l| l.name == label.name)
+            && !module.imports.contains(&label.name)
+        {
+            return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                &file,
+                line,
+                format!(
+                    "Label '{}' is not defined, labels of other modules have to be imported with '#import'.",
+                    label.name
+                ),
+            )));
+        }
+
+        let kind = match (get_argument_size_by_im(ins.instruction_mode), label.part) {
+            (2, _) => RelocationKind::Abs16,
+            // zero page offsets are the low byte of the address
+            (_, AddressPart::Low) => RelocationKind::Low8,
+            (_, AddressPart::High) => RelocationKind::High8,
+            (size, AddressPart::Full) => {
+                return Err(AssemblerError::Range(SyntaxError::in_file(
+                    &file,
+                    line,
+                    format!(
+                        "The address of label '{}' does not fit into {} byte(s).",
+                        label.name, size
+                    ),
+                )))
+            }
+        };
+
+        relocations.push(Relocation {
+            // the argument follows the instruction byte
+            offset: addresses[idx] + 1,
+            symbol: label.name,
+            addend: label.offset,
+            kind,
+            file,
+            line,
+        });
+
+        // the linker writes the address over the placeholder
+        ins.argument = Some(Argument::Explicit(0));
+    }
+
+    let code_addresses = expansion_end_jumps(&instructions, &addresses)
+        .into_iter()
+        .map(|(idx, _)| addresses[idx] + 1)
+        .collect();
+
+    let symbols = module
+        .labels
+        .iter()
+        .map(|l| Symbol {
+            name: l.name.clone(),
+            section: l.section,
+            offset: match l.section {
+                Section::Code => l.address,
+                Section::Data => l.address - DATA_START as u32,
+                Section::ZeroPage => l.address - ZERO_PAGE_START as u32,
+            },
+            exported: module.exports.contains(&l.name),
+        })
+        .collect();
+
+    let program = build_program(&instructions, Vec::new())?;
+
+    Ok(ObjectFile {
+        file: options.file_name.clone(),
+        code: program.bytes,
+        data_size: module.data_size,
+        zero_page_size: module.zero_page_size,
+        symbols,
+        imports: module.imports,
+        relocations,
+        code_addresses,
+        source_map: program.source_map,
+    })
+}
@@ -0,0 +1,230 @@
+//! Pseudo-instructions, built into the assembler and expanded to sequences of native instructions.
+
+use crate::{
+    get_argument_size_by_im, get_instruction_by_name, InstructionMode, IM_ABSOLUTE, IM_ACCUMULATOR,
+    IM_CONSTANT, IM_IMMEDIATE, IM_IMPLIED, IM_INDIRECT,
+};
+
+use super::listing::format_instruction;
+use super::{Argument, Instruction, LabelRef, PseudoSource};
+
+/// A native instruction of an expansion: (name, instruction mode, argument).
+type Native = (&'static str, InstructionMode, Option<Argument>);
+
+/// Argument of a pseudo-instruction: (instruction mode, argument).
+type Operand = (InstructionMode, Option<Argument>);
+
+const JUMP_MODES: InstructionMode = IM_CONSTANT | IM_INDIRECT;
+
+pub struct PseudoInstruction {
+    pub name: &'static str,
+    /// Allowed instruction modes of the argument.
+    pub modes: InstructionMode,
+    pub description: &'static str,
+    /// The native instructions it expands to, `x` stands for the argument.
+    pub expansion: &'static str,
+    /// Returns the native instructions, `end` is the address right after the expansion.
+    expand: fn(&Operand, u32) -> Vec<Native>,
+}
+
+pub const PSEUDO_INSTRUCTIONS: &[PseudoInstruction] = &[
+    PseudoInstruction {
+        name: "LDB",
+        modes: IM_IMMEDIATE | IM_ABSOLUTE | IM_INDIRECT,
+        description: "Loads a value in register B, A and the flags are kept.",
+        expansion: "PSA; LDA x; TAB; POA",
+        expand: |(im, arg), _| {
+            vec![
+                ("PSA", IM_IMPLIED, None),
+                ("LDA", *im, arg.clone()),
+                ("TAB", IM_IMPLIED, None),
+                ("POA", IM_IMPLIED, None),
+            ]
+        },
+    },
+    PseudoInstruction {
+        name: "CLR",
+        modes: IM_ACCUMULATOR | IM_CONSTANT,
+        description: "Sets register A or a byte in memory to 0, the flags are kept.",
+        expansion: "LDA #0 (CLR %A) or PSA; LDA #0; STA x; POA",
+        expand: |(im, arg), _| match *im {
+            IM_ACCUMULATOR => vec![("LDA", IM_IMMEDIATE, Some(Argument::Explicit(0)))],
+            _ => vec![
+                ("PSA", IM_IMPLIED, None),
+                ("LDA", IM_IMMEDIATE, Some(Argument::Explicit(0))),
+                ("STA", *im, arg.clone()),
+                ("POA", IM_IMPLIED, None),
+            ],
+        },
+    },
+    PseudoInstruction {
+        name: "INC16",
+        modes: IM_ABSOLUTE,
+        description: "Increments the 16-bit value at the address (high byte first), A is kept and Wrap is set on an overflow.",
+        expansion: "PSA; LDA x+1; ADD #1; STA x+1; LDA x; ADC #0; STA x; POA",
+        expand: |(_, arg), _| word_operation(arg, "ADD", "ADC"),
+    },
+    PseudoInstruction {
+        name: "DEC16",
+        modes: IM_ABSOLUTE,
+        description: "Decrements the 16-bit value at the address (high byte first), A is kept and Wrap is set on an underflow.",
+        expansion: "PSA; LDA x+1; SUB #1; STA x+1; LDA x; SBB #0; STA x; POA",
+        expand: |(_, arg), _| word_operation(arg, "SUB", "SBB"),
+    },
+    PseudoInstruction {
+        name: "PSB",
+        modes: IM_IMPLIED,
+        description: "Pushes value in register B on top of the stack, A is set to B.",
+        expansion: "TBA; PSA",
+        expand: |_, _| vec![("TBA", IM_IMPLIED, None), ("PSA", IM_IMPLIED, None)],
+    },
+    PseudoInstruction {
+        name: "POB",
+        modes: IM_IMPLIED,
+        description: "Pops the top value from the stack and saves it in B, A is set to the same value.",
+        expansion: "POA; TAB",
+        expand: |_, _| vec![("POA", IM_IMPLIED, None), ("TAB", IM_IMPLIED, None)],
+    },
+    PseudoInstruction {
+        name: "JNW",
+        modes: JUMP_MODES,
+        description: "Sets the Program Counter to Arg if the Wrap flag is not set.",
+        expansion: "JW next; JMP x",
+        expand: |(im, arg), end| {
+            vec![
+                ("JW", IM_CONSTANT, Some(Argument::Explicit(end))),
+                ("JMP", *im, arg.clone()),
+            ]
+        },
+    },
+    PseudoInstruction {
+        name: "JC",
+        modes: JUMP_MODES,
+        description: "Jumps if an addition carried (Wrap is set), same as JW.",
+        expansion: "JW x",
+        expand: |(im, arg), _| vec![("JW", *im, arg.clone())],
+    },
+    PseudoInstruction {
+        name: "JNC",
+        modes: JUMP_MODES,
+        description: "Jumps if an addition did not carry (Wrap is not set), same as JNW.",
+        expansion: "JW next; JMP x",
+        expand: |(im, arg), end| {
+            vec![
+                ("JW", IM_CONSTANT, Some(Argument::Explicit(end))),
+                ("JMP", *im, arg.clone()),
+            ]
+        },
+    },
+    PseudoInstruction {
+        name: "JLT",
+        modes: JUMP_MODES,
+        description: "Jumps if A was less than the operand of CMP (unsigned).",
+        expansion: "JW x",
+        expand: |(im, arg), _| vec![("JW", *im, arg.clone())],
+    },
+    PseudoInstruction {
+        name: "JGE",
+        modes: JUMP_MODES,
+        description: "Jumps if A was greater than or equal to the operand of CMP (unsigned).",
+        expansion: "JW next; JMP x",
+        expand: |(im, arg), end| {
+            vec![
+                ("JW", IM_CONSTANT, Some(Argument::Explicit(end))),
+                ("JMP", *im, arg.clone()),
+            ]
+        },
+    },
+    PseudoInstruction {
+        name: "JGT",
+        modes: JUMP_MODES,
+        description: "Jumps if A was greater than the operand of CMP (unsigned).",
+        expansion: "JW next; JZ next; JMP x",
+        expand: |(im, arg), end| {
+            vec![
+                ("JW", IM_CONSTANT, Some(Argument::Explicit(end))),
+                ("JZ", IM_CONSTANT, Some(Argument::Explicit(end))),
+                ("JMP", *im, arg.clone()),
+            ]
+        },
+    },
+    PseudoInstruction {
+        name: "JLE",
+        modes: JUMP_MODES,
+        description: "Jumps if A was less than or equal to the operand of CMP (unsigned).",
+        expansion: "JW x; JZ x",
+        expand: |(im, arg), _| vec![("JW", *im, arg.clone()), ("JZ", *im, arg.clone())],
+    },
+];
+
+/// Adds to or subtracts from a 16-bit value, the low byte is stored after the high byte.
+fn word_operation(arg: &Option<Argument>, low: &'static str, high: &'static str) -> Vec<Native> {
+    let high_address = arg.clone();
+    let low_address = match arg {
+        Some(Argument::Explicit(address)) => Some(Argument::Explicit(address.wrapping_add(1))),
+        Some(Argument::Label(label)) => Some(Argument::Label(LabelRef {
+            offset: label.offset + 1,
+            ..label.clone()
+        })),
+        other => other.clone(),
+    };
+    let one = Some(Argument::Explicit(1));
+    let zero = Some(Argument::Explicit(0));
+
+    vec![
+        ("PSA", IM_IMPLIED, None),
+        ("LDA", IM_ABSOLUTE, low_address.clone()),
+        (low, IM_IMMEDIATE, one),
+        ("STA", IM_CONSTANT, low_address),
+        ("LDA", IM_ABSOLUTE, high_address.clone()),
+        (high, IM_IMMEDIATE, zero),
+        ("STA", IM_CONSTANT, high_address),
+        ("POA", IM_IMPLIED, None),
+    ]
+}
+
+/// Looks up a pseudo-instruction, instructions of the ISA take precedence over pseudo-instructions with the same name.
+pub fn pseudo_instruction_by_name(name: &str) -> Option<&'static PseudoInstruction> {
+    if get_instruction_by_name(name).is_some() {
+        return None;
+    }
+    PSEUDO_INSTRUCTIONS
+        .iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+impl PseudoInstruction {
+    /// Expands the pseudo-instruction placed at `address` to native instructions.
+    /// Returns the name of the native instruction if the active ISA lacks one of them.
+    pub fn expand(&self, ins: &Instruction, address: u32) -> Result<Vec<Instruction>, String> {
+        let text = format_instruction(ins);
+        let operand = (ins.instruction_mode, ins.argument.clone());
+
+        // the size of the expansion does not depend on the address it jumps to
+        let size: u32 = (self.expand)(&operand, 0)
+            .iter()
+            .map(|(_, im, _)| 1 + get_argument_size_by_im(*im))
+            .sum();
+
+        (self.expand)(&operand, address + size)
+            .into_iter()
+            .enumerate()
+            .map(
+                |(idx, (name, instruction_mode, argument))| match get_instruction_by_name(name) {
+                    Some(native) if native.2 & instruction_mode != 0 => Ok(Instruction {
+                        name: name.to_string(),
+                        argument,
+                        instruction_mode,
+                        line: ins.line,
+                        file: ins.file.clone(),
+                        pseudo: Some(PseudoSource {
+                            text: text.clone(),
+                            is_first: idx == 0,
+                        }),
+                    }),
+                    _ => Err(name.to_string()),
+                },
+            )
+            .collect()
+    }
+}
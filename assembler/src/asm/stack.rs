@@ -0,0 +1,325 @@
+//! Static analysis of the stack usage: the call graph, the worst-case stack depth of every routine
+//! and pushes and pops which do not balance.
+
+use crate::AssemblerError;
+
+use super::asm::{parse_file, Diagnostic};
+use super::cfg::{flow, instruction_addresses, Flow};
+use super::{Instruction, Label};
+
+/// Number of bytes of the hardware stack, the 8-bit stack pointer wraps around after it.
+pub const STACK_SIZE: u32 = 256;
+
+/// Bytes `JSR` pushes for the return address.
+const RETURN_ADDRESS_SIZE: u32 = 2;
+
+/// Worst-case number of bytes on the stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    Bounded(u32),
+    /// The routine is recursive or calls a recursive routine.
+    Unbounded,
+    /// The routine calls a routine indirectly.
+    Unknown,
+}
+
+/// A routine, entered at the start of the program or by `JSR`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Routine {
+    pub name: String,
+    pub address: u32,
+    /// Routines it calls: (index of the `JSR` instruction, bytes on the stack before the call, address of the routine).
+    /// The address is `None` for indirect calls.
+    pub calls: Vec<(usize, u32, Option<u32>)>,
+    /// Most bytes the routine itself has on the stack at once, without its callees.
+    pub local_depth: u32,
+    /// Most bytes on the stack while the routine runs, including its callees but not its own return address.
+    pub depth: Depth,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StackAnalysis {
+    /// The routine at the start of the program comes first.
+    pub routines: Vec<Routine>,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Number of bytes an instruction pushes, negative if it pops.
+pub fn stack_effect(name: &str) -> i32 {
+    match name.to_uppercase().as_str() {
+        "PSA" | "PSF" => 1,
+        "POA" | "POF" => -1,
+        _ => 0,
+    }
+}
+
+/// Analyzes the stack usage of a source file.
+pub fn analyze_file(file_in: &str) -> Result<StackAnalysis, AssemblerError> {
+    let (instructions, labels) = parse_file(file_in, false)?;
+    Ok(analyze_stack(&instructions, &labels))
+}
+
+/// Finds the routines of the program and follows every path through them, tracking the bytes on the stack.
+pub fn analyze_stack(instructions: &[Instruction], labels: &[Label]) -> StackAnalysis {
+    let mut analysis = StackAnalysis::default();
+    if instructions.is_empty() {
+        return analysis;
+    }
+
+    let addresses = instruction_addresses(instructions);
+
+    // the start of the program and every target of a JSR
+    let mut entries = vec![0];
+    for ins in instructions {
+        if let Flow::Call(Some(address)) = flow(ins) {
+            if !entries.contains(&address) && addresses.binary_search(&address).is_ok() {
+                entries.push(address);
+            }
+        }
+    }
+
+    for address in entries {
+        let name = labels
+            .iter()
+            .find(|l| l.address == address)
+            .map(|l| l.name.clone())
+            .unwrap_or_else(|| match address {
+                0 => String::from("(start)"),
+                _ => format!("0x{:04x}", address),
+            });
+
+        let routine = walk_routine(
+            instructions,
+            &addresses,
+            name,
+            address,
+            &mut analysis.warnings,
+        );
+        analysis.routines.push(routine);
+    }
+
+    let mut states = vec![State::Unvisited; analysis.routines.len()];
+    for idx in 0..analysis.routines.len() {
+        routine_depth(
+            idx,
+            &mut analysis,
+            instructions,
+            &mut states,
+            &mut Vec::new(),
+        );
+    }
+
+    if let Depth::Bounded(depth) = analysis.routines[0].depth {
+        if depth > STACK_SIZE {
+            let ins = &instructions[0];
+            analysis.warnings.push(warning(
+                ins,
+                format!(
+                    "The program can have {} bytes on the stack, but the stack only holds {}.",
+                    depth, STACK_SIZE
+                ),
+            ));
+        }
+    }
+
+    // the same problem can be found on several paths
+    let mut warnings: Vec<Diagnostic> = Vec::new();
+    for w in analysis.warnings {
+        if !warnings.contains(&w) {
+            warnings.push(w);
+        }
+    }
+    analysis.warnings = warnings;
+    analysis
+}
+
+fn warning(ins: &Instruction, message: String) -> Diagnostic {
+    Diagnostic {
+        file: ins.file.clone(),
+        line: ins.line,
+        message,
+    }
+}
+
+/// Follows every path from the entry of the routine until it returns, halts or leaves the code.
+fn walk_routine(
+    instructions: &[Instruction],
+    addresses: &[u32],
+    name: String,
+    address: u32,
+    warnings: &mut Vec<Diagnostic>,
+) -> Routine {
+    let index_of = |address: u32| addresses.binary_search(&address).ok();
+
+    let mut routine = Routine {
+        name,
+        address,
+        calls: Vec::new(),
+        local_depth: 0,
+        depth: Depth::Unknown,
+    };
+
+    // bytes on the stack when the instruction is reached
+    let mut heights: Vec<Option<i32>> = vec![None; instructions.len()];
+    let mut work: Vec<(usize, i32)> = index_of(address).map(|idx| (idx, 0)).into_iter().collect();
+
+    while let Some((idx, height)) = work.pop() {
+        let ins = match instructions.get(idx) {
+            Some(ins) => ins,
+            None => continue,
+        };
+
+        match heights[idx] {
+            Some(h) if h == height => continue,
+            Some(h) => {
+                warnings.push(warning(
+                    ins,
+                    format!(
+                        "Paths reach this instruction with {} and {} byte(s) on the stack.",
+                        h.min(height),
+                        h.max(height)
+                    ),
+                ));
+                continue;
+            }
+            None => heights[idx] = Some(height),
+        }
+
+        let height = height + stack_effect(&ins.name);
+        if height < 0 {
+            warnings.push(warning(
+                ins,
+                format!(
+                    "'{}' pops more bytes than routine '{}' pushed.",
+                    ins.name.to_uppercase(),
+                    routine.name
+                ),
+            ));
+            continue;
+        }
+        routine.local_depth = routine.local_depth.max(height as u32);
+
+        match flow(ins) {
+            Flow::Next | Flow::IndirectBranch => work.push((idx + 1, height)),
+            Flow::Call(target) => {
+                routine.calls.push((idx, height as u32, target));
+                work.push((idx + 1, height));
+            }
+            Flow::Jump(target) => work.extend(index_of(target).map(|t| (t, height))),
+            Flow::Branch(target) => {
+                work.extend(index_of(target).map(|t| (t, height)));
+                work.push((idx + 1, height));
+            }
+            Flow::Return if height != 0 => warnings.push(warning(
+                ins,
+                format!(
+                    "Routine '{}' returns with {} pushed byte(s) left on the stack, 'RTS' takes them as the return address.",
+                    routine.name, height
+                ),
+            )),
+            Flow::Return | Flow::IndirectJump | Flow::Halt => {}
+        }
+    }
+    routine
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Unvisited,
+    Visiting,
+    Done,
+}
+
+/// Computes the worst-case depth of the routine from the depths of its callees, reporting recursion.
+/// `path` holds the routines being computed, the callers of the routine.
+fn routine_depth(
+    idx: usize,
+    analysis: &mut StackAnalysis,
+    instructions: &[Instruction],
+    states: &mut Vec<State>,
+    path: &mut Vec<usize>,
+) -> Depth {
+    match states[idx] {
+        State::Done => return analysis.routines[idx].depth,
+        State::Visiting => return Depth::Unbounded,
+        State::Unvisited => {}
+    }
+    states[idx] = State::Visiting;
+    path.push(idx);
+
+    let mut depth = Depth::Bounded(analysis.routines[idx].local_depth);
+    for (call, height, target) in analysis.routines[idx].calls.clone() {
+        let callee = target.and_then(|t| analysis.routines.iter().position(|r| r.address == t));
+
+        let callee_depth = match callee {
+            Some(callee) if states[callee] == State::Visiting => {
+                let start = path.iter().position(|&r| r == callee).unwrap();
+                let cycle: Vec<&str> = path[start..]
+                    .iter()
+                    .chain(std::iter::once(&callee))
+                    .map(|&r| analysis.routines[r].name.as_str())
+                    .collect();
+                let message = format!(
+                    "Routine '{}' is recursive ({}), its stack depth is unbounded.",
+                    analysis.routines[callee].name,
+                    cycle.join(" -> ")
+                );
+                analysis
+                    .warnings
+                    .push(warning(&instructions[call], message));
+                Depth::Unbounded
+            }
+            Some(callee) => routine_depth(callee, analysis, instructions, states, path),
+            None => Depth::Unknown,
+        };
+
+        depth = match (depth, callee_depth) {
+            (Depth::Unbounded, _) | (_, Depth::Unbounded) => Depth::Unbounded,
+            (Depth::Unknown, _) | (_, Depth::Unknown) => Depth::Unknown,
+            (Depth::Bounded(d), Depth::Bounded(c)) => {
+                Depth::Bounded(d.max(height + RETURN_ADDRESS_SIZE + c))
+            }
+        };
+    }
+
+    path.pop();
+    states[idx] = State::Done;
+    analysis.routines[idx].depth = depth;
+    depth
+}
+
+/// Lists every routine with its worst-case stack depth and the routines it calls.
+pub fn format_stack_report(analysis: &StackAnalysis) -> String {
+    let mut output = String::from("ROUTINE                   ADDRESS  DEPTH      CALLS\n");
+
+    for routine in &analysis.routines {
+        let depth = match routine.depth {
+            Depth::Bounded(d) => d.to_string(),
+            Depth::Unbounded => String::from("unbounded"),
+            Depth::Unknown => String::from("unknown"),
+        };
+
+        let mut calls: Vec<String> = Vec::new();
+        for (_, _, target) in &routine.calls {
+            let name = match target.and_then(|t| analysis.routines.iter().find(|r| r.address == t))
+            {
+                Some(callee) => callee.name.clone(),
+                None => String::from("(indirect)"),
+            };
+            if !calls.contains(&name) {
+                calls.push(name);
+            }
+        }
+
+        let line = format!(
+            "{: <24}  0x{:04x}   {: <9}  {}",
+            routine.name,
+            routine.address,
+            depth,
+            calls.join(", ")
+        );
+        output += line.trim_end();
+        output += "\n";
+    }
+    output
+}
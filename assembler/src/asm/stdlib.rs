@@ -0,0 +1,34 @@
+//! The standard library, a set of routines bundled with the assembler.
+//!
+//! A library file is included by name with `#include <name>` and is only included once per program.
+
+/// Name and source of every library file.
+pub const LIBRARIES: [(&str, &str); 9] = [
+    ("macros", include_str!("../../stdlib/macros.asm")),
+    ("math16", include_str!("../../stdlib/math16.asm")),
+    ("shift16", include_str!("../../stdlib/shift16.asm")),
+    ("mul", include_str!("../../stdlib/mul.asm")),
+    ("div", include_str!("../../stdlib/div.asm")),
+    ("tty", include_str!("../../stdlib/tty.asm")),
+    ("number", include_str!("../../stdlib/number.asm")),
+    ("keyboard", include_str!("../../stdlib/keyboard.asm")),
+    ("mem", include_str!("../../stdlib/mem.asm")),
+];
+
+/// Returns the source of the library file `name`.
+pub fn library(name: &str) -> Option<&'static str> {
+    LIBRARIES
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, source)| *source)
+}
+
+/// Returns the name of the library file referenced by the path of an `#include`, e.g. `<math16>`.
+pub fn library_name(path: &str) -> Option<&str> {
+    path.strip_prefix('<')?.strip_suffix('>').map(str::trim)
+}
+
+/// Name diagnostics use for the library file `name`.
+pub fn file_name(name: &str) -> String {
+    format!("stdlib/{}.asm", name.to_lowercase())
+}
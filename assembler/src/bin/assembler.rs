@@ -1,8 +1,20 @@
-use std::fs::File;
-
 use chrono::Utc;
+use clap::CommandFactory;
 use clap::Parser;
-use tower_assembler::{asm::asm::assembler, AssemblerError};
+use tower_assembler::{
+    asm::{
+        asm::assembler,
+        format::formatter,
+        graph::grapher,
+        listing::lister,
+        object::object_assembler,
+        pseudo::PSEUDO_INSTRUCTIONS,
+        stack::{analyze_file, format_stack_report},
+    },
+    get_available_im_names,
+    isa::{isa, set_isa, Isa},
+    AssemblerError,
+};
 
 // use tower_assembler::asm
 
@@ -10,58 +22,158 @@ use tower_assembler::{asm::asm::assembler, AssemblerError};
 #[clap(author, version, about, long_about = None)]
 struct Args {
     /// Source file to be assembled
-    #[clap(short, long)]
-    r#in: String,
+    #[clap(short, long, required_unless_present = "help-isa")]
+    r#in: Option<String>,
 
     /// File to be written the output to.
     #[clap(short, long)]
     out: Option<String>,
 
+    /// ISA description (TOML or JSON) to use instead of the built-in Tower ISA
+    #[clap(long)]
+    isa: Option<String>,
+
+    /// Print the instructions and pseudo-instructions of the ISA
+    #[clap(long)]
+    help_isa: bool,
+
     #[clap(subcommand)]
-    cmd: Action,
+    cmd: Option<Action>,
 }
 
 #[derive(clap::Subcommand, Debug)]
 enum Action {
-    Assemble,
+    Assemble {
+        /// File to write the listing to
+        #[clap(long)]
+        listing: Option<String>,
+
+        /// Microcode source used to annotate the listing with cycle counts
+        #[clap(long, requires = "listing")]
+        microcode: Option<String>,
+
+        /// Use the zero page mode for operands in the zero page and report the bytes saved
+        #[clap(long)]
+        zero_page: bool,
+    },
+    /// Assemble a module to a relocatable object file for tower-link
+    Object {
+        /// Use the zero page mode for operands in the zero page
+        #[clap(long)]
+        zero_page: bool,
+    },
+    /// Print the call graph and the worst-case stack depth of every routine
+    Stack,
+    /// Write the control-flow graph of the basic blocks as a Graphviz DOT file
+    Graph {
+        /// File to write the call graph to
+        #[clap(long)]
+        calls: Option<String>,
+    },
     Disassemble,
+    /// Reformat the source file in place
+    Fmt {
+        /// Only check that the file is formatted, fail if it is not
+        #[clap(long)]
+        check: bool,
+    },
 }
 
-const ASSEMBLER_DEFAULT_OUT_FILE: &'static str = "program.bin";
-const DISASSEMBLER_DEFAULT_OUT_FILE: &'static str = "out.txt";
+const ASSEMBLER_DEFAULT_OUT_FILE: &str = "program.bin";
+const OBJECT_DEFAULT_OUT_FILE: &str = "program.o";
+const GRAPH_DEFAULT_OUT_FILE: &str = "program.dot";
+const DISASSEMBLER_DEFAULT_OUT_FILE: &str = "out.txt";
 
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("{}", format!("❌ Error: {}", e.message));
-        if let Some(serr) = e.syntax_error {
-            eprintln!("{}", format!("{} (line {})", serr.message, serr.line));
+    match run() {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("❌ Error [{}]: {}", e.code(), e);
+            std::process::exit(e.exit_code());
         }
     }
 }
 
-fn run() -> Result<(), AssemblerError> {
+/// Lists the instructions of the active ISA followed by the pseudo-instructions of the assembler.
+fn isa_help() -> String {
+    let mut output = String::from("Instructions:\n");
+    for ins in &isa().instructions {
+        output += &format!(
+            "  {: <6} {: <42} {}\n",
+            ins.name,
+            get_available_im_names(ins.modes).join(", "),
+            ins.description.as_deref().unwrap_or_default()
+        );
+    }
+
+    output += "\nPseudo-instructions:\n";
+    for pseudo in PSEUDO_INSTRUCTIONS {
+        output += &format!(
+            "  {: <6} {: <42} {}\n  {: <6} {: <42} = {}\n",
+            pseudo.name,
+            get_available_im_names(pseudo.modes).join(", "),
+            pseudo.description,
+            "",
+            "",
+            pseudo.expansion
+        );
+    }
+    output
+}
+
+/// Returns whether the command succeeded, `fmt --check` fails on a file which is not formatted.
+fn run() -> Result<bool, AssemblerError> {
     let args = Args::parse();
 
-    let input_file_path = &args.r#in;
-    let output_file_path = &args.out;
+    if let Some(isa_path) = &args.isa {
+        set_isa(Isa::from_file(isa_path)?)?;
+    }
 
-    if matches!(File::open(input_file_path), Err(_)) {
-        return Err(AssemblerError::new(
-            String::from("Failed to read the input file."),
-            None,
-        ));
+    if args.help_isa {
+        print!("{}", isa_help());
+        return Ok(true);
     }
 
+    let input_file_path = args.r#in.as_ref().unwrap();
+    let output_file_path = &args.out;
+    let cmd = match args.cmd {
+        Some(cmd) => cmd,
+        None => Args::command()
+            .name(env!("CARGO_BIN_NAME"))
+            .error(
+                clap::ErrorKind::MissingSubcommand,
+                "A subcommand is required unless --help-isa is used",
+            )
+            .exit(),
+    };
+
     let start_time = Utc::now();
 
-    match args.cmd {
-        Action::Assemble => {
+    match cmd {
+        Action::Assemble {
+            listing,
+            microcode,
+            zero_page,
+        } => {
             let output_file_path = output_file_path
                 .clone()
                 .unwrap_or(String::from(ASSEMBLER_DEFAULT_OUT_FILE));
 
             println!("Assembling... '{}'", input_file_path);
-            assembler(&input_file_path, &output_file_path)?;
+            let program = assembler(input_file_path, &output_file_path, zero_page)?;
+
+            for w in &program.warnings {
+                println!("⚠️  {}", w);
+            }
+            if zero_page {
+                println!("Zero page mode saved {} byte(s)", program.zero_page_saved);
+            }
+
+            if let Some(listing) = listing {
+                lister(input_file_path, &listing, microcode.as_deref(), zero_page)?;
+                println!("Listing written to '{}'", listing);
+            }
 
             let now = Utc::now();
             let delta_time = now - start_time;
@@ -71,6 +183,41 @@ fn run() -> Result<(), AssemblerError> {
                 delta_time.num_milliseconds()
             );
         }
+        Action::Object { zero_page } => {
+            let output_file_path = output_file_path
+                .clone()
+                .unwrap_or(String::from(OBJECT_DEFAULT_OUT_FILE));
+
+            println!("Assembling object... '{}'", input_file_path);
+            object_assembler(input_file_path, &output_file_path, zero_page)?;
+
+            let now = Utc::now();
+            let delta_time = now - start_time;
+            println!(
+                "✔️  Finished and written to '{}' (after {}ms)",
+                output_file_path,
+                delta_time.num_milliseconds()
+            );
+        }
+        Action::Stack => {
+            let analysis = analyze_file(input_file_path)?;
+            print!("{}", format_stack_report(&analysis));
+
+            for w in &analysis.warnings {
+                println!("⚠️  {}", w);
+            }
+        }
+        Action::Graph { calls } => {
+            let output_file_path = output_file_path
+                .clone()
+                .unwrap_or(String::from(GRAPH_DEFAULT_OUT_FILE));
+
+            grapher(input_file_path, &output_file_path, calls.as_deref())?;
+            println!("✔️  Control-flow graph written to '{}'", output_file_path);
+            if let Some(calls) = calls {
+                println!("✔️  Call graph written to '{}'", calls);
+            }
+        }
         Action::Disassemble => {
             let output_file_path = output_file_path
                 .clone()
@@ -85,7 +232,19 @@ fn run() -> Result<(), AssemblerError> {
                 delta_time.num_milliseconds()
             );
         }
+        Action::Fmt { check } => match (formatter(input_file_path, check)?, check) {
+            (Some(line), true) => {
+                eprintln!(
+                    "❌ {}:{}: The file is not formatted.",
+                    input_file_path, line
+                );
+                return Ok(false);
+            }
+            (Some(_), false) => println!("✔️  Formatted '{}'", input_file_path),
+            (None, true) => println!("✔️  '{}' is formatted", input_file_path),
+            (None, false) => println!("✔️  '{}' is already formatted", input_file_path),
+        },
     }
 
-    Ok(())
+    Ok(true)
 }
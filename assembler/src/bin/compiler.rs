@@ -0,0 +1,67 @@
+use chrono::Utc;
+use clap::Parser;
+use tower_assembler::{
+    asm::asm::assembler,
+    compiler::{compiler, CompileOptions},
+    AssemblerError, ZERO_PAGE_START,
+};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Source file to be compiled
+    #[clap(short, long)]
+    r#in: String,
+
+    /// File to write the generated assembly to
+    #[clap(short, long, default_value = "program.asm")]
+    out: String,
+
+    /// First address of the zero page, where variables are allocated (e.g. 0x100)
+    #[clap(long, parse(try_from_str = parse_address))]
+    zero_page: Option<u16>,
+
+    /// Also assemble the generated code to this file
+    #[clap(long)]
+    bin: Option<String>,
+}
+
+fn parse_address(arg: &str) -> Result<u16, String> {
+    let digits = arg.trim_start_matches("0x").trim_start_matches("0X");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("'{}' is not a 16 bit hex address", arg))
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("❌ Error [{}]: {}", e.code(), e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run() -> Result<(), AssemblerError> {
+    let args = Args::parse();
+    let start_time = Utc::now();
+
+    let options = CompileOptions {
+        file_name: args.r#in.clone(),
+        zero_page: args.zero_page.unwrap_or(ZERO_PAGE_START),
+    };
+
+    println!("Compiling... '{}'", args.r#in);
+    compiler(&args.r#in, &args.out, &options)?;
+
+    if let Some(bin) = &args.bin {
+        println!("Assembling... '{}'", args.out);
+        for w in assembler(&args.out, bin, false)?.warnings {
+            println!("⚠️  {}", w);
+        }
+    }
+
+    let delta_time = Utc::now() - start_time;
+    println!(
+        "✔️  Finished and written to '{}' (after {}ms)",
+        args.bin.as_ref().unwrap_or(&args.out),
+        delta_time.num_milliseconds()
+    );
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use chrono::Utc;
+use clap::Parser;
+use tower_assembler::{
+    emu::{
+        runner::{load_microcode, report, run_test_file, ReportFormat, DEFAULT_MAX_CYCLES},
+        Machine, StopReason,
+    },
+    isa::{set_isa, Isa},
+    read_file_binary, write_file, AssemblerError,
+};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Microcode to execute, either its source or an assembled `.bin` file
+    #[clap(short, long)]
+    microcode: String,
+
+    /// ISA description (TOML or JSON) to use instead of the built-in Tower ISA
+    #[clap(long)]
+    isa: Option<String>,
+
+    #[clap(subcommand)]
+    cmd: Action,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Action {
+    /// Run an assembled program until it halts and print the state of the machine
+    Run {
+        /// Assembled program
+        #[clap(short, long)]
+        r#in: String,
+
+        /// Stop after this many clock cycles
+        #[clap(long, default_value_t = DEFAULT_MAX_CYCLES)]
+        max_cycles: u64,
+
+        /// Text typed on the keyboard, read from 0xFF02 one key at a time
+        #[clap(long)]
+        input: Option<String>,
+    },
+    /// Assemble and run test programs, a test fails when it halts at the `_failed` label
+    Test {
+        /// Test program sources
+        #[clap(required = true)]
+        files: Vec<String>,
+
+        /// Report format: text, tap or junit
+        #[clap(long, default_value = "text")]
+        format: ReportFormat,
+
+        /// File to write the report to instead of the standard output
+        #[clap(short, long)]
+        out: Option<String>,
+
+        /// Cycles a test may take before it is considered stuck
+        #[clap(long, default_value_t = DEFAULT_MAX_CYCLES)]
+        max_cycles: u64,
+    },
+}
+
+fn main() {
+    match run() {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("❌ Error [{}]: {}", e.code(), e);
+            std::process::exit(e.exit_code());
+        }
+    }
+}
+
+/// Returns whether everything that was run succeeded.
+fn run() -> Result<bool, AssemblerError> {
+    let args = Args::parse();
+
+    if let Some(isa_path) = &args.isa {
+        set_isa(Isa::from_file(isa_path)?)?;
+    }
+
+    let microcode = load_microcode(&args.microcode)?;
+
+    match args.cmd {
+        Action::Run {
+            r#in,
+            max_cycles,
+            input,
+        } => {
+            let program = read_file_binary(&r#in)?;
+            let start_time = Utc::now();
+
+            let mut machine = Machine::new(microcode, &program);
+            if let Some(input) = input {
+                // escapes are not processed by every shell, so a literal \n ends a line as well
+                machine.keyboard.extend(input.replace("\\n", "\n").bytes());
+            }
+            let reason = machine.run(max_cycles);
+
+            for (port, value) in &machine.io_writes {
+                println!("I/O 0x{:04x} <- 0x{:02x} ({})", port, value, value);
+            }
+            let tty = machine.tty_output();
+            if !tty.is_empty() {
+                println!("TTY:\n{}", tty);
+            }
+            println!(
+                "A: 0x{:02x}  B: 0x{:02x}  SP: 0x{:02x}  PC: 0x{:04x}  FLAGS: {:04b}",
+                machine.a, machine.b, machine.sp, machine.pc, machine.flags
+            );
+
+            let delta_time = Utc::now() - start_time;
+            match reason {
+                StopReason::Halted => {
+                    println!(
+                        "✔️  Halted after {} cycles (after {}ms)",
+                        machine.cycles,
+                        delta_time.num_milliseconds()
+                    );
+                    Ok(true)
+                }
+                StopReason::CycleLimit => {
+                    println!("❌ Did not halt within {} cycles", max_cycles);
+                    Ok(false)
+                }
+            }
+        }
+        Action::Test {
+            files,
+            format,
+            out,
+            max_cycles,
+        } => {
+            let results: Vec<_> = files
+                .iter()
+                .map(|f| run_test_file(f, &microcode, max_cycles))
+                .collect();
+
+            let output = report(&results, format);
+            match out {
+                Some(out) => write_file(&out, output.as_bytes())?,
+                None => print!("{}", output),
+            }
+
+            Ok(results.iter().all(|r| r.passed()))
+        }
+    }
+}
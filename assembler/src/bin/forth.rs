@@ -0,0 +1,45 @@
+use chrono::Utc;
+use clap::Parser;
+use tower_assembler::{asm::asm::assembler, forth::forth, AssemblerError};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// File to write the assembly of the Forth kernel to
+    #[clap(short, long, default_value = "forth.asm")]
+    out: String,
+
+    /// Also assemble the kernel to this file
+    #[clap(long)]
+    bin: Option<String>,
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("❌ Error [{}]: {}", e.code(), e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run() -> Result<(), AssemblerError> {
+    let args = Args::parse();
+    let start_time = Utc::now();
+
+    println!("Generating the Forth kernel...");
+    forth(&args.out)?;
+
+    if let Some(bin) = &args.bin {
+        println!("Assembling... '{}'", args.out);
+        for w in assembler(&args.out, bin, false)?.warnings {
+            println!("⚠️  {}", w);
+        }
+    }
+
+    let delta_time = Utc::now() - start_time;
+    println!(
+        "✔️  Finished and written to '{}' (after {}ms)",
+        args.bin.as_ref().unwrap_or(&args.out),
+        delta_time.num_milliseconds()
+    );
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use clap::Parser;
+use tower_assembler::{
+    isa::{set_isa, Isa},
+    lsp::server,
+};
+
+/// Language server for Tower assembly and microassembly, communicating over the standard input and output
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// ISA description (TOML or JSON) to use instead of the built-in Tower ISA
+    #[clap(long)]
+    isa: Option<String>,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if let Some(isa_path) = &args.isa {
+        let result = Isa::from_file(isa_path).and_then(set_isa);
+        if let Err(e) = result {
+            eprintln!("❌ Error [{}]: {}", e.code(), e);
+            std::process::exit(e.exit_code());
+        }
+    }
+
+    if let Err(e) = server::run() {
+        eprintln!("❌ Error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -1,10 +1,12 @@
-use std::fs::File;
-
 use chrono::Utc;
 use clap::Parser;
 use tower_assembler::{
-    microasm::{asm::assembler, disasm::disassembler},
-    AssemblerError,
+    isa::{set_isa, Isa},
+    microasm::{
+        asm::assembler, coverage::coverage_report, disasm::disassembler, format::formatter,
+        timing::timing_report, verify::verifier,
+    },
+    AssemblerError, SyntaxError,
 };
 
 #[derive(Parser, Debug)]
@@ -18,51 +20,94 @@ struct Args {
     #[clap(short, long)]
     out: Option<String>,
 
+    /// ISA description (TOML or JSON) to use instead of the built-in Tower ISA
+    #[clap(long)]
+    isa: Option<String>,
+
     #[clap(subcommand)]
     cmd: Action,
 }
 
 #[derive(clap::Subcommand, Debug)]
 enum Action {
-    Assemble,
+    Assemble {
+        /// Merge adjacent micro steps where possible and report the step counts
+        #[clap(long)]
+        optimize: bool,
+    },
     Disassemble,
+    /// Check every micro step for bus contention and conflicting control signals
+    Verify,
+    /// Report which opcode, instruction mode and flag combinations are left undefined
+    Coverage {
+        /// Fail if any combination allowed by the assembler is not fully defined
+        #[clap(long)]
+        strict: bool,
+
+        /// The input file is an assembled microcode binary instead of a source file
+        #[clap(long)]
+        binary: bool,
+    },
+    /// Print the number of clock cycles every instruction takes in each of its modes
+    Timing,
+    /// Reformat the source file in place
+    Fmt {
+        /// Only check that the file is formatted, fail if it is not
+        #[clap(long)]
+        check: bool,
+    },
 }
 
-const ASSEMBLER_DEFAULT_OUT_FILE: &'static str = "microcode.bin";
-const DISASSEMBLER_DEFAULT_OUT_FILE: &'static str = "out.txt";
+const ASSEMBLER_DEFAULT_OUT_FILE: &str = "microcode.bin";
+const DISASSEMBLER_DEFAULT_OUT_FILE: &str = "out.txt";
 
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("{}", format!("❌ Error: {}", e.message));
-        if let Some(serr) = e.syntax_error {
-            eprintln!("{}", format!("{} (line {})", serr.message, serr.line));
+    match run() {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("❌ Error [{}]: {}", e.code(), e);
+            std::process::exit(e.exit_code());
         }
     }
 }
 
-fn run() -> Result<(), AssemblerError> {
+/// Returns whether the command succeeded, `fmt --check` fails on a file which is not formatted.
+fn run() -> Result<bool, AssemblerError> {
     let args = Args::parse();
 
     let input_file_path = &args.r#in;
     let output_file_path = &args.out;
 
-    if matches!(File::open(input_file_path), Err(_)) {
-        return Err(AssemblerError::new(
-            String::from("Failed to read the input file."),
-            None,
-        ));
+    if let Some(isa_path) = &args.isa {
+        set_isa(Isa::from_file(isa_path)?)?;
     }
 
     let start_time = Utc::now();
 
     match args.cmd {
-        Action::Assemble => {
+        Action::Assemble { optimize } => {
             let output_file_path = output_file_path
                 .clone()
                 .unwrap_or(String::from(ASSEMBLER_DEFAULT_OUT_FILE));
 
             println!("Assembling... '{}'", input_file_path);
-            assembler(input_file_path, &output_file_path)?;
+            let report = assembler(input_file_path, &output_file_path, optimize)?;
+
+            if optimize {
+                println!("{: <6}{: <13}{: >6}    AFTER", "NAME", "MODE", "BEFORE");
+                for r in report.iter().filter(|r| r.before != r.after) {
+                    println!("{}", r);
+                }
+
+                let saved: usize = report.iter().map(|r| r.before.1 - r.after.1).sum();
+                println!(
+                    "Optimized {} of {} instruction mode(s), {} step(s) saved in the longest paths",
+                    report.iter().filter(|r| r.before != r.after).count(),
+                    report.len(),
+                    saved
+                );
+            }
 
             let now = Utc::now();
             let delta_time = now - start_time;
@@ -87,7 +132,74 @@ fn run() -> Result<(), AssemblerError> {
                 delta_time.num_milliseconds()
             );
         }
+        Action::Verify => {
+            println!("Verifying... '{}'", input_file_path);
+            let conflicts = verifier(input_file_path)?;
+
+            for c in &conflicts {
+                println!("⚠️  {}", c);
+            }
+
+            if !conflicts.is_empty() {
+                return Err(AssemblerError::Semantic(SyntaxError::new(
+                    0,
+                    format!("Found {} conflict(s) in the microcode.", conflicts.len()),
+                )));
+            }
+
+            let now = Utc::now();
+            let delta_time = now - start_time;
+            println!(
+                "✔️  No conflicts found (after {}ms)",
+                delta_time.num_milliseconds()
+            );
+        }
+        Action::Coverage { strict, binary } => {
+            println!("Checking coverage... '{}'", input_file_path);
+            let report = coverage_report(input_file_path, binary)?;
+
+            for ins in &report {
+                print!("{}", ins);
+            }
+
+            let incomplete = report.iter().filter(|ins| !ins.is_complete()).count();
+
+            if strict && incomplete > 0 {
+                return Err(AssemblerError::Semantic(SyntaxError::new(
+                    0,
+                    format!("{} instruction(s) are not fully defined.", incomplete),
+                )));
+            }
+
+            let now = Utc::now();
+            let delta_time = now - start_time;
+            println!(
+                "✔️  Finished, {} instruction(s) not fully defined (after {}ms)",
+                incomplete,
+                delta_time.num_milliseconds()
+            );
+        }
+        Action::Timing => {
+            let table = timing_report(input_file_path)?;
+
+            println!("{: <6}{: <13}CYCLES", "NAME", "MODE");
+            for t in &table {
+                println!("{}", t);
+            }
+        }
+        Action::Fmt { check } => match (formatter(input_file_path, check)?, check) {
+            (Some(line), true) => {
+                eprintln!(
+                    "❌ {}:{}: The file is not formatted.",
+                    input_file_path, line
+                );
+                return Ok(false);
+            }
+            (Some(_), false) => println!("✔️  Formatted '{}'", input_file_path),
+            (None, true) => println!("✔️  '{}' is formatted", input_file_path),
+            (None, false) => println!("✔️  '{}' is already formatted", input_file_path),
+        },
     }
 
-    Ok(())
+    Ok(true)
 }
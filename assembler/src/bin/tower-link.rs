@@ -0,0 +1,46 @@
+use chrono::Utc;
+use clap::Parser;
+use tower_assembler::{link::linker, AssemblerError};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Object files to be linked, the first one holds the entry point
+    #[clap(required = true)]
+    objects: Vec<String>,
+
+    /// File to write the linked program to
+    #[clap(short, long, default_value = "program.bin")]
+    out: String,
+
+    /// File to write the memory map of the program to
+    #[clap(long)]
+    map: Option<String>,
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("❌ Error [{}]: {}", e.code(), e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run() -> Result<(), AssemblerError> {
+    let args = Args::parse();
+    let start_time = Utc::now();
+
+    println!("Linking... {}", args.objects.join(", "));
+    linker(&args.objects, &args.out, args.map.as_deref())?;
+
+    if let Some(map) = &args.map {
+        println!("Map written to '{}'", map);
+    }
+
+    let delta_time = Utc::now() - start_time;
+    println!(
+        "✔️  Finished and written to '{}' (after {}ms)",
+        args.out,
+        delta_time.num_milliseconds()
+    );
+    Ok(())
+}
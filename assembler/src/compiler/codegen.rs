@@ -0,0 +1,962 @@
+use std::collections::HashMap;
+
+use crate::{AssemblerError, SyntaxError, ZERO_PAGE_SIZE};
+
+use super::{BinaryOp, CompileOptions, Expr, Function, Program, Stmt, Type, UnaryOp, VarDecl};
+
+const START_LABEL: &str = "_start";
+const MAIN_FUNCTION: &str = "main";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Addr {
+    Fixed(u16),
+    /// Offset in the temporaries of the current function, resolved once the function is generated.
+    Temp(u16),
+}
+
+impl Addr {
+    fn offset(&self, n: u16) -> Addr {
+        match self {
+            Addr::Fixed(a) => Addr::Fixed(a.wrapping_add(n)),
+            Addr::Temp(o) => Addr::Temp(o + n),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Loc {
+    Const(u16),
+    Mem(Addr),
+}
+
+/// Result of an expression, words are stored in big endian order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Value {
+    ty: Type,
+    loc: Loc,
+}
+
+impl Value {
+    fn constant(n: u16) -> Value {
+        let ty = if n > 0xFF { Type::Word } else { Type::Byte };
+        Value {
+            ty,
+            loc: Loc::Const(n),
+        }
+    }
+
+    fn as_const(&self) -> Option<u16> {
+        match self.loc {
+            Loc::Const(n) => Some(n),
+            Loc::Mem(_) => None,
+        }
+    }
+
+    /// Returns the operand for loading the high or low byte, bytes have a high byte of 0.
+    fn part(&self, high: bool) -> Arg {
+        match (self.loc, self.ty, high) {
+            (Loc::Const(n), _, true) => Arg::Imm((n >> 8) as u8),
+            (Loc::Const(n), _, false) => Arg::Imm(n as u8),
+            (Loc::Mem(_), Type::Byte, true) => Arg::Imm(0),
+            (Loc::Mem(a), Type::Byte, false) => Arg::Load(a),
+            (Loc::Mem(a), Type::Word, true) => Arg::Load(a),
+            (Loc::Mem(a), Type::Word, false) => Arg::Load(a.offset(1)),
+        }
+    }
+}
+
+/// Address of the low byte or the high byte of a variable.
+fn byte_addr(addr: Addr, ty: Type, high: bool) -> Addr {
+    match (ty, high) {
+        (Type::Word, false) => addr.offset(1),
+        _ => addr,
+    }
+}
+
+/// Bytes of a value in the order they are computed, the low byte first so the wrap flag carries into the high one.
+fn parts(ty: Type) -> &'static [bool] {
+    match ty {
+        Type::Byte => &[false],
+        Type::Word => &[false, true],
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Arg {
+    None,
+    Accumulator,
+    Imm(u8),
+    Load(Addr),
+    Store(Addr),
+    Label(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Out {
+    Ins(&'static str, Arg),
+    Label(String),
+    Comment(String),
+    Empty,
+}
+
+#[derive(Debug, Clone)]
+struct Variable {
+    ty: Type,
+    address: u16,
+}
+
+#[derive(Debug, Clone)]
+struct FunctionInfo {
+    params: Vec<Variable>,
+    returns: Option<Variable>,
+    line: u32,
+}
+
+fn function_label(name: &str) -> String {
+    format!("_fn_{}", name)
+}
+
+/// Generates the assembly of a parsed program.
+pub fn generate(
+    program: &Program,
+    source: &str,
+    options: &CompileOptions,
+) -> Result<String, AssemblerError> {
+    let mut gen = Generator {
+        file: &options.file_name,
+        source: source.lines().collect(),
+        zero_page: options.zero_page,
+        next_free: 0,
+        allocations: Vec::new(),
+        globals: HashMap::new(),
+        functions: HashMap::new(),
+        scopes: Vec::new(),
+        current: None,
+        out: Vec::new(),
+        code: Vec::new(),
+        temps: 0,
+        max_temps: 0,
+        labels: 0,
+        loops: Vec::new(),
+        calls: Vec::new(),
+        last_comment: 0,
+    };
+
+    // allocate the globals and the parameters first, so functions can be called before they are defined
+    for var in &program.globals {
+        if gen.globals.contains_key(&var.name) {
+            return Err(gen.semantic(
+                var.line,
+                format!("Variable '{}' is already defined.", var.name),
+            ));
+        }
+        let address = match var.address {
+            Some(a) => a,
+            None => gen.allocate(var.ty.size(), &var.name, var.ty, var.line)?,
+        };
+        gen.globals.insert(
+            var.name.clone(),
+            Variable {
+                ty: var.ty,
+                address,
+            },
+        );
+    }
+
+    for f in &program.functions {
+        if gen.functions.contains_key(&f.name) {
+            return Err(gen.semantic(f.line, format!("Function '{}' is already defined.", f.name)));
+        }
+
+        let mut params = Vec::new();
+        for (idx, (name, ty)) in f.params.iter().enumerate() {
+            if f.params[..idx].iter().any(|(n, _)| n == name) {
+                return Err(
+                    gen.semantic(f.line, format!("Parameter '{}' is already defined.", name))
+                );
+            }
+            let description = format!("{}.{}", f.name, name);
+            let address = gen.allocate(ty.size(), &description, *ty, f.line)?;
+            params.push(Variable { ty: *ty, address });
+        }
+
+        let returns = match f.returns {
+            Some(ty) => {
+                let description = format!("{}.return", f.name);
+                let address = gen.allocate(ty.size(), &description, ty, f.line)?;
+                Some(Variable { ty, address })
+            }
+            None => None,
+        };
+
+        gen.functions.insert(
+            f.name.clone(),
+            FunctionInfo {
+                params,
+                returns,
+                line: f.line,
+            },
+        );
+    }
+
+    match program.functions.iter().find(|f| f.name == MAIN_FUNCTION) {
+        Some(main) if !main.params.is_empty() || main.returns.is_some() => {
+            return Err(gen.semantic(
+                main.line,
+                String::from("Function 'main' cannot have parameters or return a value."),
+            ))
+        }
+        Some(_) => {}
+        None => {
+            return Err(gen.semantic(0, String::from("Missing function 'main'.")));
+        }
+    }
+
+    // initial values of the globals, then the program itself
+    gen.out.push(Out::Label(START_LABEL.to_string()));
+    for var in &program.globals {
+        if let Some(value) = &var.value {
+            gen.comment(var.line);
+            let target = gen.globals[&var.name].clone();
+            gen.assign(&target, value, var.line)?;
+            gen.temps = 0;
+        }
+    }
+    gen.ins("JSR", Arg::Label(function_label(MAIN_FUNCTION)));
+    gen.ins("HLT", Arg::None);
+    gen.finish_function("start")?;
+
+    for f in &program.functions {
+        gen.function(f)?;
+    }
+
+    gen.check_recursion()?;
+    Ok(gen.render())
+}
+
+struct Generator<'a> {
+    file: &'a str,
+    source: Vec<&'a str>,
+    zero_page: u16,
+    /// Offset of the first free byte of the zero page.
+    next_free: u16,
+    /// (address, description) of the allocated variables.
+    allocations: Vec<(u16, String)>,
+
+    globals: HashMap<String, Variable>,
+    functions: HashMap<String, FunctionInfo>,
+    /// Local variables of the current function, the parameters are the outermost scope.
+    scopes: Vec<Vec<(String, Variable)>>,
+    current: Option<String>,
+
+    /// Code of the current function.
+    out: Vec<Out>,
+    /// Code of the finished functions.
+    code: Vec<Out>,
+    temps: u16,
+    max_temps: u16,
+    labels: u32,
+    /// (continue label, break label) of the enclosing loops.
+    loops: Vec<(String, String)>,
+    /// (caller, callee, line) of every call.
+    calls: Vec<(String, String, u32)>,
+    last_comment: u32,
+}
+
+impl Generator<'_> {
+    fn semantic(&self, line: u32, message: String) -> AssemblerError {
+        AssemblerError::Semantic(SyntaxError::in_file(self.file, line, message))
+    }
+
+    fn allocate(
+        &mut self,
+        size: u16,
+        description: &str,
+        ty: Type,
+        line: u32,
+    ) -> Result<u16, AssemblerError> {
+        if self.next_free + size > ZERO_PAGE_SIZE {
+            return Err(AssemblerError::Range(SyntaxError::in_file(
+                self.file,
+                line,
+                format!(
+                    "The zero page is full, {} byte(s) needed for '{}'.",
+                    size, description
+                ),
+            )));
+        }
+
+        let address = self.zero_page.wrapping_add(self.next_free);
+        self.next_free += size;
+        let ty = match ty {
+            Type::Byte => "byte",
+            Type::Word => "word",
+        };
+        self.allocations
+            .push((address, format!("{} ({})", description, ty)));
+        Ok(address)
+    }
+
+    fn ins(&mut self, name: &'static str, arg: Arg) {
+        self.out.push(Out::Ins(name, arg));
+    }
+
+    fn label(&mut self, label: String) {
+        self.out.push(Out::Label(label));
+    }
+
+    fn new_label(&mut self) -> String {
+        self.labels += 1;
+        format!("_l{}", self.labels)
+    }
+
+    /// Adds the source line as a comment, so the generated code can be followed.
+    fn comment(&mut self, line: u32) {
+        if line == self.last_comment {
+            return;
+        }
+        self.last_comment = line;
+        if let Some(text) = self.source.get(line as usize - 1) {
+            self.out.push(Out::Comment(text.trim().to_string()));
+        }
+    }
+
+    fn temp(&mut self, ty: Type) -> Addr {
+        let addr = Addr::Temp(self.temps);
+        self.temps += ty.size();
+        self.max_temps = self.max_temps.max(self.temps);
+        addr
+    }
+
+    /// Allocates the temporaries of the finished function and moves its code to the output.
+    fn finish_function(&mut self, name: &str) -> Result<(), AssemblerError> {
+        let base = if self.max_temps > 0 {
+            let description = format!("{}.temporaries[{}]", name, self.max_temps);
+            self.allocate(self.max_temps, &description, Type::Byte, 0)?
+        } else {
+            0
+        };
+
+        let resolve = |addr: Addr| match addr {
+            Addr::Temp(o) => Addr::Fixed(base.wrapping_add(o)),
+            fixed => fixed,
+        };
+        for out in self.out.drain(..) {
+            let out = match out {
+                Out::Ins(name, Arg::Load(a)) => Out::Ins(name, Arg::Load(resolve(a))),
+                Out::Ins(name, Arg::Store(a)) => Out::Ins(name, Arg::Store(resolve(a))),
+                other => other,
+            };
+            self.code.push(out);
+        }
+        self.code.push(Out::Empty);
+
+        self.temps = 0;
+        self.max_temps = 0;
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str, line: u32) -> Result<Variable, AssemblerError> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|s| s.iter().rev())
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+            .or_else(|| self.globals.get(name).cloned())
+            .ok_or_else(|| self.semantic(line, format!("Variable '{}' is not defined.", name)))
+    }
+
+    fn function(&mut self, f: &Function) -> Result<(), AssemblerError> {
+        let info = self.functions[&f.name].clone();
+        self.current = Some(f.name.clone());
+        self.scopes = vec![f
+            .params
+            .iter()
+            .zip(&info.params)
+            .map(|((name, _), var)| (name.clone(), var.clone()))
+            .collect()];
+
+        self.label(function_label(&f.name));
+        self.block(&f.body)?;
+        self.ins("RTS", Arg::None);
+
+        self.scopes.clear();
+        self.finish_function(&f.name)
+    }
+
+    fn block(&mut self, statements: &[Stmt]) -> Result<(), AssemblerError> {
+        self.scopes.push(Vec::new());
+        for stmt in statements {
+            self.statement(stmt)?;
+            // temporaries only live during a statement
+            self.temps = 0;
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), AssemblerError> {
+        match stmt {
+            Stmt::Var(VarDecl {
+                name,
+                ty,
+                value,
+                line,
+                ..
+            }) => {
+                self.comment(*line);
+                let scope = self.scopes.last().unwrap();
+                if scope.iter().any(|(n, _)| n == name) {
+                    return Err(
+                        self.semantic(*line, format!("Variable '{}' is already defined.", name))
+                    );
+                }
+
+                let description = format!("{}.{}", self.current.clone().unwrap_or_default(), name);
+                let address = self.allocate(ty.size(), &description, *ty, *line)?;
+                let var = Variable { ty: *ty, address };
+                self.scopes
+                    .last_mut()
+                    .unwrap()
+                    .push((name.clone(), var.clone()));
+
+                if let Some(value) = value {
+                    self.assign(&var, value, *line)?;
+                }
+            }
+            Stmt::Assign(name, value, line) => {
+                self.comment(*line);
+                let var = self.lookup(name, *line)?;
+                self.assign(&var, value, *line)?;
+            }
+            Stmt::If(cond, then, otherwise, line) => {
+                self.comment(*line);
+                let (then_label, else_label, end_label) =
+                    (self.new_label(), self.new_label(), self.new_label());
+
+                self.condition(cond, &then_label, &else_label, *line)?;
+                self.label(then_label);
+                self.block(then)?;
+                self.ins("JMP", Arg::Label(end_label.clone()));
+                self.label(else_label);
+                self.block(otherwise)?;
+                self.label(end_label);
+            }
+            Stmt::While(cond, body, line) => {
+                self.comment(*line);
+                let (start_label, body_label, end_label) =
+                    (self.new_label(), self.new_label(), self.new_label());
+
+                self.label(start_label.clone());
+                self.condition(cond, &body_label, &end_label, *line)?;
+                self.label(body_label);
+
+                self.loops.push((start_label.clone(), end_label.clone()));
+                self.block(body)?;
+                self.loops.pop();
+
+                self.ins("JMP", Arg::Label(start_label));
+                self.label(end_label);
+            }
+            Stmt::Return(value, line) => {
+                self.comment(*line);
+                let name = self.current.clone().unwrap_or_default();
+                let returns = self.functions[&name].returns.clone();
+
+                match (value, returns) {
+                    (Some(value), Some(target)) => self.assign(&target, value, *line)?,
+                    (None, None) => {}
+                    (Some(_), None) => {
+                        return Err(self.semantic(
+                            *line,
+                            format!("Function '{}' does not return a value.", name),
+                        ))
+                    }
+                    (None, Some(_)) => {
+                        return Err(self.semantic(
+                            *line,
+                            format!("Function '{}' has to return a value.", name),
+                        ))
+                    }
+                }
+                self.ins("RTS", Arg::None);
+            }
+            Stmt::Break(line) | Stmt::Continue(line) => {
+                self.comment(*line);
+                let Some((continue_label, break_label)) = self.loops.last().cloned() else {
+                    return Err(self.semantic(
+                        *line,
+                        String::from("'break' and 'continue' can only be used in a loop."),
+                    ));
+                };
+                let target = match stmt {
+                    Stmt::Break(_) => break_label,
+                    _ => continue_label,
+                };
+                self.ins("JMP", Arg::Label(target));
+            }
+            Stmt::Expr(expr, line) => {
+                self.comment(*line);
+                match expr {
+                    Expr::Call(name, args) => {
+                        self.call(name, args, *line)?;
+                    }
+                    _ => {
+                        self.expr(expr, *line)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Stores a value, truncating words stored in bytes.
+    fn store(&mut self, value: Value, addr: Addr, ty: Type) {
+        for high in parts(ty).iter().rev() {
+            self.ins("LDA", value.part(*high));
+            self.ins("STA", Arg::Store(byte_addr(addr, ty, *high)));
+        }
+    }
+
+    fn assign(&mut self, var: &Variable, value: &Expr, line: u32) -> Result<(), AssemblerError> {
+        let value = self.expr(value, line)?;
+        self.store(value, Addr::Fixed(var.address), var.ty);
+        Ok(())
+    }
+
+    /// Calls a function, returns the location of its return value.
+    fn call(
+        &mut self,
+        name: &str,
+        args: &[Expr],
+        line: u32,
+    ) -> Result<Option<Value>, AssemblerError> {
+        let Some(info) = self.functions.get(name).cloned() else {
+            return Err(self.semantic(line, format!("Function '{}' is not defined.", name)));
+        };
+        if args.len() != info.params.len() {
+            return Err(self.semantic(
+                line,
+                format!(
+                    "Wrong number of arguments for function '{}'. This function requires {} arguments.",
+                    name,
+                    info.params.len()
+                ),
+            ));
+        }
+
+        // evaluate all arguments before setting any parameter, an argument can call the same function
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.expr(arg, line)?);
+        }
+        for (value, param) in values.into_iter().zip(&info.params) {
+            self.store(value, Addr::Fixed(param.address), param.ty);
+        }
+
+        self.calls.push((
+            self.current.clone().unwrap_or_default(),
+            name.to_string(),
+            line,
+        ));
+        self.ins("JSR", Arg::Label(function_label(name)));
+
+        Ok(info.returns.map(|r| Value {
+            ty: r.ty,
+            loc: Loc::Mem(Addr::Fixed(r.address)),
+        }))
+    }
+
+    fn expr(&mut self, expr: &Expr, line: u32) -> Result<Value, AssemblerError> {
+        match expr {
+            Expr::Number(n) => Ok(Value::constant(*n as u16)),
+            Expr::Variable(name) => {
+                let var = self.lookup(name, line)?;
+                Ok(Value {
+                    ty: var.ty,
+                    loc: Loc::Mem(Addr::Fixed(var.address)),
+                })
+            }
+            Expr::Call(name, args) => {
+                let Some(result) = self.call(name, args, line)? else {
+                    return Err(self.semantic(
+                        line,
+                        format!("Function '{}' does not return a value.", name),
+                    ));
+                };
+                // the next call of the function overwrites its return value
+                let temp = self.temp(result.ty);
+                self.store(result, temp, result.ty);
+                Ok(Value {
+                    ty: result.ty,
+                    loc: Loc::Mem(temp),
+                })
+            }
+            Expr::Unary(UnaryOp::LogicalNot, _) => self.truth_value(expr, line),
+            Expr::Binary(op, _, _) if op.is_condition() => self.truth_value(expr, line),
+            Expr::Unary(op, operand) => {
+                let value = self.expr(operand, line)?;
+                if let Some(n) = value.as_const() {
+                    return Ok(Value::constant(match op {
+                        UnaryOp::Negate => n.wrapping_neg(),
+                        _ => !n & if value.ty == Type::Word { 0xFFFF } else { 0xFF },
+                    }));
+                }
+
+                let dest = self.temp(value.ty);
+                for (idx, high) in parts(value.ty).iter().enumerate() {
+                    if *op == UnaryOp::Negate {
+                        self.ins("LDA", Arg::Imm(0));
+                        self.ins(if idx == 0 { "SUB" } else { "SBB" }, value.part(*high));
+                    } else {
+                        self.ins("LDA", value.part(*high));
+                        self.ins("NOT", Arg::Accumulator);
+                    }
+                    self.ins("STA", Arg::Store(byte_addr(dest, value.ty, *high)));
+                }
+                Ok(Value {
+                    ty: value.ty,
+                    loc: Loc::Mem(dest),
+                })
+            }
+            Expr::Binary(op, left, right) => {
+                let left = self.expr(left, line)?;
+                let right = self.expr(right, line)?;
+                match op {
+                    BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+                        let Some(count) = right.as_const() else {
+                            return Err(self.semantic(
+                                line,
+                                String::from("The number of shifted bits has to be a constant."),
+                            ));
+                        };
+                        Ok(self.shift(*op, left, count))
+                    }
+                    _ => Ok(self.arithmetic(*op, left, right)),
+                }
+            }
+        }
+    }
+
+    fn arithmetic(&mut self, op: BinaryOp, left: Value, right: Value) -> Value {
+        let ty = left.ty.max(right.ty);
+
+        if let (Some(l), Some(r)) = (left.as_const(), right.as_const()) {
+            return Value::constant(match op {
+                BinaryOp::Add => l.wrapping_add(r),
+                BinaryOp::Sub => l.wrapping_sub(r),
+                BinaryOp::And => l & r,
+                BinaryOp::Or => l | r,
+                _ => l ^ r,
+            });
+        }
+
+        let dest = self.temp(ty);
+        let scratch = match op {
+            BinaryOp::Xor => Some(self.temp(Type::Byte)),
+            _ => None,
+        };
+
+        for (idx, high) in parts(ty).iter().enumerate() {
+            let (l, r) = (left.part(*high), right.part(*high));
+            let d = byte_addr(dest, ty, *high);
+
+            match op {
+                BinaryOp::Add | BinaryOp::Sub => {
+                    let name = match (op, idx) {
+                        (BinaryOp::Add, 0) => "ADD",
+                        (BinaryOp::Add, _) => "ADC",
+                        (_, 0) => "SUB",
+                        _ => "SBB",
+                    };
+                    self.ins("LDA", l);
+                    self.ins(name, r);
+                }
+                BinaryOp::And => {
+                    self.ins("LDA", l);
+                    self.ins("NAND", r);
+                    self.ins("NOT", Arg::Accumulator);
+                }
+                BinaryOp::Or => {
+                    // l | r = ~l NAND ~r
+                    self.ins("LDA", r);
+                    self.ins("NOT", Arg::Accumulator);
+                    self.ins("STA", Arg::Store(d));
+                    self.ins("LDA", l);
+                    self.ins("NOT", Arg::Accumulator);
+                    self.ins("NAND", Arg::Load(d));
+                }
+                _ => {
+                    // l ^ r = (l NAND n) NAND (r NAND n) where n = l NAND r
+                    let s = scratch.unwrap();
+                    self.ins("LDA", l.clone());
+                    self.ins("NAND", r.clone());
+                    self.ins("STA", Arg::Store(d));
+                    self.ins("LDA", l);
+                    self.ins("NAND", Arg::Load(d));
+                    self.ins("STA", Arg::Store(s));
+                    self.ins("LDA", r);
+                    self.ins("NAND", Arg::Load(d));
+                    self.ins("NAND", Arg::Load(s));
+                }
+            }
+            self.ins("STA", Arg::Store(d));
+        }
+
+        Value {
+            ty,
+            loc: Loc::Mem(dest),
+        }
+    }
+
+    fn shift(&mut self, op: BinaryOp, value: Value, count: u16) -> Value {
+        if let Some(n) = value.as_const() {
+            let n = match op {
+                BinaryOp::ShiftLeft => n.checked_shl(count as u32).unwrap_or(0),
+                _ => n.checked_shr(count as u32).unwrap_or(0),
+            };
+            return Value::constant(n);
+        }
+
+        let ty = value.ty;
+        let dest = self.temp(ty);
+        self.store(value, dest, ty);
+        let (high, low) = (byte_addr(dest, ty, true), byte_addr(dest, ty, false));
+
+        // shifting by the size of the value or more leaves nothing
+        let count = count.min(ty.size() * 8);
+        for _ in 0..count {
+            match (op, ty) {
+                (BinaryOp::ShiftLeft, Type::Byte) => {
+                    self.ins("LDA", Arg::Load(low));
+                    self.ins("SL", Arg::Accumulator);
+                }
+                (_, Type::Byte) => {
+                    self.ins("LDA", Arg::Load(low));
+                    self.ins("SR", Arg::Accumulator);
+                }
+                (BinaryOp::ShiftLeft, Type::Word) => {
+                    // the wrap flag carries the shifted out bit into the high byte
+                    self.ins("LDA", Arg::Load(low));
+                    self.ins("SL", Arg::Accumulator);
+                    self.ins("STA", Arg::Store(low));
+                    self.ins("LDA", Arg::Load(high));
+                    self.ins("ADC", Arg::Load(high));
+                    self.ins("STA", Arg::Store(high));
+                    continue;
+                }
+                (_, Type::Word) => {
+                    // SR does not use the wrap flag, the lowest bit of the high byte is moved by hand
+                    let skip = self.new_label();
+                    self.ins("LDA", Arg::Load(low));
+                    self.ins("SR", Arg::Accumulator);
+                    self.ins("STA", Arg::Store(low));
+                    self.ins("LDA", Arg::Load(high));
+                    self.ins("NAND", Arg::Imm(1));
+                    self.ins("CMP", Arg::Imm(0xFF));
+                    self.ins("JZ", Arg::Label(skip.clone()));
+                    self.ins("LDA", Arg::Load(low));
+                    self.ins("ADD", Arg::Imm(0x80));
+                    self.ins("STA", Arg::Store(low));
+                    self.label(skip);
+                    self.ins("LDA", Arg::Load(high));
+                    self.ins("SR", Arg::Accumulator);
+                    self.ins("STA", Arg::Store(high));
+                    continue;
+                }
+            }
+            self.ins("STA", Arg::Store(low));
+        }
+
+        Value {
+            ty,
+            loc: Loc::Mem(dest),
+        }
+    }
+
+    /// Turns a condition into 1 (true) or 0 (false).
+    fn truth_value(&mut self, expr: &Expr, line: u32) -> Result<Value, AssemblerError> {
+        let dest = self.temp(Type::Byte);
+        let (true_label, false_label, end_label) =
+            (self.new_label(), self.new_label(), self.new_label());
+
+        self.condition(expr, &true_label, &false_label, line)?;
+        for (label, value) in [(true_label, 1), (false_label, 0)] {
+            self.label(label);
+            self.ins("LDA", Arg::Imm(value));
+            self.ins("STA", Arg::Store(dest));
+            self.ins("JMP", Arg::Label(end_label.clone()));
+        }
+        self.label(end_label);
+
+        Ok(Value {
+            ty: Type::Byte,
+            loc: Loc::Mem(dest),
+        })
+    }
+
+    /// Jumps to one of the labels depending on the condition.
+    fn condition(
+        &mut self,
+        expr: &Expr,
+        true_label: &str,
+        false_label: &str,
+        line: u32,
+    ) -> Result<(), AssemblerError> {
+        let jump = |label: &str| Arg::Label(label.to_string());
+
+        match expr {
+            Expr::Unary(UnaryOp::LogicalNot, operand) => {
+                self.condition(operand, false_label, true_label, line)
+            }
+            Expr::Binary(BinaryOp::LogicalAnd, left, right) => {
+                let next = self.new_label();
+                self.condition(left, &next, false_label, line)?;
+                self.label(next);
+                self.condition(right, true_label, false_label, line)
+            }
+            Expr::Binary(BinaryOp::LogicalOr, left, right) => {
+                let next = self.new_label();
+                self.condition(left, true_label, &next, line)?;
+                self.label(next);
+                self.condition(right, true_label, false_label, line)
+            }
+            Expr::Binary(op, left, right) if op.is_condition() => {
+                let left = self.expr(left, line)?;
+                let right = self.expr(right, line)?;
+
+                match op {
+                    BinaryOp::Equal => self.equal(left, right, true_label, false_label),
+                    BinaryOp::NotEqual => self.equal(left, right, false_label, true_label),
+                    BinaryOp::Less => self.less(left, right, true_label, false_label),
+                    BinaryOp::GreaterEqual => self.less(left, right, false_label, true_label),
+                    BinaryOp::Greater => self.less(right, left, true_label, false_label),
+                    _ => self.less(right, left, false_label, true_label),
+                }
+                Ok(())
+            }
+            _ => {
+                let value = self.expr(expr, line)?;
+                if let Some(n) = value.as_const() {
+                    self.ins("JMP", jump(if n != 0 { true_label } else { false_label }));
+                    return Ok(());
+                }
+
+                for high in parts(value.ty).iter().rev() {
+                    self.ins("LDA", value.part(*high));
+                    self.ins("CMP", Arg::Imm(0));
+                    self.ins("JNZ", jump(true_label));
+                }
+                self.ins("JMP", jump(false_label));
+                Ok(())
+            }
+        }
+    }
+
+    fn equal(&mut self, left: Value, right: Value, equal_label: &str, different_label: &str) {
+        if let (Some(l), Some(r)) = (left.as_const(), right.as_const()) {
+            let target = if l == r { equal_label } else { different_label };
+            self.ins("JMP", Arg::Label(target.to_string()));
+            return;
+        }
+
+        let ty = left.ty.max(right.ty);
+        for high in parts(ty).iter().rev() {
+            self.ins("LDA", left.part(*high));
+            self.ins("CMP", right.part(*high));
+            self.ins("JNZ", Arg::Label(different_label.to_string()));
+        }
+        self.ins("JMP", Arg::Label(equal_label.to_string()));
+    }
+
+    fn less(&mut self, left: Value, right: Value, less_label: &str, other_label: &str) {
+        if let (Some(l), Some(r)) = (left.as_const(), right.as_const()) {
+            let target = if l < r { less_label } else { other_label };
+            self.ins("JMP", Arg::Label(target.to_string()));
+            return;
+        }
+
+        // the subtraction borrows (sets the wrap flag) when left < right
+        match left.ty.max(right.ty) {
+            Type::Byte => {
+                self.ins("LDA", left.part(false));
+                self.ins("CMP", right.part(false));
+            }
+            Type::Word => {
+                self.ins("LDA", left.part(false));
+                self.ins("SUB", right.part(false));
+                self.ins("LDA", left.part(true));
+                self.ins("SBB", right.part(true));
+            }
+        }
+        self.ins("JW", Arg::Label(less_label.to_string()));
+        self.ins("JMP", Arg::Label(other_label.to_string()));
+    }
+
+    /// Variables of functions are not on the stack, so a function cannot call itself.
+    fn check_recursion(&self) -> Result<(), AssemblerError> {
+        for name in self.functions.keys() {
+            // depth first search for a path leading back to the function
+            let mut stack: Vec<(String, u32)> = vec![(name.clone(), self.functions[name].line)];
+            let mut visited: Vec<String> = Vec::new();
+
+            while let Some((caller, _)) = stack.pop() {
+                for (_, callee, line) in self.calls.iter().filter(|(c, _, _)| *c == caller) {
+                    if callee == name {
+                        return Err(self.semantic(
+                            *line,
+                            format!(
+                                "Function '{}' is called recursively, functions cannot be recursive since their variables are not on the stack.",
+                                name
+                            ),
+                        ));
+                    }
+                    if !visited.contains(callee) {
+                        visited.push(callee.clone());
+                        stack.push((callee.clone(), *line));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn render(&self) -> String {
+        let mut output = format!("; compiled from {}\n;\n; zero page\n", self.file);
+        for (address, description) in &self.allocations {
+            output += &format!("; 0x{:04X} {}\n", address, description);
+        }
+        output.push('\n');
+
+        let address = |a: &Addr| match a {
+            Addr::Fixed(a) => format!("0x{:04X}", a),
+            Addr::Temp(_) => unreachable!("temporaries are resolved when a function is finished"),
+        };
+
+        for out in &self.code {
+            match out {
+                Out::Label(label) => output += &format!("{}:\n", label),
+                Out::Comment(text) => output += &format!("\t; {}\n", text),
+                Out::Empty => output.push('\n'),
+                Out::Ins(name, arg) => {
+                    let arg = match arg {
+                        Arg::None => String::new(),
+                        Arg::Accumulator => String::from(" %A"),
+                        Arg::Imm(n) => format!(" #{}", n),
+                        Arg::Load(a) => format!(" *{}", address(a)),
+                        Arg::Store(a) => format!(" &{}", address(a)),
+                        Arg::Label(l) => format!(" {}", l),
+                    };
+                    output += &format!("\t{}{}\n", name, arg);
+                }
+            }
+        }
+
+        // a single line break at the end of the file
+        while output.ends_with("\n\n") {
+            output.pop();
+        }
+        output
+    }
+}
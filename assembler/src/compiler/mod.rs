@@ -0,0 +1,151 @@
+pub mod codegen;
+pub mod parser;
+
+use crate::{read_file, write_file, AssemblerError, ZERO_PAGE_START};
+
+// ==============================================
+// =             SHARED DEFINITIONS             =
+// ==============================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Type {
+    Byte,
+    Word,
+}
+
+impl Type {
+    pub fn size(&self) -> u16 {
+        match self {
+            Type::Byte => 1,
+            Type::Word => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+    LogicalNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    And,
+    Or,
+    Xor,
+    ShiftLeft,
+    ShiftRight,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    LogicalAnd,
+    LogicalOr,
+}
+
+impl BinaryOp {
+    /// Comparisons and logical operators produce a truth value (0 or 1).
+    pub fn is_condition(&self) -> bool {
+        !matches!(
+            self,
+            BinaryOp::Add
+                | BinaryOp::Sub
+                | BinaryOp::And
+                | BinaryOp::Or
+                | BinaryOp::Xor
+                | BinaryOp::ShiftLeft
+                | BinaryOp::ShiftRight
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(u32),
+    Variable(String),
+    Call(String, Vec<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VarDecl {
+    pub name: String,
+    pub ty: Type,
+    /// Fixed address of the variable (e.g. memory mapped I/O), variables are allocated in the zero page otherwise.
+    pub address: Option<u16>,
+    pub value: Option<Expr>,
+    pub line: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Var(VarDecl),
+    Assign(String, Expr, u32),
+    If(Expr, Vec<Stmt>, Vec<Stmt>, u32),
+    While(Expr, Vec<Stmt>, u32),
+    Return(Option<Expr>, u32),
+    Break(u32),
+    Continue(u32),
+    Expr(Expr, u32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<(String, Type)>,
+    pub returns: Option<Type>,
+    pub body: Vec<Stmt>,
+    pub line: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Program {
+    pub globals: Vec<VarDecl>,
+    pub functions: Vec<Function>,
+}
+
+pub struct CompileOptions {
+    /// Name of the source, used by diagnostics.
+    pub file_name: String,
+    /// First address of the zero page, where variables are allocated.
+    pub zero_page: u16,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        CompileOptions {
+            file_name: String::from("<input>"),
+            zero_page: ZERO_PAGE_START,
+        }
+    }
+}
+// ==============================================
+
+/// Compiles a program to Tower assembly, which can be assembled with `asm::asm::assemble_str`.
+pub fn compile_str(source: &str, options: &CompileOptions) -> Result<String, AssemblerError> {
+    let program = parser::parse(source, &options.file_name)?;
+    codegen::generate(&program, source, options)
+}
+
+/// Compiles a source file and writes the generated assembly to the output file.
+pub fn compiler(
+    file_in: &str,
+    file_out: &str,
+    options: &CompileOptions,
+) -> Result<String, AssemblerError> {
+    let source = read_file(file_in)?;
+    let options = CompileOptions {
+        file_name: file_in.to_string(),
+        zero_page: options.zero_page,
+    };
+
+    let code = compile_str(&source, &options)?;
+    write_file(file_out, code.as_bytes())?;
+    Ok(code)
+}
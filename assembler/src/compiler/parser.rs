@@ -0,0 +1,457 @@
+use crate::{AssemblerError, SyntaxError};
+
+use super::{BinaryOp, Expr, Function, Program, Stmt, Type, UnaryOp, VarDecl};
+
+const KEYWORDS: [&str; 10] = [
+    "byte", "word", "func", "if", "else", "while", "return", "break", "continue", "at",
+];
+
+/// Longer symbols first, so `<=` is not read as `<` and `=`.
+const SYMBOLS: [&str; 26] = [
+    "==", "!=", "<=", ">=", "<<", ">>", "&&", "||", "+=", "-=", "->", "+", "-", "&", "|", "^", "~",
+    "!", "<", ">", "=", "(", ")", "{", "}", ",",
+];
+const TERMINATOR: &str = ";";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    /// Identifiers and keywords, lower-cased since the language is case insensitive.
+    Ident(String),
+    Number(u32),
+    Symbol(&'static str),
+    Eof,
+}
+
+impl std::fmt::Display for Tok {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tok::Ident(name) => write!(f, "'{}'", name),
+            Tok::Number(n) => write!(f, "'{}'", n),
+            Tok::Symbol(s) => write!(f, "'{}'", s),
+            Tok::Eof => write!(f, "the end of the file"),
+        }
+    }
+}
+
+fn parse_number(word: &str) -> Option<u32> {
+    if let Some(hex) = word.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = word.strip_prefix("0b") {
+        u32::from_str_radix(bin, 2).ok()
+    } else {
+        word.parse().ok()
+    }
+}
+
+fn tokenize(source: &str, file: &str) -> Result<Vec<(Tok, u32)>, AssemblerError> {
+    let mut tokens = Vec::new();
+
+    for (line_idx, line) in source.lines().enumerate() {
+        let real_line = line_idx as u32 + 1;
+        let code = match line.find("//") {
+            Some(idx) => &line[..idx],
+            None => line,
+        };
+
+        let mut rest = code.trim_start();
+        while !rest.is_empty() {
+            let word_len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+
+            let (tok, len) = if word_len > 0 {
+                let word = rest[..word_len].to_lowercase();
+                let tok = if word.starts_with(|c: char| c.is_ascii_digit()) {
+                    match parse_number(&word) {
+                        Some(n) if n <= 0xFFFF => Tok::Number(n),
+                        Some(_) => {
+                            return Err(AssemblerError::Range(SyntaxError::in_file(
+                                file,
+                                real_line,
+                                format!("Value '{}' does not fit into a word.", word),
+                            )))
+                        }
+                        None => {
+                            return Err(AssemblerError::Lex(SyntaxError::in_file(
+                                file,
+                                real_line,
+                                format!("Failed to parse value '{}'.", word),
+                            )))
+                        }
+                    }
+                } else {
+                    Tok::Ident(word)
+                };
+                (tok, word_len)
+            } else if rest.starts_with(TERMINATOR) {
+                (Tok::Symbol(TERMINATOR), 1)
+            } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+                (Tok::Symbol(symbol), symbol.len())
+            } else {
+                let c = rest.chars().next().unwrap();
+                let message = match c {
+                    '*' | '/' | '%' => format!(
+                        "Unsupported operator '{}', the ISA has no multiplication or division.",
+                        c
+                    ),
+                    _ => format!("Unexpected character '{}'.", c),
+                };
+                return Err(AssemblerError::Lex(SyntaxError::in_file(
+                    file, real_line, message,
+                )));
+            };
+
+            tokens.push((tok, real_line));
+            rest = rest[len..].trim_start();
+        }
+    }
+
+    let last_line = source.lines().count() as u32;
+    tokens.push((Tok::Eof, last_line));
+    Ok(tokens)
+}
+
+/// Parses a program written in the Tower language.
+pub fn parse(source: &str, file: &str) -> Result<Program, AssemblerError> {
+    let tokens = tokenize(source, file)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        file,
+    };
+
+    let mut program = Program::default();
+    while parser.peek() != &Tok::Eof {
+        if parser.is_ident("func") {
+            program.functions.push(parser.function()?);
+        } else if parser.is_type() {
+            program.globals.push(parser.var_decl(true)?);
+        } else {
+            return Err(parser.error(format!(
+                "Expected a variable or a function, found {}.",
+                parser.peek()
+            )));
+        }
+    }
+    Ok(program)
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Tok, u32)>,
+    pos: usize,
+    file: &'a str,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> &Tok {
+        &self.tokens[self.pos].0
+    }
+
+    fn line(&self) -> u32 {
+        self.tokens[self.pos].1
+    }
+
+    fn next(&mut self) -> Tok {
+        let tok = self.tokens[self.pos].0.clone();
+        if tok != Tok::Eof {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn error(&self, message: String) -> AssemblerError {
+        AssemblerError::Parse(SyntaxError::in_file(self.file, self.line(), message))
+    }
+
+    fn is_ident(&self, name: &str) -> bool {
+        matches!(self.peek(), Tok::Ident(i) if i == name)
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Tok::Symbol(s) if *s == symbol)
+    }
+
+    fn is_type(&self) -> bool {
+        self.is_ident("byte") || self.is_ident("word")
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = self.is_symbol(symbol);
+        if found {
+            self.next();
+        }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), AssemblerError> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(format!("Expected '{}', found {}.", symbol, self.peek())))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, AssemblerError> {
+        match self.peek().clone() {
+            Tok::Ident(name) if !KEYWORDS.contains(&name.as_str()) => {
+                self.next();
+                Ok(name)
+            }
+            tok => Err(self.error(format!("Expected a name, found {}.", tok))),
+        }
+    }
+
+    fn ty(&mut self) -> Result<Type, AssemblerError> {
+        let ty = match self.peek() {
+            Tok::Ident(t) if t == "byte" => Type::Byte,
+            Tok::Ident(t) if t == "word" => Type::Word,
+            tok => return Err(self.error(format!("Expected a type, found {}.", tok))),
+        };
+        self.next();
+        Ok(ty)
+    }
+
+    fn var_decl(&mut self, is_global: bool) -> Result<VarDecl, AssemblerError> {
+        let line = self.line();
+        let ty = self.ty()?;
+        let name = self.name()?;
+
+        let address = if self.is_ident("at") {
+            if !is_global {
+                return Err(self.error(String::from(
+                    "Only global variables can be placed at a fixed address.",
+                )));
+            }
+            self.next();
+            match self.next() {
+                Tok::Number(n) => Some(n as u16),
+                tok => return Err(self.error(format!("Expected an address, found {}.", tok))),
+            }
+        } else {
+            None
+        };
+
+        let value = if self.eat_symbol("=") {
+            Some(self.expr()?)
+        } else {
+            None
+        };
+        self.expect_symbol(TERMINATOR)?;
+
+        Ok(VarDecl {
+            name,
+            ty,
+            address,
+            value,
+            line,
+        })
+    }
+
+    fn function(&mut self) -> Result<Function, AssemblerError> {
+        let line = self.line();
+        self.next();
+        let name = self.name()?;
+
+        self.expect_symbol("(")?;
+        let mut params = Vec::new();
+        if !self.is_symbol(")") {
+            loop {
+                let ty = self.ty()?;
+                params.push((self.name()?, ty));
+                if !self.eat_symbol(",") {
+                    break;
+                }
+            }
+        }
+        self.expect_symbol(")")?;
+
+        let returns = if self.eat_symbol("->") {
+            Some(self.ty()?)
+        } else {
+            None
+        };
+
+        Ok(Function {
+            name,
+            params,
+            returns,
+            body: self.block()?,
+            line,
+        })
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, AssemblerError> {
+        self.expect_symbol("{")?;
+        let mut statements = Vec::new();
+        while !self.eat_symbol("}") {
+            if self.peek() == &Tok::Eof {
+                return Err(self.error(String::from("Missing '}' at the end of a block.")));
+            }
+            statements.push(self.statement()?);
+        }
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> Result<Stmt, AssemblerError> {
+        let line = self.line();
+
+        if self.is_type() {
+            return Ok(Stmt::Var(self.var_decl(false)?));
+        }
+
+        let keyword = match self.peek() {
+            Tok::Ident(k) => k.clone(),
+            _ => String::new(),
+        };
+        let stmt = match keyword.as_str() {
+            "if" => {
+                self.next();
+                let cond = self.condition()?;
+                let then = self.block()?;
+                let otherwise = if self.is_ident("else") {
+                    self.next();
+                    if self.is_ident("if") {
+                        vec![self.statement()?]
+                    } else {
+                        self.block()?
+                    }
+                } else {
+                    Vec::new()
+                };
+                return Ok(Stmt::If(cond, then, otherwise, line));
+            }
+            "while" => {
+                self.next();
+                let cond = self.condition()?;
+                return Ok(Stmt::While(cond, self.block()?, line));
+            }
+            "return" => {
+                self.next();
+                let value = if self.is_symbol(TERMINATOR) {
+                    None
+                } else {
+                    Some(self.expr()?)
+                };
+                Stmt::Return(value, line)
+            }
+            "break" => {
+                self.next();
+                Stmt::Break(line)
+            }
+            "continue" => {
+                self.next();
+                Stmt::Continue(line)
+            }
+            _ => {
+                let is_assignment = matches!(self.tokens.get(self.pos + 1),
+                    Some((Tok::Symbol(s), _)) if ["=", "+=", "-="].contains(s));
+
+                if is_assignment {
+                    let name = self.name()?;
+                    let value = match self.next() {
+                        Tok::Symbol("+=") => Expr::Binary(
+                            BinaryOp::Add,
+                            Box::new(Expr::Variable(name.clone())),
+                            Box::new(self.expr()?),
+                        ),
+                        Tok::Symbol("-=") => Expr::Binary(
+                            BinaryOp::Sub,
+                            Box::new(Expr::Variable(name.clone())),
+                            Box::new(self.expr()?),
+                        ),
+                        _ => self.expr()?,
+                    };
+                    Stmt::Assign(name, value, line)
+                } else {
+                    Stmt::Expr(self.expr()?, line)
+                }
+            }
+        };
+        self.expect_symbol(TERMINATOR)?;
+        Ok(stmt)
+    }
+
+    fn condition(&mut self) -> Result<Expr, AssemblerError> {
+        self.expect_symbol("(")?;
+        let cond = self.expr()?;
+        self.expect_symbol(")")?;
+        Ok(cond)
+    }
+
+    fn expr(&mut self) -> Result<Expr, AssemblerError> {
+        self.binary(0)
+    }
+
+    /// Parses binary operators, `level` indexes the precedence table (lowest precedence first).
+    fn binary(&mut self, level: usize) -> Result<Expr, AssemblerError> {
+        const LEVELS: [&[(&str, BinaryOp)]; 8] = [
+            &[("||", BinaryOp::LogicalOr)],
+            &[("&&", BinaryOp::LogicalAnd)],
+            &[
+                ("==", BinaryOp::Equal),
+                ("!=", BinaryOp::NotEqual),
+                ("<=", BinaryOp::LessEqual),
+                (">=", BinaryOp::GreaterEqual),
+                ("<", BinaryOp::Less),
+                (">", BinaryOp::Greater),
+            ],
+            &[("|", BinaryOp::Or)],
+            &[("^", BinaryOp::Xor)],
+            &[("&", BinaryOp::And)],
+            &[("<<", BinaryOp::ShiftLeft), (">>", BinaryOp::ShiftRight)],
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+        ];
+
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+
+        let mut left = self.binary(level + 1)?;
+        while let Some((_, op)) = LEVELS[level].iter().find(|(s, _)| self.is_symbol(s)) {
+            self.next();
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(*op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, AssemblerError> {
+        let op = match self.peek() {
+            Tok::Symbol("-") => UnaryOp::Negate,
+            Tok::Symbol("~") => UnaryOp::Not,
+            Tok::Symbol("!") => UnaryOp::LogicalNot,
+            _ => return self.primary(),
+        };
+        self.next();
+        Ok(Expr::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Expr, AssemblerError> {
+        match self.peek().clone() {
+            Tok::Number(n) => {
+                self.next();
+                Ok(Expr::Number(n))
+            }
+            Tok::Symbol("(") => self.condition(),
+            Tok::Ident(_) => {
+                let name = self.name()?;
+                if !self.eat_symbol("(") {
+                    return Ok(Expr::Variable(name));
+                }
+
+                let mut args = Vec::new();
+                if !self.is_symbol(")") {
+                    loop {
+                        args.push(self.expr()?);
+                        if !self.eat_symbol(",") {
+                            break;
+                        }
+                    }
+                }
+                self.expect_symbol(")")?;
+                Ok(Expr::Call(name, args))
+            }
+            tok => Err(self.error(format!("Expected a value, found {}.", tok))),
+        }
+    }
+}
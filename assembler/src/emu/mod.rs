@@ -0,0 +1,401 @@
+pub mod runner;
+
+use std::collections::VecDeque;
+
+use crate::isa::isa;
+use crate::ADDRESS_SPACE_SIZE;
+
+// ==============================================
+// =             SHARED DEFINITIONS             =
+// ==============================================
+
+/// Addresses below this one belong to the program ROM, writes to them are ignored.
+pub const RAM_START: u16 = 0x4000;
+/// Address bit driven by `_RAMSTART`.
+const RAM_START_BIT: u16 = 1 << 14;
+/// Address bit driven by `_SPSTART`, the stack starts one page after the start of the RAM.
+const SP_START_BIT: u16 = 1 << 8;
+/// First address of the memory mapped I/O.
+pub const IO_START: u16 = 0xFF00;
+/// Characters written here are printed on the TTY.
+pub const TTY: u16 = 0xFF01;
+/// Reading returns the next key pressed on the keyboard, or 0 when no key is waiting.
+pub const KEYBOARD: u16 = 0xFF02;
+
+/// Bits of the flags register, `WRAP` and `ZERO` are set by the ALU and `INCWRAP` by the Incrementer.
+pub const FLAG_WRAP: u8 = 1 << 0;
+pub const FLAG_ZERO: u8 = 1 << 1;
+pub const FLAG_INCWRAP: u8 = 1 << 2;
+/// The flags register is 4 bits wide.
+const FLAGS_MASK: u8 = 0x0f;
+
+/// Bits of the control word for every control signal the emulator implements.
+/// Signals missing from the active ISA get an empty mask and are never active.
+#[derive(Debug, Clone, Copy)]
+struct Signals {
+    iend: u64,
+    hlt: u64,
+    pci: u64,
+    pco: u64,
+    pcj: u64,
+    spi: u64,
+    spo: u64,
+    spoa: u64,
+    ai: u64,
+    bi: u64,
+    bo: u64,
+    hi: u64,
+    ho: u64,
+    li: u64,
+    lo: u64,
+    hlo: u64,
+    hli: u64,
+    arhi: u64,
+    arho: u64,
+    arli: u64,
+    arlo: u64,
+    arhlo: u64,
+    aluo: u64,
+    opadd: u64,
+    opsub: u64,
+    opnot: u64,
+    opnand: u64,
+    opsr: u64,
+    alufi: u64,
+    ince: u64,
+    dec: u64,
+    inci: u64,
+    inco: u64,
+    fi: u64,
+    fo: u64,
+    mi: u64,
+    mo: u64,
+    ini: u64,
+    ramstart: u64,
+    spstart: u64,
+}
+
+impl Signals {
+    fn from_isa() -> Self {
+        let mask = |name: &str| isa().signal_bit(name).map_or(0, |bit| 1 << bit);
+
+        Signals {
+            iend: mask("IEND"),
+            hlt: mask("HLT"),
+            pci: mask("PCI"),
+            pco: mask("PCO"),
+            pcj: mask("PCJ"),
+            spi: mask("SPI"),
+            spo: mask("SPO"),
+            spoa: mask("SPOA"),
+            ai: mask("AI"),
+            bi: mask("BI"),
+            bo: mask("BO"),
+            hi: mask("HI"),
+            ho: mask("HO"),
+            li: mask("LI"),
+            lo: mask("LO"),
+            hlo: mask("HLO"),
+            hli: mask("HLI"),
+            arhi: mask("ARHI"),
+            arho: mask("ARHO"),
+            arli: mask("ARLI"),
+            arlo: mask("ARLO"),
+            arhlo: mask("ARHLO"),
+            aluo: mask("ALUO"),
+            opadd: mask("OPADD"),
+            opsub: mask("OPSUB"),
+            opnot: mask("OPNOT"),
+            opnand: mask("OPNAND"),
+            opsr: mask("OPSR"),
+            alufi: mask("ALUFI"),
+            ince: mask("INCE"),
+            dec: mask("DEC"),
+            inci: mask("INCI"),
+            inco: mask("INCO"),
+            fi: mask("FI"),
+            fo: mask("FO"),
+            mi: mask("MI"),
+            mo: mask("MO"),
+            ini: mask("INI"),
+            ramstart: mask("_RAMSTART"),
+            spstart: mask("_SPSTART"),
+        }
+    }
+}
+
+/// Why the machine stopped running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// A micro step with the `HLT` signal was executed.
+    Halted,
+    /// The cycle limit was reached before the machine halted.
+    CycleLimit,
+}
+
+/// A Tower computer executing its microcode one micro step (one clock cycle) at a time.
+#[derive(Debug, Clone)]
+pub struct Machine {
+    microcode: Vec<u8>,
+    signals: Signals,
+    pub memory: Vec<u8>,
+
+    pub pc: u16,
+    pub sp: u8,
+    pub a: u8,
+    pub b: u8,
+    pub h: u8,
+    pub l: u8,
+    pub arh: u8,
+    pub arl: u8,
+    pub incrementer: u8,
+    pub flags: u8,
+    pub ir: u8,
+    pub step: u32,
+
+    /// Address the instruction in the instruction register was fetched from.
+    pub instruction_address: u16,
+    pub halted: bool,
+    /// Number of clock cycles executed so far.
+    pub cycles: u64,
+    /// Values written to the memory mapped I/O, in order.
+    pub io_writes: Vec<(u16, u8)>,
+    /// Keys waiting to be read from the keyboard.
+    pub keyboard: VecDeque<u8>,
+}
+
+impl Machine {
+    /// Creates a machine running the assembled microcode with the program loaded at the start of the ROM.
+    pub fn new(microcode: Vec<u8>, program: &[u8]) -> Self {
+        let mut memory = vec![0; ADDRESS_SPACE_SIZE];
+        let len = program.len().min(RAM_START as usize);
+        memory[..len].copy_from_slice(&program[..len]);
+
+        Machine {
+            microcode,
+            signals: Signals::from_isa(),
+            memory,
+            pc: 0,
+            sp: 0,
+            a: 0,
+            b: 0,
+            h: 0,
+            l: 0,
+            arh: 0,
+            arl: 0,
+            incrementer: 0,
+            flags: 0,
+            ir: 0,
+            step: 0,
+            instruction_address: 0,
+            halted: false,
+            cycles: 0,
+            io_writes: Vec::new(),
+            keyboard: VecDeque::new(),
+        }
+    }
+
+    /// Returns the control word of the current micro step.
+    pub fn control_word(&self) -> u64 {
+        let isa = isa();
+        let address = &isa.address;
+        let control_bytes = isa.control_bytes();
+
+        let flag_mask = (1 << address.flag_bits) - 1;
+        let rom_address = ((self.ir as usize) << (address.flag_bits + address.step_bits))
+            | ((self.flags as usize & flag_mask) << address.step_bits)
+            | self.step as usize;
+
+        let start = rom_address * control_bytes;
+        match self.microcode.get(start..start + control_bytes) {
+            // the control word is stored most significant byte first
+            Some(bytes) => bytes.iter().fold(0, |word, &b| (word << 8) | b as u64),
+            None => 0,
+        }
+    }
+
+    /// Executes a single micro step.
+    pub fn clock(&mut self) {
+        if self.halted {
+            return;
+        }
+        let s = self.signals;
+        let cw = self.control_word();
+        let on = |mask: u64| cw & mask != 0;
+
+        // the Incrementer counts on the falling edge, so its new value is already available in the same step
+        if on(s.ince) {
+            let (value, wrapped) = if on(s.dec) {
+                self.incrementer.overflowing_sub(1)
+            } else {
+                self.incrementer.overflowing_add(1)
+            };
+            self.incrementer = value;
+            self.flags = (self.flags & !FLAG_INCWRAP) | if wrapped { FLAG_INCWRAP } else { 0 };
+        }
+
+        // address bus
+        let mut address: u16 = 0;
+        if on(s.pco) {
+            address |= self.pc;
+        }
+        if on(s.hlo) {
+            address |= u16::from_be_bytes([self.h, self.l]);
+        }
+        if on(s.arhlo) {
+            address |= u16::from_be_bytes([self.arh, self.arl]);
+        }
+        if on(s.spoa) {
+            address |= self.sp as u16;
+        }
+        if on(s.ramstart) {
+            address = (address & 0x00ff) | RAM_START_BIT;
+        }
+        if on(s.spstart) {
+            address |= SP_START_BIT;
+        }
+
+        // ALU
+        let carry = on(s.alufi) && self.flags & FLAG_WRAP != 0;
+        let alu_op = s.opadd | s.opsub | s.opnot | s.opnand | s.opsr;
+        let (alu_result, wrapped) = if on(s.opadd) {
+            let (sum, w1) = self.a.overflowing_add(self.b);
+            let (sum, w2) = sum.overflowing_add(carry as u8);
+            (sum, w1 || w2)
+        } else if on(s.opsub) {
+            let (diff, w1) = self.a.overflowing_sub(self.b);
+            let (diff, w2) = diff.overflowing_sub(carry as u8);
+            (diff, w1 || w2)
+        } else if on(s.opnot) {
+            (!self.a, false)
+        } else if on(s.opnand) {
+            (!(self.a & self.b), false)
+        } else if on(s.opsr) {
+            (self.a >> 1, false)
+        } else {
+            // without an operation the ALU passes A through
+            (self.a, false)
+        };
+
+        // data bus
+        let memory_out = if on(s.mo) { self.read(address) } else { 0 };
+        let mut data: u8 = 0;
+        let drivers = [
+            (s.spo, self.sp),
+            (s.bo, self.b),
+            (s.ho, self.h),
+            (s.lo, self.l),
+            (s.arho, self.arh),
+            (s.arlo, self.arl),
+            (s.aluo, alu_result),
+            (s.inco, self.incrementer),
+            (s.fo, self.flags),
+            (s.mo, memory_out),
+        ];
+        for (mask, value) in drivers {
+            if on(mask) {
+                data |= value;
+            }
+        }
+
+        // latch everything at the end of the step
+        if on(alu_op) {
+            self.flags &= !(FLAG_WRAP | FLAG_ZERO);
+            if wrapped {
+                self.flags |= FLAG_WRAP;
+            }
+            if alu_result == 0 {
+                self.flags |= FLAG_ZERO;
+            }
+        }
+        if on(s.fi) {
+            self.flags = data & FLAGS_MASK;
+        }
+        if on(s.spi) {
+            self.sp = data;
+        }
+        if on(s.ai) {
+            self.a = data;
+        }
+        if on(s.bi) {
+            self.b = data;
+        }
+        if on(s.hi) {
+            self.h = data;
+        }
+        if on(s.li) {
+            self.l = data;
+        }
+        if on(s.hli) {
+            [self.h, self.l] = address.to_be_bytes();
+        }
+        if on(s.arhi) {
+            self.arh = data;
+        }
+        if on(s.arli) {
+            self.arl = data;
+        }
+        if on(s.inci) {
+            self.incrementer = data;
+        }
+        if on(s.mi) {
+            self.write(address, data);
+        }
+        if on(s.ini) {
+            self.ir = data;
+            self.instruction_address = address;
+        }
+        if on(s.pcj) {
+            self.pc = address;
+        } else if on(s.pci) {
+            self.pc = self.pc.wrapping_add(1);
+        }
+
+        self.cycles += 1;
+        if on(s.hlt) {
+            self.halted = true;
+        }
+        if on(s.iend) {
+            self.step = 0;
+        } else {
+            self.step = (self.step + 1) % isa().max_micro_steps() as u32;
+        }
+    }
+
+    /// Runs until the machine halts or `max_cycles` clock cycles have been executed in total.
+    pub fn run(&mut self, max_cycles: u64) -> StopReason {
+        while !self.halted {
+            if self.cycles >= max_cycles {
+                return StopReason::CycleLimit;
+            }
+            self.clock();
+        }
+        StopReason::Halted
+    }
+
+    /// Returns the characters written to the TTY.
+    pub fn tty_output(&self) -> String {
+        self.io_writes
+            .iter()
+            .filter(|(port, _)| *port == TTY)
+            .map(|(_, value)| *value as char)
+            .collect()
+    }
+
+    fn read(&mut self, address: u16) -> u8 {
+        match address {
+            KEYBOARD => self.keyboard.pop_front().unwrap_or(0),
+            _ => self.memory[address as usize],
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if address < RAM_START {
+            return;
+        }
+        if address >= IO_START {
+            self.io_writes.push((address, value));
+        }
+        self.memory[address as usize] = value;
+    }
+}
@@ -0,0 +1,255 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::asm::asm::{assemble_str, AssembleOptions, AssembledProgram};
+use crate::asm::loader::FileLoader;
+use crate::asm::Label;
+use crate::microasm;
+use crate::microasm::asm::MicroassembleOptions;
+use crate::{read_file, read_file_binary, AssemblerError};
+
+use super::{Machine, StopReason};
+
+/// A test fails when it halts in the block of this label.
+pub const FAIL_LABEL: &str = "_failed";
+/// If a test defines this label, it only passes when it halts in its block.
+pub const PASS_LABEL: &str = "_pass";
+
+/// Cycles a test may take before it is considered stuck.
+pub const DEFAULT_MAX_CYCLES: u64 = 1_000_000;
+
+/// Reads the microcode from an assembled `.bin` file, or assembles it from its source otherwise.
+pub fn load_microcode(path: &str) -> Result<Vec<u8>, AssemblerError> {
+    if path.to_lowercase().ends_with(".bin") {
+        return read_file_binary(path);
+    }
+    let options = MicroassembleOptions {
+        file_name: path.to_string(),
+        loader: Some(&FileLoader),
+        ..Default::default()
+    };
+    Ok(microasm::asm::assemble_str(&read_file(path)?, &options)?.bytes)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TestStatus {
+    Passed,
+    Failed(String),
+    /// The test could not be run at all.
+    Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub name: String,
+    pub status: TestStatus,
+    /// Clock cycles executed before the machine stopped.
+    pub cycles: u64,
+    pub duration: Duration,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.status == TestStatus::Passed
+    }
+}
+
+impl fmt::Display for TestResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.status {
+            TestStatus::Passed => write!(f, "✔️  {} ({} cycles)", self.name, self.cycles),
+            TestStatus::Failed(reason) => {
+                write!(f, "❌ {} ({} cycles): {}", self.name, self.cycles, reason)
+            }
+            TestStatus::Error(reason) => write!(f, "❌ {}: {}", self.name, reason),
+        }
+    }
+}
+
+/// Assembles a test program and runs it.
+pub fn run_test_file(path: &str, microcode: &[u8], max_cycles: u64) -> TestResult {
+    let start = Instant::now();
+    let error = |reason: String| TestResult {
+        name: path.to_string(),
+        status: TestStatus::Error(reason),
+        cycles: 0,
+        duration: start.elapsed(),
+    };
+
+    let source = match read_file(path) {
+        Ok(source) => source,
+        Err(e) => return error(e.to_string()),
+    };
+    let loader = FileLoader;
+    let options = AssembleOptions {
+        file_name: path.to_string(),
+        loader: Some(&loader),
+        ..Default::default()
+    };
+
+    match assemble_str(&source, &options) {
+        Ok(program) => run_test(path, &program, microcode, max_cycles),
+        Err(diagnostics) => error(diagnostics.to_string()),
+    }
+}
+
+/// Runs an assembled test program until it halts and decides whether it passed from the label it halted at.
+pub fn run_test(
+    name: &str,
+    program: &AssembledProgram,
+    microcode: &[u8],
+    max_cycles: u64,
+) -> TestResult {
+    let start = Instant::now();
+    let mut machine = Machine::new(microcode.to_vec(), &program.bytes);
+
+    let status = match machine.run(max_cycles) {
+        StopReason::CycleLimit => {
+            TestStatus::Failed(format!("did not halt within {} cycles", max_cycles))
+        }
+        StopReason::Halted => {
+            let address = machine.instruction_address as u32;
+            let label = enclosing_label(&program.symbols, address).map(|l| l.name.as_str());
+            let has_pass_label = program.symbols.iter().any(|l| l.name == PASS_LABEL);
+
+            match label {
+                Some(FAIL_LABEL) => {
+                    TestStatus::Failed(format!("halted at {} (A = {})", FAIL_LABEL, machine.a))
+                }
+                Some(PASS_LABEL) => TestStatus::Passed,
+                _ if has_pass_label => TestStatus::Failed(format!(
+                    "halted at 0x{:04x} outside of {} (A = {})",
+                    address, PASS_LABEL, machine.a
+                )),
+                _ => TestStatus::Passed,
+            }
+        }
+    };
+
+    TestResult {
+        name: name.to_string(),
+        status,
+        cycles: machine.cycles,
+        duration: start.elapsed(),
+    }
+}
+
+/// Returns the last label at or before `address`.
+fn enclosing_label(labels: &[Label], address: u32) -> Option<&Label> {
+    labels
+        .iter()
+        .filter(|l| l.address <= address)
+        .max_by_key(|l| l.address)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    /// Test Anything Protocol, version 13.
+    Tap,
+    /// JUnit XML as understood by most CI servers.
+    Junit,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(ReportFormat::Text),
+            "tap" => Ok(ReportFormat::Tap),
+            "junit" => Ok(ReportFormat::Junit),
+            _ => Err(format!(
+                "Unknown report format '{}'. Available formats are: text, tap, junit",
+                s
+            )),
+        }
+    }
+}
+
+pub fn report(results: &[TestResult], format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Text => text_report(results),
+        ReportFormat::Tap => tap_report(results),
+        ReportFormat::Junit => junit_report(results),
+    }
+}
+
+fn text_report(results: &[TestResult]) -> String {
+    let mut output = String::new();
+    for r in results {
+        output += &format!("{}\n", r);
+    }
+    let failed = results.iter().filter(|r| !r.passed()).count();
+    output += &format!(
+        "{} test(s), {} passed, {} failed\n",
+        results.len(),
+        results.len() - failed,
+        failed
+    );
+    output
+}
+
+fn tap_report(results: &[TestResult]) -> String {
+    let mut output = format!("TAP version 13\n1..{}\n", results.len());
+    for (idx, r) in results.iter().enumerate() {
+        let ok = if r.passed() { "ok" } else { "not ok" };
+        output += &format!("{} {} - {}\n", ok, idx + 1, r.name);
+
+        output += "  ---\n";
+        output += &format!("  cycles: {}\n", r.cycles);
+        match &r.status {
+            TestStatus::Passed => {}
+            TestStatus::Failed(reason) | TestStatus::Error(reason) => {
+                output += &format!("  message: {:?}\n", reason);
+            }
+        }
+        output += "  ...\n";
+    }
+    output
+}
+
+fn junit_report(results: &[TestResult]) -> String {
+    let count = |f: fn(&TestStatus) -> bool| results.iter().filter(|r| f(&r.status)).count();
+    let failures = count(|s| matches!(s, TestStatus::Failed(_)));
+    let errors = count(|s| matches!(s, TestStatus::Error(_)));
+    let total_time: f64 = results.iter().map(|r| r.duration.as_secs_f64()).sum();
+
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    output += &format!(
+        "<testsuite name=\"tower\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">\n",
+        results.len(),
+        failures,
+        errors,
+        total_time
+    );
+    for r in results {
+        output += &format!(
+            "  <testcase name=\"{}\" classname=\"tower\" time=\"{:.3}\">\n",
+            escape_xml(&r.name),
+            r.duration.as_secs_f64()
+        );
+        match &r.status {
+            TestStatus::Passed => {}
+            TestStatus::Failed(reason) => {
+                output += &format!("    <failure message=\"{}\"/>\n", escape_xml(reason))
+            }
+            TestStatus::Error(reason) => {
+                output += &format!("    <error message=\"{}\"/>\n", escape_xml(reason))
+            }
+        }
+        output += &format!("    <system-out>cycles: {}</system-out>\n", r.cycles);
+        output += "  </testcase>\n";
+    }
+    output += "</testsuite>\n";
+    output
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\n', "&#10;")
+}
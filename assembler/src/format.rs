@@ -0,0 +1,124 @@
+use crate::asm::char_literals;
+use crate::microasm::COMMENT_IDENT;
+use crate::{read_file, write_file, AssemblerError};
+
+// ==============================================
+// =             SHARED DEFINITIONS             =
+// ==============================================
+
+/// Formatted code is indented with tabs, which count as this many columns when measuring the original indentation.
+pub const TAB_WIDTH: usize = 4;
+/// Longer runs of empty lines are shortened.
+pub const MAX_EMPTY_LINES: usize = 2;
+
+/// A line of formatted code before the comments are aligned.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Line {
+    pub indent: usize,
+    pub code: String,
+    pub comment: Option<String>,
+}
+
+impl Line {
+    fn width(&self) -> usize {
+        self.indent * TAB_WIDTH + self.code.chars().count()
+    }
+}
+
+/// Splits a line into its code and its comment (starting with the comment identifier).
+/// The comment identifier can be used in character literals.
+pub fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let comment_idx = line
+        .char_indices()
+        .zip(char_literals(line))
+        .find(|((_, c), in_literal)| *c == COMMENT_IDENT && !in_literal)
+        .map(|((idx, _), _)| idx);
+    match comment_idx {
+        Some(idx) => (line[..idx].trim(), Some(line[idx..].trim_end())),
+        None => (line.trim(), None),
+    }
+}
+
+/// Returns the number of columns a line is indented by.
+pub fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .fold(0, |width, c| match c {
+            '\t' => (width / TAB_WIDTH + 1) * TAB_WIDTH,
+            _ => width + 1,
+        })
+}
+
+/// Joins the lines, aligning the comments of consecutive lines with code and removing extra empty lines.
+pub fn render(lines: &[Line]) -> String {
+    let mut output = String::new();
+    let mut empty_lines = 0;
+    let mut idx = 0;
+
+    while idx < lines.len() {
+        // consecutive lines with code and a comment share the column of their comments
+        let run = lines[idx..]
+            .iter()
+            .take_while(|l| !l.code.is_empty() && l.comment.is_some())
+            .count()
+            .max(1);
+        let column = lines[idx..idx + run]
+            .iter()
+            .map(|l| l.width() + 1)
+            .max()
+            .unwrap_or(0);
+
+        for line in &lines[idx..idx + run] {
+            if line.code.is_empty() && line.comment.is_none() {
+                empty_lines += 1;
+                continue;
+            }
+
+            if empty_lines > 0 && !output.is_empty() {
+                output += &"\n".repeat(empty_lines.min(MAX_EMPTY_LINES));
+            }
+            empty_lines = 0;
+
+            output += &"\t".repeat(line.indent);
+            output += &line.code;
+            match (&line.comment, line.code.is_empty()) {
+                (Some(comment), true) => output += comment,
+                (Some(comment), false) => {
+                    output += &" ".repeat(column - line.width());
+                    output += comment;
+                }
+                (None, _) => {}
+            }
+            output.push('\n');
+        }
+        idx += run;
+    }
+
+    output
+}
+
+/// Formats a source file in place, or only checks that it is formatted.
+/// Returns the first line which was (or would have been) changed, `None` if the file is formatted.
+pub fn format_file(
+    path: &str,
+    check: bool,
+    format: fn(&str) -> String,
+) -> Result<Option<u32>, AssemblerError> {
+    let source = read_file(path)?;
+    let formatted = format(&source);
+
+    if formatted == source {
+        return Ok(None);
+    }
+
+    let line = source
+        .lines()
+        .zip(formatted.lines())
+        .position(|(a, b)| a != b)
+        .unwrap_or(source.lines().count().min(formatted.lines().count()));
+
+    if !check {
+        write_file(path, formatted.as_bytes())?;
+    }
+    Ok(Some(line as u32 + 1))
+}
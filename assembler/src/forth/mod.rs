@@ -0,0 +1,823 @@
+mod words;
+
+use crate::emu::{KEYBOARD, RAM_START, TTY};
+use crate::{write_file, AssemblerError, ZERO_PAGE_START};
+
+use words::{Word, WORDS};
+
+// ==============================================
+// =                MEMORY LAYOUT               =
+// ==============================================
+
+// Variables of the kernel in the zero page, 16 bit values are stored high byte first like the pointers of the
+// indirect mode.
+/// Instruction pointer of the inner interpreter.
+const IP: u16 = ZERO_PAGE_START;
+/// Execution token being executed.
+const W: u16 = ZERO_PAGE_START + 2;
+/// Scratch registers of the primitives.
+const X: u16 = ZERO_PAGE_START + 4;
+const Y: u16 = ZERO_PAGE_START + 6;
+const Z: u16 = ZERO_PAGE_START + 8;
+/// Pointer into the dictionary.
+const P: u16 = ZERO_PAGE_START + 10;
+/// Header being looked at by `_find` and `WORDS`.
+const HP: u16 = ZERO_PAGE_START + 12;
+/// Pointers to the top of the data stack, the high bytes of the cells are on one page and the low bytes on the
+/// next one, so both pointers share their low byte (the stack depth).
+const SPH: u16 = ZERO_PAGE_START + 14;
+const SPL: u16 = ZERO_PAGE_START + 16;
+const HERE: u16 = ZERO_PAGE_START + 18;
+const LATEST: u16 = ZERO_PAGE_START + 20;
+/// 0 while interpreting, 1 while compiling.
+const STATE: u16 = ZERO_PAGE_START + 22;
+/// Pointer into the input buffer, its low byte is the parse position (`>IN`).
+const TIBP: u16 = ZERO_PAGE_START + 24;
+/// Number of characters in the input buffer.
+const TLEN: u16 = ZERO_PAGE_START + 26;
+/// Length of the parsed word.
+const WLEN: u16 = ZERO_PAGE_START + 27;
+/// Pointer into the parsed word.
+const WP: u16 = ZERO_PAGE_START + 28;
+const CNT: u16 = ZERO_PAGE_START + 30;
+const FLAG: u16 = ZERO_PAGE_START + 31;
+/// Two cells executing a word from the outer interpreter: its execution token and `(DONE)`.
+const THREAD: u16 = ZERO_PAGE_START + 32;
+/// Byte temporaries.
+const T: u16 = ZERO_PAGE_START + 36;
+
+// The hardware stack (the return stack) takes the page after the zero page.
+const STACK_HIGH: u16 = ZERO_PAGE_START + 0x200;
+const STACK_LOW: u16 = ZERO_PAGE_START + 0x300;
+/// Terminal input buffer.
+const TIB: u16 = ZERO_PAGE_START + 0x400;
+/// Buffer of the parsed word.
+const WORD_BUFFER: u16 = ZERO_PAGE_START + 0x500;
+/// The dictionary grows from here to the I/O mapped memory.
+const DICTIONARY: u16 = ZERO_PAGE_START + 0x600;
+
+/// The jump table of the primitives follows the jump to the boot code at address 0.
+const JUMP_TABLE: u16 = 3;
+/// Headers of immediate words have this bit set in their length byte.
+const IMMEDIATE: u8 = 0x80;
+// ==============================================
+
+/// Execution token of a primitive, the address of its entry in the jump table.
+///
+/// Execution tokens below the RAM are primitives, the others point to the body of a colon definition.
+fn xt(label: &str) -> u16 {
+    let token = WORDS
+        .iter()
+        .position(|w| w.label == label)
+        .unwrap_or_else(|| panic!("unknown primitive '{}'", label));
+    JUMP_TABLE + 3 * token as u16
+}
+
+fn addr(address: u16) -> String {
+    format!("0x{:04X}", address)
+}
+
+/// Builds the source of the kernel.
+struct Asm {
+    text: String,
+    labels: u32,
+}
+
+impl Asm {
+    fn label(&mut self, name: &str) {
+        self.text += &format!("{}:\n", name);
+    }
+
+    fn ins(&mut self, ins: &str) {
+        self.text += &format!("\t{}\n", ins);
+    }
+
+    fn comment(&mut self, text: &str) {
+        self.text += &format!("\t; {}\n", text);
+    }
+
+    fn empty(&mut self) {
+        self.text.push('\n');
+    }
+
+    /// Returns a new label for local jumps.
+    fn local(&mut self) -> String {
+        self.labels += 1;
+        format!("_k{}", self.labels)
+    }
+
+    /// Instruction reading a variable.
+    fn op(&mut self, name: &str, var: u16) {
+        self.ins(&format!("{} *{}", name, addr(var)));
+    }
+
+    fn op_imm(&mut self, name: &str, value: u8) {
+        self.ins(&format!("{} #{}", name, value));
+    }
+
+    /// Instruction accessing the memory a pointer variable points to.
+    fn op_ind(&mut self, name: &str, var: u16) {
+        self.ins(&format!("{} @{}", name, addr(var)));
+    }
+
+    fn lda(&mut self, var: u16) {
+        self.op("LDA", var);
+    }
+
+    fn lda_imm(&mut self, value: u8) {
+        self.op_imm("LDA", value);
+    }
+
+    fn sta(&mut self, var: u16) {
+        self.ins(&format!("STA &{}", addr(var)));
+    }
+
+    fn jump(&mut self, name: &str, label: &str) {
+        self.ins(&format!("{} {}", name, label));
+    }
+
+    fn jsr(&mut self, label: &str) {
+        self.jump("JSR", label);
+    }
+
+    fn next(&mut self) {
+        self.jump("JMP", "_next");
+    }
+
+    fn set8(&mut self, var: u16, value: u8) {
+        self.lda_imm(value);
+        self.sta(var);
+    }
+
+    fn set16(&mut self, var: u16, value: u16) {
+        self.set8(var, (value >> 8) as u8);
+        self.set8(var + 1, value as u8);
+    }
+
+    fn copy16(&mut self, from: u16, to: u16) {
+        for offset in [0, 1] {
+            self.lda(from + offset);
+            self.sta(to + offset);
+        }
+    }
+
+    fn inc16(&mut self, var: u16) {
+        self.lda(var + 1);
+        self.op_imm("ADD", 1);
+        self.sta(var + 1);
+        self.lda(var);
+        self.op_imm("ADC", 0);
+        self.sta(var);
+    }
+
+    /// `to = a + b` or `to = a - b`, the low byte first so the wrap flag carries into the high byte.
+    fn add16(&mut self, ops: [&str; 2], a: u16, b: u16, to: u16) {
+        self.lda(a + 1);
+        self.op(ops[0], b + 1);
+        self.sta(to + 1);
+        self.lda(a);
+        self.op(ops[1], b);
+        self.sta(to);
+    }
+
+    /// Adds a constant to a variable.
+    fn add16_imm(&mut self, var: u16, value: u16) {
+        self.lda(var + 1);
+        self.op_imm("ADD", value as u8);
+        self.sta(var + 1);
+        self.lda(var);
+        self.op_imm("ADC", (value >> 8) as u8);
+        self.sta(var);
+    }
+
+    /// Jumps to the label when the 16 bit variable is zero.
+    fn jump_if_zero16(&mut self, var: u16, label: &str) {
+        let nonzero = self.local();
+        self.lda(var);
+        self.op_imm("CMP", 0);
+        self.jump("JNZ", &nonzero);
+        self.lda(var + 1);
+        self.op_imm("CMP", 0);
+        self.jump("JZ", label);
+        self.label(&nonzero);
+    }
+
+    fn emit(&mut self, c: u8) {
+        self.lda_imm(c);
+        self.sta(TTY);
+    }
+
+    fn print(&mut self, text: &str) {
+        for c in text.bytes() {
+            self.emit(c);
+        }
+    }
+
+    /// Appends the execution token of a primitive to the dictionary.
+    fn compile(&mut self, label: &str) {
+        self.set16(X, xt(label));
+        self.jsr("_comma");
+    }
+}
+
+/// Generates the Tower assembly of a Forth kernel.
+///
+/// The kernel is indirect threaded: colon definitions are lists of execution tokens in RAM, primitives are machine
+/// code in ROM reached through a jump table. The return stack is the hardware stack, the data stack lives in RAM
+/// and the outer interpreter reads lines from the keyboard and prints on the TTY.
+pub fn kernel() -> String {
+    let mut a = Asm {
+        text: String::new(),
+        labels: 0,
+    };
+
+    a.text += "; Forth kernel for the Tower computer\n";
+    a.text += &format!(
+        "; zero page 0x{:04X}, data stack 0x{:04X}, input 0x{:04X}, dictionary 0x{:04X}\n\n",
+        ZERO_PAGE_START, STACK_HIGH, TIB, DICTIONARY
+    );
+
+    a.label("_reset");
+    a.jump("JMP", "_boot");
+    a.empty();
+
+    a.label("_jump_table");
+    for word in WORDS {
+        a.jump("JMP", word.label);
+    }
+    a.empty();
+
+    inner_interpreter(&mut a);
+    stack_routines(&mut a);
+    dictionary_routines(&mut a);
+    outer_interpreter(&mut a);
+    boot(&mut a);
+
+    for word in WORDS {
+        a.empty();
+        if let Some(name) = word.name {
+            a.text += &format!("; {}\n", name);
+        }
+        a.label(word.label);
+        (word.code)(&mut a);
+    }
+
+    a.text
+}
+
+fn inner_interpreter(a: &mut Asm) {
+    a.label("_next");
+    a.comment("W = the cell IP points to, IP = the next cell");
+    a.op_ind("LDA", IP);
+    a.sta(W);
+    a.inc16(IP);
+    a.op_ind("LDA", IP);
+    a.sta(W + 1);
+    a.inc16(IP);
+    a.label("_dispatch");
+    a.lda(W);
+    a.op_imm("CMP", (RAM_START >> 8) as u8);
+    a.jump("JW", "_dispatch_primitive");
+    a.comment("colon definition, save IP on the return stack and run its body");
+    a.lda(IP);
+    a.ins("PSA");
+    a.lda(IP + 1);
+    a.ins("PSA");
+    a.copy16(W, IP);
+    a.next();
+    a.label("_dispatch_primitive");
+    a.comment("W points to the entry of the primitive in the jump table");
+    a.op_ind("JMP", W);
+    a.empty();
+
+    a.label("_fetch_ip");
+    a.comment("W = the cell IP points to, IP = the next cell");
+    a.op_ind("LDA", IP);
+    a.sta(W);
+    a.inc16(IP);
+    a.op_ind("LDA", IP);
+    a.sta(W + 1);
+    a.inc16(IP);
+    a.ins("RTS");
+    a.empty();
+}
+
+fn stack_routines(a: &mut Asm) {
+    for (name, var) in [("x", X), ("y", Y), ("z", Z)] {
+        a.label(&format!("_pop_{}", name));
+        a.lda(SPH + 1);
+        a.op_imm("CMP", 0);
+        a.jump("JZ", "_underflow");
+        a.op_imm("SUB", 1);
+        a.sta(SPH + 1);
+        a.sta(SPL + 1);
+        a.op_ind("LDA", SPH);
+        a.sta(var);
+        a.op_ind("LDA", SPL);
+        a.sta(var + 1);
+        a.ins("RTS");
+        a.empty();
+
+        a.label(&format!("_push_{}", name));
+        a.lda(var);
+        a.op_ind("STA", SPH);
+        a.lda(var + 1);
+        a.op_ind("STA", SPL);
+        a.lda(SPH + 1);
+        a.op_imm("ADD", 1);
+        a.sta(SPH + 1);
+        a.sta(SPL + 1);
+        a.ins("RTS");
+        a.empty();
+    }
+
+    a.label("_push_true");
+    a.set16(X, 0xFFFF);
+    a.jsr("_push_x");
+    a.next();
+    a.label("_push_false");
+    a.set16(X, 0);
+    a.jsr("_push_x");
+    a.next();
+    a.empty();
+
+    a.label("_underflow");
+    a.print(" stack empty");
+    a.jump("JMP", "_abort");
+    a.empty();
+}
+
+fn dictionary_routines(a: &mut Asm) {
+    a.label("_comma");
+    a.comment("appends the cell X to the dictionary");
+    a.lda(X);
+    a.op_ind("STA", HERE);
+    a.inc16(HERE);
+    a.label("_c_comma");
+    a.comment("appends the low byte of X to the dictionary");
+    a.lda(X + 1);
+    a.op_ind("STA", HERE);
+    a.inc16(HERE);
+    a.ins("RTS");
+    a.empty();
+
+    a.label("_resolve");
+    a.comment("stores HERE in the cell X points to");
+    a.lda(HERE);
+    a.op_ind("STA", X);
+    a.inc16(X);
+    a.lda(HERE + 1);
+    a.op_ind("STA", X);
+    a.ins("RTS");
+    a.empty();
+
+    a.label("_create");
+    a.comment(
+        "appends a header for the parsed word: link, length, name, the execution token follows",
+    );
+    a.copy16(HERE, Y);
+    a.copy16(LATEST, X);
+    a.jsr("_comma");
+    a.copy16(Y, LATEST);
+    a.lda(WLEN);
+    a.sta(X + 1);
+    a.jsr("_c_comma");
+    a.set8(CNT, 0);
+    let (name_loop, done) = (a.local(), a.local());
+    a.label(&name_loop);
+    a.lda(CNT);
+    a.op("CMP", WLEN);
+    a.jump("JZ", &done);
+    a.sta(WP + 1);
+    a.op_ind("LDA", WP);
+    a.sta(X + 1);
+    a.jsr("_c_comma");
+    a.lda(CNT);
+    a.op_imm("ADD", 1);
+    a.sta(CNT);
+    a.jump("JMP", &name_loop);
+    a.label(&done);
+    a.ins("RTS");
+    a.empty();
+
+    a.label("_set_immediate");
+    a.copy16(LATEST, P);
+    a.add16_imm(P, 2);
+    a.op_ind("LDA", P);
+    a.ins("NOT %A");
+    a.op_imm("NAND", !IMMEDIATE);
+    a.op_ind("STA", P);
+    a.ins("RTS");
+    a.empty();
+
+    a.label("_find");
+    a.comment("looks up the parsed word, FLAG = 0 if not found, 1 if found, 2 if immediate, X = execution token");
+    a.copy16(LATEST, HP);
+    let (find_loop, chars, found, next, normal) =
+        (a.local(), a.local(), a.local(), a.local(), a.local());
+    a.label(&find_loop);
+    a.jump_if_zero16(HP, "_find_missing");
+    a.copy16(HP, P);
+    a.add16_imm(P, 2);
+    a.op_ind("LDA", P);
+    a.op_imm("NAND", !IMMEDIATE);
+    a.ins("NOT %A");
+    a.op("CMP", WLEN);
+    a.jump("JNZ", &next);
+    a.set8(CNT, 0);
+    a.label(&chars);
+    a.lda(CNT);
+    a.op("CMP", WLEN);
+    a.jump("JZ", &found);
+    a.inc16(P);
+    a.lda(CNT);
+    a.sta(WP + 1);
+    a.op_ind("LDA", P);
+    a.op_ind("CMP", WP);
+    a.jump("JNZ", &next);
+    a.lda(CNT);
+    a.op_imm("ADD", 1);
+    a.sta(CNT);
+    a.jump("JMP", &chars);
+
+    a.label(&found);
+    a.inc16(P);
+    a.op_ind("LDA", P);
+    a.sta(X);
+    a.inc16(P);
+    a.op_ind("LDA", P);
+    a.sta(X + 1);
+    a.copy16(HP, P);
+    a.add16_imm(P, 2);
+    a.op_ind("LDA", P);
+    a.op_imm("CMP", IMMEDIATE);
+    a.jump("JW", &normal);
+    a.set8(FLAG, 2);
+    a.ins("RTS");
+    a.label(&normal);
+    a.set8(FLAG, 1);
+    a.ins("RTS");
+
+    a.label(&next);
+    a.comment("follow the link to the previous header");
+    a.copy16(HP, P);
+    a.inc16(P);
+    a.op_ind("LDA", HP);
+    a.sta(T);
+    a.op_ind("LDA", P);
+    a.sta(HP + 1);
+    a.lda(T);
+    a.sta(HP);
+    a.jump("JMP", &find_loop);
+
+    a.label("_find_missing");
+    a.set8(FLAG, 0);
+    a.ins("RTS");
+    a.empty();
+}
+
+fn outer_interpreter(a: &mut Asm) {
+    a.label("_key");
+    a.comment("waits for a key, A = the key");
+    a.lda(KEYBOARD);
+    a.op_imm("CMP", 0);
+    a.jump("JZ", "_key");
+    a.ins("RTS");
+    a.empty();
+
+    a.label("_read_line");
+    a.set8(TLEN, 0);
+    a.label("_read_char");
+    a.jsr("_key");
+    a.op_imm("CMP", b'\n');
+    a.jump("JZ", "_line_read");
+    a.op_imm("CMP", b'\r');
+    a.jump("JZ", "_line_read");
+    a.op_imm("CMP", 8);
+    a.jump("JZ", "_backspace");
+    a.sta(T);
+    a.lda(TLEN);
+    a.op_imm("CMP", 0xFF);
+    a.jump("JZ", "_read_char");
+    a.sta(TIBP + 1);
+    a.lda(T);
+    a.op_ind("STA", TIBP);
+    a.sta(TTY);
+    a.lda(TLEN);
+    a.op_imm("ADD", 1);
+    a.sta(TLEN);
+    a.jump("JMP", "_read_char");
+    a.label("_backspace");
+    a.lda(TLEN);
+    a.op_imm("CMP", 0);
+    a.jump("JZ", "_read_char");
+    a.op_imm("SUB", 1);
+    a.sta(TLEN);
+    a.emit(8);
+    a.jump("JMP", "_read_char");
+    a.label("_line_read");
+    a.emit(b' ');
+    a.set8(TIBP + 1, 0);
+    a.empty();
+
+    a.label("_interpret");
+    a.jsr("_word");
+    a.lda(WLEN);
+    a.op_imm("CMP", 0);
+    a.jump("JZ", "_line_done");
+    a.jsr("_find");
+    a.lda(FLAG);
+    a.op_imm("CMP", 0);
+    a.jump("JZ", "_interpret_number");
+    a.op_imm("CMP", 2);
+    a.jump("JZ", "_interpret_execute");
+    a.lda(STATE);
+    a.op_imm("CMP", 0);
+    a.jump("JZ", "_interpret_execute");
+    a.jsr("_comma");
+    a.jump("JMP", "_interpret");
+    a.label("_interpret_execute");
+    a.comment("run the word, followed by (DONE) which returns here");
+    a.copy16(X, THREAD);
+    a.set16(IP, THREAD);
+    a.next();
+    a.label("_interpret_number");
+    a.jsr("_number");
+    a.lda(FLAG);
+    a.op_imm("CMP", 0);
+    a.jump("JZ", "_undefined");
+    a.lda(STATE);
+    a.op_imm("CMP", 0);
+    a.jump("JNZ", "_compile_number");
+    a.jsr("_push_x");
+    a.jump("JMP", "_interpret");
+    a.label("_compile_number");
+    a.copy16(X, Y);
+    a.compile("_w_lit");
+    a.copy16(Y, X);
+    a.jsr("_comma");
+    a.jump("JMP", "_interpret");
+    a.label("_line_done");
+    a.lda(STATE);
+    a.op_imm("CMP", 0);
+    a.jump("JNZ", "_line_compiled");
+    a.print(" ok");
+    a.label("_line_compiled");
+    a.emit(b'\n');
+    a.jump("JMP", "_read_line");
+    a.empty();
+
+    a.label("_undefined");
+    a.comment("prints the unknown word");
+    a.emit(b' ');
+    a.set8(CNT, 0);
+    let (print_loop, done) = (a.local(), a.local());
+    a.label(&print_loop);
+    a.lda(CNT);
+    a.op("CMP", WLEN);
+    a.jump("JZ", &done);
+    a.sta(WP + 1);
+    a.op_ind("LDA", WP);
+    a.sta(TTY);
+    a.lda(CNT);
+    a.op_imm("ADD", 1);
+    a.sta(CNT);
+    a.jump("JMP", &print_loop);
+    a.label(&done);
+    a.print(" ?");
+    a.label("_abort");
+    a.comment("empties the data stack and reads the next line");
+    a.emit(b'\n');
+    a.set8(STATE, 0);
+    a.set8(SPH + 1, 0);
+    a.set8(SPL + 1, 0);
+    a.jump("JMP", "_read_line");
+    a.empty();
+
+    word(a);
+    number(a);
+    print_number(a);
+}
+
+/// `_word` parses the next word of the input, upper-cased, into the word buffer.
+fn word(a: &mut Asm) {
+    let (skip, skip_next, copy, upper, store, done) = (
+        a.local(),
+        a.local(),
+        a.local(),
+        a.local(),
+        a.local(),
+        a.local(),
+    );
+
+    a.label("_word");
+    a.set8(WLEN, 0);
+    a.label(&skip);
+    a.comment("skip the spaces and control characters");
+    a.lda(TIBP + 1);
+    a.op("CMP", TLEN);
+    a.jump("JZ", &done);
+    a.op_ind("LDA", TIBP);
+    a.op_imm("CMP", b' ' + 1);
+    a.jump("JW", &skip_next);
+    a.jump("JMP", &copy);
+    a.label(&skip_next);
+    a.lda(TIBP + 1);
+    a.op_imm("ADD", 1);
+    a.sta(TIBP + 1);
+    a.jump("JMP", &skip);
+
+    a.label(&copy);
+    a.lda(TIBP + 1);
+    a.op("CMP", TLEN);
+    a.jump("JZ", &done);
+    a.op_ind("LDA", TIBP);
+    a.op_imm("CMP", b' ' + 1);
+    a.jump("JW", &done);
+    a.op_imm("CMP", b'a');
+    a.jump("JW", &store);
+    a.op_imm("CMP", b'z' + 1);
+    a.jump("JW", &upper);
+    a.jump("JMP", &store);
+    a.label(&upper);
+    a.op_imm("SUB", b'a' - b'A');
+    a.label(&store);
+    a.sta(T);
+    a.lda(WLEN);
+    a.sta(WP + 1);
+    a.lda(T);
+    a.op_ind("STA", WP);
+    a.lda(WLEN);
+    a.op_imm("ADD", 1);
+    a.sta(WLEN);
+    a.lda(TIBP + 1);
+    a.op_imm("ADD", 1);
+    a.sta(TIBP + 1);
+    a.jump("JMP", &copy);
+    a.label(&done);
+    a.ins("RTS");
+    a.empty();
+}
+
+/// `_number` converts the parsed word to a signed decimal number, FLAG = 0 if it is not a number, X = the number.
+fn number(a: &mut Asm) {
+    let (digits, digit, done, positive, fail) =
+        (a.local(), a.local(), a.local(), a.local(), a.local());
+
+    a.label("_number");
+    a.set16(X, 0);
+    a.set8(CNT, 0);
+    a.set8(T + 1, 0);
+    a.set8(WP + 1, 0);
+    a.op_ind("LDA", WP);
+    a.op_imm("CMP", b'-');
+    a.jump("JNZ", &digits);
+    a.lda(WLEN);
+    a.op_imm("CMP", 1);
+    a.jump("JZ", &fail);
+    a.set8(CNT, 1);
+    a.set8(T + 1, 1);
+
+    a.label(&digits);
+    a.lda(CNT);
+    a.op("CMP", WLEN);
+    a.jump("JZ", &done);
+    a.sta(WP + 1);
+    a.op_ind("LDA", WP);
+    a.op_imm("SUB", b'0');
+    a.op_imm("CMP", 10);
+    a.jump("JW", &digit);
+    a.jump("JMP", &fail);
+    a.label(&digit);
+    a.sta(T);
+    a.comment("X = X * 10 + digit");
+    a.add16(["ADD", "ADC"], X, X, X);
+    a.copy16(X, Y);
+    a.add16(["ADD", "ADC"], X, X, X);
+    a.add16(["ADD", "ADC"], X, X, X);
+    a.add16(["ADD", "ADC"], X, Y, X);
+    a.lda(X + 1);
+    a.op("ADD", T);
+    a.sta(X + 1);
+    a.lda(X);
+    a.op_imm("ADC", 0);
+    a.sta(X);
+    a.lda(CNT);
+    a.op_imm("ADD", 1);
+    a.sta(CNT);
+    a.jump("JMP", &digits);
+
+    a.label(&done);
+    a.lda(T + 1);
+    a.op_imm("CMP", 0);
+    a.jump("JZ", &positive);
+    a.set16(Y, 0);
+    a.add16(["SUB", "SBB"], Y, X, X);
+    a.label(&positive);
+    a.set8(FLAG, 1);
+    a.ins("RTS");
+    a.label(&fail);
+    a.set8(FLAG, 0);
+    a.ins("RTS");
+    a.empty();
+}
+
+/// `_print_number` prints X as a signed decimal number followed by a space.
+fn print_number(a: &mut Asm) {
+    let positive = a.local();
+
+    a.label("_print_number");
+    a.lda(X);
+    a.op_imm("CMP", 0x80);
+    a.jump("JW", &positive);
+    a.emit(b'-');
+    a.set16(Y, 0);
+    a.add16(["SUB", "SBB"], Y, X, X);
+    a.label(&positive);
+    a.set8(FLAG, 0);
+
+    // the ISA cannot divide, every digit is the number of times its power of ten can be subtracted
+    for power in [10000u16, 1000, 100, 10] {
+        let (count, digit, skip) = (a.local(), a.local(), a.local());
+        a.set8(CNT, 0);
+        a.label(&count);
+        a.lda(X + 1);
+        a.op_imm("SUB", power as u8);
+        a.sta(T);
+        a.lda(X);
+        a.op_imm("SBB", (power >> 8) as u8);
+        a.jump("JW", &digit);
+        a.sta(X);
+        a.lda(T);
+        a.sta(X + 1);
+        a.lda(CNT);
+        a.op_imm("ADD", 1);
+        a.sta(CNT);
+        a.jump("JMP", &count);
+        a.label(&digit);
+        a.comment("leading zeros are not printed");
+        a.lda(CNT);
+        a.op("ADD", FLAG);
+        a.op_imm("CMP", 0);
+        a.jump("JZ", &skip);
+        a.lda(CNT);
+        a.op_imm("ADD", b'0');
+        a.sta(TTY);
+        a.set8(FLAG, 1);
+        a.label(&skip);
+    }
+
+    a.lda(X + 1);
+    a.op_imm("ADD", b'0');
+    a.sta(TTY);
+    a.emit(b' ');
+    a.ins("RTS");
+    a.empty();
+}
+
+/// Initializes the variables, builds the headers of the primitives and starts the outer interpreter.
+fn boot(a: &mut Asm) {
+    a.label("_boot");
+    a.set16(SPH, STACK_HIGH);
+    a.set16(SPL, STACK_LOW);
+    a.set16(TIBP, TIB);
+    a.set16(WP, WORD_BUFFER);
+    a.set16(HERE, DICTIONARY);
+    a.set16(LATEST, 0);
+    a.set8(STATE, 0);
+    a.set16(THREAD + 2, xt("_w_done"));
+
+    for Word {
+        name,
+        label,
+        immediate,
+        ..
+    } in WORDS
+    {
+        let Some(name) = name else {
+            continue;
+        };
+        a.comment(name);
+        for (idx, c) in name.bytes().enumerate() {
+            a.lda_imm(c);
+            a.sta(WORD_BUFFER + idx as u16);
+        }
+        a.set8(WLEN, name.len() as u8);
+        a.jsr("_create");
+        a.compile(label);
+        if *immediate {
+            a.jsr("_set_immediate");
+        }
+    }
+
+    a.print("Tower Forth\n");
+    a.jump("JMP", "_read_line");
+    a.empty();
+}
+
+/// Writes the kernel to a file.
+pub fn forth(file_out: &str) -> Result<String, AssemblerError> {
+    let code = kernel();
+    write_file(file_out, code.as_bytes())?;
+    Ok(code)
+}
@@ -0,0 +1,820 @@
+use crate::emu::TTY;
+
+use super::{Asm, CNT, FLAG, HERE, IP, LATEST, P, STATE, T, TIBP, TLEN, W, WLEN, X, Y, Z};
+
+/// A primitive, its token is its position in `WORDS`.
+pub(super) struct Word {
+    /// Name in the dictionary, internal words have none.
+    pub name: Option<&'static str>,
+    pub label: &'static str,
+    /// Immediate words are executed while compiling.
+    pub immediate: bool,
+    pub code: fn(&mut Asm),
+}
+
+const fn word(name: &'static str, label: &'static str, code: fn(&mut Asm)) -> Word {
+    Word {
+        name: Some(name),
+        label,
+        immediate: false,
+        code,
+    }
+}
+
+const fn immediate(name: &'static str, label: &'static str, code: fn(&mut Asm)) -> Word {
+    Word {
+        name: Some(name),
+        label,
+        immediate: true,
+        code,
+    }
+}
+
+const fn internal(label: &'static str, code: fn(&mut Asm)) -> Word {
+    Word {
+        name: None,
+        label,
+        immediate: false,
+        code,
+    }
+}
+
+pub(super) const WORDS: &[Word] = &[
+    word("EXIT", "_w_exit", exit),
+    internal("_w_lit", lit),
+    internal("_w_branch", branch),
+    internal("_w_zero_branch", zero_branch),
+    internal("_w_done", done),
+    internal("_w_print_string", print_string),
+    word("EXECUTE", "_w_execute", execute),
+    // stack
+    word("DUP", "_w_dup", dup),
+    word("DROP", "_w_drop", drop),
+    word("SWAP", "_w_swap", swap),
+    word("OVER", "_w_over", over),
+    word("ROT", "_w_rot", rot),
+    word(">R", "_w_to_r", to_r),
+    word("R>", "_w_r_from", r_from),
+    word("R@", "_w_r_fetch", r_fetch),
+    // memory
+    word("@", "_w_fetch", fetch),
+    word("!", "_w_store", store),
+    word("C@", "_w_c_fetch", c_fetch),
+    word("C!", "_w_c_store", c_store),
+    // arithmetic and logic
+    word("+", "_w_plus", plus),
+    word("-", "_w_minus", minus),
+    word("*", "_w_star", star),
+    word("AND", "_w_and", and),
+    word("OR", "_w_or", or),
+    word("XOR", "_w_xor", xor),
+    word("INVERT", "_w_invert", invert),
+    word("NEGATE", "_w_negate", negate),
+    word("1+", "_w_one_plus", one_plus),
+    word("1-", "_w_one_minus", one_minus),
+    word("2*", "_w_two_star", two_star),
+    word("2/", "_w_two_slash", two_slash),
+    word("=", "_w_equal", equal),
+    word("<", "_w_less", less),
+    word(">", "_w_greater", greater),
+    word("U<", "_w_u_less", u_less),
+    word("0=", "_w_zero_equal", zero_equal),
+    word("0<", "_w_zero_less", zero_less),
+    // I/O
+    word("EMIT", "_w_emit", emit),
+    word("KEY", "_w_key", key),
+    word(".", "_w_dot", dot),
+    word("CR", "_w_cr", cr),
+    word("SPACE", "_w_space", space),
+    word("WORDS", "_w_words", words),
+    word("BYE", "_w_bye", bye),
+    // compiler
+    word("HERE", "_w_here", here),
+    word(",", "_w_comma", comma),
+    word("C,", "_w_c_comma", c_comma),
+    word("ALLOT", "_w_allot", allot),
+    word(":", "_w_colon", colon),
+    immediate(";", "_w_semicolon", semicolon),
+    word("IMMEDIATE", "_w_immediate", set_immediate),
+    word("'", "_w_tick", tick),
+    immediate("[", "_w_left_bracket", left_bracket),
+    word("]", "_w_right_bracket", right_bracket),
+    word("VARIABLE", "_w_variable", variable),
+    word("CONSTANT", "_w_constant", constant),
+    immediate("IF", "_w_if", if_),
+    immediate("ELSE", "_w_else", else_),
+    immediate("THEN", "_w_then", then),
+    immediate("BEGIN", "_w_begin", begin),
+    immediate("UNTIL", "_w_until", until),
+    immediate("AGAIN", "_w_again", again),
+    immediate("WHILE", "_w_while", while_),
+    immediate("REPEAT", "_w_repeat", repeat),
+    immediate(".\"", "_w_dot_quote", dot_quote),
+    immediate("(", "_w_paren", paren),
+    immediate("\\", "_w_backslash", backslash),
+];
+
+// ========== inner interpreter ==========
+
+fn exit(a: &mut Asm) {
+    a.ins("POA");
+    a.sta(IP + 1);
+    a.ins("POA");
+    a.sta(IP);
+    a.next();
+}
+
+fn lit(a: &mut Asm) {
+    a.jsr("_fetch_ip");
+    a.copy16(W, X);
+    a.jsr("_push_x");
+    a.next();
+}
+
+fn branch(a: &mut Asm) {
+    a.jsr("_fetch_ip");
+    a.copy16(W, IP);
+    a.next();
+}
+
+fn zero_branch(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.jsr("_fetch_ip");
+    a.lda(X);
+    a.op_imm("CMP", 0);
+    a.jump("JNZ", "_next");
+    a.lda(X + 1);
+    a.op_imm("CMP", 0);
+    a.jump("JNZ", "_next");
+    a.copy16(W, IP);
+    a.next();
+}
+
+fn done(a: &mut Asm) {
+    a.comment("a word run by the outer interpreter has finished");
+    a.jump("JMP", "_interpret");
+}
+
+fn print_string(a: &mut Asm) {
+    a.comment("prints the counted string following the cell");
+    let (print_loop, end) = (a.local(), a.local());
+    a.op_ind("LDA", IP);
+    a.sta(CNT);
+    a.inc16(IP);
+    a.label(&print_loop);
+    a.lda(CNT);
+    a.op_imm("CMP", 0);
+    a.jump("JZ", &end);
+    a.op_ind("LDA", IP);
+    a.sta(TTY);
+    a.inc16(IP);
+    a.lda(CNT);
+    a.op_imm("SUB", 1);
+    a.sta(CNT);
+    a.jump("JMP", &print_loop);
+    a.label(&end);
+    a.next();
+}
+
+fn execute(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.copy16(X, W);
+    a.jump("JMP", "_dispatch");
+}
+
+// ========== stack ==========
+
+fn dup(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.jsr("_push_x");
+    a.jsr("_push_x");
+    a.next();
+}
+
+fn drop(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.next();
+}
+
+fn swap(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.jsr("_pop_y");
+    a.jsr("_push_x");
+    a.jsr("_push_y");
+    a.next();
+}
+
+fn over(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.jsr("_pop_y");
+    a.jsr("_push_y");
+    a.jsr("_push_x");
+    a.jsr("_push_y");
+    a.next();
+}
+
+fn rot(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.jsr("_pop_y");
+    a.jsr("_pop_z");
+    a.jsr("_push_y");
+    a.jsr("_push_x");
+    a.jsr("_push_z");
+    a.next();
+}
+
+fn to_r(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.lda(X);
+    a.ins("PSA");
+    a.lda(X + 1);
+    a.ins("PSA");
+    a.next();
+}
+
+fn r_from(a: &mut Asm) {
+    a.ins("POA");
+    a.sta(X + 1);
+    a.ins("POA");
+    a.sta(X);
+    a.jsr("_push_x");
+    a.next();
+}
+
+fn r_fetch(a: &mut Asm) {
+    a.ins("POA");
+    a.sta(X + 1);
+    a.ins("POA");
+    a.sta(X);
+    a.ins("PSA");
+    a.lda(X + 1);
+    a.ins("PSA");
+    a.jsr("_push_x");
+    a.next();
+}
+
+// ========== memory ==========
+
+fn fetch(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.op_ind("LDA", X);
+    a.sta(Y);
+    a.inc16(X);
+    a.op_ind("LDA", X);
+    a.sta(Y + 1);
+    a.jsr("_push_y");
+    a.next();
+}
+
+fn store(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.jsr("_pop_y");
+    a.lda(Y);
+    a.op_ind("STA", X);
+    a.inc16(X);
+    a.lda(Y + 1);
+    a.op_ind("STA", X);
+    a.next();
+}
+
+fn c_fetch(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.op_ind("LDA", X);
+    a.sta(Y + 1);
+    a.set8(Y, 0);
+    a.jsr("_push_y");
+    a.next();
+}
+
+fn c_store(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.jsr("_pop_y");
+    a.lda(Y + 1);
+    a.op_ind("STA", X);
+    a.next();
+}
+
+// ========== arithmetic and logic ==========
+
+/// Pops X and then Y, runs the code and pushes X.
+fn binary(a: &mut Asm, code: impl Fn(&mut Asm)) {
+    a.jsr("_pop_x");
+    a.jsr("_pop_y");
+    code(a);
+    a.jsr("_push_x");
+    a.next();
+}
+
+fn unary(a: &mut Asm, code: impl Fn(&mut Asm)) {
+    a.jsr("_pop_x");
+    code(a);
+    a.jsr("_push_x");
+    a.next();
+}
+
+fn plus(a: &mut Asm) {
+    binary(a, |a| a.add16(["ADD", "ADC"], Y, X, X));
+}
+
+fn minus(a: &mut Asm) {
+    binary(a, |a| a.add16(["SUB", "SBB"], Y, X, X));
+}
+
+fn star(a: &mut Asm) {
+    binary(a, |a| {
+        // shift and add, Z collects the product while Y is shifted out
+        let (mul_loop, add, skip, done) = (a.local(), a.local(), a.local(), a.local());
+        a.set16(Z, 0);
+        a.label(&mul_loop);
+        a.jump_if_zero16(Y, &done);
+        a.lda(Y + 1);
+        a.op_imm("NAND", 1);
+        a.op_imm("CMP", 0xFF);
+        a.jump("JNZ", &add);
+        a.jump("JMP", &skip);
+        a.label(&add);
+        a.add16(["ADD", "ADC"], Z, X, Z);
+        a.label(&skip);
+        a.add16(["ADD", "ADC"], X, X, X);
+        shift_right(a, Y);
+        a.jump("JMP", &mul_loop);
+        a.label(&done);
+        a.copy16(Z, X);
+    });
+}
+
+/// Logical shift right of a 16 bit variable, SR does not shift through the wrap flag.
+fn shift_right(a: &mut Asm, var: u16) {
+    let skip = a.local();
+    a.lda(var + 1);
+    a.ins("SR %A");
+    a.sta(var + 1);
+    a.lda(var);
+    a.op_imm("NAND", 1);
+    a.op_imm("CMP", 0xFF);
+    a.jump("JZ", &skip);
+    a.lda(var + 1);
+    a.op_imm("ADD", 0x80);
+    a.sta(var + 1);
+    a.label(&skip);
+    a.lda(var);
+    a.ins("SR %A");
+    a.sta(var);
+}
+
+fn and(a: &mut Asm) {
+    binary(a, |a| {
+        for offset in [0, 1] {
+            a.lda(Y + offset);
+            a.op("NAND", X + offset);
+            a.ins("NOT %A");
+            a.sta(X + offset);
+        }
+    });
+}
+
+fn or(a: &mut Asm) {
+    binary(a, |a| {
+        // x | y = ~x NAND ~y
+        for offset in [0, 1] {
+            a.lda(X + offset);
+            a.ins("NOT %A");
+            a.sta(X + offset);
+            a.lda(Y + offset);
+            a.ins("NOT %A");
+            a.op("NAND", X + offset);
+            a.sta(X + offset);
+        }
+    });
+}
+
+fn xor(a: &mut Asm) {
+    binary(a, |a| {
+        // x ^ y = (x NAND n) NAND (y NAND n) where n = x NAND y
+        for offset in [0, 1] {
+            a.lda(X + offset);
+            a.op("NAND", Y + offset);
+            a.sta(T);
+            a.lda(X + offset);
+            a.op("NAND", T);
+            a.sta(T + 1);
+            a.lda(Y + offset);
+            a.op("NAND", T);
+            a.op("NAND", T + 1);
+            a.sta(X + offset);
+        }
+    });
+}
+
+fn invert(a: &mut Asm) {
+    unary(a, |a| {
+        for offset in [0, 1] {
+            a.lda(X + offset);
+            a.ins("NOT %A");
+            a.sta(X + offset);
+        }
+    });
+}
+
+fn negate(a: &mut Asm) {
+    unary(a, |a| {
+        a.set16(Y, 0);
+        a.add16(["SUB", "SBB"], Y, X, X);
+    });
+}
+
+fn one_plus(a: &mut Asm) {
+    unary(a, |a| a.inc16(X));
+}
+
+fn one_minus(a: &mut Asm) {
+    unary(a, |a| a.add16_imm(X, 0xFFFF));
+}
+
+fn two_star(a: &mut Asm) {
+    unary(a, |a| a.add16(["ADD", "ADC"], X, X, X));
+}
+
+fn two_slash(a: &mut Asm) {
+    unary(a, |a| {
+        // keep the sign bit
+        let (positive, done) = (a.local(), a.local());
+        a.lda(X);
+        a.op_imm("CMP", 0x80);
+        a.jump("JW", &positive);
+        shift_right(a, X);
+        a.op_imm("ADD", 0x80);
+        a.sta(X);
+        a.jump("JMP", &done);
+        a.label(&positive);
+        shift_right(a, X);
+        a.label(&done);
+    });
+}
+
+/// Pops X and then Y and jumps to `_push_true` if Y < X, or X < Y when swapped.
+fn compare(a: &mut Asm, signed: bool, swapped: bool) {
+    let (x, y) = if swapped { (Y, X) } else { (X, Y) };
+    a.jsr("_pop_x");
+    a.jsr("_pop_y");
+    if signed {
+        // flipping the sign bits turns a signed comparison into an unsigned one
+        for var in [X, Y] {
+            a.lda(var);
+            a.op_imm("ADD", 0x80);
+            a.sta(var);
+        }
+    }
+    a.lda(y + 1);
+    a.op("SUB", x + 1);
+    a.lda(y);
+    a.op("SBB", x);
+    a.jump("JW", "_push_true");
+    a.jump("JMP", "_push_false");
+}
+
+fn equal(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.jsr("_pop_y");
+    for offset in [0, 1] {
+        a.lda(X + offset);
+        a.op("CMP", Y + offset);
+        a.jump("JNZ", "_push_false");
+    }
+    a.jump("JMP", "_push_true");
+}
+
+fn less(a: &mut Asm) {
+    compare(a, true, false);
+}
+
+fn greater(a: &mut Asm) {
+    compare(a, true, true);
+}
+
+fn u_less(a: &mut Asm) {
+    compare(a, false, false);
+}
+
+fn zero_equal(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.jump_if_zero16(X, "_push_true");
+    a.jump("JMP", "_push_false");
+}
+
+fn zero_less(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.lda(X);
+    a.op_imm("CMP", 0x80);
+    a.jump("JW", "_push_false");
+    a.jump("JMP", "_push_true");
+}
+
+// ========== I/O ==========
+
+fn emit(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.lda(X + 1);
+    a.sta(TTY);
+    a.next();
+}
+
+fn key(a: &mut Asm) {
+    a.jsr("_key");
+    a.sta(X + 1);
+    a.set8(X, 0);
+    a.jsr("_push_x");
+    a.next();
+}
+
+fn dot(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.jsr("_print_number");
+    a.next();
+}
+
+fn cr(a: &mut Asm) {
+    a.emit(b'\n');
+    a.next();
+}
+
+fn space(a: &mut Asm) {
+    a.emit(b' ');
+    a.next();
+}
+
+fn words(a: &mut Asm) {
+    let (header_loop, name_loop, name_done, done) = (a.local(), a.local(), a.local(), a.local());
+    a.copy16(LATEST, X);
+    a.label(&header_loop);
+    a.jump_if_zero16(X, &done);
+    a.copy16(X, P);
+    a.add16_imm(P, 2);
+    a.op_ind("LDA", P);
+    a.op_imm("NAND", 0x7F);
+    a.ins("NOT %A");
+    a.sta(CNT);
+    a.label(&name_loop);
+    a.lda(CNT);
+    a.op_imm("CMP", 0);
+    a.jump("JZ", &name_done);
+    a.inc16(P);
+    a.op_ind("LDA", P);
+    a.sta(TTY);
+    a.lda(CNT);
+    a.op_imm("SUB", 1);
+    a.sta(CNT);
+    a.jump("JMP", &name_loop);
+    a.label(&name_done);
+    a.emit(b' ');
+    a.comment("follow the link");
+    a.op_ind("LDA", X);
+    a.sta(Y);
+    a.inc16(X);
+    a.op_ind("LDA", X);
+    a.sta(X + 1);
+    a.lda(Y);
+    a.sta(X);
+    a.jump("JMP", &header_loop);
+    a.label(&done);
+    a.next();
+}
+
+fn bye(a: &mut Asm) {
+    a.ins("HLT");
+}
+
+// ========== compiler ==========
+
+fn here(a: &mut Asm) {
+    a.copy16(HERE, X);
+    a.jsr("_push_x");
+    a.next();
+}
+
+fn comma(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.jsr("_comma");
+    a.next();
+}
+
+fn c_comma(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.jsr("_c_comma");
+    a.next();
+}
+
+fn allot(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.add16(["ADD", "ADC"], HERE, X, HERE);
+    a.next();
+}
+
+/// Parses a name and appends its header, the body starts after the execution token.
+fn header(a: &mut Asm) {
+    let named = a.local();
+    a.jsr("_word");
+    a.lda(WLEN);
+    a.op_imm("CMP", 0);
+    a.jump("JNZ", &named);
+    a.print(" name?");
+    a.jump("JMP", "_abort");
+    a.label(&named);
+    a.jsr("_create");
+    a.copy16(HERE, X);
+    a.add16_imm(X, 2);
+    a.jsr("_comma");
+}
+
+fn colon(a: &mut Asm) {
+    header(a);
+    a.set8(STATE, 1);
+    a.next();
+}
+
+fn semicolon(a: &mut Asm) {
+    a.compile("_w_exit");
+    a.set8(STATE, 0);
+    a.next();
+}
+
+fn set_immediate(a: &mut Asm) {
+    a.jsr("_set_immediate");
+    a.next();
+}
+
+fn tick(a: &mut Asm) {
+    let found = a.local();
+    a.jsr("_word");
+    a.jsr("_find");
+    a.lda(FLAG);
+    a.op_imm("CMP", 0);
+    a.jump("JNZ", &found);
+    a.jump("JMP", "_undefined");
+    a.label(&found);
+    a.jsr("_push_x");
+    a.next();
+}
+
+fn left_bracket(a: &mut Asm) {
+    a.set8(STATE, 0);
+    a.next();
+}
+
+fn right_bracket(a: &mut Asm) {
+    a.set8(STATE, 1);
+    a.next();
+}
+
+fn variable(a: &mut Asm) {
+    a.comment("body: LIT <address of the cell> EXIT <cell>");
+    header(a);
+    a.compile("_w_lit");
+    a.copy16(HERE, X);
+    a.add16_imm(X, 4);
+    a.jsr("_comma");
+    a.compile("_w_exit");
+    a.set16(X, 0);
+    a.jsr("_comma");
+    a.next();
+}
+
+fn constant(a: &mut Asm) {
+    a.comment("body: LIT <value> EXIT");
+    a.jsr("_pop_z");
+    header(a);
+    a.compile("_w_lit");
+    a.copy16(Z, X);
+    a.jsr("_comma");
+    a.compile("_w_exit");
+    a.next();
+}
+
+/// Appends a branch with an unresolved target and pushes the address of the target.
+fn forward_branch(a: &mut Asm, branch: &str) {
+    a.compile(branch);
+    a.copy16(HERE, X);
+    a.jsr("_push_x");
+    a.set16(X, 0);
+    a.jsr("_comma");
+}
+
+fn if_(a: &mut Asm) {
+    forward_branch(a, "_w_zero_branch");
+    a.next();
+}
+
+fn else_(a: &mut Asm) {
+    a.jsr("_pop_z");
+    forward_branch(a, "_w_branch");
+    a.copy16(Z, X);
+    a.jsr("_resolve");
+    a.next();
+}
+
+fn then(a: &mut Asm) {
+    a.jsr("_pop_x");
+    a.jsr("_resolve");
+    a.next();
+}
+
+fn begin(a: &mut Asm) {
+    a.copy16(HERE, X);
+    a.jsr("_push_x");
+    a.next();
+}
+
+fn until(a: &mut Asm) {
+    a.compile("_w_zero_branch");
+    a.jsr("_pop_x");
+    a.jsr("_comma");
+    a.next();
+}
+
+fn again(a: &mut Asm) {
+    a.compile("_w_branch");
+    a.jsr("_pop_x");
+    a.jsr("_comma");
+    a.next();
+}
+
+fn while_(a: &mut Asm) {
+    forward_branch(a, "_w_zero_branch");
+    a.next();
+}
+
+fn repeat(a: &mut Asm) {
+    a.jsr("_pop_z");
+    a.compile("_w_branch");
+    a.jsr("_pop_x");
+    a.jsr("_comma");
+    a.copy16(Z, X);
+    a.jsr("_resolve");
+    a.next();
+}
+
+/// Reads the input up to the delimiter (which is skipped), `A` holds the character for the code.
+fn parse_until(a: &mut Asm, delimiter: u8, code: impl Fn(&mut Asm)) {
+    let (parse_loop, end) = (a.local(), a.local());
+    a.label(&parse_loop);
+    a.lda(TIBP + 1);
+    a.op("CMP", TLEN);
+    a.jump("JZ", &end);
+    a.op_ind("LDA", TIBP);
+    a.sta(T);
+    a.lda(TIBP + 1);
+    a.op_imm("ADD", 1);
+    a.sta(TIBP + 1);
+    a.lda(T);
+    a.op_imm("CMP", delimiter);
+    a.jump("JZ", &end);
+    code(a);
+    a.jump("JMP", &parse_loop);
+    a.label(&end);
+}
+
+fn skip_delimiter(a: &mut Asm) {
+    let end = a.local();
+    a.lda(TIBP + 1);
+    a.op("CMP", TLEN);
+    a.jump("JZ", &end);
+    a.op_imm("ADD", 1);
+    a.sta(TIBP + 1);
+    a.label(&end);
+}
+
+fn dot_quote(a: &mut Asm) {
+    let compiling = a.local();
+    skip_delimiter(a);
+    a.lda(STATE);
+    a.op_imm("CMP", 0);
+    a.jump("JNZ", &compiling);
+    parse_until(a, b'"', |a| a.sta(TTY));
+    a.next();
+
+    a.label(&compiling);
+    a.comment("(.\") followed by the length and the characters");
+    a.compile("_w_print_string");
+    a.copy16(HERE, Z);
+    a.set8(X + 1, 0);
+    a.jsr("_c_comma");
+    parse_until(a, b'"', |a| {
+        a.sta(X + 1);
+        a.jsr("_c_comma");
+        a.op_ind("LDA", Z);
+        a.op_imm("ADD", 1);
+        a.op_ind("STA", Z);
+    });
+    a.next();
+}
+
+fn paren(a: &mut Asm) {
+    parse_until(a, b')', |_| {});
+    a.next();
+}
+
+fn backslash(a: &mut Asm) {
+    a.lda(TLEN);
+    a.sta(TIBP + 1);
+    a.next();
+}
@@ -0,0 +1,304 @@
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::{get_im_name, im_idx_to_val, read_file, AssemblerError, InstructionMode, SyntaxError};
+
+/// Number of instruction modes the assembler syntax knows about.
+pub const INSTRUCTION_MODE_COUNT: u32 = 7;
+
+/// Widest microcode ROM address that is accepted, in bits.
+pub const MAX_ADDRESS_BITS: u32 = 24;
+
+const TOWER_ISA: &str = include_str!("../isa/tower.toml");
+
+static ACTIVE_ISA: OnceLock<Isa> = OnceLock::new();
+
+/// Returns the ISA used by the assemblers and disassemblers. This is the built-in Tower ISA unless `set_isa` was called before.
+pub fn isa() -> &'static Isa {
+    ACTIVE_ISA.get_or_init(Isa::tower)
+}
+
+/// Replaces the built-in ISA. Has to be called before anything is assembled, the ISA cannot be changed once it is in use.
+pub fn set_isa(isa: Isa) -> Result<(), AssemblerError> {
+    isa.validate()?;
+    ACTIVE_ISA
+        .set(isa)
+        .or(Err(AssemblerError::Semantic(SyntaxError::new(
+            0,
+            String::from("The ISA is already in use and cannot be changed."),
+        ))))
+}
+
+/// Widths of the fields the microcode ROM address is made of.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AddressFields {
+    pub opcode_bits: u32,
+    pub mode_bits: u32,
+    pub flag_bits: u32,
+    pub step_bits: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InstructionSpec {
+    pub name: String,
+    pub opcode: u32,
+    /// Allowed instruction modes, written as a list of mode names in the description.
+    #[serde(deserialize_with = "deserialize_modes")]
+    pub modes: InstructionMode,
+    /// What the instruction does, shown by the language server.
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SignalSpec {
+    pub name: String,
+    /// Bit of the control word driving the signal.
+    pub bit: u32,
+    /// What the signal does, shown by the language server.
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Description of an instruction set: its instructions, control signals, flags and the layout of the microcode ROM.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Isa {
+    /// Flag names, the first one is the lowest bit of the flags field.
+    pub flags: Vec<String>,
+    pub address: AddressFields,
+    pub instructions: Vec<InstructionSpec>,
+    pub signals: Vec<SignalSpec>,
+}
+
+fn deserialize_modes<'de, D>(deserializer: D) -> Result<InstructionMode, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let names: Vec<String> = Vec::deserialize(deserializer)?;
+
+    let mut modes = 0;
+    for name in names {
+        let idx = (0..INSTRUCTION_MODE_COUNT)
+            .find(|&i| get_im_name(i).unwrap().eq_ignore_ascii_case(&name))
+            .ok_or_else(|| {
+                serde::de::Error::custom(format!("unknown instruction mode '{}'", name))
+            })?;
+        modes |= im_idx_to_val(idx);
+    }
+    Ok(modes)
+}
+
+impl Isa {
+    /// The instruction set of the Tower computer.
+    pub fn tower() -> Isa {
+        Isa::from_toml(TOWER_ISA).expect("the built-in ISA description is invalid")
+    }
+
+    /// Reads an ISA description from a JSON file if its extension is `.json`, or from a TOML file otherwise.
+    pub fn from_file(path: &str) -> Result<Isa, AssemblerError> {
+        let contents = read_file(path)?;
+
+        if path.to_lowercase().ends_with(".json") {
+            Isa::from_json(&contents)
+        } else {
+            Isa::from_toml(&contents)
+        }
+    }
+
+    pub fn from_toml(description: &str) -> Result<Isa, AssemblerError> {
+        let isa: Isa = toml::from_str(description).map_err(|e| {
+            AssemblerError::Parse(SyntaxError::new(
+                0,
+                format!("Invalid ISA description: {}", e.message()),
+            ))
+        })?;
+        isa.validate()?;
+        Ok(isa)
+    }
+
+    pub fn from_json(description: &str) -> Result<Isa, AssemblerError> {
+        let isa: Isa = serde_json::from_str(description).map_err(|e| {
+            AssemblerError::Parse(SyntaxError::new(
+                0,
+                format!("Invalid ISA description: {}", e),
+            ))
+        })?;
+        isa.validate()?;
+        Ok(isa)
+    }
+
+    /// Checks for duplicate names, opcodes and bits and for values which do not fit into their fields.
+    pub fn validate(&self) -> Result<(), AssemblerError> {
+        let fail = |message: String| Err(AssemblerError::Semantic(SyntaxError::new(0, message)));
+        let address = &self.address;
+
+        if (1 << address.mode_bits) <= INSTRUCTION_MODE_COUNT {
+            return fail(format!(
+                "{} mode bits cannot hold all {} instruction modes.",
+                address.mode_bits, INSTRUCTION_MODE_COUNT
+            ));
+        }
+        if address.opcode_bits + address.mode_bits > u8::BITS {
+            return fail(format!(
+                "{} opcode bits and {} mode bits do not fit into the {}-bit instruction byte.",
+                address.opcode_bits,
+                address.mode_bits,
+                u8::BITS
+            ));
+        }
+        if address.step_bits == 0 {
+            return fail(String::from("At least one step bit is required."));
+        }
+        let address_bits =
+            address.opcode_bits + address.mode_bits + address.flag_bits + address.step_bits;
+        if address_bits > MAX_ADDRESS_BITS {
+            return fail(format!(
+                "The microcode ROM address is {} bits wide, at most {} bits are supported.",
+                address_bits, MAX_ADDRESS_BITS
+            ));
+        }
+
+        if self.flags.len() > address.flag_bits as usize {
+            return fail(format!(
+                "{} flags do not fit into {} flag bits.",
+                self.flags.len(),
+                address.flag_bits
+            ));
+        }
+        for (idx, flag) in self.flags.iter().enumerate() {
+            if self.flags[..idx]
+                .iter()
+                .any(|f| f.eq_ignore_ascii_case(flag))
+            {
+                return fail(format!("Flag '{}' is defined more than once.", flag));
+            }
+        }
+
+        for (idx, ins) in self.instructions.iter().enumerate() {
+            let earlier = &self.instructions[..idx];
+
+            if ins.name.is_empty() || ins.name.contains(char::is_whitespace) {
+                return fail(format!("Invalid instruction name '{}'.", ins.name));
+            }
+            if earlier
+                .iter()
+                .any(|i| i.name.eq_ignore_ascii_case(&ins.name))
+            {
+                return fail(format!(
+                    "Instruction '{}' is defined more than once.",
+                    ins.name
+                ));
+            }
+            if ins.opcode >= (1 << address.opcode_bits) {
+                return fail(format!(
+                    "Opcode 0x{:02x} of '{}' does not fit into {} opcode bits.",
+                    ins.opcode, ins.name, address.opcode_bits
+                ));
+            }
+            if let Some(other) = earlier.iter().find(|i| i.opcode == ins.opcode) {
+                return fail(format!(
+                    "Opcode 0x{:02x} is used by both '{}' and '{}'.",
+                    ins.opcode, other.name, ins.name
+                ));
+            }
+            if ins.modes == 0 {
+                return fail(format!(
+                    "Instruction '{}' has no instruction modes.",
+                    ins.name
+                ));
+            }
+        }
+
+        for (idx, sig) in self.signals.iter().enumerate() {
+            let earlier = &self.signals[..idx];
+
+            if sig.name.is_empty() || sig.name.contains(char::is_whitespace) {
+                return fail(format!("Invalid control signal name '{}'.", sig.name));
+            }
+            if earlier
+                .iter()
+                .any(|s| s.name.eq_ignore_ascii_case(&sig.name))
+            {
+                return fail(format!(
+                    "Control signal '{}' is defined more than once.",
+                    sig.name
+                ));
+            }
+            if sig.bit >= u64::BITS {
+                return fail(format!(
+                    "Bit {} of '{}' does not fit into the control word, at most {} bits are supported.",
+                    sig.bit,
+                    sig.name,
+                    u64::BITS
+                ));
+            }
+            if let Some(other) = earlier.iter().find(|s| s.bit == sig.bit) {
+                return fail(format!(
+                    "Bit {} is used by both '{}' and '{}'.",
+                    sig.bit, other.name, sig.name
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn instruction_by_name(&self, name: &str) -> Option<&InstructionSpec> {
+        self.instructions
+            .iter()
+            .find(|i| i.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn instruction_by_opcode(&self, opcode: u32) -> Option<&InstructionSpec> {
+        self.instructions.iter().find(|i| i.opcode == opcode)
+    }
+
+    /// Returns the control word bit of a signal.
+    pub fn signal_bit(&self, name: &str) -> Option<u64> {
+        self.signals
+            .iter()
+            .find(|s| s.name.eq_ignore_ascii_case(name))
+            .map(|s| s.bit as u64)
+    }
+
+    /// Returns the name of the signal driven by a control word bit.
+    pub fn signal_name(&self, bit: u64) -> Option<&str> {
+        self.signals
+            .iter()
+            .find(|s| s.bit as u64 == bit)
+            .map(|s| s.name.as_str())
+    }
+
+    /// Returns the bit value of a flag.
+    pub fn flag_value(&self, name: &str) -> Option<u32> {
+        self.flags
+            .iter()
+            .position(|f| f.eq_ignore_ascii_case(name))
+            .map(|idx| 1 << idx)
+    }
+
+    pub fn flag_combinations(&self) -> usize {
+        1 << self.address.flag_bits
+    }
+
+    pub fn max_micro_steps(&self) -> usize {
+        1 << self.address.step_bits
+    }
+
+    /// Number of bytes every control word takes in the microcode ROM.
+    pub fn control_bytes(&self) -> usize {
+        let highest_bit = self.signals.iter().map(|s| s.bit).max().unwrap_or(0);
+        highest_bit as usize / 8 + 1
+    }
+
+    /// Number of control words in the microcode ROM.
+    pub fn rom_size(&self) -> usize {
+        let address = &self.address;
+        1 << (address.opcode_bits + address.mode_bits + address.flag_bits + address.step_bits)
+    }
+}
@@ -1,7 +1,16 @@
+use std::error::Error;
+use std::fmt;
 use std::fs::File;
-use std::io::prelude::*;
+use std::io::{self, prelude::*};
 
 pub mod asm;
+pub mod compiler;
+pub mod emu;
+pub mod format;
+pub mod forth;
+pub mod isa;
+pub mod link;
+pub mod lsp;
 pub mod microasm;
 
 pub type InstructionMode = u32;
@@ -17,6 +26,26 @@ pub const IM_INDIRECT: InstructionMode = im_idx_to_val(4);
 pub const IM_ZEROPAGE: InstructionMode = im_idx_to_val(5);
 pub const IM_ACCUMULATOR: InstructionMode = im_idx_to_val(6);
 
+/// Number of bytes addressable by the Tower architecture.
+pub const ADDRESS_SPACE_SIZE: usize = 0x10000;
+
+/// First address of the zero page, the first 256 bytes of the RAM.
+///
+/// The circuit maps its RAM at `0x4000` (`_RAMSTART`), the memory map of `spec/arch.md` places the zero page at `0x100`.
+pub const ZERO_PAGE_START: u16 = 0x4000;
+
+/// Number of bytes of the zero page.
+pub const ZERO_PAGE_SIZE: u16 = 0x100;
+
+/// Size of the ROM, code is placed at its start.
+pub const ROM_SIZE: u16 = 0x4000;
+
+/// First address of the RAM after the zero page and the stack, data sections are placed from here on.
+pub const DATA_START: u16 = 0x4200;
+
+/// First address after the RAM available to data sections, the registers of the standard library start here.
+pub const DATA_END: u16 = 0xFEE0;
+
 pub const fn get_argument_size_by_im(im: InstructionMode) -> u32 {
     match im {
         IM_ABSOLUTE | IM_CONSTANT | IM_INDIRECT => 2,
@@ -25,6 +54,7 @@ pub const fn get_argument_size_by_im(im: InstructionMode) -> u32 {
     }
 }
 
+#[allow(clippy::result_unit_err)]
 pub fn get_im_name(im: InstructionMode) -> Result<&'static str, ()> {
     let im_v = im_idx_to_val(im);
     let im = match im_v {
@@ -51,110 +81,153 @@ pub fn get_available_im_names(ims: u32) -> Vec<String> {
     output
 }
 
-const IM_IMM_ABS_ZP_IND: u32 = IM_IMMEDIATE | IM_ABSOLUTE | IM_ZEROPAGE | IM_INDIRECT;
-pub type Instruction = (&'static str, u32);
-pub static INSTRUCTIONS: &[Instruction] = &[
-    ("NOP", IM_IMPLIED),
-    ("LDA", IM_IMM_ABS_ZP_IND),
-    ("STA", IM_CONSTANT | IM_INDIRECT | IM_ZEROPAGE),
-    ("ADC", IM_IMM_ABS_ZP_IND),
-    ("ADD", IM_IMM_ABS_ZP_IND),
-    ("SBB", IM_IMM_ABS_ZP_IND),
-    ("SUB", IM_IMM_ABS_ZP_IND),
-    ("INC", IM_ABSOLUTE | IM_ACCUMULATOR),
-    ("DEC", IM_ABSOLUTE | IM_ACCUMULATOR),
-    ("CMP", IM_IMM_ABS_ZP_IND),
-    ("JMP", IM_CONSTANT | IM_INDIRECT),
-    ("JW", IM_CONSTANT | IM_INDIRECT),
-    ("JZ", IM_CONSTANT | IM_INDIRECT),
-    ("JNZ", IM_CONSTANT | IM_INDIRECT),
-    ("NOT", IM_ABSOLUTE | IM_ACCUMULATOR),
-    ("NAND", IM_IMM_ABS_ZP_IND),
-    ("SR", IM_ABSOLUTE | IM_ACCUMULATOR),
-    ("SL", IM_ABSOLUTE | IM_ACCUMULATOR),
-    ("JSR", IM_CONSTANT | IM_INDIRECT),
-    ("RTS", IM_IMPLIED),
-    ("TBA", IM_IMPLIED),
-    ("PSA", IM_IMPLIED),
-    ("PSF", IM_IMPLIED),
-    ("POA", IM_IMPLIED),
-    ("POF", IM_IMPLIED),
-    ("TBA", IM_IMPLIED),
-    ("TAB", IM_IMPLIED),
-    ("TFA", IM_IMPLIED),
-    ("TAF", IM_IMPLIED),
-    ("HLT", IM_IMPLIED),
-];
-
+/// Looks up an instruction of the active ISA, returning its opcode, name and allowed instruction modes.
 pub fn get_instruction_by_name(name: &str) -> Option<(u32, &'static str, u32)> {
-    let sig_idx = INSTRUCTIONS
-        .iter()
-        .position(|&i| i.0.to_lowercase() == name.to_lowercase());
-
-    if let None = sig_idx {
-        return None;
-    }
+    let ins = isa::isa().instruction_by_name(name)?;
 
-    let sig_idx = sig_idx.unwrap();
-    let sig = INSTRUCTIONS[sig_idx];
-
-    return Some((sig_idx as u32, sig.0, sig.1));
+    Some((ins.opcode, ins.name.as_str(), ins.modes))
 }
 
 pub fn read_file(path: &str) -> Result<String, AssemblerError> {
-    let mut file = File::open(path).or(Err(AssemblerError::new(
-        format!("File '{}' was not found", path),
-        None,
-    )))?;
+    let mut file = File::open(path).map_err(|e| AssemblerError::io(path, e))?;
 
     let mut contents = String::new();
     file.read_to_string(&mut contents)
-        .or(Err(AssemblerError::new(
-            "Failed to read the input file".to_string(),
-            None,
-        )))?;
+        .map_err(|e| AssemblerError::io(path, e))?;
 
     Ok(contents)
 }
 
 pub fn read_file_binary(path: &str) -> Result<Vec<u8>, AssemblerError> {
-    let mut file = File::open(path).or(Err(AssemblerError::new(
-        format!("File '{}' was not found", path),
-        None,
-    )))?;
+    let mut file = File::open(path).map_err(|e| AssemblerError::io(path, e))?;
 
     let mut contents: Vec<u8> = Vec::new();
-    file.read_to_end(&mut contents).or(Err(AssemblerError::new(
-        "Failed to read the input file".to_string(),
-        None,
-    )))?;
+    file.read_to_end(&mut contents)
+        .map_err(|e| AssemblerError::io(path, e))?;
 
     Ok(contents)
 }
 
+pub fn write_file(path: &str, contents: &[u8]) -> Result<(), AssemblerError> {
+    let mut file = File::create(path).map_err(|e| AssemblerError::io(path, e))?;
+    file.write_all(contents)
+        .map_err(|e| AssemblerError::io(path, e))
+}
+
+/// Everything that can go wrong while assembling or disassembling.
 #[derive(Debug)]
-pub struct AssemblerError {
-    pub message: String,
-    pub syntax_error: Option<SyntaxError>,
+pub enum AssemblerError {
+    /// A file could not be read or written.
+    Io { path: String, source: io::Error },
+    /// The source could not be split into tokens.
+    Lex(SyntaxError),
+    /// The tokens do not form valid code, or an input file is malformed.
+    Parse(SyntaxError),
+    /// The code is well formed, but refers to something that does not exist or cannot be used this way.
+    Semantic(SyntaxError),
+    /// A value does not fit into the space it has.
+    Range(SyntaxError),
 }
 
 impl AssemblerError {
-    pub fn new(message: String, syntax_error: Option<SyntaxError>) -> Self {
-        AssemblerError {
-            message,
-            syntax_error,
+    pub fn io(path: &str, source: io::Error) -> Self {
+        AssemblerError::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    /// Stable code identifying the kind of the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AssemblerError::Io { .. } => "E0001",
+            AssemblerError::Lex(_) => "E0002",
+            AssemblerError::Parse(_) => "E0003",
+            AssemblerError::Semantic(_) => "E0004",
+            AssemblerError::Range(_) => "E0005",
+        }
+    }
+
+    /// Exit code the binaries terminate with (from sysexits.h).
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            // EX_IOERR
+            AssemblerError::Io { .. } => 74,
+            // EX_DATAERR
+            _ => 65,
+        }
+    }
+
+    /// Location and message of the error, if it is about the code.
+    pub fn syntax_error(&self) -> Option<&SyntaxError> {
+        match self {
+            AssemblerError::Io { .. } => None,
+            AssemblerError::Lex(serr)
+            | AssemblerError::Parse(serr)
+            | AssemblerError::Semantic(serr)
+            | AssemblerError::Range(serr) => Some(serr),
+        }
+    }
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssemblerError::Io { path, source } => write!(f, "'{}': {}", path, source),
+            AssemblerError::Lex(serr)
+            | AssemblerError::Parse(serr)
+            | AssemblerError::Semantic(serr)
+            | AssemblerError::Range(serr) => write!(f, "{}", serr),
         }
     }
 }
 
+impl Error for AssemblerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AssemblerError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// A message about the code, pointing to where the problem is.
 #[derive(Debug)]
 pub struct SyntaxError {
+    /// Line in the file, 0 if the error is not about a single line.
     pub line: u32,
     pub message: String,
+    /// Source file the error is in, if there can be more than one.
+    pub file: Option<String>,
 }
 
 impl SyntaxError {
     pub fn new(line: u32, message: String) -> Self {
-        SyntaxError { line, message }
+        SyntaxError {
+            line,
+            message,
+            file: None,
+        }
+    }
+
+    pub fn in_file(file: &str, line: u32, message: String) -> Self {
+        SyntaxError {
+            line,
+            message,
+            file: Some(file.to_string()),
+        }
     }
 }
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.line) {
+            (Some(file), 0) => write!(f, "{}: {}", file, self.message),
+            (Some(file), line) => write!(f, "{}:{}: {}", file, line, self.message),
+            (None, 0) => write!(f, "{}", self.message),
+            (None, line) => write!(f, "line {}: {}", line, self.message),
+        }
+    }
+}
+
+impl Error for SyntaxError {}
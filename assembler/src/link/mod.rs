@@ -0,0 +1,325 @@
+//! The linker, combining relocatable object files to a program laid out per the Tower memory map.
+
+use crate::asm::asm::{AssembledProgram, SourceMapEntry};
+use crate::asm::object::{ObjectFile, RelocationKind};
+use crate::asm::{Label, Section};
+use crate::{
+    write_file, AssemblerError, SyntaxError, DATA_END, DATA_START, ROM_SIZE, ZERO_PAGE_SIZE,
+    ZERO_PAGE_START,
+};
+
+/// Placement of one module in the linked program.
+#[derive(Debug, Clone)]
+pub struct ModuleLayout {
+    /// Source file of the module.
+    pub file: String,
+    pub code_start: u32,
+    pub code_size: u32,
+    pub data_start: u32,
+    pub data_size: u32,
+    pub zero_page_start: u32,
+    pub zero_page_size: u32,
+    /// Labels of the module with their final addresses.
+    pub symbols: Vec<Label>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LinkedProgram {
+    pub program: AssembledProgram,
+    /// Layout of the modules, in the order of the objects.
+    pub modules: Vec<ModuleLayout>,
+}
+
+/// Links the object files to the output file, optionally writing a map of the layout.
+pub fn linker(
+    files_in: &[String],
+    file_out: &str,
+    map: Option<&str>,
+) -> Result<(), AssemblerError> {
+    let objects = files_in
+        .iter()
+        .map(|f| ObjectFile::read(f))
+        .collect::<Result<Vec<ObjectFile>, AssemblerError>>()?;
+
+    let linked = link(&objects)?;
+    write_file(file_out, &linked.program.bytes)?;
+
+    if let Some(map) = map {
+        write_file(map, format_map(&linked).as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Checks every byte the linker patches lies inside the code of the object.
+fn check_offsets(obj: &ObjectFile) -> Result<(), AssemblerError> {
+    let code_size = obj.code.len();
+    let invalid = |message: String| {
+        Err(AssemblerError::Parse(SyntaxError::in_file(
+            &obj.file,
+            0,
+            format!("Invalid object file: {}", message),
+        )))
+    };
+
+    for rel in &obj.relocations {
+        let width = match rel.kind {
+            RelocationKind::Abs16 => 2,
+            RelocationKind::Low8 | RelocationKind::High8 => 1,
+        };
+        if rel.offset as usize + width > code_size {
+            return invalid(format!(
+                "the relocation of '{}' at offset {} is outside of the {} bytes of code.",
+                rel.symbol, rel.offset, code_size
+            ));
+        }
+    }
+    for &offset in &obj.code_addresses {
+        if offset as usize + 2 > code_size {
+            return invalid(format!(
+                "the code address at offset {} is outside of the {} bytes of code.",
+                offset, code_size
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Places the code of the objects after each other from `0x0000`, so the first object holds the entry point,
+/// their data sections from `DATA_START` and their zero page variables from `ZERO_PAGE_START`. Then patches every use of a label with its final address.
+pub fn link(objects: &[ObjectFile]) -> Result<LinkedProgram, AssemblerError> {
+    let mut modules: Vec<ModuleLayout> = Vec::new();
+    let mut code_start = 0;
+    let mut data_start = DATA_START as u32;
+    let mut zero_page_start = ZERO_PAGE_START as u32;
+
+    for obj in objects {
+        check_offsets(obj)?;
+    }
+
+    for obj in objects {
+        let code_size = obj.code.len() as u32;
+        if code_start + code_size > ROM_SIZE as u32 {
+            return Err(AssemblerError::Range(SyntaxError::in_file(
+                &obj.file,
+                0,
+                format!(
+                    "The code of the program does not fit into the ROM of 0x{:x} bytes.",
+                    ROM_SIZE
+                ),
+            )));
+        }
+        if data_start + obj.data_size > DATA_END as u32 {
+            return Err(AssemblerError::Range(SyntaxError::in_file(
+                &obj.file,
+                0,
+                format!(
+                    "The data of the program does not fit into the RAM (0x{:04x}-0x{:04x}).",
+                    DATA_START,
+                    DATA_END - 1
+                ),
+            )));
+        }
+        if zero_page_start + obj.zero_page_size > (ZERO_PAGE_START + ZERO_PAGE_SIZE) as u32 {
+            return Err(AssemblerError::Range(SyntaxError::in_file(
+                &obj.file,
+                0,
+                format!(
+                    "The variables of the program do not fit into the zero page (0x{:04x}-0x{:04x}).",
+                    ZERO_PAGE_START,
+                    ZERO_PAGE_START + ZERO_PAGE_SIZE - 1
+                ),
+            )));
+        }
+
+        let symbols = obj
+            .symbols
+            .iter()
+            .map(|s| Label {
+                name: s.name.clone(),
+                address: match s.section {
+                    Section::Code => code_start + s.offset,
+                    Section::Data => data_start + s.offset,
+                    Section::ZeroPage => zero_page_start + s.offset,
+                },
+                section: s.section,
+            })
+            .collect();
+
+        modules.push(ModuleLayout {
+            file: obj.file.clone(),
+            code_start,
+            code_size,
+            data_start,
+            data_size: obj.data_size,
+            zero_page_start,
+            zero_page_size: obj.zero_page_size,
+            symbols,
+        });
+        code_start += code_size;
+        data_start += obj.data_size;
+        zero_page_start += obj.zero_page_size;
+    }
+
+    // an imported label has to come from another module, whether the local one is exported or not
+    for obj in objects {
+        if let Some(name) = obj
+            .imports
+            .iter()
+            .find(|name| obj.symbols.iter().any(|s| s.name == **name))
+        {
+            return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                &obj.file,
+                0,
+                format!(
+                    "Label '{}' is imported, but it is also defined in this module.",
+                    name
+                ),
+            )));
+        }
+    }
+
+    // labels exported by the modules: (label, index of the module)
+    let mut exports: Vec<(&Label, usize)> = Vec::new();
+    for (idx, obj) in objects.iter().enumerate() {
+        for (symbol, label) in obj.symbols.iter().zip(&modules[idx].symbols) {
+            if !symbol.exported {
+                continue;
+            }
+            if let Some((_, other)) = exports.iter().find(|(l, _)| l.name == label.name) {
+                return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                    &obj.file,
+                    0,
+                    format!(
+                        "Label '{}' is exported by both '{}' and '{}'.",
+                        label.name, objects[*other].file, obj.file
+                    ),
+                )));
+            }
+            exports.push((label, idx));
+        }
+    }
+
+    let mut bytes = Vec::new();
+    let mut source_map = Vec::new();
+
+    for (obj, module) in objects.iter().zip(&modules) {
+        let mut code = obj.code.clone();
+
+        for rel in &obj.relocations {
+            let label = if obj.imports.contains(&rel.symbol) {
+                exports
+                    .iter()
+                    .find(|(l, _)| l.name == rel.symbol)
+                    .map(|(l, _)| *l)
+            } else {
+                module.symbols.iter().find(|l| l.name == rel.symbol)
+            };
+
+            let address = match label {
+                Some(label) => label.address + rel.addend,
+                None => {
+                    return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                        &rel.file,
+                        rel.line,
+                        format!(
+                            "Label '{}' is not defined, it is not exported by any module.",
+                            rel.symbol
+                        ),
+                    )))
+                }
+            };
+
+            let offset = rel.offset as usize;
+            match rel.kind {
+                RelocationKind::Abs16 => {
+                    code[offset] = ((address >> 8) & 0xFF) as u8;
+                    code[offset + 1] = (address & 0xFF) as u8;
+                }
+                RelocationKind::Low8 => code[offset] = (address & 0xFF) as u8,
+                RelocationKind::High8 => code[offset] = ((address >> 8) & 0xFF) as u8,
+            }
+        }
+
+        for &offset in &obj.code_addresses {
+            let offset = offset as usize;
+            let address =
+                ((code[offset] as u32) << 8 | code[offset + 1] as u32) + module.code_start;
+            code[offset] = ((address >> 8) & 0xFF) as u8;
+            code[offset + 1] = (address & 0xFF) as u8;
+        }
+
+        // imports which are not used still have to be defined
+        for name in &obj.imports {
+            if !exports.iter().any(|(l, _)| l.name == *name) {
+                return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                    &obj.file,
+                    0,
+                    format!(
+                        "Label '{}' is imported, but it is not exported by any module.",
+                        name
+                    ),
+                )));
+            }
+        }
+
+        bytes.extend(code);
+        source_map.extend(obj.source_map.iter().map(|e| SourceMapEntry {
+            address: module.code_start + e.address,
+            ..e.clone()
+        }));
+    }
+
+    Ok(LinkedProgram {
+        program: AssembledProgram {
+            bytes,
+            symbols: modules.iter().flat_map(|m| m.symbols.clone()).collect(),
+            source_map,
+            warnings: Vec::new(),
+            zero_page_saved: 0,
+        },
+        modules,
+    })
+}
+
+/// Lists the sections of every module followed by all labels, ordered by their address.
+pub fn format_map(linked: &LinkedProgram) -> String {
+    let mut output = String::from("SECTION  START   END     SIZE    MODULE\n");
+    for module in &linked.modules {
+        for (section, start, size) in [
+            ("code", module.code_start, module.code_size),
+            ("data", module.data_start, module.data_size),
+            ("zpage", module.zero_page_start, module.zero_page_size),
+        ] {
+            if size == 0 {
+                continue;
+            }
+            output += &format!(
+                "{: <7}  0x{:04x}  0x{:04x}  0x{:04x}  {}\n",
+                section,
+                start,
+                start + size - 1,
+                size,
+                module.file
+            );
+        }
+    }
+
+    let mut symbols: Vec<(&Label, &str)> = linked
+        .modules
+        .iter()
+        .flat_map(|m| m.symbols.iter().map(move |l| (l, m.file.as_str())))
+        .collect();
+    symbols.sort_by_key(|(l, _)| l.address);
+
+    output += "\nADDRESS  SECTION  LABEL                     MODULE\n";
+    for (label, file) in symbols {
+        output += &format!(
+            "0x{:04x}   {: <7}  {: <24}  {}\n",
+            label.address,
+            label.section.name(),
+            label.name,
+            file
+        );
+    }
+    output
+}
@@ -0,0 +1,489 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use lsp_types::{
+    CompletionItem, CompletionItemKind, Diagnostic, DiagnosticSeverity, DocumentSymbol, Hover,
+    HoverContents, Location, MarkupContent, MarkupKind, Position, Range,
+    SymbolKind as LspSymbolKind, Url,
+};
+
+use crate::asm::asm::{assemble_str, AssembleOptions};
+use crate::asm::loader::{resolve, FileLoader, SourceLoader};
+use crate::asm::pseudo::{pseudo_instruction_by_name, PseudoInstruction, PSEUDO_INSTRUCTIONS};
+use crate::isa::{isa, InstructionSpec};
+use crate::microasm::asm::MicroassembleOptions;
+use crate::{get_available_im_names, microasm, AssemblerError};
+
+use super::index::{identifiers, Occurrence, SymbolKind};
+use super::{Document, SourceKind};
+
+/// Documents opened in the editor.
+pub type Workspace = HashMap<Url, Document>;
+
+const ASSEMBLY_MARKERS: [&str; 9] = [
+    "macro", "end", "include", "section", "reserve", "export", "import", "zp", "var",
+];
+const MICROASSEMBLY_MARKERS: [&str; 8] = [
+    "def", "macro", "pref", "suf", "if", "else", "end", "include",
+];
+
+/// Name used for a document by the assemblers, includes are resolved relative to it.
+fn file_name(uri: &Url) -> String {
+    match uri.to_file_path() {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(_) => uri.to_string(),
+    }
+}
+
+fn line_range(doc: &Document, line: u32) -> Range {
+    let len = doc.line(line).chars().count() as u32;
+    Range::new(Position::new(line, 0), Position::new(line, len))
+}
+
+fn occurrence_range(o: &Occurrence) -> Range {
+    Range::new(Position::new(o.line, o.start), Position::new(o.line, o.end))
+}
+
+/// Loads included files from the open documents first, so unsaved changes are used, and from the file system otherwise.
+struct WorkspaceLoader<'a> {
+    workspace: &'a Workspace,
+}
+
+impl SourceLoader for WorkspaceLoader<'_> {
+    fn load(&self, path: &str, from: &str) -> Result<(String, String), AssemblerError> {
+        let resolved = resolve(path, from);
+        let open = Url::from_file_path(&resolved)
+            .ok()
+            .and_then(|uri| self.workspace.get(&uri));
+
+        match open {
+            Some(doc) => Ok((resolved.to_string_lossy().to_string(), doc.text.clone())),
+            None => FileLoader.load(path, from),
+        }
+    }
+}
+
+/// Returns the document together with the documents it includes, directly or through other includes.
+fn related_documents(uri: &Url, doc: &Document, workspace: &Workspace) -> Vec<(Url, Document)> {
+    let mut related = vec![(uri.clone(), doc.clone())];
+    let mut idx = 0;
+
+    while idx < related.len() {
+        let (current_uri, current) = related[idx].clone();
+        idx += 1;
+
+        for (_, path) in &current.index.includes {
+            let resolved = resolve(path, &file_name(&current_uri));
+            let Ok(included_uri) = Url::from_file_path(&resolved) else {
+                continue;
+            };
+            if related.iter().any(|(u, _)| *u == included_uri) {
+                continue;
+            }
+
+            let included = match workspace.get(&included_uri) {
+                Some(d) => d.clone(),
+                None => match std::fs::read_to_string(&resolved) {
+                    Ok(text) => Document::new(text, current.kind),
+                    Err(_) => continue,
+                },
+            };
+            related.push((included_uri, included));
+        }
+    }
+    related
+}
+
+/// Assembles the document and reports the errors and warnings found.
+pub fn diagnostics(uri: &Url, doc: &Document, workspace: &Workspace) -> Vec<Diagnostic> {
+    let diagnostic = |line: u32, severity, message: String| Diagnostic {
+        range: line_range(doc, line),
+        severity: Some(severity),
+        source: Some(String::from("tower")),
+        message,
+        ..Default::default()
+    };
+
+    match doc.kind {
+        SourceKind::Assembly => {
+            let name = file_name(uri);
+            let loader = WorkspaceLoader { workspace };
+            let options = AssembleOptions {
+                file_name: name.clone(),
+                loader: Some(&loader),
+                ..Default::default()
+            };
+
+            let (errors, warnings) = match assemble_str(&doc.text, &options) {
+                Ok(program) => (Vec::new(), program.warnings),
+                Err(d) => (d.errors, d.warnings),
+            };
+
+            let to_diagnostic = |d: crate::asm::asm::Diagnostic, severity| {
+                if d.file == name {
+                    return diagnostic(d.line.saturating_sub(1), severity, d.message);
+                }
+                // problems in included files are shown at the include
+                let include_line = doc
+                    .index
+                    .includes
+                    .iter()
+                    .find(|(_, path)| Path::new(path).file_name() == Path::new(&d.file).file_name())
+                    .or(doc.index.includes.first())
+                    .map_or(0, |(line, _)| *line);
+                diagnostic(include_line, severity, format!("{}", d))
+            };
+
+            errors
+                .into_iter()
+                .map(|d| to_diagnostic(d, DiagnosticSeverity::ERROR))
+                .chain(
+                    warnings
+                        .into_iter()
+                        .map(|d| to_diagnostic(d, DiagnosticSeverity::WARNING)),
+                )
+                .collect()
+        }
+        SourceKind::Microassembly => {
+            let name = file_name(uri);
+            let loader = WorkspaceLoader { workspace };
+
+            let options = MicroassembleOptions {
+                file_name: name.clone(),
+                loader: Some(&loader),
+                ..Default::default()
+            };
+
+            match microasm::asm::assemble_str(&doc.text, &options) {
+                Ok(_) => Vec::new(),
+                Err(e) => {
+                    let include_line = |file: &str| {
+                        doc.index
+                            .includes
+                            .iter()
+                            .find(|(_, path)| {
+                                Path::new(path).file_name() == Path::new(file).file_name()
+                            })
+                            .or(doc.index.includes.first())
+                            .map_or(0, |(line, _)| *line)
+                    };
+                    let (line, message) = match (e.syntax_error(), &e) {
+                        (Some(serr), _) if serr.file.as_ref().is_none_or(|f| *f == name) => {
+                            (serr.line.saturating_sub(1), serr.message.clone())
+                        }
+                        // problems in included files are shown at the include
+                        (Some(serr), _) => (
+                            include_line(serr.file.as_deref().unwrap_or_default()),
+                            serr.to_string(),
+                        ),
+                        // so are included files which cannot be loaded
+                        (None, AssemblerError::Io { path, .. }) => {
+                            (include_line(path), e.to_string())
+                        }
+                        (None, _) => (0, e.to_string()),
+                    };
+                    vec![diagnostic(line, DiagnosticSeverity::ERROR, message)]
+                }
+            }
+        }
+    }
+}
+
+/// Returns the identifier at a position.
+fn word_at(doc: &Document, position: Position) -> Option<(u32, u32, String)> {
+    identifiers(doc.line(position.line))
+        .into_iter()
+        .find(|(start, end, _)| *start <= position.character && position.character <= *end)
+}
+
+fn markdown(value: String) -> Hover {
+    Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: None,
+    }
+}
+
+fn describe_instruction(ins: &InstructionSpec) -> String {
+    let mut text = format!("**{}** `0x{:02x}`\n\n", ins.name, ins.opcode);
+    if let Some(description) = &ins.description {
+        text += &format!("{}\n\n", description);
+    }
+    text += &format!("Modes: {}", get_available_im_names(ins.modes).join(", "));
+    text
+}
+
+fn describe_pseudo_instruction(pseudo: &PseudoInstruction) -> String {
+    format!(
+        "**{}** (pseudo-instruction)\n\n{}\n\nModes: {}\n\nExpands to `{}`",
+        pseudo.name,
+        pseudo.description,
+        get_available_im_names(pseudo.modes).join(", "),
+        pseudo.expansion
+    )
+}
+
+pub fn hover(
+    uri: &Url,
+    doc: &Document,
+    position: Position,
+    workspace: &Workspace,
+) -> Option<Hover> {
+    let (start, _, word) = word_at(doc, position)?;
+    let line = doc.line(position.line);
+
+    // macros and labels of the document and its includes
+    if let Some(o) = doc.index.occurrence_at(position.line, position.character) {
+        for (_, related) in related_documents(uri, doc, workspace) {
+            let Some(def) = related.index.definition(&o.name, o.kind) else {
+                continue;
+            };
+            let text = match related.index.block(&o.name, o.kind) {
+                Some(block) => {
+                    let lines: Vec<&str> = (block.start_line..=block.end_line)
+                        .map(|l| related.line(l))
+                        .collect();
+                    format!("```\n{}\n```", lines.join("\n"))
+                }
+                None => format!("Label `{}`, defined on line {}", def.name, def.line + 1),
+            };
+            return Some(markdown(text));
+        }
+    }
+
+    let is_first_word = identifiers(line).first().map(|w| w.0) == Some(start);
+    match doc.kind {
+        SourceKind::Assembly if is_first_word => match isa().instruction_by_name(&word) {
+            Some(ins) => Some(markdown(describe_instruction(ins))),
+            None => pseudo_instruction_by_name(&word)
+                .map(|pseudo| markdown(describe_pseudo_instruction(pseudo))),
+        },
+        SourceKind::Assembly => None,
+        SourceKind::Microassembly => {
+            let keyword = line.split_whitespace().next().unwrap_or_default();
+
+            if keyword.eq_ignore_ascii_case("#def") && !is_first_word {
+                return isa()
+                    .instruction_by_name(&word)
+                    .map(|ins| markdown(describe_instruction(ins)));
+            }
+            if keyword.eq_ignore_ascii_case("#if") {
+                let bit = isa()
+                    .flags
+                    .iter()
+                    .position(|f| f.eq_ignore_ascii_case(&word))?;
+                return Some(markdown(format!(
+                    "Flag **{}**, bit {} of the flags field",
+                    isa().flags[bit],
+                    bit
+                )));
+            }
+
+            let signal = isa()
+                .signals
+                .iter()
+                .find(|s| s.name.eq_ignore_ascii_case(&word))?;
+            let mut text = format!("**{}** (bit {})", signal.name, signal.bit);
+            if let Some(description) = &signal.description {
+                text += &format!("\n\n{}", description);
+            }
+            Some(markdown(text))
+        }
+    }
+}
+
+fn completion_item(
+    label: &str,
+    kind: CompletionItemKind,
+    detail: Option<String>,
+) -> CompletionItem {
+    CompletionItem {
+        label: label.to_string(),
+        kind: Some(kind),
+        detail,
+        ..Default::default()
+    }
+}
+
+pub fn completion(
+    uri: &Url,
+    doc: &Document,
+    position: Position,
+    workspace: &Workspace,
+) -> Vec<CompletionItem> {
+    let line = doc.line(position.line);
+    let prefix: String = line
+        .chars()
+        .take(position.character as usize)
+        .collect::<String>()
+        .trim_start()
+        .to_lowercase();
+
+    let related = related_documents(uri, doc, workspace);
+    let names = |kind: SymbolKind| -> Vec<String> {
+        let mut names: Vec<String> = related
+            .iter()
+            .flat_map(|(_, d)| d.index.definitions(kind).map(|o| o.name.clone()))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    };
+    let symbols = |kind: SymbolKind, item_kind| {
+        names(kind)
+            .into_iter()
+            .map(move |n| completion_item(&n, item_kind, None))
+    };
+    let instructions = || {
+        isa()
+            .instructions
+            .iter()
+            .map(|i| completion_item(&i.name, CompletionItemKind::KEYWORD, i.description.clone()))
+    };
+
+    // markers
+    if prefix.starts_with('#') && !prefix.contains(char::is_whitespace) {
+        let markers: &[&str] = match doc.kind {
+            SourceKind::Assembly => &ASSEMBLY_MARKERS,
+            SourceKind::Microassembly => &MICROASSEMBLY_MARKERS,
+        };
+        return markers
+            .iter()
+            .map(|m| completion_item(m, CompletionItemKind::KEYWORD, None))
+            .collect();
+    }
+
+    match doc.kind {
+        SourceKind::Assembly => {
+            if prefix.starts_with('#') {
+                Vec::new()
+            } else if !prefix.contains(char::is_whitespace) {
+                instructions()
+                    .chain(PSEUDO_INSTRUCTIONS.iter().map(|p| {
+                        completion_item(
+                            p.name,
+                            CompletionItemKind::KEYWORD,
+                            Some(p.description.to_string()),
+                        )
+                    }))
+                    .chain(symbols(SymbolKind::Macro, CompletionItemKind::FUNCTION))
+                    .collect()
+            } else {
+                symbols(SymbolKind::Label, CompletionItemKind::CONSTANT).collect()
+            }
+        }
+        SourceKind::Microassembly => {
+            if prefix.starts_with("#if ") {
+                isa()
+                    .flags
+                    .iter()
+                    .flat_map(|f| [f.to_lowercase(), format!("!{}", f.to_lowercase())])
+                    .map(|f| completion_item(&f, CompletionItemKind::ENUM_MEMBER, None))
+                    .collect()
+            } else if prefix.starts_with("#def ") {
+                instructions().collect()
+            } else if prefix.starts_with('#') {
+                Vec::new()
+            } else {
+                isa()
+                    .signals
+                    .iter()
+                    .map(|s| {
+                        completion_item(&s.name, CompletionItemKind::FIELD, s.description.clone())
+                    })
+                    .chain(symbols(SymbolKind::Macro, CompletionItemKind::FUNCTION))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Finds where the label or macro at a position is defined.
+pub fn definition(
+    uri: &Url,
+    doc: &Document,
+    position: Position,
+    workspace: &Workspace,
+) -> Vec<Location> {
+    let Some(o) = doc.index.occurrence_at(position.line, position.character) else {
+        return Vec::new();
+    };
+
+    related_documents(uri, doc, workspace)
+        .into_iter()
+        .filter_map(|(u, d)| {
+            d.index
+                .definition(&o.name, o.kind)
+                .map(|def| Location::new(u.clone(), occurrence_range(def)))
+        })
+        .take(1)
+        .collect()
+}
+
+/// Finds every use of the label or macro at a position in the document, the documents it includes and all open documents.
+pub fn references(
+    uri: &Url,
+    doc: &Document,
+    position: Position,
+    workspace: &Workspace,
+    include_declaration: bool,
+) -> Vec<Location> {
+    let Some(o) = doc.index.occurrence_at(position.line, position.character) else {
+        return Vec::new();
+    };
+
+    let mut documents = related_documents(uri, doc, workspace);
+    for (u, d) in workspace {
+        if d.kind == doc.kind && !documents.iter().any(|(known, _)| known == u) {
+            documents.push((u.clone(), d.clone()));
+        }
+    }
+
+    documents
+        .iter()
+        .flat_map(|(u, d)| {
+            d.index
+                .occurrences_of(&o.name, o.kind)
+                .filter(|r| include_declaration || !r.is_definition)
+                .map(|r| Location::new(u.clone(), occurrence_range(r)))
+                .collect::<Vec<Location>>()
+        })
+        .collect()
+}
+
+/// Lists the `#def` and `#macro` blocks and the labels of a document.
+#[allow(deprecated)]
+pub fn document_symbols(doc: &Document) -> Vec<DocumentSymbol> {
+    let symbol = |o: &Occurrence, range: Range| DocumentSymbol {
+        name: o.name.clone(),
+        detail: None,
+        kind: match o.kind {
+            SymbolKind::Label => LspSymbolKind::CONSTANT,
+            SymbolKind::Macro => LspSymbolKind::FUNCTION,
+            SymbolKind::Instruction => LspSymbolKind::CLASS,
+        },
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range: occurrence_range(o),
+        children: None,
+    };
+
+    doc.index
+        .occurrences
+        .iter()
+        .filter(|o| o.is_definition)
+        .map(|o| {
+            let range = match doc.index.blocks.iter().find(|b| b.start_line == o.line) {
+                Some(block) => {
+                    let end = line_range(doc, block.end_line).end;
+                    Range::new(Position::new(block.start_line, 0), end)
+                }
+                None => occurrence_range(o),
+            };
+            symbol(o, range)
+        })
+        .collect()
+}
@@ -0,0 +1,316 @@
+use crate::asm::{self, char_literals, Token};
+use crate::isa::isa;
+use crate::microasm::{self, LineType, COMMENT_IDENT};
+
+use super::SourceKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Label,
+    Macro,
+    /// An instruction defined with `#def` in microcode.
+    Instruction,
+}
+
+/// A name written in the source. Lines and columns start at 0, columns count characters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Occurrence {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub line: u32,
+    pub start: u32,
+    pub end: u32,
+    pub is_definition: bool,
+}
+
+/// Lines taken by a `#macro` or `#def` block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub start_line: u32,
+    pub end_line: u32,
+}
+
+/// Labels and macros defined and used in a document, found by running every line through the tokenizer,
+/// so a line with an error does not hide the rest of the document.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Index {
+    pub occurrences: Vec<Occurrence>,
+    pub blocks: Vec<Block>,
+    /// Line and path of every `#include`.
+    pub includes: Vec<(u32, String)>,
+}
+
+impl Index {
+    pub fn build(text: &str, kind: SourceKind) -> Index {
+        match kind {
+            SourceKind::Assembly => build_assembly(text),
+            SourceKind::Microassembly => build_microassembly(text),
+        }
+    }
+
+    /// Returns the occurrence at a position.
+    pub fn occurrence_at(&self, line: u32, column: u32) -> Option<&Occurrence> {
+        self.occurrences
+            .iter()
+            .find(|o| o.line == line && o.start <= column && column <= o.end)
+    }
+
+    /// Returns all occurrences of a name, definitions included.
+    pub fn occurrences_of(
+        &self,
+        name: &str,
+        kind: SymbolKind,
+    ) -> impl Iterator<Item = &Occurrence> {
+        let name = name.to_string();
+        self.occurrences
+            .iter()
+            .filter(move |o| o.kind == kind && o.name.eq_ignore_ascii_case(&name))
+    }
+
+    pub fn definition(&self, name: &str, kind: SymbolKind) -> Option<&Occurrence> {
+        self.occurrences_of(name, kind).find(|o| o.is_definition)
+    }
+
+    /// Returns the definitions of a kind.
+    pub fn definitions(&self, kind: SymbolKind) -> impl Iterator<Item = &Occurrence> {
+        self.occurrences
+            .iter()
+            .filter(move |o| o.kind == kind && o.is_definition)
+    }
+
+    pub fn block(&self, name: &str, kind: SymbolKind) -> Option<&Block> {
+        self.blocks
+            .iter()
+            .find(|b| b.kind == kind && b.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Returns the start, end and text of every identifier in a line, comments, character literals and registers
+/// (`%A`) excluded.
+pub fn identifiers(line: &str) -> Vec<(u32, u32, String)> {
+    let mut found = Vec::new();
+    let mut current: Option<(u32, String)> = None;
+    let literals = char_literals(line);
+
+    for (idx, c) in line.chars().chain([' ']).enumerate() {
+        let in_literal = literals.get(idx).copied().unwrap_or(false);
+        if c == COMMENT_IDENT && !in_literal {
+            break;
+        }
+        if (c.is_ascii_alphanumeric() || c == '_') && !in_literal {
+            current.get_or_insert((idx as u32, String::new())).1.push(c);
+        } else if let Some((start, word)) = current.take() {
+            found.push((start, idx as u32, word));
+        }
+    }
+    if let Some((start, word)) = current {
+        let end = start + word.chars().count() as u32;
+        found.push((start, end, word));
+    }
+
+    let chars: Vec<char> = line.chars().collect();
+    found.retain(|(start, _, _)| *start == 0 || chars[*start as usize - 1] != '%');
+    found
+}
+
+fn occurrence(
+    (start, end, name): &(u32, u32, String),
+    kind: SymbolKind,
+    line: u32,
+    is_definition: bool,
+) -> Occurrence {
+    Occurrence {
+        name: name.clone(),
+        kind,
+        line,
+        start: *start,
+        end: *end,
+        is_definition,
+    }
+}
+
+/// Labels can be used as arguments, anything else is a number.
+fn is_label_name(word: &str) -> bool {
+    let first = word.chars().next().unwrap_or('0');
+    first.is_ascii_alphabetic() || first == '_'
+}
+
+fn build_assembly(text: &str) -> Index {
+    let tokens: Vec<(u32, Token)> = text
+        .lines()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            let token = asm::asm::tokenize(line, "").ok()?.pop()?;
+            Some((idx as u32, token.1))
+        })
+        .collect();
+
+    let macros: Vec<String> = tokens
+        .iter()
+        .filter_map(|(_, t)| match t {
+            Token::Marker(name, args) if name == "macro" => args.first().cloned(),
+            _ => None,
+        })
+        .collect();
+    let is_macro = |name: &str| macros.iter().any(|m| m.eq_ignore_ascii_case(name));
+
+    let mut index = Index::default();
+    let mut open_macro: Option<Block> = None;
+
+    for (line_idx, token) in tokens {
+        let words = identifiers(text.lines().nth(line_idx as usize).unwrap_or_default());
+
+        match token {
+            Token::Label(_) => {
+                if let Some(word) = words.first() {
+                    index
+                        .occurrences
+                        .push(occurrence(word, SymbolKind::Label, line_idx, true));
+                }
+            }
+            Token::Marker(name, args) => match name.as_str() {
+                "macro" => {
+                    // the first identifier is the marker itself
+                    if let Some(word) = words.get(1) {
+                        index
+                            .occurrences
+                            .push(occurrence(word, SymbolKind::Macro, line_idx, true));
+                        open_macro = Some(Block {
+                            name: word.2.clone(),
+                            kind: SymbolKind::Macro,
+                            start_line: line_idx,
+                            end_line: line_idx,
+                        });
+                    }
+                }
+                "end" => {
+                    if let Some(mut block) = open_macro.take() {
+                        block.end_line = line_idx;
+                        index.blocks.push(block);
+                    }
+                }
+                "include" => {
+                    let path = args.join(" ");
+                    index
+                        .includes
+                        .push((line_idx, path.trim_matches('"').to_string()));
+                }
+                _ => {}
+            },
+            Token::Instruction(name, _) => {
+                let mut words = words.iter();
+                let Some(first) = words.next() else {
+                    continue;
+                };
+                if is_macro(&name) {
+                    index
+                        .occurrences
+                        .push(occurrence(first, SymbolKind::Macro, line_idx, false));
+                }
+                for word in words.filter(|w| is_label_name(&w.2)) {
+                    index
+                        .occurrences
+                        .push(occurrence(word, SymbolKind::Label, line_idx, false));
+                }
+            }
+        }
+    }
+
+    // a macro which is never closed takes the rest of the document
+    if let Some(mut block) = open_macro {
+        block.end_line = text.lines().count().saturating_sub(1) as u32;
+        index.blocks.push(block);
+    }
+    index
+}
+
+fn build_microassembly(text: &str) -> Index {
+    let tokens: Vec<(u32, LineType)> = text
+        .lines()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            let token = microasm::asm::tokenize(line, "").ok()?.pop()?;
+            Some((idx as u32, token.1))
+        })
+        .collect();
+
+    let macros: Vec<String> = tokens
+        .iter()
+        .filter_map(|(_, t)| match t {
+            LineType::KeyLine(name, args) if name == "macro" => args.first().cloned(),
+            _ => None,
+        })
+        .collect();
+    let is_macro = |name: &str| macros.iter().any(|m| m.eq_ignore_ascii_case(name));
+
+    let mut index = Index::default();
+    let mut open_block: Option<Block> = None;
+    let mut last_line = 0;
+    // parameters of the current macro
+    let mut params: Vec<String> = Vec::new();
+
+    for (line_idx, token) in tokens {
+        let words = identifiers(text.lines().nth(line_idx as usize).unwrap_or_default());
+
+        match token {
+            LineType::KeyLine(name, args) if name == "def" || name == "macro" => {
+                params = match name.as_str() {
+                    "macro" => args.iter().skip(1).cloned().collect(),
+                    _ => Vec::new(),
+                };
+
+                // a block lasts until the next one starts
+                if let Some(mut block) = open_block.take() {
+                    block.end_line = last_line;
+                    index.blocks.push(block);
+                }
+
+                let kind = if name == "def" {
+                    SymbolKind::Instruction
+                } else {
+                    SymbolKind::Macro
+                };
+                if let Some(word) = words.get(1) {
+                    index
+                        .occurrences
+                        .push(occurrence(word, kind, line_idx, true));
+                    open_block = Some(Block {
+                        name: word.2.clone(),
+                        kind,
+                        start_line: line_idx,
+                        end_line: line_idx,
+                    });
+                }
+            }
+            LineType::KeyLine(name, args) if name == "include" => {
+                let path = args.join(" ");
+                index
+                    .includes
+                    .push((line_idx, path.trim_matches('"').to_string()));
+            }
+            LineType::StepLine(_) => {
+                // words which are no control signal are macros, they can be defined in included files
+                let is_macro_use = |name: &str| {
+                    is_macro(name)
+                        || (isa().signal_bit(&name.to_lowercase()).is_none()
+                            && !params.contains(&name.to_lowercase()))
+                };
+                for word in words.iter().filter(|w| is_macro_use(&w.2)) {
+                    index
+                        .occurrences
+                        .push(occurrence(word, SymbolKind::Macro, line_idx, false));
+                }
+            }
+            _ => {}
+        }
+        last_line = line_idx;
+    }
+
+    if let Some(mut block) = open_block {
+        block.end_line = last_line;
+        index.blocks.push(block);
+    }
+    index
+}
@@ -0,0 +1,60 @@
+pub mod features;
+pub mod index;
+pub mod server;
+
+use index::Index;
+
+// ==============================================
+// =             SHARED DEFINITIONS             =
+// ==============================================
+
+/// Language of a source file, programs and microcode both use the `.asm` extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    Assembly,
+    Microassembly,
+}
+
+impl SourceKind {
+    /// Uses the language id sent by the editor, or looks for markers only the microassembler knows.
+    pub fn detect(language_id: &str, text: &str) -> SourceKind {
+        match language_id {
+            "tower-asm" => SourceKind::Assembly,
+            "tower-microasm" => SourceKind::Microassembly,
+            _ => {
+                let is_microcode = text.lines().any(|line| {
+                    let first = line.split_whitespace().next().unwrap_or_default();
+                    ["#def", "#pref", "#suf"]
+                        .iter()
+                        .any(|m| first.eq_ignore_ascii_case(m))
+                });
+
+                if is_microcode {
+                    SourceKind::Microassembly
+                } else {
+                    SourceKind::Assembly
+                }
+            }
+        }
+    }
+}
+
+/// A source file known to the language server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    pub text: String,
+    pub kind: SourceKind,
+    pub index: Index,
+}
+
+impl Document {
+    pub fn new(text: String, kind: SourceKind) -> Self {
+        let index = Index::build(&text, kind);
+        Document { text, kind, index }
+    }
+
+    /// Returns a line of the document without its line break.
+    pub fn line(&self, line: u32) -> &str {
+        self.text.lines().nth(line as usize).unwrap_or_default()
+    }
+}
@@ -0,0 +1,201 @@
+use std::error::Error;
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationTrait, PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, References,
+    Request as RequestTrait,
+};
+use lsp_types::{
+    CompletionOptions, DocumentSymbolResponse, GotoDefinitionResponse, OneOf,
+    PublishDiagnosticsParams, ServerCapabilities, TextDocumentPositionParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+
+use super::features::{self, Workspace};
+use super::{Document, SourceKind};
+
+type ServerResult<T> = Result<T, Box<dyn Error + Sync + Send>>;
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(true.into()),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![String::from("#")]),
+            ..Default::default()
+        }),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+/// Runs the language server on the standard input and output until the editor shuts it down.
+pub fn run() -> ServerResult<()> {
+    let (connection, io_threads) = Connection::stdio();
+
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+    main_loop(&connection)?;
+
+    // the writer thread stops once the connection is gone
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+fn main_loop(connection: &Connection) -> ServerResult<()> {
+    let mut workspace = Workspace::new();
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                let response = handle_request(&workspace, request);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                if let Some(uri) = handle_notification(&mut workspace, notification)? {
+                    publish_diagnostics(connection, &workspace, uri)?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Updates the open documents, returns the document whose diagnostics changed.
+fn handle_notification(
+    workspace: &mut Workspace,
+    notification: Notification,
+) -> ServerResult<Option<Url>> {
+    match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: <DidOpenTextDocument as NotificationTrait>::Params =
+                serde_json::from_value(notification.params)?;
+            let doc = params.text_document;
+            let kind = SourceKind::detect(&doc.language_id, &doc.text);
+
+            workspace.insert(doc.uri.clone(), Document::new(doc.text, kind));
+            Ok(Some(doc.uri))
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: <DidChangeTextDocument as NotificationTrait>::Params =
+                serde_json::from_value(notification.params)?;
+            let uri = params.text_document.uri;
+
+            // the whole document is sent with every change
+            let (Some(change), Some(doc)) = (params.content_changes.last(), workspace.get(&uri))
+            else {
+                return Ok(None);
+            };
+            let kind = doc.kind;
+            workspace.insert(uri.clone(), Document::new(change.text.clone(), kind));
+            Ok(Some(uri))
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: <DidCloseTextDocument as NotificationTrait>::Params =
+                serde_json::from_value(notification.params)?;
+            workspace.remove(&params.text_document.uri);
+            Ok(Some(params.text_document.uri))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn publish_diagnostics(
+    connection: &Connection,
+    workspace: &Workspace,
+    uri: Url,
+) -> ServerResult<()> {
+    let diagnostics = match workspace.get(&uri) {
+        Some(doc) => features::diagnostics(&uri, doc, workspace),
+        // clear the diagnostics of closed documents
+        None => Vec::new(),
+    };
+
+    let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+    let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+    connection
+        .sender
+        .send(Message::Notification(notification))?;
+    Ok(())
+}
+
+fn handle_request(workspace: &Workspace, request: Request) -> Response {
+    let id = request.id.clone();
+
+    match dispatch(workspace, request) {
+        Ok(Some(result)) => Response::new_ok(id, result),
+        Ok(None) => Response::new_err(
+            id,
+            ErrorCode::MethodNotFound as i32,
+            String::from("Unsupported request."),
+        ),
+        Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+    }
+}
+
+/// Returns the result of a request, or `None` if the request is not supported.
+fn dispatch(workspace: &Workspace, request: Request) -> ServerResult<Option<serde_json::Value>> {
+    // looks up the document a request is about, requests for unknown documents have an empty result
+    let document = |position: &TextDocumentPositionParams| {
+        let uri = position.text_document.uri.clone();
+        workspace.get(&uri).map(|doc| (uri, doc, position.position))
+    };
+
+    let result = match request.method.as_str() {
+        HoverRequest::METHOD => {
+            let params: <HoverRequest as RequestTrait>::Params =
+                serde_json::from_value(request.params)?;
+            let hover = document(&params.text_document_position_params)
+                .and_then(|(uri, doc, pos)| features::hover(&uri, doc, pos, workspace));
+            serde_json::to_value(hover)?
+        }
+        Completion::METHOD => {
+            let params: <Completion as RequestTrait>::Params =
+                serde_json::from_value(request.params)?;
+            let items = document(&params.text_document_position)
+                .map(|(uri, doc, pos)| features::completion(&uri, doc, pos, workspace))
+                .unwrap_or_default();
+            serde_json::to_value(items)?
+        }
+        GotoDefinition::METHOD => {
+            let params: <GotoDefinition as RequestTrait>::Params =
+                serde_json::from_value(request.params)?;
+            let locations = document(&params.text_document_position_params)
+                .map(|(uri, doc, pos)| features::definition(&uri, doc, pos, workspace))
+                .unwrap_or_default();
+            serde_json::to_value(GotoDefinitionResponse::Array(locations))?
+        }
+        References::METHOD => {
+            let params: <References as RequestTrait>::Params =
+                serde_json::from_value(request.params)?;
+            let include_declaration = params.context.include_declaration;
+            let locations = document(&params.text_document_position)
+                .map(|(uri, doc, pos)| {
+                    features::references(&uri, doc, pos, workspace, include_declaration)
+                })
+                .unwrap_or_default();
+            serde_json::to_value(locations)?
+        }
+        DocumentSymbolRequest::METHOD => {
+            let params: <DocumentSymbolRequest as RequestTrait>::Params =
+                serde_json::from_value(request.params)?;
+            let symbols = workspace
+                .get(&params.text_document.uri)
+                .map(features::document_symbols)
+                .unwrap_or_default();
+            serde_json::to_value(DocumentSymbolResponse::Nested(symbols))?
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(result))
+}
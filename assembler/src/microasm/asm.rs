@@ -1,72 +1,153 @@
+use crate::asm::loader::{FileLoader, Includer, SourceLoader};
+use crate::isa::isa;
 use crate::{
-    get_instruction_by_name, read_file, AssemblerError, SyntaxError, IM_ABSOLUTE, IM_ACCUMULATOR,
-    IM_CONSTANT, IM_IMMEDIATE, IM_IMPLIED, IM_INDIRECT, IM_ZEROPAGE,
+    get_im_name, get_instruction_by_name, read_file, write_file, AssemblerError, SyntaxError,
+    IM_ABSOLUTE, IM_ACCUMULATOR, IM_CONSTANT, IM_IMMEDIATE, IM_IMPLIED, IM_INDIRECT, IM_ZEROPAGE,
 };
 use regex::Regex;
-use std::io::Write;
-use std::mem::MaybeUninit;
-use std::{collections::VecDeque, fs::File};
+use std::collections::VecDeque;
+use std::fmt;
 
 use crate::microasm::{
     Conditional, InstructionDef, LineType, MacroDef, MicroStep, TokenizedLine, COMMENT_IDENT,
-    CONTROL_SIGNALS, FLAGS, FLAGS_BIT_SIZE, FLAG_COMBINATIONS, INSTRUCTION_MODE_BIT_SIZE,
-    INSTRUCTION_MODE_COUNT, MAX_MICRO_STEP_COUNT, OPCODE_BIT_SIZE, STEP_COUNTER_BIT_SIZE,
-    TOTAL_DEF_COMBINATIONS,
 };
 
+use super::timing::{format_cycles, step_count};
+use super::verify::{check_step, signal_access, State};
 use super::ConditionalStep;
 
-pub fn assembler(file_in: &str, file_out: &str) -> Result<(), AssemblerError> {
+/// Assembles a microcode source file, files included by it are loaded relative to its directory.
+/// With `optimize` set, adjacent steps are merged first and the step counts before and after are returned.
+pub fn assembler(
+    file_in: &str,
+    file_out: &str,
+    optimize: bool,
+) -> Result<Vec<OptimizationReport>, AssemblerError> {
     let input = read_file(file_in)?;
-    // tokenize
-    let tokens = match tokenize(input) {
-        Ok(t) => t,
-        Err(e) => {
-            return Err(AssemblerError::new(
-                String::from("Assembly failed at tokenization step."),
-                Some(e),
-            ))
-        }
+    let options = MicroassembleOptions {
+        file_name: file_in.to_string(),
+        loader: Some(&FileLoader),
+        optimize,
     };
+    let microcode = assemble_str(&input, &options)?;
 
-    // parse
-    let parsed = match parse(tokens) {
-        Ok(idf) => idf,
-        Err(e) => {
-            return Err(AssemblerError::new(
-                String::from("Assembly failed at parsing step."),
-                Some(e),
-            ))
+    // write to output file
+    write_file(file_out, &microcode.bytes)?;
+    Ok(microcode.report)
+}
+
+/// Options of a microcode assembly from memory.
+pub struct MicroassembleOptions<'a> {
+    /// Name of the source, used by diagnostics. Includes are resolved relative to it.
+    pub file_name: String,
+    /// Loads the files included with `#include`. Including a file is an error without a loader.
+    pub loader: Option<&'a dyn SourceLoader>,
+    /// Merges adjacent steps where possible.
+    pub optimize: bool,
+}
+
+impl Default for MicroassembleOptions<'_> {
+    fn default() -> Self {
+        MicroassembleOptions {
+            file_name: String::from("<input>"),
+            loader: None,
+            optimize: false,
         }
+    }
+}
+
+/// Result of a microcode assembly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssembledMicrocode {
+    /// Contents of the microcode ROM.
+    pub bytes: Vec<u8>,
+    /// Step counts before and after the optimization, empty if it is not enabled.
+    pub report: Vec<OptimizationReport>,
+}
+
+/// Assembles microcode without touching the file system, included files are loaded by the loader of the options.
+pub fn assemble_str(
+    source: &str,
+    options: &MicroassembleOptions,
+) -> Result<AssembledMicrocode, AssemblerError> {
+    let mut parsed = parse_source(source, options)?;
+
+    // optimize
+    let report = if options.optimize {
+        optimize_steps(&mut parsed)
+    } else {
+        Vec::new()
     };
 
     // assemble
-    let output = assemble(parsed);
+    Ok(AssembledMicrocode {
+        bytes: assemble(parsed),
+        report,
+    })
+}
 
-    // write to output file
-    let mut file = File::create(file_out).unwrap();
-    file.write_all(&output).unwrap();
-    Ok(())
+/// Reads a microcode source file and runs it through the tokenizer, resolving includes, and the parser.
+pub(crate) fn parse_file(file_in: &str) -> Result<Vec<InstructionDef>, AssemblerError> {
+    let input = read_file(file_in)?;
+    let options = MicroassembleOptions {
+        file_name: file_in.to_string(),
+        loader: Some(&FileLoader),
+        ..Default::default()
+    };
+    parse_source(&input, &options)
+}
+
+/// Runs microcode source through the tokenizer, resolving includes, and the parser, without touching the file system.
+/// The instruction definitions can be checked with `verify`, `coverage` and `timing`.
+pub fn parse_source(
+    source: &str,
+    options: &MicroassembleOptions,
+) -> Result<Vec<InstructionDef>, AssemblerError> {
+    let file_name = options.file_name.as_str();
+
+    // tokenize, replacing the includes with the tokens of the included files
+    let includer = Includer {
+        loader: options.loader,
+        libraries: false,
+        tokenize: &|code: &str, file: &str| tokenize(code, file).map_err(AssemblerError::Lex),
+        include: &|t: &TokenizedLine| match &t.1 {
+            LineType::KeyLine(keyword, args) if keyword == "include" => Some((t.0, args.clone())),
+            _ => None,
+        },
+    };
+    let tokens = includer.resolve(source, file_name)?;
+    if tokens.is_empty() {
+        return Err(AssemblerError::Lex(SyntaxError::in_file(
+            file_name,
+            0,
+            String::from("No code was found."),
+        )));
+    }
+
+    // parse
+    parse(tokens)
 }
 
 /// Takes the raw input data as String and returns a vector of tokens. Tokens are individual lines identified by their contents.
-fn tokenize(code: String) -> Result<Vec<TokenizedLine>, SyntaxError> {
+/// The path of an `#include` keeps its case, everything else is lower-cased.
+pub(crate) fn tokenize(code: &str, file: &str) -> Result<Vec<TokenizedLine>, SyntaxError> {
     let mut tokenized_lines: Vec<TokenizedLine> = Vec::new();
 
+    // split by whitespace or commas
+    let words_re = Regex::new(r"\s*,\s*|\s+").unwrap();
+
     for (line_idx, line) in code.lines().enumerate() {
         let real_line = line_idx as u32 + 1;
-        let line = line.trim().to_lowercase();
 
         // check for comments and remove if found
-        let comment_idx = line.chars().position(|c| c == COMMENT_IDENT);
-        let line = if let Some(idx) = comment_idx {
-            line[0..idx].trim().to_string()
-        } else {
-            line.trim().to_string()
+        let original = match line.find(COMMENT_IDENT) {
+            Some(idx) => line[0..idx].trim(),
+            None => line.trim(),
         };
+        let line = original.to_lowercase();
 
         // skip empty lines
-        if line.len() == 0 {
+        if line.is_empty() {
             continue;
         }
 
@@ -77,23 +158,34 @@ fn tokenize(code: String) -> Result<Vec<TokenizedLine>, SyntaxError> {
             .collect();
 
         // the line is a key line (#def, #macro,...)
-        let tokenized = if let Some('#') = line.chars().nth(0) {
+        let tokenized = if let Some('#') = line.chars().next() {
             if line.chars().count() == 1 {
-                return Err(SyntaxError::new(
+                return Err(SyntaxError::in_file(
+                    file,
                     real_line,
                     String::from("No keyword was specified."),
                 ));
             }
 
+            let args = match &words[0][1..] {
+                "include" => original
+                    .split_whitespace()
+                    .skip(1)
+                    .map(String::from)
+                    .collect(),
+                _ => words[1..].to_vec(),
+            };
             TokenizedLine(
                 real_line,
-                LineType::KeyLine(words[0][1..].to_string(), words[1..].to_vec()),
+                LineType::KeyLine(words[0][1..].to_string(), args),
+                file.to_string(),
             )
         }
         // the line is a label line
         else if let Some(':') = line.chars().last() {
             if words.len() > 1 {
-                return Err(SyntaxError::new(
+                return Err(SyntaxError::in_file(
+                    file,
                     real_line,
                     String::from("Invalid label definition, a label can only be one word."),
                 ));
@@ -103,28 +195,26 @@ fn tokenize(code: String) -> Result<Vec<TokenizedLine>, SyntaxError> {
             // remove the colon
             label.pop();
 
-            TokenizedLine(real_line, LineType::LabelLine(label))
+            TokenizedLine(real_line, LineType::LabelLine(label), file.to_string())
         } else {
-            // split by whitespace or commas
-            let re = Regex::new(r"\s*,\s*|\s+").unwrap();
-            let words: Vec<String> = re.split(&line).map(|s| s.trim().to_lowercase()).collect();
+            let words: Vec<String> = words_re
+                .split(&line)
+                .map(|s| s.trim().to_lowercase())
+                .collect();
 
-            TokenizedLine(real_line, LineType::StepLine(words))
+            TokenizedLine(real_line, LineType::StepLine(words), file.to_string())
         };
 
         tokenized_lines.push(tokenized);
     }
 
-    if tokenized_lines.len() == 0 {
-        return Err(SyntaxError::new(0, String::from("No code was found.")));
-    }
-
     Ok(tokenized_lines)
 }
 
 /// Takes the tokens produced by the tokenizer and parses them, pasting macro code, adding prefixes and suffixes and different conditional definitions.
 /// The result is a vector of instruction definitions defined for every combination of instruction modes and flags.
-fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError> {
+fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, AssemblerError> {
+    let isa = isa();
     let mut instructions: Vec<InstructionDef> = Vec::new();
 
     // keeps track of the currently defined macros
@@ -136,7 +226,7 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
 
     // currently defined instruction
     let mut is_defining_instruction = false;
-    let mut current_instruction: Option<[InstructionDef; TOTAL_DEF_COMBINATIONS]> = None;
+    let mut current_instruction: Option<Vec<InstructionDef>> = None;
     let mut currently_defined_im: Option<u32> = None;
 
     // currently defined prefix/suffix
@@ -149,7 +239,7 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
     let mut conditional_stack: VecDeque<Conditional> = VecDeque::new();
 
     for token in &tokens {
-        let (real_line, line) = (&token.0, &token.1);
+        let (real_line, line, file) = (&token.0, &token.1, &token.2);
 
         let mut is_new_def = false;
         if let LineType::KeyLine(keyword, _) = line {
@@ -161,7 +251,7 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
         // this is a new definition
         if is_new_def {
             // add a suffix if it is defined
-            let extra_steps = current_suf.clone().unwrap_or(Vec::new());
+            let extra_steps = current_suf.clone().unwrap_or_default();
 
             // complete the current definition and save it to its corresponding vector
             if is_defining_instruction {
@@ -181,7 +271,7 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
                     let steps: Vec<Vec<u64>> = extra_steps
                         .iter()
                         .filter(|&xs| {
-                            if xs.conditions.len() > 0 {
+                            if !xs.conditions.is_empty() {
                                 for c in &xs.conditions {
                                     let is_flag_set = (ins.flags & c.flag) != 0;
 
@@ -190,21 +280,22 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
                                     }
                                 }
                             }
-                            return true;
+                            true
                         })
                         .map(|xs| xs.step.clone())
                         .collect();
 
                     ins.steps.extend(steps);
 
-                    if ins.steps.len() > MAX_MICRO_STEP_COUNT {
-                        return Err(SyntaxError::new(
+                    if ins.steps.len() > isa.max_micro_steps() {
+                        return Err(AssemblerError::Range(SyntaxError::in_file(
+                            file,
                             *real_line,
                             format!(
                                 "Invalid instruction definition, maximum step count is {}. The added suffix has brought the step count over the limit.",
-                                MAX_MICRO_STEP_COUNT
+                                isa.max_micro_steps()
                             ),
-                        ));
+                        )));
                     }
                 }
 
@@ -224,11 +315,12 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
         match line {
             LineType::KeyLine(keyword, args) => match &keyword[..] {
                 "def" => {
-                    if args.len() == 0 {
-                        return Err(SyntaxError::new(
+                    if args.is_empty() {
+                        return Err(AssemblerError::Parse(SyntaxError::in_file(
+                            file,
                             *real_line,
                             String::from("Instruction name not provided."),
-                        ));
+                        )));
                     }
 
                     let inst_name = args[0].to_lowercase().trim().to_string();
@@ -237,28 +329,45 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
                     let exists = get_instruction_by_name(&inst_name).is_some();
 
                     if !exists {
-                        return Err(SyntaxError::new(
+                        return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                            file,
                             *real_line,
                             format!("Unknown instruction '{}'", inst_name),
-                        ));
+                        )));
                     }
 
                     let is_already_defined =
                         instructions.iter().find(|&i| i.name == inst_name).is_some();
 
                     if is_already_defined {
-                        return Err(SyntaxError::new(
+                        return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                            file,
                             *real_line,
                             format!("Instruction '{}' is already defined.", inst_name),
-                        ));
+                        )));
+                    }
+
+                    // every word after the name is an option
+                    let mut optimize = true;
+                    for option in &args[1..] {
+                        match &option[..] {
+                            "noopt" => optimize = false,
+                            _ => {
+                                return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                                    file,
+                                    *real_line,
+                                    format!("Unknown option '{}' for '{}'.", option, inst_name),
+                                )));
+                            }
+                        }
                     }
 
                     is_defining_instruction = true;
 
                     // add a prefix if it is defined
-                    let prefix_steps = current_pref.clone().unwrap_or(Vec::new());
+                    let prefix_steps = current_pref.clone().unwrap_or_default();
 
-                    let modes: [u32; INSTRUCTION_MODE_COUNT] = [
+                    let modes = [
                         IM_IMPLIED,
                         IM_IMMEDIATE,
                         IM_CONSTANT,
@@ -269,93 +378,101 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
                     ];
 
                     // initialize a version of the instruction for every Instruction Mode and flag combination
-                    let instruction_versions = {
-                        let mut array: [MaybeUninit<InstructionDef>; TOTAL_DEF_COMBINATIONS] =
-                            unsafe { MaybeUninit::uninit().assume_init() };
-
-                        for im_idx in 0..INSTRUCTION_MODE_COUNT {
-                            let m = modes[im_idx].clone();
-
-                            for flg_idx in 0..FLAG_COMBINATIONS {
-                                let flg_val = flg_idx as u32;
-
-                                // remove all steps from the prefix which dont match the current flags
-                                let steps = prefix_steps
-                                    .iter()
-                                    .filter(|&xs| {
-                                        if xs.conditions.len() > 0 {
-                                            for c in &xs.conditions {
-                                                let is_flag_set = (flg_val & c.flag) != 0;
-
-                                                if is_flag_set == c.is_inverted {
-                                                    return false;
-                                                }
+                    let mut instruction_versions = Vec::new();
+                    for &m in &modes {
+                        for flg_val in 0..isa.flag_combinations() as u32 {
+                            // remove all steps from the prefix which dont match the current flags
+                            let steps = prefix_steps
+                                .iter()
+                                .filter(|&xs| {
+                                    if !xs.conditions.is_empty() {
+                                        for c in &xs.conditions {
+                                            let is_flag_set = (flg_val & c.flag) != 0;
+
+                                            if is_flag_set == c.is_inverted {
+                                                return false;
                                             }
                                         }
-                                        return true;
-                                    })
-                                    .map(|xs| xs.step.clone())
-                                    .collect();
-
-                                let ins_v = InstructionDef {
-                                    name: inst_name.clone(),
-                                    instruction_mode: m,
-                                    flags: flg_val,
-                                    steps,
-                                };
-
-                                let abs_idx = (im_idx * FLAG_COMBINATIONS) + flg_idx;
-                                let element = array.get_mut(abs_idx).unwrap();
-                                *element = MaybeUninit::new(ins_v);
-                            }
-                        }
-
-                        unsafe {
-                            std::mem::transmute::<_, [InstructionDef; TOTAL_DEF_COMBINATIONS]>(
-                                array,
-                            )
+                                    }
+                                    true
+                                })
+                                .map(|xs| xs.step.clone())
+                                .collect();
+
+                            instruction_versions.push(InstructionDef {
+                                name: inst_name.clone(),
+                                instruction_mode: m,
+                                flags: flg_val,
+                                steps,
+                                optimize,
+                            });
                         }
-                    };
+                    }
 
                     current_instruction = Some(instruction_versions);
                 }
                 "macro" => {
+                    if args.is_empty() {
+                        return Err(AssemblerError::Parse(SyntaxError::in_file(
+                            file,
+                            *real_line,
+                            String::from("Macro name not provided."),
+                        )));
+                    }
+
                     let macro_name = args[0].to_lowercase().trim().to_string();
 
                     // check if this name is used by a control signal, macro or an instruction
-                    let is_used = CONTROL_SIGNALS
-                        .iter()
-                        .find(|&s| s.to_lowercase() == macro_name)
-                        .is_some()
-                        || macros.iter().find(|&m| m.name == macro_name).is_some()
-                        || get_instruction_by_name(&macro_name).is_some();
+                    let is_used = |name: &str| {
+                        isa.signal_bit(name).is_some()
+                            || macros.iter().any(|m| m.name == name)
+                            || get_instruction_by_name(name).is_some()
+                    };
 
-                    if is_used {
-                        return Err(SyntaxError::new(
-                            *real_line,
-                            format!("The name '{}' is already used.", macro_name),
-                        ));
+                    // every word after the name is a parameter
+                    let params = &args[1..];
+                    for (idx, name) in args.iter().enumerate() {
+                        if is_used(name) {
+                            return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                                file,
+                                *real_line,
+                                format!("The name '{}' is already used.", name),
+                            )));
+                        }
+                        if args[..idx].contains(name) {
+                            return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                                file,
+                                *real_line,
+                                format!(
+                                    "Parameter '{}' of macro '{}' is listed twice.",
+                                    name, macro_name
+                                ),
+                            )));
+                        }
                     }
 
                     let new_macro_def = MacroDef {
                         name: macro_name,
+                        params: params.to_vec(),
                         steps: Vec::new(),
+                        lines: Vec::new(),
                     };
 
                     current_macro = Some(new_macro_def);
                     is_defining_macro = true;
                 }
                 "if" => {
-                    if args.len() != 1 || args[0].len() == 0 {
-                        return Err(SyntaxError::new(
+                    if args.len() != 1 || args[0].is_empty() {
+                        return Err(AssemblerError::Parse(SyntaxError::in_file(
+                            file,
                             *real_line,
-                            format!("Condition not provided."),
-                        ));
+                            "Condition not provided.".to_string(),
+                        )));
                     }
 
                     let flag_name = args[0].trim().to_lowercase();
 
-                    let is_inverted = flag_name.chars().next().unwrap() == '!';
+                    let is_inverted = flag_name.starts_with('!');
 
                     let flag_name = if is_inverted {
                         flag_name[1..].to_string()
@@ -364,45 +481,46 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
                     };
 
                     // check if a flag with this name exists
-                    let flg_idx = FLAGS.iter().position(|&f| f.to_lowercase() == flag_name);
-
-                    if !flg_idx.is_some() {
-                        return Err(SyntaxError::new(
-                            *real_line,
-                            format!("Unknown flag '{}'.", flag_name),
-                        ));
-                    }
-
-                    // convert the flag name to interger value
-                    let flag = 2_u32.pow(flg_idx.unwrap() as u32);
+                    let flag = match isa.flag_value(&flag_name) {
+                        Some(f) => f,
+                        None => {
+                            return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                                file,
+                                *real_line,
+                                format!("Unknown flag '{}'.", flag_name),
+                            )));
+                        }
+                    };
 
                     // push this conditional to the top of the conditional stack
                     let conditional = Conditional { flag, is_inverted };
                     conditional_stack.push_back(conditional);
                 }
                 "end" => {
-                    if conditional_stack.len() == 0 {
-                        return Err(SyntaxError::new(
+                    if conditional_stack.is_empty() {
+                        return Err(AssemblerError::Parse(SyntaxError::in_file(
+                            file,
                             *real_line,
                             String::from(
                                 "Invalid use of 'end', there is no conditional to be closed.",
                             ),
-                        ));
+                        )));
                     }
 
                     // remove the last conditional
                     conditional_stack.pop_back().unwrap();
                 }
                 "else" => {
-                    if conditional_stack.len() == 0 {
-                        return Err(SyntaxError::new(
+                    if conditional_stack.is_empty() {
+                        return Err(AssemblerError::Parse(SyntaxError::in_file(
+                            file,
                             *real_line,
                             String::from("Invalid use of 'else', there is no if block."),
-                        ));
+                        )));
                     }
 
                     // invert the last conditional
-                    let mut last_conditional = conditional_stack.back_mut().unwrap();
+                    let last_conditional = conditional_stack.back_mut().unwrap();
                     last_conditional.is_inverted = !last_conditional.is_inverted;
                 }
                 "pref" => {
@@ -414,97 +532,68 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
                     current_suf = Some(Vec::new());
                 }
                 _ => {
-                    return Err(SyntaxError::new(
+                    return Err(AssemblerError::Parse(SyntaxError::in_file(
+                        file,
                         *real_line,
                         format!("Invalid keyword '{}'", keyword),
-                    ));
+                    )));
                 }
             },
             LineType::StepLine(words) => {
-                // store all the control signals for this step
-                let mut control_signals: Vec<u64> = Vec::new();
-
-                // store the additional steps added by a macro
-                let mut macro_steps = Vec::new();
-
-                // get the control signals from words
-                for word in words {
-                    let signal_idx = CONTROL_SIGNALS
-                        .iter()
-                        .position(|&s| &s.to_lowercase() == word);
-                    let signal_exists = signal_idx.is_some();
-
-                    let macro_def = macros.iter().find(|&m| m.name == *word);
-                    let macro_exists = macro_def.is_some();
-
-                    if signal_exists {
-                        control_signals.push(signal_idx.unwrap() as u64);
-                    } else {
-                        if macro_exists {
-                            let macro_def = macro_def.unwrap();
-                            if words.len() > 1 {
-                                if macro_def.steps.len() > 1 {
-                                    return Err(SyntaxError::new(
-                                        *real_line,
-                                        format!("Invalid macro usage. Multi step macro '{}' cannot be used inline.", macro_def.name),
-                                    ));
-                                }
-                            }
-
-                            if macro_def.steps.len() == 1 {
-                                control_signals
-                                    .extend(macro_def.steps.first().unwrap().step.clone());
-                            } else {
-                                macro_steps.extend(macro_def.steps.to_vec());
-                            }
-                        } else {
-                            return Err(SyntaxError::new(
-                                *real_line,
-                                format!("Unknown identifier '{}'.", word),
-                            ));
-                        }
+                // the lines of a macro with parameters are expanded when it is used
+                if let Some(macro_def) = current_macro.as_mut().filter(|m| !m.params.is_empty()) {
+                    if let Some(word) = words.iter().find(|&w| {
+                        !macro_def.params.contains(w)
+                            && isa.signal_bit(w).is_none()
+                            && !macros.iter().any(|m| m.name == *w)
+                    }) {
+                        return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                            file,
+                            *real_line,
+                            format!("Unknown identifier '{}'.", word),
+                        )));
                     }
+
+                    macro_def
+                        .lines
+                        .push((words.clone(), conditional_stack.clone().into()));
+                    continue;
                 }
-                for m in &mut macro_steps {
-                    m.conditions.extend(conditional_stack.clone());
+
+                let mut steps = resolve_step(words, &macros, &[]).map_err(|e| {
+                    AssemblerError::Semantic(SyntaxError::in_file(file, *real_line, e))
+                })?;
+                for s in &mut steps {
+                    s.conditions.extend(conditional_stack.clone());
                 }
-                // add all the steps together
-                let mut steps = if control_signals.len() > 0 {
-                    let conditional = ConditionalStep {
-                        conditions: conditional_stack.clone().into(),
-                        step: control_signals,
-                    };
-                    [conditional].to_vec()
-                } else {
-                    Vec::new()
-                };
-                steps.extend(macro_steps);
 
                 if is_defining_pref {
                     let current_pref = current_pref.as_mut().unwrap();
                     current_pref.extend(steps.clone());
-                    if current_pref.len() > MAX_MICRO_STEP_COUNT {
-                        return Err(SyntaxError::new(
+                    if current_pref.len() > isa.max_micro_steps() {
+                        return Err(AssemblerError::Range(SyntaxError::in_file(
+                            file,
                             *real_line,
                             format!(
                                 "Invalid prefix definition, maximum step count is {}.",
-                                MAX_MICRO_STEP_COUNT
+                                isa.max_micro_steps()
                             ),
-                        ));
+                        )));
                     }
                     continue;
                 }
                 if is_defining_suf {
                     let current_suf = current_suf.as_mut().unwrap();
                     current_suf.extend(steps.clone());
-                    if current_suf.len() > MAX_MICRO_STEP_COUNT {
-                        return Err(SyntaxError::new(
+                    if current_suf.len() > isa.max_micro_steps() {
+                        return Err(AssemblerError::Range(SyntaxError::in_file(
+                            file,
                             *real_line,
                             format!(
                                 "Invalid suffix definition, maximum step count is {}.",
-                                MAX_MICRO_STEP_COUNT
+                                isa.max_micro_steps()
                             ),
-                        ));
+                        )));
                     }
                     continue;
                 }
@@ -522,13 +611,13 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
                             return false;
                         }
 
-                        if let Some(im) = currently_defined_im.clone() {
+                        if let Some(im) = currently_defined_im {
                             if im != *inm {
                                 return false;
                             }
                         }
 
-                        if conditional_stack.len() > 0 {
+                        if !conditional_stack.is_empty() {
                             for c in &conditional_stack {
                                 let is_flag_set = (fgs & c.flag) != 0;
 
@@ -549,7 +638,7 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
 
                         // add all of the appropriate steps
                         'step_loop: for s in &steps {
-                            if s.conditions.len() > 0 {
+                            if !s.conditions.is_empty() {
                                 for c in &s.conditions {
                                     let is_flag_set = (ins.flags & c.flag) != 0;
 
@@ -561,14 +650,15 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
                             ins.steps.push(s.step.clone());
                         }
 
-                        if ins.steps.len() > MAX_MICRO_STEP_COUNT {
-                            return Err(SyntaxError::new(
+                        if ins.steps.len() > isa.max_micro_steps() {
+                            return Err(AssemblerError::Range(SyntaxError::in_file(
+                                file,
                                 *real_line,
                                 format!(
                                     "Invalid instruction definition, maximum step count is {}.",
-                                    MAX_MICRO_STEP_COUNT
+                                    isa.max_micro_steps()
                                 ),
-                            ));
+                            )));
                         }
                     }
                     continue;
@@ -578,14 +668,15 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
 
                     macro_def.steps.extend(steps);
 
-                    if macro_def.steps.len() > MAX_MICRO_STEP_COUNT {
-                        return Err(SyntaxError::new(
+                    if macro_def.steps.len() > isa.max_micro_steps() {
+                        return Err(AssemblerError::Range(SyntaxError::in_file(
+                            file,
                             *real_line,
                             format!(
                                 "Invalid macro definition, maximum step count is {}.",
-                                MAX_MICRO_STEP_COUNT
+                                isa.max_micro_steps()
                             ),
-                        ));
+                        )));
                     }
                 }
             }
@@ -602,23 +693,25 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
                     "zpage" => IM_ZEROPAGE,
                     "accumulator" => IM_ACCUMULATOR,
                     _ => {
-                        return Err(SyntaxError::new(
+                        return Err(AssemblerError::Parse(SyntaxError::in_file(
+                            file,
                             *real_line,
                             format!("Invalid Instruction Mode label '{}'", label),
-                        ));
+                        )));
                     }
                 };
 
                 let current_instruction_name = &current_instruction.as_ref().unwrap()[0].name;
                 let inst = get_instruction_by_name(current_instruction_name).unwrap();
                 if (inst.2 & instruction_mode_val) == 0 {
-                    return Err(SyntaxError::new(
+                    return Err(AssemblerError::Semantic(SyntaxError::in_file(
+                        file,
                         *real_line,
                         format!(
                             "Cannot define instruction mode '{}' for '{}'.",
                             formated_label, current_instruction_name
                         ),
-                    ));
+                    )));
                 }
 
                 currently_defined_im = Some(instruction_mode_val);
@@ -627,7 +720,7 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
     }
 
     // add a suffix if it is defined
-    let extra_steps = current_suf.clone().unwrap_or(Vec::new());
+    let extra_steps = current_suf.clone().unwrap_or_default();
 
     // finish the last definition
     if is_defining_instruction {
@@ -637,7 +730,7 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
             let steps: Vec<MicroStep> = extra_steps
                 .iter()
                 .filter(|&xs| {
-                    if xs.conditions.len() > 0 {
+                    if !xs.conditions.is_empty() {
                         for c in &xs.conditions {
                             let is_flag_set = (ins.flags & c.flag) != 0;
 
@@ -646,19 +739,21 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
                             }
                         }
                     }
-                    return true;
+                    true
                 })
                 .map(|xs| xs.step.clone())
                 .collect();
 
             ins.steps.extend(steps);
-            if ins.steps.len() > MAX_MICRO_STEP_COUNT {
-                return Err(SyntaxError::new(
-                    tokens.last().unwrap().0,
+            if ins.steps.len() > isa.max_micro_steps() {
+                let last = tokens.last().unwrap();
+                return Err(AssemblerError::Range(SyntaxError::in_file(
+                    &last.2,
+                    last.0,
                     format!(
-                        "Invalid instruction definition, maximum step count is {}. The added suffix has brought the step count over the limit.", MAX_MICRO_STEP_COUNT
+                        "Invalid instruction definition, maximum step count is {}. The added suffix has brought the step count over the limit.", isa.max_micro_steps()
 					),
-                ));
+                )));
             }
         }
 
@@ -669,7 +764,7 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
     let mut final_instructions = Vec::new();
     for ins in instructions {
         let available_ims = get_instruction_by_name(&ins.name).unwrap().2;
-        if ins.steps.len() == 0 || (available_ims & ins.instruction_mode) == 0 {
+        if ins.steps.is_empty() || (available_ims & ins.instruction_mode) == 0 {
             continue;
         }
         final_instructions.push(ins);
@@ -678,27 +773,287 @@ fn parse(tokens: Vec<TokenizedLine>) -> Result<Vec<InstructionDef>, SyntaxError>
     Ok(final_instructions)
 }
 
+/// Resolves the words of a step line. The control signals of the line and of single step macros form the first step,
+/// the steps of a multi step macro follow it. A macro with parameters takes the rest of the line as its arguments.
+/// `trace` holds the macros the line was expanded from, it is added to the error messages.
+fn resolve_step(
+    words: &[String],
+    macros: &[MacroDef],
+    trace: &[&str],
+) -> Result<Vec<ConditionalStep>, String> {
+    let with_trace = |message: String| match trace.is_empty() {
+        true => message,
+        false => format!("{} (macro trace: {})", message, trace.join("->")),
+    };
+
+    // store all the control signals for this step
+    let mut control_signals: Vec<u64> = Vec::new();
+
+    // store the additional steps added by a macro
+    let mut macro_steps = Vec::new();
+
+    let mut idx = 0;
+    while idx < words.len() {
+        let word = &words[idx];
+        idx += 1;
+
+        if let Some(signal_bit) = isa().signal_bit(word) {
+            control_signals.push(signal_bit);
+            continue;
+        }
+
+        let macro_def = match macros.iter().find(|&m| m.name == *word) {
+            Some(m) => m,
+            None => return Err(with_trace(format!("Unknown identifier '{}'.", word))),
+        };
+
+        let (steps, arg_count) = if macro_def.params.is_empty() {
+            (macro_def.steps.clone(), 0)
+        } else {
+            let args = &words[idx..];
+            idx = words.len();
+            (expand_macro(macro_def, args, macros, trace)?, args.len())
+        };
+
+        if words.len() > 1 + arg_count && steps.len() > 1 {
+            return Err(with_trace(format!(
+                "Invalid macro usage. Multi step macro '{}' cannot be used inline.",
+                macro_def.name
+            )));
+        }
+
+        if steps.len() == 1 {
+            control_signals.extend(steps[0].step.clone());
+        } else {
+            macro_steps.extend(steps);
+        }
+    }
+
+    // add all the steps together
+    let mut steps = Vec::new();
+    if !control_signals.is_empty() {
+        steps.push(ConditionalStep {
+            conditions: Vec::new(),
+            step: control_signals,
+        });
+    }
+    steps.extend(macro_steps);
+    Ok(steps)
+}
+
+/// Expands the lines of a macro with parameters, replacing every parameter with its argument.
+fn expand_macro(
+    macro_def: &MacroDef,
+    args: &[String],
+    macros: &[MacroDef],
+    trace: &[&str],
+) -> Result<Vec<ConditionalStep>, String> {
+    let mut message = None;
+    if trace.contains(&macro_def.name.as_str()) {
+        message = Some(format!("Macro '{}' cannot use itself.", macro_def.name));
+    } else if args.len() != macro_def.params.len() {
+        message = Some(format!(
+            "Macro '{}' takes {} argument(s) ({}), {} were given.",
+            macro_def.name,
+            macro_def.params.len(),
+            macro_def.params.join(", "),
+            args.len()
+        ));
+    }
+    if let Some(message) = message {
+        return Err(match trace.is_empty() {
+            true => message,
+            false => format!("{} (macro trace: {})", message, trace.join("->")),
+        });
+    }
+
+    let mut trace = trace.to_vec();
+    trace.push(&macro_def.name);
+
+    let mut steps = Vec::new();
+    for (words, conditions) in &macro_def.lines {
+        let words: Vec<String> = words
+            .iter()
+            .map(|w| match macro_def.params.iter().position(|p| p == w) {
+                Some(p) => args[p].clone(),
+                None => w.clone(),
+            })
+            .collect();
+
+        for mut s in resolve_step(&words, macros, &trace)? {
+            s.conditions.extend(conditions.clone());
+            steps.push(s);
+        }
+    }
+    Ok(steps)
+}
+
+/// Step counts of an instruction in one instruction mode before and after the optimization.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptimizationReport {
+    pub name: String,
+    pub instruction_mode: u32,
+    /// (min, max) steps across all flag combinations.
+    pub before: (usize, usize),
+    pub after: (usize, usize),
+}
+
+impl fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = get_im_name(self.instruction_mode.trailing_zeros()).unwrap_or("Unknown");
+        write!(
+            f,
+            "{: <6}{: <13}{: >6} -> {}",
+            self.name.to_uppercase(),
+            mode,
+            format_cycles(self.before.0, self.before.1),
+            format_cycles(self.after.0, self.after.1)
+        )
+    }
+}
+
+/// Merges adjacent micro steps of every definition which has not opted out with `noopt`.
+/// Returns the step counts of every instruction mode, counted like the timing table, ordered by opcode and instruction mode.
+pub fn optimize_steps(instruction_defs: &mut [InstructionDef]) -> Vec<OptimizationReport> {
+    let mut report: Vec<OptimizationReport> = Vec::new();
+
+    for idf in instruction_defs.iter_mut() {
+        let before = step_count(&idf.steps);
+        if idf.optimize {
+            idf.steps = merge_steps(&idf.steps);
+        }
+        let after = step_count(&idf.steps);
+
+        let existing = report
+            .iter_mut()
+            .find(|r| r.name == idf.name && r.instruction_mode == idf.instruction_mode);
+
+        if let Some(r) = existing {
+            r.before = (r.before.0.min(before), r.before.1.max(before));
+            r.after = (r.after.0.min(after), r.after.1.max(after));
+        } else {
+            report.push(OptimizationReport {
+                name: idf.name.clone(),
+                instruction_mode: idf.instruction_mode,
+                before: (before, before),
+                after: (after, after),
+            });
+        }
+    }
+
+    report.sort_by_key(|r| {
+        let opcode = get_instruction_by_name(&r.name).map(|i| i.0).unwrap_or(0);
+        (opcode, r.instruction_mode)
+    });
+    report
+}
+
+/// Greedily merges every step into the one before it as long as they stay compatible.
+fn merge_steps(steps: &[MicroStep]) -> Vec<MicroStep> {
+    let mut merged: Vec<MicroStep> = Vec::new();
+
+    for step in steps {
+        if let Some(last) = merged.last_mut() {
+            if can_merge(last, step) {
+                for cs in step {
+                    if !last.contains(cs) {
+                        last.push(*cs);
+                    }
+                }
+                continue;
+            }
+        }
+        merged.push(step.clone());
+    }
+    merged
+}
+
+/// Checks whether two consecutive steps can be executed as a single one.
+fn can_merge(first: &MicroStep, second: &MicroStep) -> bool {
+    let signal_names = |step: &MicroStep| -> Vec<&'static str> {
+        step.iter()
+            .filter_map(|&cs| isa().signal_name(cs))
+            .collect()
+    };
+    let accessed = |names: &[&'static str], is_write: bool| -> Vec<State> {
+        names
+            .iter()
+            .filter_map(|&n| signal_access(n))
+            .flat_map(|access| {
+                if is_write {
+                    access.writes
+                } else {
+                    access.reads
+                }
+            })
+            .copied()
+            .collect()
+    };
+
+    let first = signal_names(first);
+    let second = signal_names(second);
+
+    // nothing is known about the dependencies of signals outside of the Tower ISA
+    if first
+        .iter()
+        .chain(second.iter())
+        .any(|&n| signal_access(n).is_none())
+    {
+        return false;
+    }
+
+    let mut combined = first.clone();
+    combined.extend(second.iter().filter(|s| !first.contains(s)));
+
+    // the merged step has to be free of bus contention and conflicting signals
+    if !check_step(&combined).is_empty() {
+        return false;
+    }
+
+    let first_writes = accessed(&first, true);
+    let second_writes = accessed(&second, true);
+
+    // the microcode ROM is addressed by the instruction, the flags and the step counter,
+    // so every step depends on them before any of its signals are active
+    let mut second_reads = accessed(&second, false);
+    second_reads.extend([State::InstructionRegister, State::Flags, State::StepCounter]);
+
+    // read after write and write after write
+    if first_writes
+        .iter()
+        .any(|w| second_reads.contains(w) || second_writes.contains(w))
+    {
+        return false;
+    }
+
+    // registers are only latched at the end of a step, but combinational state changes right away
+    let first_reads = accessed(&first, false);
+    !second_writes
+        .iter()
+        .any(|w| w.is_combinational() && first_reads.contains(w))
+}
+
 /// Takes the defined instructions and converts them to a binary file that is to be used inside the microcode ROM.
 fn assemble(instruction_defs: Vec<InstructionDef>) -> Vec<u8> {
-    let combs = 2_u32.pow(OPCODE_BIT_SIZE)
-        * 2_u32.pow(STEP_COUNTER_BIT_SIZE)
-        * 2_u32.pow(FLAGS_BIT_SIZE)
-        * 2_u32.pow(INSTRUCTION_MODE_BIT_SIZE);
+    let isa = isa();
+    let address = &isa.address;
+    let control_bytes = isa.control_bytes();
 
-    let mut raw_bytes: Vec<u8> = vec![0; (combs * 5) as usize];
+    let mut raw_bytes: Vec<u8> = vec![0; isa.rom_size() * control_bytes];
 
     for idf in &instruction_defs {
         let inst = get_instruction_by_name(&idf.name).unwrap();
 
-        let opcode = inst.0 << (STEP_COUNTER_BIT_SIZE + FLAGS_BIT_SIZE + INSTRUCTION_MODE_BIT_SIZE);
+        let opcode = inst.0 << (address.step_bits + address.flag_bits + address.mode_bits);
         let instruction_mode = ((idf.instruction_mode as f32).log2() as u32)
-            << (STEP_COUNTER_BIT_SIZE + FLAGS_BIT_SIZE);
-        let flags = idf.flags << STEP_COUNTER_BIT_SIZE;
-        let instruction_raw_start_idx = (opcode | instruction_mode | flags) * 5;
+            << (address.step_bits + address.flag_bits);
+        let flags = idf.flags << address.step_bits;
+        let instruction_raw_start_idx =
+            (opcode | instruction_mode | flags) as usize * control_bytes;
 
-        for si in 0..MAX_MICRO_STEP_COUNT {
+        for si in 0..isa.max_micro_steps() {
             let step = idf.steps.get(si);
-            let real_byte_idx = instruction_raw_start_idx as usize + si * 5;
+            let real_byte_idx = instruction_raw_start_idx + si * control_bytes;
 
             if let Some(control_signals) = step {
                 // construct the control word
@@ -706,16 +1061,12 @@ fn assemble(instruction_defs: Vec<InstructionDef>) -> Vec<u8> {
                 for cl in control_signals {
                     control_word |= 2_u64.pow(*cl as u32);
                 }
-                // split the control word into five bytes
-                let control_bytes = &[
-                    (control_word >> 32) as u8,
-                    ((control_word >> 24) & 0xff) as u8,
-                    ((control_word >> 16) & 0xff) as u8,
-                    ((control_word >> 8) & 0xff) as u8,
-                    (control_word & 0xff) as u8,
-                ];
-
-                raw_bytes.splice(real_byte_idx..real_byte_idx + 5, *control_bytes);
+                // split the control word into bytes, most significant first
+                let bytes = (0..control_bytes)
+                    .rev()
+                    .map(|i| ((control_word >> (i * 8)) & 0xff) as u8);
+
+                raw_bytes.splice(real_byte_idx..real_byte_idx + control_bytes, bytes);
                 continue;
             }
             break;
@@ -0,0 +1,180 @@
+use std::fmt;
+
+use crate::isa::{isa, INSTRUCTION_MODE_COUNT};
+use crate::{get_im_name, im_idx_to_val, read_file_binary, AssemblerError};
+
+use crate::microasm::{asm::parse_file, disasm::disassemble, format_flags, InstructionDef};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModeCoverage {
+    pub instruction_mode: u32,
+    /// Flag combinations which have a definition.
+    pub defined_flags: Vec<u32>,
+    /// Flag combinations whose definition never reaches IEND or HLT.
+    pub unterminated_flags: Vec<u32>,
+}
+
+impl ModeCoverage {
+    pub fn is_complete(&self) -> bool {
+        self.defined_flags.len() == isa().flag_combinations() && self.unterminated_flags.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstructionCoverage {
+    pub opcode: u32,
+    pub name: &'static str,
+    /// Coverage of every mode the program assembler allows for this instruction.
+    pub modes: Vec<ModeCoverage>,
+    /// Modes which have a definition but are not allowed by the program assembler.
+    pub disallowed_modes: Vec<u32>,
+}
+
+impl InstructionCoverage {
+    pub fn is_complete(&self) -> bool {
+        self.modes.iter().all(|m| m.is_complete()) && self.disallowed_modes.is_empty()
+    }
+}
+
+fn mode_name(instruction_mode: u32) -> &'static str {
+    get_im_name(instruction_mode.trailing_zeros()).unwrap_or("Unknown")
+}
+
+fn flag_list(flags: &[u32]) -> String {
+    flags
+        .iter()
+        .map(|&f| format_flags(f))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl fmt::Display for InstructionCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} (opcode 0x{:02x})", self.name, self.opcode)?;
+
+        for m in &self.modes {
+            let name = mode_name(m.instruction_mode);
+            if m.is_complete() {
+                writeln!(f, "    ✔️  {}", name)?;
+                continue;
+            }
+
+            let missing: Vec<u32> = (0..isa().flag_combinations() as u32)
+                .filter(|flg| !m.defined_flags.contains(flg))
+                .collect();
+
+            if missing.len() == isa().flag_combinations() {
+                writeln!(f, "    ❌ {}: not defined", name)?;
+                continue;
+            }
+            if !missing.is_empty() {
+                writeln!(
+                    f,
+                    "    ❌ {}: missing flag combinations: {}",
+                    name,
+                    flag_list(&missing)
+                )?;
+            }
+            if !m.unterminated_flags.is_empty() {
+                writeln!(
+                    f,
+                    "    ❌ {}: no IEND or HLT for flag combinations: {}",
+                    name,
+                    flag_list(&m.unterminated_flags)
+                )?;
+            }
+        }
+
+        for &im in &self.disallowed_modes {
+            writeln!(
+                f,
+                "    ❌ {}: defined, but not allowed by the assembler",
+                mode_name(im)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds the coverage report for a microcode source file, or for an assembled microcode ROM if `is_binary` is set.
+pub fn coverage_report(
+    file_in: &str,
+    is_binary: bool,
+) -> Result<Vec<InstructionCoverage>, AssemblerError> {
+    let instruction_defs = if is_binary {
+        disassemble(read_file_binary(file_in)?)?
+    } else {
+        parse_file(file_in)?
+    };
+
+    Ok(coverage(&instruction_defs))
+}
+
+/// Checks which opcode, instruction mode and flag combinations are covered by the instruction definitions.
+pub fn coverage(instruction_defs: &[InstructionDef]) -> Vec<InstructionCoverage> {
+    let terminators: Vec<u64> = ["IEND", "HLT"]
+        .iter()
+        .filter_map(|s| isa().signal_bit(s))
+        .collect();
+
+    let mut instructions: Vec<_> = isa().instructions.iter().collect();
+    instructions.sort_by_key(|i| i.opcode);
+
+    let mut report = Vec::new();
+
+    for ins in instructions {
+        let (name, available_ims) = (ins.name.as_str(), ins.modes);
+
+        let defs: Vec<&InstructionDef> = instruction_defs
+            .iter()
+            .filter(|idf| idf.name.to_lowercase() == name.to_lowercase())
+            .filter(|idf| idf.steps.iter().any(|s| !s.is_empty()))
+            .collect();
+
+        let mut modes = Vec::new();
+        let mut disallowed_modes = Vec::new();
+
+        for im_idx in 0..INSTRUCTION_MODE_COUNT {
+            let im = im_idx_to_val(im_idx);
+            let mode_defs: Vec<&&InstructionDef> = defs
+                .iter()
+                .filter(|idf| idf.instruction_mode == im)
+                .collect();
+
+            if (available_ims & im) == 0 {
+                if !mode_defs.is_empty() {
+                    disallowed_modes.push(im);
+                }
+                continue;
+            }
+
+            let mut defined_flags: Vec<u32> = mode_defs.iter().map(|idf| idf.flags).collect();
+            defined_flags.sort();
+
+            let mut unterminated_flags: Vec<u32> = mode_defs
+                .iter()
+                .filter(|idf| {
+                    !idf.steps
+                        .iter()
+                        .any(|s| s.iter().any(|cs| terminators.contains(cs)))
+                })
+                .map(|idf| idf.flags)
+                .collect();
+            unterminated_flags.sort();
+
+            modes.push(ModeCoverage {
+                instruction_mode: im,
+                defined_flags,
+                unterminated_flags,
+            });
+        }
+
+        report.push(InstructionCoverage {
+            opcode: ins.opcode,
+            name,
+            modes,
+            disallowed_modes,
+        });
+    }
+    report
+}
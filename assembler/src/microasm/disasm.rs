@@ -1,10 +1,9 @@
-use std::{fs::File, io::Write};
+use crate::isa::isa;
+use crate::{
+    get_im_name, im_idx_to_val, read_file_binary, write_file, AssemblerError, SyntaxError,
+};
 
-use crate::{get_im_name, read_file_binary, AssemblerError, INSTRUCTIONS};
-
-use crate::microasm::{InstructionDef, CONTROL_BYTES, CONTROL_SIGNALS};
-
-use super::{INSTRUCTION_MODE_BIT_SIZE, FLAGS_BIT_SIZE, STEP_COUNTER_BIT_SIZE};
+use crate::microasm::InstructionDef;
 
 pub fn disassembler(file_in: &str, file_out: &str) -> Result<(), AssemblerError> {
     let input = read_file_binary(file_in)?;
@@ -15,10 +14,10 @@ pub fn disassembler(file_in: &str, file_out: &str) -> Result<(), AssemblerError>
     let mut output = String::new();
     let mut prev: Option<InstructionDef> = None;
     for ins in &disassembled {
-        if ins.steps.first().unwrap().len() == 0 {
+        if ins.steps.first().unwrap().is_empty() {
             continue;
         }
-        if matches!(prev, None) || prev.as_ref().unwrap().name != ins.name {
+        if prev.is_none() || prev.as_ref().unwrap().name != ins.name {
             // print the instruction name inside frame
             output += &format!("\n{:=^bar_len$}\n", "");
             output += &format!("={: ^len$}=\n", ins.name, len = bar_len - 2);
@@ -32,7 +31,7 @@ pub fn disassembler(file_in: &str, file_out: &str) -> Result<(), AssemblerError>
         // print border
         output += &format!("+{:-^len$}+\n", "", len = bar_len - 2);
 
-        let instruction_mode = get_im_name(ins.instruction_mode).unwrap();
+        let instruction_mode = get_im_name(ins.instruction_mode.trailing_zeros()).unwrap();
 
         output += &format!(
             "|{: <len$}|\n",
@@ -47,15 +46,12 @@ pub fn disassembler(file_in: &str, file_out: &str) -> Result<(), AssemblerError>
         output += &format!("|{:-^len$}|\n", "MICROSTEPS", len = bar_len - 2);
 
         for s in &ins.steps {
-            if s.len() == 0 {
+            if s.is_empty() {
                 continue;
             }
             let named = s
                 .iter()
-                .map(|&s| {
-                    let si = (s as f32).log2() as usize;
-                    CONTROL_SIGNALS[si]
-                })
+                .filter_map(|&s| isa().signal_name(s))
                 .collect::<Vec<&str>>();
             let steps_str = named.join(", ");
 
@@ -64,43 +60,49 @@ pub fn disassembler(file_in: &str, file_out: &str) -> Result<(), AssemblerError>
         output += &format!("+{:-^len$}+\n", "", len = bar_len - 2);
     }
 
-    let mut output_file = File::create(file_out).unwrap();
-    output_file.write(output.as_bytes()).unwrap();
-    Ok(())
+    write_file(file_out, output.as_bytes())
 }
 
 /// Takes a vector of bytes containing the microcode and generates instruction definitions for it
-fn disassemble(input_bytes: Vec<u8>) -> Result<Vec<InstructionDef>, AssemblerError> {
-    if input_bytes.len() % CONTROL_BYTES != 0 {
-        return Err(AssemblerError::new(
-            String::from("Invalid Tower microassembly code."),
-            None,
-        ));
+pub(crate) fn disassemble(input_bytes: Vec<u8>) -> Result<Vec<InstructionDef>, AssemblerError> {
+    let isa = isa();
+    let address = &isa.address;
+    let control_bytes_len = isa.control_bytes();
+
+    if !input_bytes.len().is_multiple_of(control_bytes_len) {
+        return Err(AssemblerError::Parse(SyntaxError::new(
+            0,
+            format!(
+                "Invalid Tower microassembly code, its size is not a multiple of {} bytes.",
+                control_bytes_len
+            ),
+        )));
     }
 
-    let code_len = (input_bytes.len() / CONTROL_BYTES) as u32;
+    let code_len = (input_bytes.len() / control_bytes_len) as u32;
 
     let mut output: Vec<InstructionDef> = Vec::new();
     let mut current_instruction: Option<InstructionDef> = None;
 
     for addr in 0..code_len {
-        let abs_byte = (addr * CONTROL_BYTES as u32) as usize;
+        let abs_byte = (addr * control_bytes_len as u32) as usize;
 
         // get individual components of the address
-        let opcode = addr >> (INSTRUCTION_MODE_BIT_SIZE + FLAGS_BIT_SIZE + STEP_COUNTER_BIT_SIZE);
+        let opcode = addr >> (address.mode_bits + address.flag_bits + address.step_bits);
 
-        let instruction_mode = (addr >> (FLAGS_BIT_SIZE + STEP_COUNTER_BIT_SIZE)) & 0b111;
-        let flags = (addr >> (STEP_COUNTER_BIT_SIZE)) & 0b111;
-        let _micro_step = addr & 0b1111;
+        let instruction_mode = im_idx_to_val(
+            (addr >> (address.flag_bits + address.step_bits)) & ((1 << address.mode_bits) - 1),
+        );
+        let flags = (addr >> address.step_bits) & ((1 << address.flag_bits) - 1);
 
-        let ins_signature = INSTRUCTIONS.get(opcode as usize);
-        if let None = ins_signature {
+        let ins_signature = isa.instruction_by_opcode(opcode);
+        if ins_signature.is_none() {
             continue;
         }
         let ins_signature = ins_signature.unwrap();
 
         let mut found_csignals: Vec<u64> = Vec::new();
-        let mut control_bytes = input_bytes[abs_byte..(abs_byte + CONTROL_BYTES)].to_vec();
+        let mut control_bytes = input_bytes[abs_byte..(abs_byte + control_bytes_len)].to_vec();
 
         // convert to little endian
         control_bytes.reverse();
@@ -111,15 +113,15 @@ fn disassemble(input_bytes: Vec<u8>) -> Result<Vec<InstructionDef>, AssemblerErr
             control_word |= (*cb as u64) << (i * 8);
         }
 
-        for (i, _cs) in CONTROL_SIGNALS.iter().enumerate() {
-            let cs_val = 2_u64.pow(i as u32);
+        for cs in &isa.signals {
+            let cs_val = 2_u64.pow(cs.bit);
             if (control_word & cs_val) != 0 {
-                found_csignals.push(cs_val);
+                found_csignals.push(cs.bit as u64);
             }
         }
 
         if let Some(current_instruction) = current_instruction.as_mut() {
-            if ins_signature.0 == current_instruction.name
+            if ins_signature.name == current_instruction.name
                 && flags == current_instruction.flags
                 && instruction_mode == current_instruction.instruction_mode
             {
@@ -131,10 +133,11 @@ fn disassemble(input_bytes: Vec<u8>) -> Result<Vec<InstructionDef>, AssemblerErr
         }
 
         let new_current_instruction = InstructionDef {
-            name: ins_signature.0.to_string(),
+            name: ins_signature.name.clone(),
             flags,
             instruction_mode,
             steps: Vec::from([found_csignals]),
+            optimize: false,
         };
         current_instruction = Some(new_current_instruction);
     }
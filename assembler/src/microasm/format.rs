@@ -0,0 +1,118 @@
+use crate::format::{format_file, render, split_comment, Line};
+use crate::AssemblerError;
+
+/// Formats a microcode source.
+///
+/// `#def`, `#macro`, `#pref`, `#suf` and mode labels start at the first column, micro steps are indented one level and
+/// every `#if` block one more. Control signals, instruction and macro names are upper-cased, flags, labels and macro parameters
+/// lower-cased.
+pub fn format_source(source: &str) -> String {
+    let mut lines = Vec::new();
+
+    // number of open #if blocks
+    let mut depth: usize = 0;
+    // parameters of the current macro, lower-cased like flags
+    let mut params: Vec<String> = Vec::new();
+
+    for raw in source.lines() {
+        let (code, comment) = split_comment(raw);
+        let comment = comment.map(str::to_string);
+
+        // split by whitespace or commas
+        let words: Vec<&str> = code
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|w| !w.is_empty())
+            .collect();
+        let Some(first) = words.first() else {
+            // comments in the first column introduce blocks, the others belong to the steps around them
+            let indent = match comment {
+                Some(_) if raw.starts_with(char::is_whitespace) => 1 + depth,
+                _ => 0,
+            };
+            lines.push(Line {
+                indent,
+                code: String::new(),
+                comment,
+            });
+            continue;
+        };
+
+        let line = if let Some(keyword) = first.strip_prefix('#') {
+            let keyword = keyword.to_lowercase();
+            let args: Vec<String> = match keyword.as_str() {
+                // the name and its options or parameters
+                "def" | "macro" => words[1..]
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, w)| match idx {
+                        0 => w.to_uppercase(),
+                        _ => w.to_lowercase(),
+                    })
+                    .collect(),
+                // paths keep their case
+                "include" => words[1..].iter().map(|w| w.to_string()).collect(),
+                _ => words[1..].iter().map(|w| w.to_lowercase()).collect(),
+            };
+
+            match keyword.as_str() {
+                "macro" => params = args.iter().skip(1).cloned().collect(),
+                "if" | "else" | "end" => {}
+                _ => params.clear(),
+            }
+
+            let indent = match keyword.as_str() {
+                "if" => {
+                    depth += 1;
+                    depth
+                }
+                "else" => depth,
+                "end" => {
+                    depth = depth.saturating_sub(1);
+                    depth + 1
+                }
+                _ => {
+                    depth = 0;
+                    0
+                }
+            };
+
+            let mut code = format!("#{}", keyword);
+            for a in &args {
+                code += &format!(" {}", a);
+            }
+            Line {
+                indent,
+                code,
+                comment,
+            }
+        } else if code.ends_with(':') {
+            Line {
+                indent: 0,
+                code: words.join(" ").to_lowercase(),
+                comment,
+            }
+        } else {
+            let words: Vec<String> = words
+                .iter()
+                .map(|w| match params.contains(&w.to_lowercase()) {
+                    true => w.to_lowercase(),
+                    false => w.to_uppercase(),
+                })
+                .collect();
+            Line {
+                indent: 1 + depth,
+                code: words.join(" "),
+                comment,
+            }
+        };
+        lines.push(line);
+    }
+
+    render(&lines)
+}
+
+/// Formats a microcode source file in place, or only checks that it is formatted.
+/// Returns the first line which is not formatted, `None` if the file is formatted.
+pub fn formatter(input_file_path: &str, check: bool) -> Result<Option<u32>, AssemblerError> {
+    format_file(input_file_path, check, format_source)
+}
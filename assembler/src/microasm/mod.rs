@@ -1,5 +1,11 @@
 pub mod asm;
+pub mod coverage;
 pub mod disasm;
+pub mod format;
+pub mod timing;
+pub mod verify;
+
+use crate::isa::isa;
 
 // ==============================================
 // =             SHARED DEFINITIONS             =
@@ -7,67 +13,7 @@ pub mod disasm;
 
 pub const COMMENT_IDENT: char = ';';
 
-pub const CONTROL_SIGNALS: &[&'static str] = &[
-    "IEND",
-    "HLT",
-    "PCI",
-    "PCO",
-    "PCJ",
-    "SPI",
-    "SPO",
-    "SPOA",
-    "AI",
-    "BI",
-    "BO",
-    "HI",
-    "HO",
-    "LI",
-    "LO",
-    "HLO",
-    "HLI",
-    "ARHI",
-    "ARHO",
-    "ARLI",
-    "ARLO",
-    "ARHLO",
-    "ALUO",
-    "OPADD",
-    "OPSUB",
-    "OPNOT",
-    "OPNAND",
-    "OPSR",
-	"ALUFI",
-    "INCE",
-    "DEC",
-    "INCI",
-    "INCO",
-    "FI",
-    "FO",
-    "MI",
-    "MO",
-    "INI",
-    "_RAMSTART",
-    "_SPSTART",
-];
-
-// constants
-pub const OPCODE_BIT_SIZE: u32 = 5;
-pub const STEP_COUNTER_BIT_SIZE: u32 = 4;
-pub const INSTRUCTION_MODE_BIT_SIZE: u32 = 3;
-pub const FLAGS_BIT_SIZE: u32 = 3;
-
-pub const MAX_INSTRUCTION_MODE_COUNT: usize = 2_usize.pow(INSTRUCTION_MODE_BIT_SIZE);
-pub const INSTRUCTION_MODE_COUNT: usize = MAX_INSTRUCTION_MODE_COUNT - 1;
-pub const FLAG_COMBINATIONS: usize = 2_usize.pow(FLAGS_BIT_SIZE);
-pub const TOTAL_DEF_COMBINATIONS: usize = INSTRUCTION_MODE_COUNT * FLAG_COMBINATIONS;
-
-pub const MAX_MICRO_STEP_COUNT: usize = 16;
-
-pub const FLAGS: [&'static str; FLAGS_BIT_SIZE as usize] = ["WRAP", "ZERO", "INCWRAP"];
-
-pub const CONTROL_BYTES: usize = 5;
-
-// the values are exponents
+// the values are exponents (bits of the control word)
 pub type MicroStep = Vec<u64>;
 
 #[derive(Debug, Clone)]
@@ -77,7 +23,11 @@ pub struct ConditionalStep {
 }
 pub struct MacroDef {
     name: String,
+    /// Replaced by the arguments of every use, a macro with parameters is only expanded when it is used.
+    params: Vec<String>,
     steps: Vec<ConditionalStep>,
+    /// Step lines of a macro with parameters and the conditionals around them.
+    lines: Vec<(Vec<String>, Vec<Conditional>)>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -86,10 +36,13 @@ pub struct InstructionDef {
     instruction_mode: u32,
     flags: u32,
     steps: Vec<MicroStep>,
+    /// Cleared by `#def NAME noopt`, keeps the steps exactly as written.
+    optimize: bool,
 }
 
+///                     (line, token, file)
 #[derive(Debug, PartialEq)]
-pub struct TokenizedLine(u32, LineType);
+pub struct TokenizedLine(pub(crate) u32, pub(crate) LineType, pub(crate) String);
 
 #[derive(Debug, PartialEq)]
 pub enum LineType {
@@ -107,4 +60,20 @@ pub struct Conditional {
     is_inverted: bool,
 }
 
+/// Formats a flag combination as a list of the set flags.
+pub fn format_flags(flags: u32) -> String {
+    let set: Vec<&str> = isa()
+        .flags
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| (flags & (1 << i)) != 0)
+        .map(|(_, f)| f.as_str())
+        .collect();
+
+    if set.is_empty() {
+        String::from("none")
+    } else {
+        set.join("|")
+    }
+}
 // ==============================================
@@ -0,0 +1,102 @@
+use std::fmt;
+
+use crate::isa::isa;
+use crate::{get_im_name, get_instruction_by_name, AssemblerError};
+
+use crate::microasm::{asm::parse_file, InstructionDef, MicroStep};
+
+/// Number of clock cycles an instruction takes in one instruction mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstructionTiming {
+    pub name: String,
+    pub instruction_mode: u32,
+    /// Fewest cycles across all flag combinations.
+    pub min: usize,
+    /// Most cycles across all flag combinations.
+    pub max: usize,
+}
+
+impl fmt::Display for InstructionTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = get_im_name(self.instruction_mode.trailing_zeros()).unwrap_or("Unknown");
+        write!(
+            f,
+            "{: <6}{: <13}{}",
+            self.name.to_uppercase(),
+            mode,
+            format_cycles(self.min, self.max)
+        )
+    }
+}
+
+/// Formats a cycle range, collapsing it to a single number when both ends are equal.
+pub fn format_cycles(min: usize, max: usize) -> String {
+    if min == max {
+        min.to_string()
+    } else {
+        format!("{}-{}", min, max)
+    }
+}
+
+/// Counts the clock cycles spent in a definition. Every micro step takes one cycle, the step with IEND or HLT included.
+/// Without IEND the step counter runs through all of its steps before it wraps around.
+pub fn step_count(steps: &[MicroStep]) -> usize {
+    let terminator = steps.iter().position(|s| {
+        s.iter()
+            .any(|&cs| matches!(isa().signal_name(cs), Some("IEND" | "HLT")))
+    });
+
+    match terminator {
+        Some(idx) => idx + 1,
+        None => isa().max_micro_steps(),
+    }
+}
+
+/// Reads and parses a microcode source file and derives the timing table from it.
+pub fn timing_report(file_in: &str) -> Result<Vec<InstructionTiming>, AssemblerError> {
+    let instruction_defs = parse_file(file_in)?;
+    Ok(timing(&instruction_defs))
+}
+
+/// Derives the cycle counts of every instruction and instruction mode from their definitions.
+/// The table is ordered by opcode and instruction mode.
+pub fn timing(instruction_defs: &[InstructionDef]) -> Vec<InstructionTiming> {
+    let mut table: Vec<InstructionTiming> = Vec::new();
+
+    for idf in instruction_defs {
+        let cycles = step_count(&idf.steps);
+
+        let existing = table
+            .iter_mut()
+            .find(|t| t.name == idf.name && t.instruction_mode == idf.instruction_mode);
+
+        if let Some(t) = existing {
+            t.min = t.min.min(cycles);
+            t.max = t.max.max(cycles);
+        } else {
+            table.push(InstructionTiming {
+                name: idf.name.clone(),
+                instruction_mode: idf.instruction_mode,
+                min: cycles,
+                max: cycles,
+            });
+        }
+    }
+
+    table.sort_by_key(|t| {
+        let opcode = get_instruction_by_name(&t.name).map(|i| i.0).unwrap_or(0);
+        (opcode, t.instruction_mode)
+    });
+    table
+}
+
+/// Looks up the timing of an instruction in a specific instruction mode.
+pub fn find_timing<'a>(
+    table: &'a [InstructionTiming],
+    name: &str,
+    instruction_mode: u32,
+) -> Option<&'a InstructionTiming> {
+    table.iter().find(|t| {
+        t.name.to_lowercase() == name.to_lowercase() && t.instruction_mode == instruction_mode
+    })
+}
@@ -0,0 +1,283 @@
+use std::fmt;
+
+use crate::{get_im_name, AssemblerError};
+
+use crate::isa::isa;
+use crate::microasm::{asm::parse_file, format_flags, InstructionDef};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bus {
+    Data,
+    Address,
+}
+
+impl fmt::Display for Bus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bus::Data => write!(f, "data bus"),
+            Bus::Address => write!(f, "address bus"),
+        }
+    }
+}
+
+/// Describes how a control signal interacts with the buses and the ALU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SignalClass {
+    /// Bus the signal puts a value on.
+    pub drives: Option<Bus>,
+    /// Buses the signal takes a value from (memory also needs an address to work with).
+    pub receives: &'static [Bus],
+    /// The signal selects the ALU operation.
+    pub is_alu_op: bool,
+}
+
+const fn driver(bus: Bus) -> SignalClass {
+    SignalClass {
+        drives: Some(bus),
+        receives: &[],
+        is_alu_op: false,
+    }
+}
+
+const fn receiver(buses: &'static [Bus]) -> SignalClass {
+    SignalClass {
+        drives: None,
+        receives: buses,
+        is_alu_op: false,
+    }
+}
+
+/// Returns the class of a control signal of the Tower ISA. Signals it does not know are treated as not touching any bus.
+pub fn classify_signal(name: &str) -> SignalClass {
+    match name {
+        "PCO" | "HLO" | "ARHLO" | "SPOA" => driver(Bus::Address),
+        "SPO" | "BO" | "HO" | "LO" | "ARHO" | "ARLO" | "ALUO" | "INCO" | "FO" => driver(Bus::Data),
+        "MO" => SignalClass {
+            drives: Some(Bus::Data),
+            receives: &[Bus::Address],
+            is_alu_op: false,
+        },
+        "PCJ" | "HLI" => receiver(&[Bus::Address]),
+        "SPI" | "AI" | "BI" | "HI" | "LI" | "ARHI" | "ARLI" | "INCI" | "FI" | "INI" => {
+            receiver(&[Bus::Data])
+        }
+        "MI" => receiver(&[Bus::Data, Bus::Address]),
+        "OPADD" | "OPSUB" | "OPNOT" | "OPNAND" | "OPSR" => SignalClass {
+            is_alu_op: true,
+            ..Default::default()
+        },
+        _ => SignalClass::default(),
+    }
+}
+
+/// Pieces of CPU state a control signal can read or change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    ProgramCounter,
+    StackPointer,
+    A,
+    B,
+    H,
+    L,
+    ArH,
+    ArL,
+    Incrementer,
+    Flags,
+    Memory,
+    InstructionRegister,
+    StepCounter,
+    /// Operation selected by the ALU op signals.
+    AluMode,
+    /// Direction selected by DEC.
+    IncrementerMode,
+    /// Address page selected by `_RAMSTART` and `_SPSTART`.
+    AddressMode,
+}
+
+impl State {
+    /// Combinational state is not latched at the end of a step, so it affects everything active in the same step.
+    pub fn is_combinational(&self) -> bool {
+        matches!(
+            self,
+            State::AluMode | State::IncrementerMode | State::AddressMode
+        )
+    }
+}
+
+/// State read and changed by a control signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SignalAccess {
+    pub reads: &'static [State],
+    pub writes: &'static [State],
+}
+
+/// Returns the state accessed by a control signal of the Tower ISA, or `None` if the signal is not part of it.
+pub fn signal_access(name: &str) -> Option<SignalAccess> {
+    use State::*;
+
+    let (reads, writes): (&'static [State], &'static [State]) = match name {
+        "IEND" | "HLT" => (&[], &[StepCounter]),
+        "PCI" => (&[ProgramCounter], &[ProgramCounter]),
+        "PCO" => (&[ProgramCounter, AddressMode], &[]),
+        "PCJ" => (&[AddressMode], &[ProgramCounter]),
+        "SPI" => (&[], &[StackPointer]),
+        "SPO" => (&[StackPointer], &[]),
+        "SPOA" => (&[StackPointer, AddressMode], &[]),
+        "AI" => (&[], &[A]),
+        "BI" => (&[], &[B]),
+        "BO" => (&[B], &[]),
+        "HI" => (&[], &[H]),
+        "HO" => (&[H], &[]),
+        "LI" => (&[], &[L]),
+        "LO" => (&[L], &[]),
+        "HLO" => (&[H, L, AddressMode], &[]),
+        "HLI" => (&[AddressMode], &[H, L]),
+        "ARHI" => (&[], &[ArH]),
+        "ARHO" => (&[ArH], &[]),
+        "ARLI" => (&[], &[ArL]),
+        "ARLO" => (&[ArL], &[]),
+        "ARHLO" => (&[ArH, ArL, AddressMode], &[]),
+        "ALUO" => (&[A, B, AluMode], &[]),
+        "OPADD" | "OPSUB" | "OPNOT" | "OPNAND" | "OPSR" => (&[A, B], &[AluMode, Flags]),
+        "ALUFI" => (&[Flags], &[AluMode]),
+        "INCE" => (&[Incrementer, IncrementerMode], &[Incrementer, Flags]),
+        "DEC" => (&[], &[IncrementerMode]),
+        "INCI" => (&[], &[Incrementer]),
+        "INCO" => (&[Incrementer], &[]),
+        "FI" => (&[], &[Flags]),
+        "FO" => (&[Flags], &[]),
+        "MI" => (&[AddressMode], &[Memory]),
+        "MO" => (&[Memory, AddressMode], &[]),
+        "INI" => (&[], &[InstructionRegister]),
+        "_RAMSTART" | "_SPSTART" => (&[], &[AddressMode]),
+        _ => return None,
+    };
+
+    Some(SignalAccess { reads, writes })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConflictKind {
+    /// More than one signal drives the same bus.
+    BusContention(Bus, Vec<&'static str>),
+    /// Signals take a value from a bus nothing is driving.
+    UndrivenBus(Bus, Vec<&'static str>),
+    /// More than one ALU operation is selected.
+    AluOpConflict(Vec<&'static str>),
+    /// `_SPSTART` is only relative to `_RAMSTART` and does nothing on its own.
+    SpStartWithoutRamStart,
+}
+
+impl fmt::Display for ConflictKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConflictKind::BusContention(bus, signals) => {
+                write!(f, "multiple drivers on the {}: {}", bus, signals.join(", "))
+            }
+            ConflictKind::UndrivenBus(bus, signals) => {
+                write!(f, "nothing drives the {} for: {}", bus, signals.join(", "))
+            }
+            ConflictKind::AluOpConflict(signals) => {
+                write!(f, "conflicting ALU operations: {}", signals.join(", "))
+            }
+            ConflictKind::SpStartWithoutRamStart => write!(f, "_SPSTART used without _RAMSTART"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub instruction: String,
+    pub instruction_mode: u32,
+    pub flags: u32,
+    /// Index of the micro step (prefix steps included).
+    pub step: usize,
+    pub kind: ConflictKind,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = get_im_name(self.instruction_mode.trailing_zeros()).unwrap_or("Unknown");
+        write!(
+            f,
+            "{} ({}, flags: {}) step {}: {}",
+            self.instruction.to_uppercase(),
+            mode,
+            format_flags(self.flags),
+            self.step,
+            self.kind
+        )
+    }
+}
+
+/// Reads and parses a microcode source file and checks all of its instruction definitions.
+pub fn verifier(file_in: &str) -> Result<Vec<Conflict>, AssemblerError> {
+    let instruction_defs = parse_file(file_in)?;
+    Ok(verify(&instruction_defs))
+}
+
+/// Checks every step of every instruction definition for bus contention and control signal conflicts.
+pub fn verify(instruction_defs: &[InstructionDef]) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+
+    for idf in instruction_defs {
+        for (step_idx, step) in idf.steps.iter().enumerate() {
+            let signals: Vec<&'static str> =
+                step.iter().filter_map(|&s| isa().signal_name(s)).collect();
+
+            for kind in check_step(&signals) {
+                conflicts.push(Conflict {
+                    instruction: idf.name.clone(),
+                    instruction_mode: idf.instruction_mode,
+                    flags: idf.flags,
+                    step: step_idx,
+                    kind,
+                });
+            }
+        }
+    }
+    conflicts
+}
+
+/// Checks the control signals active during a single micro step.
+pub fn check_step(signals: &[&'static str]) -> Vec<ConflictKind> {
+    let mut found = Vec::new();
+
+    for bus in [Bus::Data, Bus::Address] {
+        let drivers: Vec<&'static str> = signals
+            .iter()
+            .filter(|&&s| classify_signal(s).drives == Some(bus))
+            .copied()
+            .collect();
+        let receivers: Vec<&'static str> = signals
+            .iter()
+            .filter(|&&s| classify_signal(s).receives.contains(&bus))
+            .copied()
+            .collect();
+
+        if drivers.len() > 1 {
+            found.push(ConflictKind::BusContention(bus, drivers.clone()));
+        }
+
+        // _RAMSTART puts an address on the bus by itself
+        let is_injected = bus == Bus::Address && signals.contains(&"_RAMSTART");
+        if drivers.is_empty() && !receivers.is_empty() && !is_injected {
+            found.push(ConflictKind::UndrivenBus(bus, receivers));
+        }
+    }
+
+    let alu_ops: Vec<&'static str> = signals
+        .iter()
+        .filter(|&&s| classify_signal(s).is_alu_op)
+        .copied()
+        .collect();
+    if alu_ops.len() > 1 {
+        found.push(ConflictKind::AluOpConflict(alu_ops));
+    }
+
+    if signals.contains(&"_SPSTART") && !signals.contains(&"_RAMSTART") {
+        found.push(ConflictKind::SpStartWithoutRamStart);
+    }
+
+    found
+}
@@ -0,0 +1,70 @@
+; Standard library: division
+;
+; _div16: R0 = R0 / R1, R1 = R0 % R1 (unsigned)
+;	R0 = 0xFEE0 (high), 0xFEE1 (low)
+;	R1 = 0xFEE2 (high), 0xFEE3 (low)
+; Dividing by 0 gives the quotient 0xFFFF and the dividend as the remainder.
+; Changes A, B, the flags and 0xFEF0-0xFEF7.
+
+_div16:
+	; the dividend N is shifted into the remainder R bit by bit, the quotient is shifted into N
+	LDA *0xFEE0
+	STA &0xFEF0
+	LDA *0xFEE1
+	STA &0xFEF1
+	LDA #0
+	STA &0xFEF2
+	STA &0xFEF3
+	LDA *0xFEE2
+	STA &0xFEF4
+	LDA *0xFEE3
+	STA &0xFEF5
+	LDA #16
+	STA &0xFEF6
+_div16_loop:
+	; R:N = R:N << 1
+	LDA *0xFEF1
+	ADD *0xFEF1
+	STA &0xFEF1
+	LDA *0xFEF0
+	ADC *0xFEF0
+	STA &0xFEF0
+	LDA *0xFEF3
+	ADC *0xFEF3
+	STA &0xFEF3
+	LDA *0xFEF2
+	ADC *0xFEF2
+	STA &0xFEF2
+	; a bit shifted out of R means R > D
+	JW _div16_subtract
+	LDA *0xFEF3
+	SUB *0xFEF5
+	LDA *0xFEF2
+	SBB *0xFEF4
+	JW _div16_next
+_div16_subtract:
+	; R = R - D, the quotient bit is 1
+	LDA *0xFEF3
+	SUB *0xFEF5
+	STA &0xFEF3
+	LDA *0xFEF2
+	SBB *0xFEF4
+	STA &0xFEF2
+	LDA *0xFEF1
+	ADD #1
+	STA &0xFEF1
+_div16_next:
+	LDA *0xFEF6
+	SUB #1
+	STA &0xFEF6
+	JNZ _div16_loop
+
+	LDA *0xFEF0
+	STA &0xFEE0
+	LDA *0xFEF1
+	STA &0xFEE1
+	LDA *0xFEF2
+	STA &0xFEE2
+	LDA *0xFEF3
+	STA &0xFEE3
+	RTS
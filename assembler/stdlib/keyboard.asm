@@ -0,0 +1,53 @@
+; Standard library: keyboard input
+;
+; Reading 0xFF02 returns the next key, or 0 when no key was pressed.
+; The routines change A, B and the flags.
+
+; waits for a key, A = the key
+_read_key:
+	LDA *0xFF02
+	CMP #0
+	JZ _read_key
+	RTS
+
+; A = the next key, ZERO is set when no key was pressed
+_poll_key:
+	LDA *0xFF02
+	CMP #0
+	RTS
+
+; reads a line into the buffer R0 = 0xFEE0 (high), 0xFEE1 (low) points to, without the line break
+; the line is terminated with a zero, R1 = 0xFEE2 (high), 0xFEE3 (low) = its length, R0 is kept
+; the buffer is not checked for overflows, uses 0xFEF0-0xFEF1
+_read_line:
+	LDA *0xFEE0
+	STA &0xFEF0
+	LDA *0xFEE1
+	STA &0xFEF1
+	LDA #0
+	STA &0xFEE2
+	STA &0xFEE3
+_read_line_key:
+	JSR _read_key
+	CMP #10
+	JZ _read_line_done
+	CMP #13
+	JZ _read_line_done
+	STA @0xFEF0
+	LDA *0xFEF1
+	ADD #1
+	STA &0xFEF1
+	LDA *0xFEF0
+	ADC #0
+	STA &0xFEF0
+	LDA *0xFEE3
+	ADD #1
+	STA &0xFEE3
+	LDA *0xFEE2
+	ADC #0
+	STA &0xFEE2
+	JMP _read_line_key
+_read_line_done:
+	LDA #0
+	STA @0xFEF0
+	RTS
@@ -0,0 +1,10 @@
+; Standard library: macros
+;
+; MW value, target
+;	moves a value into memory, e.g. `MW #1, &0xFE00` or `MW *0xFE00, &0xFE01`
+; Macros have to be defined before they are used, include this file at the top of the program.
+
+#macro MW
+	LDA $1
+	STA $2
+#end
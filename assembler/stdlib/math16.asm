@@ -0,0 +1,82 @@
+; Standard library: 16-bit arithmetic
+;
+; Values are stored high byte first:
+;	R0 = 0xFEE0 (high), 0xFEE1 (low)
+;	R1 = 0xFEE2 (high), 0xFEE3 (low)
+; The routines change A, B and the flags.
+
+; R0 = R0 + R1, WRAP is set on a carry
+_add16:
+	LDA *0xFEE1
+	ADD *0xFEE3
+	STA &0xFEE1
+	LDA *0xFEE0
+	ADC *0xFEE2
+	STA &0xFEE0
+	RTS
+
+; R0 = R0 - R1, WRAP is set on a borrow
+_sub16:
+	LDA *0xFEE1
+	SUB *0xFEE3
+	STA &0xFEE1
+	LDA *0xFEE0
+	SBB *0xFEE2
+	STA &0xFEE0
+	RTS
+
+; R0 = R0 + 1
+_inc16:
+	LDA *0xFEE1
+	ADD #1
+	STA &0xFEE1
+	LDA *0xFEE0
+	ADC #0
+	STA &0xFEE0
+	RTS
+
+; R0 = R0 - 1
+_dec16:
+	LDA *0xFEE1
+	SUB #1
+	STA &0xFEE1
+	LDA *0xFEE0
+	SBB #0
+	STA &0xFEE0
+	RTS
+
+; R0 = -R0 (two's complement)
+_neg16:
+	LDA #0
+	SUB *0xFEE1
+	STA &0xFEE1
+	LDA #0
+	SBB *0xFEE0
+	STA &0xFEE0
+	RTS
+
+; compares R0 with R1 as unsigned numbers: ZERO is set if R0 = R1, WRAP if R0 < R1
+_cmp16:
+	LDA *0xFEE0
+	CMP *0xFEE2
+	JNZ _cmp16_done
+	LDA *0xFEE1
+	CMP *0xFEE3
+_cmp16_done:
+	RTS
+
+; compares R0 with R1 as signed numbers: ZERO is set if R0 = R1, WRAP if R0 < R1
+; uses 0xFEF0-0xFEF1
+_cmps16:
+	; flipping the sign bits turns the signed comparison into an unsigned one
+	LDA *0xFEE2
+	ADD #0x80
+	STA &0xFEF1
+	LDA *0xFEE0
+	ADD #0x80
+	CMP *0xFEF1
+	JNZ _cmps16_done
+	LDA *0xFEE1
+	CMP *0xFEE3
+_cmps16_done:
+	RTS
@@ -0,0 +1,88 @@
+; Standard library: memory
+;
+;	R0 = 0xFEE0 (high), 0xFEE1 (low)
+;	R1 = 0xFEE2 (high), 0xFEE3 (low)
+;	R2 = 0xFEE4 (high), 0xFEE5 (low)
+; The routines keep R0-R2 and change A, B, the flags and 0xFEF0-0xFEF5.
+
+; copies R2 bytes from R1 to R0, the first byte first
+_memcpy:
+	LDA *0xFEE0
+	STA &0xFEF0
+	LDA *0xFEE1
+	STA &0xFEF1
+	LDA *0xFEE2
+	STA &0xFEF2
+	LDA *0xFEE3
+	STA &0xFEF3
+	LDA *0xFEE4
+	STA &0xFEF4
+	LDA *0xFEE5
+	STA &0xFEF5
+_memcpy_loop:
+	LDA *0xFEF4
+	CMP #0
+	JNZ _memcpy_byte
+	LDA *0xFEF5
+	CMP #0
+	JZ _memcpy_done
+_memcpy_byte:
+	LDA @0xFEF2
+	STA @0xFEF0
+	LDA *0xFEF1
+	ADD #1
+	STA &0xFEF1
+	LDA *0xFEF0
+	ADC #0
+	STA &0xFEF0
+	LDA *0xFEF3
+	ADD #1
+	STA &0xFEF3
+	LDA *0xFEF2
+	ADC #0
+	STA &0xFEF2
+	LDA *0xFEF5
+	SUB #1
+	STA &0xFEF5
+	LDA *0xFEF4
+	SBB #0
+	STA &0xFEF4
+	JMP _memcpy_loop
+_memcpy_done:
+	RTS
+
+; fills R2 bytes starting at R0 with the low byte of R1
+_memset:
+	LDA *0xFEE0
+	STA &0xFEF0
+	LDA *0xFEE1
+	STA &0xFEF1
+	LDA *0xFEE4
+	STA &0xFEF4
+	LDA *0xFEE5
+	STA &0xFEF5
+_memset_loop:
+	LDA *0xFEF4
+	CMP #0
+	JNZ _memset_byte
+	LDA *0xFEF5
+	CMP #0
+	JZ _memset_done
+_memset_byte:
+	LDA *0xFEE3
+	STA @0xFEF0
+	LDA *0xFEF1
+	ADD #1
+	STA &0xFEF1
+	LDA *0xFEF0
+	ADC #0
+	STA &0xFEF0
+	LDA *0xFEF5
+	SUB #1
+	STA &0xFEF5
+	LDA *0xFEF4
+	SBB #0
+	STA &0xFEF4
+	JMP _memset_loop
+_memset_done:
+	RTS
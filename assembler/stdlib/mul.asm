@@ -0,0 +1,69 @@
+; Standard library: multiplication
+;
+; _mul16: R0 = R0 * R1, the low 16 bits of the product
+;	R0 = 0xFEE0 (high), 0xFEE1 (low)
+;	R1 = 0xFEE2 (high), 0xFEE3 (low), kept
+; Changes A, B, the flags and 0xFEF0-0xFEF5.
+
+_mul16:
+	; product P = 0, multiplicand M = R0, multiplier N = R1
+	LDA #0
+	STA &0xFEF0
+	STA &0xFEF1
+	LDA *0xFEE0
+	STA &0xFEF2
+	LDA *0xFEE1
+	STA &0xFEF3
+	LDA *0xFEE2
+	STA &0xFEF4
+	LDA *0xFEE3
+	STA &0xFEF5
+_mul16_loop:
+	LDA *0xFEF4
+	CMP #0
+	JNZ _mul16_bit
+	LDA *0xFEF5
+	CMP #0
+	JZ _mul16_done
+_mul16_bit:
+	; P = P + M if the lowest bit of N is set
+	LDA *0xFEF5
+	NAND #1
+	CMP #0xFF
+	JZ _mul16_shift
+	LDA *0xFEF1
+	ADD *0xFEF3
+	STA &0xFEF1
+	LDA *0xFEF0
+	ADC *0xFEF2
+	STA &0xFEF0
+_mul16_shift:
+	; M = M << 1
+	LDA *0xFEF3
+	ADD *0xFEF3
+	STA &0xFEF3
+	LDA *0xFEF2
+	ADC *0xFEF2
+	STA &0xFEF2
+	; N = N >> 1
+	LDA *0xFEF5
+	SR %A
+	STA &0xFEF5
+	LDA *0xFEF4
+	NAND #1
+	CMP #0xFF
+	JZ _mul16_high
+	LDA *0xFEF5
+	ADD #0x80
+	STA &0xFEF5
+_mul16_high:
+	LDA *0xFEF4
+	SR %A
+	STA &0xFEF4
+	JMP _mul16_loop
+_mul16_done:
+	LDA *0xFEF0
+	STA &0xFEE0
+	LDA *0xFEF1
+	STA &0xFEE1
+	RTS
@@ -0,0 +1,116 @@
+; Standard library: number formatting
+;
+; Prints numbers on the TTY (0xFF01), R0 = 0xFEE0 (high), 0xFEE1 (low) is kept.
+; The routines change A, B, the flags and 0xFEF8-0xFEFE.
+
+; prints R0 as an unsigned decimal number
+_print_u16:
+	LDA *0xFEE0
+	STA &0xFEF8
+	LDA *0xFEE1
+	STA &0xFEF9
+_print_u16_scratch:
+	; 1 once a digit was printed, leading zeros are skipped
+	LDA #0
+	STA &0xFEFB
+	; every digit is the number of times its power of ten can be subtracted
+	LDA #0x27
+	STA &0xFEFC
+	LDA #0x10
+	STA &0xFEFD
+	JSR _print_u16_digit
+	LDA #0x03
+	STA &0xFEFC
+	LDA #0xE8
+	STA &0xFEFD
+	JSR _print_u16_digit
+	LDA #0
+	STA &0xFEFC
+	LDA #100
+	STA &0xFEFD
+	JSR _print_u16_digit
+	LDA #0
+	STA &0xFEFC
+	LDA #10
+	STA &0xFEFD
+	JSR _print_u16_digit
+	LDA *0xFEF9
+	ADD #48
+	STA &0xFF01
+	RTS
+_print_u16_digit:
+	LDA #0
+	STA &0xFEFA
+_print_u16_count:
+	LDA *0xFEF9
+	SUB *0xFEFD
+	STA &0xFEFE
+	LDA *0xFEF8
+	SBB *0xFEFC
+	JW _print_u16_print
+	STA &0xFEF8
+	LDA *0xFEFE
+	STA &0xFEF9
+	LDA *0xFEFA
+	ADD #1
+	STA &0xFEFA
+	JMP _print_u16_count
+_print_u16_print:
+	LDA *0xFEFA
+	ADD *0xFEFB
+	CMP #0
+	JZ _print_u16_skip
+	LDA *0xFEFA
+	ADD #48
+	STA &0xFF01
+	LDA #1
+	STA &0xFEFB
+_print_u16_skip:
+	RTS
+
+; prints R0 as a signed decimal number
+_print_s16:
+	LDA *0xFEE0
+	CMP #0x80
+	JW _print_u16
+	LDA #45
+	STA &0xFF01
+	LDA #0
+	SUB *0xFEE1
+	STA &0xFEF9
+	LDA #0
+	SBB *0xFEE0
+	STA &0xFEF8
+	JMP _print_u16_scratch
+
+; prints A as two hexadecimal digits
+_print_hex8:
+	STA &0xFEFA
+	SR %A
+	SR %A
+	SR %A
+	SR %A
+	JSR _print_hex_digit
+	LDA *0xFEFA
+	NAND #0x0F
+	NOT %A
+	JMP _print_hex_digit
+
+; prints R0 as four hexadecimal digits
+_print_hex16:
+	LDA *0xFEE0
+	JSR _print_hex8
+	LDA *0xFEE1
+	JMP _print_hex8
+
+; prints the value of A (0-15) as a hexadecimal digit
+_print_hex_digit:
+	CMP #10
+	JW _print_hex_decimal
+	ADD #55
+	STA &0xFF01
+	RTS
+_print_hex_decimal:
+	ADD #48
+	STA &0xFF01
+	RTS
@@ -0,0 +1,44 @@
+; Standard library: 16-bit shifts
+;
+; The shifted value is R0 = 0xFEE0 (high), 0xFEE1 (low).
+; The routines change A, B and the flags.
+
+; R0 = R0 << 1, WRAP is set to the bit shifted out
+_shl16:
+	LDA *0xFEE1
+	ADD *0xFEE1
+	STA &0xFEE1
+	LDA *0xFEE0
+	ADC *0xFEE0
+	STA &0xFEE0
+	RTS
+
+; R0 = R0 >> 1, a 0 is shifted in
+_shr16:
+	LDA *0xFEE1
+	SR %A
+	STA &0xFEE1
+	; SR does not shift through the wrap flag, the lowest bit of the high byte is moved by hand
+	LDA *0xFEE0
+	NAND #1
+	CMP #0xFF
+	JZ _shr16_high
+	LDA *0xFEE1
+	ADD #0x80
+	STA &0xFEE1
+_shr16_high:
+	LDA *0xFEE0
+	SR %A
+	STA &0xFEE0
+	RTS
+
+; R0 = R0 >> 1, the sign bit is kept
+_sar16:
+	LDA *0xFEE0
+	CMP #0x80
+	JW _shr16
+	JSR _shr16
+	LDA *0xFEE0
+	ADD #0x80
+	STA &0xFEE0
+	RTS
@@ -0,0 +1,32 @@
+; Standard library: TTY output
+;
+; Characters written to 0xFF01 are printed on the TTY.
+; The routines change A, B and the flags.
+
+; prints the character in A
+_print_char:
+	STA &0xFF01
+	RTS
+
+; prints a line break
+_print_newline:
+	LDA #10
+	STA &0xFF01
+	RTS
+
+; prints the zero terminated string R0 = 0xFEE0 (high), 0xFEE1 (low) points to
+; R0 is left pointing to the terminating zero
+_print_string:
+	LDA @0xFEE0
+	CMP #0
+	JZ _print_string_done
+	STA &0xFF01
+	LDA *0xFEE1
+	ADD #1
+	STA &0xFEE1
+	LDA *0xFEE0
+	ADC #0
+	STA &0xFEE0
+	JMP _print_string
+_print_string_done:
+	RTS
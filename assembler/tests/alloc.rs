@@ -0,0 +1,166 @@
+//! Allocates variables with `#zp` and `#var` and runs programs using them in the emulator.
+
+mod common;
+
+use common::error;
+use tower_assembler::asm::asm::{assemble_str, AssembleOptions};
+use tower_assembler::asm::object::{assemble_object, ObjectFile, RelocationKind};
+use tower_assembler::asm::Section;
+use tower_assembler::emu::Machine;
+use tower_assembler::link::link;
+use tower_assembler::{DATA_START, ZERO_PAGE_START};
+
+fn run(source: &str) -> Machine {
+    common::run_source(source, "", 100_000)
+}
+
+fn run_bytes(bytes: &[u8]) -> Machine {
+    common::run(bytes, 100_000)
+}
+
+fn object(source: &str, file_name: &str) -> ObjectFile {
+    let options = AssembleOptions {
+        file_name: file_name.to_string(),
+        loader: None,
+        ..Default::default()
+    };
+    assemble_object(source, &options).unwrap_or_else(|e| panic!("{}\n{}", e, source))
+}
+
+#[test]
+fn test_variables_are_allocated() {
+    let program = assemble_str(
+        "#zp _counter\n#zp _pointer 2\n#var _buffer 64\n#var _flag\nHLT\n",
+        &AssembleOptions::default(),
+    )
+    .unwrap();
+
+    let allocated: Vec<(&str, u32, Section)> = program
+        .symbols
+        .iter()
+        .map(|l| (l.name.as_str(), l.address, l.section))
+        .collect();
+    let zp = ZERO_PAGE_START as u32;
+    let data = DATA_START as u32;
+    assert_eq!(
+        allocated,
+        vec![
+            ("_counter", zp, Section::ZeroPage),
+            ("_pointer", zp + 1, Section::ZeroPage),
+            ("_buffer", data, Section::Data),
+            ("_flag", data + 64, Section::Data),
+        ]
+    );
+}
+
+#[test]
+fn test_variables_as_operands() {
+    let source = "
+#zp _counter
+#var _total
+	LDA #5
+	STA _counter
+	LDA #0
+	STA _total
+_loop:
+	LDA _total
+	ADD #3
+	STA _total
+	LDA _counter
+	SUB #1
+	STA _counter
+	JNZ _loop
+	HLT
+";
+    // the zero page variable is accessed with its one-byte offset, the RAM variable with its address
+    let program = assemble_str(source, &AssembleOptions::default()).unwrap();
+    let sizes: Vec<u32> = program.source_map.iter().map(|e| e.size).collect();
+    assert_eq!(sizes, vec![2, 2, 2, 3, 3, 2, 3, 2, 2, 2, 3, 1]);
+    assert_eq!(program.bytes[3], 0x00);
+
+    let machine = run(source);
+    assert_eq!(machine.memory[ZERO_PAGE_START as usize], 0);
+    assert_eq!(machine.memory[DATA_START as usize], 15);
+}
+
+#[test]
+fn test_variables_in_macros_and_pseudo_instructions() {
+    let machine = run("
+#zp _low
+#var _wide 2
+#macro store
+	STA $1
+#end
+	LDA #7
+	store _low
+	INC16 _wide
+	INC16 _wide
+	HLT
+");
+    assert_eq!(machine.memory[ZERO_PAGE_START as usize], 7);
+    assert_eq!(
+        &machine.memory[DATA_START as usize..DATA_START as usize + 2],
+        &[0x00, 0x02]
+    );
+}
+
+#[test]
+fn test_allocation_errors() {
+    assert!(error("#zp _a 0x100\n#zp _b\nHLT\n").contains(
+        "<input>:2: Variable '_b' does not fit into the zero page (0x4000-0x40ff), 0 of its 256 bytes are left."
+    ));
+    assert!(error("#var _a 0xbcdf\n#var _b 2\nHLT\n")
+        .contains("<input>:2: Variable '_b' does not fit into the RAM (0x4200-0xfedf)."));
+    assert!(error("STA _a\n#zp _a\nHLT\n").contains(
+        "<input>:2: Variable '_a' is used before it is declared, variables have to be declared before they are used."
+    ));
+    assert!(error("#var _a 0\nHLT\n")
+        .contains("<input>:1: Invalid size '0', it has to be a number of bytes greater than 0."));
+    assert!(
+        error("#var _a\n#zp _a\nHLT\n").contains("<input>:2: Label with name '_a' already exists.")
+    );
+    assert!(error("#zp\nHLT\n").contains(
+        "<input>:1: '#zp' takes the name of the variable and its size in bytes, e.g. '#zp counter 1'."
+    ));
+}
+
+#[test]
+fn test_linked_variables() {
+    let main = "
+#import _inc
+#zp _a
+	LDA #1
+	STA _a
+	JSR _inc
+	HLT
+";
+    let lib = "
+#export _inc
+#zp _b 2
+_inc:
+	LDA #2
+	STA _b
+	RTS
+";
+    let obj = object(lib, "lib.asm");
+    assert_eq!(obj.zero_page_size, 2);
+    assert_eq!(obj.relocations[0].kind, RelocationKind::Low8);
+
+    let linked = link(&[object(main, "main.asm"), obj]).unwrap();
+    assert_eq!(
+        linked.modules[1].zero_page_start,
+        ZERO_PAGE_START as u32 + 1
+    );
+
+    let machine = run_bytes(&linked.program.bytes);
+    assert_eq!(
+        &machine.memory[ZERO_PAGE_START as usize..ZERO_PAGE_START as usize + 2],
+        &[1, 2]
+    );
+
+    let full = "#zp _c 0x100\nHLT\n";
+    assert!(link(&[object(main, "main.asm"), object(full, "full.asm")])
+        .unwrap_err()
+        .to_string()
+        .contains("The variables of the program do not fit into the zero page (0x4000-0x40ff)."));
+}
@@ -0,0 +1,69 @@
+//! Helpers shared by the tests that assemble programs and microcode or run them in the emulator.
+
+#![allow(dead_code)]
+
+use std::path::Path;
+
+use tower_assembler::asm::asm::{assemble_str, AssembleOptions, AssembledProgram};
+use tower_assembler::emu::runner::load_microcode;
+use tower_assembler::emu::{Machine, StopReason};
+use tower_assembler::microasm::asm::{self as microasm, MicroassembleOptions};
+
+/// The root of the repository, the parent of the assembler crate.
+pub fn root() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap()
+}
+
+/// Assembles the microcode of the computer.
+pub fn microcode() -> Vec<u8> {
+    let path = root().join("software/microcode/microcode.asm");
+    load_microcode(path.to_str().unwrap()).unwrap()
+}
+
+/// Assembles the source, panicking with the error and the source if it fails.
+pub fn assemble(source: &str) -> AssembledProgram {
+    assemble_str(source, &AssembleOptions::default())
+        .unwrap_or_else(|e| panic!("{}\n{}", e, source))
+}
+
+/// Returns the errors of a source which fails to assemble.
+pub fn error(source: &str) -> String {
+    assemble_str(source, &AssembleOptions::default())
+        .unwrap_err()
+        .to_string()
+}
+
+/// Assembles the microcode source to the bytes of the microcode ROM.
+pub fn microassemble(source: &str) -> Vec<u8> {
+    microasm::assemble_str(source, &MicroassembleOptions::default())
+        .unwrap_or_else(|e| panic!("{}\n{}", e, source))
+        .bytes
+}
+
+/// Returns the error of a microcode source which fails to assemble.
+pub fn microassemble_error(source: &str) -> String {
+    microasm::assemble_str(source, &MicroassembleOptions::default())
+        .unwrap_err()
+        .to_string()
+}
+
+/// Creates a machine running the program, with the input waiting on the keyboard.
+pub fn machine(bytes: &[u8], input: &str) -> Machine {
+    let mut machine = Machine::new(microcode(), bytes);
+    machine.keyboard.extend(input.bytes());
+    machine
+}
+
+/// Runs the program and checks it halts within the cycles.
+pub fn run(bytes: &[u8], max_cycles: u64) -> Machine {
+    let mut machine = machine(bytes, "");
+    assert_eq!(machine.run(max_cycles), StopReason::Halted);
+    machine
+}
+
+/// Assembles the source, runs it with the input and checks it halts within the cycles.
+pub fn run_source(source: &str, input: &str, max_cycles: u64) -> Machine {
+    let mut machine = machine(&assemble(source).bytes, input);
+    assert_eq!(machine.run(max_cycles), StopReason::Halted, "{}", source);
+    machine
+}
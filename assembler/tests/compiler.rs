@@ -0,0 +1,212 @@
+//! Compiles programs, assembles them and runs them in the emulator.
+
+mod common;
+
+use tower_assembler::asm::format::format_source;
+use tower_assembler::compiler::{compile_str, CompileOptions};
+use tower_assembler::emu::Machine;
+use tower_assembler::{AssemblerError, ZERO_PAGE_START};
+
+/// Results of the test programs are stored here.
+const RESULTS: usize = 0x5000;
+
+fn run(source: &str) -> Machine {
+    let code = compile_str(source, &CompileOptions::default()).unwrap();
+    common::run_source(&code, "", 1_000_000)
+}
+
+fn results(machine: &Machine, count: usize) -> &[u8] {
+    &machine.memory[RESULTS..RESULTS + count]
+}
+
+fn compile_error(source: &str) -> AssemblerError {
+    compile_str(source, &CompileOptions::default()).unwrap_err()
+}
+
+#[test]
+fn test_byte_and_word_arithmetic() {
+    let machine = run("
+        byte a at 0x5000;
+        byte b at 0x5001;
+        word c at 0x5002;
+        word d at 0x5004;
+        word e at 0x5006;
+
+        func main() {
+            byte x = 200;
+            a = x + 100;          // wraps at 8 bits
+            b = 3 - 5;
+            c = x;
+            c += 0x1F0;           // carries into the high byte
+            d = 0x1234 - c;
+            e = -c;
+        }
+    ");
+
+    assert_eq!(
+        results(&machine, 8),
+        &[44, 0xFE, 0x02, 0xB8, 0x0F, 0x7C, 0xFD, 0x48]
+    );
+}
+
+#[test]
+fn test_bitwise_operators_and_shifts() {
+    let machine = run("
+        byte and at 0x5000;
+        byte or at 0x5001;
+        byte xor at 0x5002;
+        byte not at 0x5003;
+        byte left at 0x5004;
+        byte right at 0x5005;
+        word wide_left at 0x5006;
+        word wide_right at 0x5008;
+
+        func main() {
+            byte x = 0b1100;
+            byte y = 0b1010;
+            and = x & y;
+            or = x | y;
+            xor = x ^ y;
+            not = ~x;
+            left = x << 2;
+            right = x >> 3;
+            word w = 0x81C3;
+            wide_left = w << 1;
+            wide_right = w >> 3;
+        }
+    ");
+
+    assert_eq!(
+        results(&machine, 10),
+        &[0b1000, 0b1110, 0b0110, 0xF3, 0b110000, 1, 0x03, 0x86, 0x10, 0x38]
+    );
+}
+
+#[test]
+fn test_comparisons_and_control_flow() {
+    let machine = run("
+        byte count at 0x5000;
+        word sum at 0x5001;
+        byte flags at 0x5003;
+
+        func main() {
+            word i = 0;
+            while (1) {
+                i += 1;
+                if (i > 300) {
+                    break;
+                }
+                if ((i & 1) == 1 || i == 2) {
+                    continue;
+                }
+                sum += i;
+                count += 1;
+            }
+            word big = 0x1234;
+            flags = (big < 0x1235) + ((big >= 0x1234) << 1) + ((big != 0x1234) << 2) + (!(count <= 148) << 3);
+        }
+    ");
+
+    // even numbers from 4 to 300
+    assert_eq!(results(&machine, 4), &[149, 0x58, 0x78, 0b1011]);
+}
+
+#[test]
+fn test_functions() {
+    let machine = run("
+        word result at 0x5000;
+        byte calls at 0x5002;
+
+        func add(word a, word b) -> word {
+            calls += 1;
+            return a + b;
+        }
+
+        func fib(byte n) -> word {
+            word a = 0;
+            word b = 1;
+            while (n != 0) {
+                word next = add(a, b);
+                a = b;
+                b = next;
+                n -= 1;
+            }
+            return a;
+        }
+
+        func main() {
+            result = add(fib(20), add(1, 2));
+        }
+    ");
+
+    // fib(20) = 6765 = 0x1A6D, plus 3
+    assert_eq!(results(&machine, 3), &[0x1A, 0x70, 22]);
+}
+
+#[test]
+fn test_variables_are_allocated_in_the_zero_page() {
+    let code = compile_str(
+        "word total; func main() { byte i = 7; total = i; }",
+        &CompileOptions {
+            zero_page: 0x100,
+            ..CompileOptions::default()
+        },
+    )
+    .unwrap();
+
+    assert!(code.contains("; 0x0100 total (word)"), "{}", code);
+    assert!(code.contains("; 0x0102 main.i (byte)"), "{}", code);
+    assert!(code.contains("STA &0x0101"), "{}", code);
+
+    let machine = run("word total; func main() { byte i = 7; total = i + 0x300; }");
+    let start = ZERO_PAGE_START as usize;
+    assert_eq!(&machine.memory[start..start + 3], &[0x03, 0x07, 0x07]);
+}
+
+#[test]
+fn test_generated_code_is_formatted() {
+    let code = compile_str(
+        "func main() { byte x = 3; while (x != 0) { x -= 1; } }",
+        &CompileOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(format_source(&code), code);
+}
+
+#[test]
+fn test_compile_errors() {
+    let cases = [
+        ("func main() { x = 1; }", "Variable 'x' is not defined."),
+        ("func main() { f(); }", "Function 'f' is not defined."),
+        (
+            "func f() {} func main() { byte x = f(); }",
+            "Function 'f' does not return a value.",
+        ),
+        (
+            "func f(byte a) {} func main() { f(); }",
+            "Wrong number of arguments for function 'f'",
+        ),
+        ("func main() { break; }", "can only be used in a loop"),
+        (
+            "func f() { g(); } func g() { f(); } func main() { f(); }",
+            "called recursively",
+        ),
+        ("func f() {}", "Missing function 'main'."),
+        (
+            "func main() { byte x = 3; x = x << x; }",
+            "has to be a constant",
+        ),
+        (
+            "func main() { byte x = 3 * 4; }",
+            "no multiplication or division",
+        ),
+        ("func main() { byte x = 0x10000; }", "0x10000"),
+        ("func main() { byte x = 1 }", "Expected ';'"),
+    ];
+
+    for (source, message) in cases {
+        let error = compile_error(source).to_string();
+        assert!(error.contains(message), "{}: {}", source, error);
+    }
+}
@@ -0,0 +1,129 @@
+//! Reports which opcode, instruction mode and flag combinations microcode leaves undefined.
+
+use tower_assembler::isa::isa;
+use tower_assembler::microasm::asm::{assemble_str, parse_source, MicroassembleOptions};
+use tower_assembler::microasm::coverage::{coverage, coverage_report, InstructionCoverage};
+use tower_assembler::{IM_ABSOLUTE, IM_CONSTANT, IM_IMMEDIATE, IM_IMPLIED};
+
+fn report(source: &str) -> Vec<InstructionCoverage> {
+    let instruction_defs = parse_source(source, &MicroassembleOptions::default())
+        .unwrap_or_else(|e| panic!("{}\n{}", e, source));
+    coverage(&instruction_defs)
+}
+
+fn find<'a>(report: &'a [InstructionCoverage], name: &str) -> &'a InstructionCoverage {
+    report.iter().find(|i| i.name == name).unwrap()
+}
+
+#[test]
+fn test_every_instruction_is_reported() {
+    let report = report("#def NOP\n\tIEND\n");
+
+    // in opcode order, with every allowed mode
+    assert_eq!(report.len(), isa().instructions.len());
+    assert!(report.windows(2).all(|w| w[0].opcode < w[1].opcode));
+    for ins in &report {
+        let spec = isa()
+            .instructions
+            .iter()
+            .find(|i| i.name == ins.name)
+            .unwrap();
+        let modes = ins.modes.iter().fold(0, |m, c| m | c.instruction_mode);
+        assert_eq!(modes, spec.modes, "{}", ins.name);
+    }
+
+    let nop = find(&report, "NOP");
+    assert!(nop.is_complete());
+    assert_eq!(
+        nop.modes[0].defined_flags,
+        (0..isa().flag_combinations() as u32).collect::<Vec<u32>>()
+    );
+    assert_eq!(nop.to_string(), "NOP (opcode 0x00)\n    ✔️  Implied\n");
+}
+
+#[test]
+fn test_undefined_modes() {
+    let report = report("#def LDA\nimm:\n\tPCO MO AI\n\tPCI IEND\n");
+    let lda = find(&report, "LDA");
+    assert!(!lda.is_complete());
+
+    let imm = lda
+        .modes
+        .iter()
+        .find(|m| m.instruction_mode == IM_IMMEDIATE)
+        .unwrap();
+    assert!(imm.is_complete());
+
+    let abs = lda
+        .modes
+        .iter()
+        .find(|m| m.instruction_mode == IM_ABSOLUTE)
+        .unwrap();
+    assert!(abs.defined_flags.is_empty() && !abs.is_complete());
+    assert!(lda.to_string().contains("    ❌ Absolute: not defined\n"));
+
+    // nothing was defined at all
+    assert!(find(&report, "STA")
+        .modes
+        .iter()
+        .all(|m| m.defined_flags.is_empty()));
+}
+
+#[test]
+fn test_missing_and_unterminated_flag_combinations() {
+    let zero = isa().flag_value("zero").unwrap();
+
+    // nothing is defined for the flag combinations without ZERO
+    let missing = report("#def NOP\n\t#if zero\n\t\tIEND\n\t#end\n");
+    let nop = find(&missing, "NOP");
+    assert!(!nop.is_complete());
+    assert_eq!(
+        nop.modes[0].defined_flags.len(),
+        isa().flag_combinations() / 2
+    );
+    assert!(nop.modes[0].defined_flags.iter().all(|f| f & zero != 0));
+    assert!(nop.to_string().contains(
+        "    ❌ Implied: missing flag combinations: none, WRAP, INCWRAP, WRAP|INCWRAP\n"
+    ));
+
+    // they run off the end of their steps
+    let unterminated = report("#def NOP\n\tPCI\n\t#if zero\n\t\tIEND\n\t#end\n");
+    let nop = find(&unterminated, "NOP");
+    assert!(!nop.is_complete());
+    assert_eq!(nop.modes[0].defined_flags.len(), isa().flag_combinations());
+    let flags = &nop.modes[0].unterminated_flags;
+    assert_eq!(flags.len(), isa().flag_combinations() / 2);
+    assert!(flags.iter().all(|f| f & zero == 0));
+    assert!(nop.to_string().contains(
+        "    ❌ Implied: no IEND or HLT for flag combinations: none, WRAP, INCWRAP, WRAP|INCWRAP\n"
+    ));
+}
+
+#[test]
+fn test_disallowed_modes() {
+    // the microassembler refuses modes the instruction does not take, a ROM can still contain them
+    let source = "#def NOP\n\tIEND\n";
+    let mut rom = assemble_str(source, &MicroassembleOptions::default())
+        .unwrap()
+        .bytes;
+    let address = &isa().address;
+    let mode_len = (1 << (address.flag_bits + address.step_bits)) * isa().control_bytes();
+    let implied = rom[..mode_len].to_vec();
+    let constant = IM_CONSTANT.trailing_zeros() as usize * mode_len;
+    rom.splice(constant..constant + mode_len, implied);
+
+    let path = std::env::temp_dir().join("coverage_disallowed_modes.bin");
+    std::fs::write(&path, &rom).unwrap();
+    let report = coverage_report(path.to_str().unwrap(), true).unwrap();
+
+    // NOP only takes the implied mode
+    let nop = find(&report, "NOP");
+    assert_eq!(nop.modes.len(), 1);
+    assert_eq!(nop.modes[0].instruction_mode, IM_IMPLIED);
+    assert!(nop.modes[0].is_complete());
+    assert_eq!(nop.disallowed_modes, vec![IM_CONSTANT]);
+    assert!(!nop.is_complete());
+    assert!(nop
+        .to_string()
+        .contains("    ❌ Constant: defined, but not allowed by the assembler\n"));
+}
@@ -0,0 +1,103 @@
+//! Checks the kinds of errors, their codes and the exit codes of the binaries.
+
+use std::error::Error;
+use std::process::Command;
+
+use tower_assembler::asm::asm::assembler;
+use tower_assembler::isa::Isa;
+use tower_assembler::microasm::asm::{assemble_str, MicroassembleOptions};
+use tower_assembler::{read_file, AssemblerError};
+
+/// Assembles the source from a file, the way the binaries do.
+fn assemble_error(name: &str, source: &str) -> AssemblerError {
+    let dir = std::env::temp_dir();
+    let source_path = dir.join(format!("{}.asm", name));
+    let out_path = dir.join(format!("{}.bin", name));
+    std::fs::write(&source_path, source).unwrap();
+
+    assembler(
+        source_path.to_str().unwrap(),
+        out_path.to_str().unwrap(),
+        false,
+    )
+    .unwrap_err()
+}
+
+#[test]
+fn test_errors_have_stable_codes() {
+    let io = read_file("/nonexistent/tower.asm").unwrap_err();
+    assert!(matches!(io, AssemblerError::Io { .. }));
+    assert_eq!((io.code(), io.exit_code()), ("E0001", 74));
+    assert!(io.syntax_error().is_none());
+    assert!(io.source().is_some());
+    assert!(io.to_string().starts_with("'/nonexistent/tower.asm': "));
+
+    let lex = assemble_str("; only a comment\n", &MicroassembleOptions::default()).unwrap_err();
+    assert!(matches!(lex, AssemblerError::Lex(_)));
+    assert_eq!((lex.code(), lex.exit_code()), ("E0002", 65));
+
+    let parse = Isa::from_toml("flags = 3").unwrap_err();
+    assert!(matches!(parse, AssemblerError::Parse(_)));
+    assert_eq!((parse.code(), parse.exit_code()), ("E0003", 65));
+
+    let semantic = assemble_error("tower_errors_semantic", "\tJMP _nowhere\n");
+    assert!(matches!(semantic, AssemblerError::Semantic(_)));
+    assert_eq!((semantic.code(), semantic.exit_code()), ("E0004", 65));
+
+    let range = assemble_error("tower_errors_range", "\tLDA #0x100\n");
+    assert!(matches!(range, AssemblerError::Range(_)));
+    assert_eq!((range.code(), range.exit_code()), ("E0005", 65));
+    assert!(range.source().is_none());
+
+    // errors about the code point at the line they are on
+    let serr = range.syntax_error().unwrap();
+    assert_eq!(serr.line, 1);
+    assert!(serr
+        .file
+        .as_ref()
+        .unwrap()
+        .ends_with("tower_errors_range.asm"));
+    assert_eq!(
+        range.to_string(),
+        format!(
+            "{}:1: Value 0x100 does not fit into 1 byte(s).",
+            serr.file.as_ref().unwrap()
+        )
+    );
+}
+
+#[test]
+fn test_binaries_exit_with_the_error() {
+    let dir = std::env::temp_dir();
+    let source_path = dir.join("tower_errors_exit.asm");
+    let out_path = dir.join("tower_errors_exit.bin");
+    std::fs::write(&source_path, "\tJMP _nowhere\n").unwrap();
+
+    let assemble = |input: &str| {
+        Command::new(env!("CARGO_BIN_EXE_assembler"))
+            .args(["-i", input, "-o", out_path.to_str().unwrap(), "assemble"])
+            .output()
+            .unwrap()
+    };
+
+    let output = assemble(source_path.to_str().unwrap());
+    assert_eq!(output.status.code(), Some(65));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Error [E0004]"), "{}", stderr);
+
+    let output = assemble("/nonexistent/tower.asm");
+    assert_eq!(output.status.code(), Some(74));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Error [E0001]"), "{}", stderr);
+
+    // an included file which cannot be read is an I/O error as well
+    std::fs::write(&source_path, "#include tower_errors_missing.asm\n\tHLT\n").unwrap();
+    let output = assemble(source_path.to_str().unwrap());
+    assert_eq!(output.status.code(), Some(74));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Error [E0001]"), "{}", stderr);
+    assert!(stderr.contains("tower_errors_missing.asm"), "{}", stderr);
+
+    std::fs::write(&source_path, "\tHLT\n").unwrap();
+    assert!(assemble(source_path.to_str().unwrap()).status.success());
+}
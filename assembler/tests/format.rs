@@ -0,0 +1,106 @@
+//! Checks the formatters and that the sources under `software` are formatted.
+
+use std::path::Path;
+use std::process::Command;
+
+use tower_assembler::{asm, microasm};
+
+fn root() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap()
+}
+
+fn sources(dir: &str) -> Vec<(String, String)> {
+    let mut files: Vec<(String, String)> = std::fs::read_dir(root().join(dir))
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "asm"))
+        .map(|p| {
+            let source = std::fs::read_to_string(&p).unwrap();
+            (p.display().to_string(), source)
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn test_software_is_formatted() {
+    for dir in ["software/programs", "software/tests"] {
+        for (path, source) in sources(dir) {
+            assert_eq!(asm::format::format_source(&source), source, "{}", path);
+        }
+    }
+    for (path, source) in sources("software/microcode") {
+        assert_eq!(microasm::format::format_source(&source), source, "{}", path);
+    }
+}
+
+#[test]
+fn test_formats_program() {
+    let source = "  ; setup\nlda #1,&0xFE00 ;a\n  sta   &0xFE00; b\n\n\n\n_loop:\n    _inner:\n  jmp _loop\nhlt;done";
+    let expected = "\
+; setup
+LDA #1, &0xFE00 ;a
+STA &0xFE00     ; b
+
+
+_loop:
+	_inner:
+		JMP _loop
+		HLT ;done
+";
+    let formatted = asm::format::format_source(source);
+    assert_eq!(formatted, expected);
+    assert_eq!(asm::format::format_source(&formatted), formatted);
+}
+
+#[test]
+fn test_formats_microcode() {
+    let source = "#def lda\n    imm:\npco mo,ai\n  #IF !Zero ; not zero\n pci\n    #else\nai\n#end";
+    let expected = "\
+#def LDA
+imm:
+	PCO MO AI
+	#if !zero ; not zero
+		PCI
+	#else
+		AI
+	#end
+";
+    let formatted = microasm::format::format_source(source);
+    assert_eq!(formatted, expected);
+    assert_eq!(microasm::format::format_source(&formatted), formatted);
+}
+
+#[test]
+fn test_check_exits_with_1() {
+    let path = std::env::temp_dir().join("tower_format_check.asm");
+    let fmt = |check: bool| {
+        let mut args = vec!["-i", path.to_str().unwrap(), "fmt"];
+        if check {
+            args.push("--check");
+        }
+        Command::new(env!("CARGO_BIN_EXE_assembler"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    std::fs::write(&path, "_start:\n\tHLT\n  LDA #1\n").unwrap();
+    let output = fmt(true);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("tower_format_check.asm:3: The file is not formatted."),
+        "{}",
+        stderr
+    );
+    assert!(!stderr.contains("Error ["), "{}", stderr);
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "_start:\n\tHLT\n  LDA #1\n"
+    );
+
+    assert!(fmt(false).status.success());
+    assert!(fmt(true).status.success());
+}
@@ -0,0 +1,129 @@
+//! Assembles the generated Forth kernel and talks to it in the emulator.
+
+mod common;
+
+use tower_assembler::asm::asm::{assemble_str, AssembleOptions};
+use tower_assembler::asm::format::format_source;
+use tower_assembler::emu::{StopReason, RAM_START};
+use tower_assembler::forth::kernel;
+
+fn run(input: &str) -> String {
+    let program = assemble_str(&kernel(), &AssembleOptions::default()).unwrap();
+
+    let mut machine = common::machine(&program.bytes, input);
+    let reason = machine.run(20_000_000);
+    let output = machine.tty_output();
+    assert_eq!(reason, StopReason::Halted, "{}", output);
+    output
+}
+
+/// Returns what was printed for every input line, without the banner and the echo of the line.
+fn session(lines: &[&str]) -> Vec<String> {
+    let input: String = lines.iter().map(|l| format!("{}\n", l)).collect();
+    let output = run(&format!("{}BYE\n", input));
+
+    output
+        .lines()
+        .skip(1)
+        .zip(lines)
+        .map(|(out, line)| out.strip_prefix(line).unwrap_or(out).trim().to_string())
+        .collect()
+}
+
+#[test]
+fn test_kernel_fits_in_rom() {
+    let program = assemble_str(&kernel(), &AssembleOptions::default()).unwrap();
+    assert!(
+        program.bytes.len() < RAM_START as usize,
+        "{} bytes",
+        program.bytes.len()
+    );
+}
+
+#[test]
+fn test_kernel_is_formatted() {
+    let code = kernel();
+    assert_eq!(format_source(&code), code);
+}
+
+#[test]
+fn test_arithmetic() {
+    assert_eq!(
+        session(&[
+            "2 3 + .",
+            "-5 3 + . 100 -7 - .",
+            "12 10 AND . 12 10 OR . 12 10 XOR . 0 INVERT .",
+            "-20 2/ . 6 2* . 300 200 * .",
+            "1 2 < . 2 1 < . -1 1 < . -1 1 U< . 3 3 = . 0 0= .",
+            "32767 . -32768 .",
+        ]),
+        [
+            "5  ok",
+            "-2 107  ok",
+            "8 14 6 -1  ok",
+            "-10 12 -5536  ok",
+            "-1 0 -1 0 -1 -1  ok",
+            "32767 -32768  ok",
+        ]
+    );
+}
+
+#[test]
+fn test_stack_words() {
+    assert_eq!(
+        session(&[
+            "1 2 3 ROT . . .",
+            "1 2 OVER . . .",
+            "1 2 SWAP . .",
+            "5 >R R@ R> + .",
+        ]),
+        ["1 3 2  ok", "1 2 1  ok", "1 2  ok", "10  ok"]
+    );
+}
+
+#[test]
+fn test_colon_definitions() {
+    assert_eq!(
+        session(&[
+            ": SQ DUP * ;",
+            "7 sq .",
+            ": FACT DUP 1 > IF DUP 1- FACT * ELSE DROP 1 THEN ;",
+            "7 FACT .",
+            ": COUNT 0 BEGIN DUP . 1+ DUP 5 = UNTIL DROP ;",
+            "COUNT",
+            ": DOWN BEGIN DUP WHILE DUP . 1- REPEAT DROP ; 3 DOWN",
+            "' SQ 9 SWAP EXECUTE .",
+        ]),
+        [
+            "ok",
+            "49  ok",
+            "ok",
+            "5040  ok",
+            "ok",
+            "0 1 2 3 4  ok",
+            "3 2 1  ok",
+            "81  ok",
+        ]
+    );
+}
+
+#[test]
+fn test_variables_constants_and_strings() {
+    assert_eq!(
+        session(&[
+            "VARIABLE V 42 V ! V @ .",
+            "10 CONSTANT TEN TEN TEN * .",
+            ": HI .\" Hello, world\" ; HI",
+            ".\" direct\" ( comment ) 1 . \\ the rest is ignored",
+        ]),
+        ["42  ok", "100  ok", "Hello, world ok", "direct1  ok"]
+    );
+}
+
+#[test]
+fn test_errors_abort_the_line() {
+    assert_eq!(
+        session(&["1 FOO 2 .", ".", "7 ."]),
+        ["FOO ?", "stack empty", "7  ok"]
+    );
+}
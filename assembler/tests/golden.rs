@@ -0,0 +1,245 @@
+//! Golden file tests: assembles every `.asm` file under `software/` and compares the output with the `.bin` file next to it,
+//! and the microcode with `circuit/microcode.bin`.
+//!
+//! Run `cargo test --test golden -- --bless` to write the current output to the golden files instead.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use tower_assembler::asm::asm::{assemble_str, AssembleOptions, SourceMapEntry};
+use tower_assembler::asm::loader::FileLoader;
+use tower_assembler::isa::isa;
+use tower_assembler::microasm;
+use tower_assembler::microasm::asm::MicroassembleOptions;
+
+/// Directories holding programs with their golden files, relative to the repository root.
+const PROGRAM_DIRS: [&str; 2] = ["software/programs", "software/tests"];
+const MICROCODE_SOURCE: &str = "software/microcode/microcode.asm";
+const MICROCODE_GOLDEN: &str = "circuit/microcode.bin";
+
+/// Differences printed per file before the rest is only counted.
+const MAX_REPORTED_DIFFS: usize = 16;
+
+enum Outcome {
+    Passed,
+    Blessed,
+    Failed(String),
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let bless = args.iter().any(|a| a == "--bless");
+    // a positional argument filters the checked files, like the default test harness does
+    let filter = args.iter().find(|a| !a.starts_with('-'));
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+
+    let mut cases: Vec<(String, Outcome)> = Vec::new();
+    for dir in PROGRAM_DIRS {
+        for source in sources_in(&root.join(dir)) {
+            let name = format!("{}/{}", dir, source.file_name().unwrap().to_string_lossy());
+            if filter.is_some_and(|f| !name.contains(f.as_str())) {
+                continue;
+            }
+            let outcome = check_program(&source, bless);
+            cases.push((name, outcome));
+        }
+    }
+    if filter.is_none_or(|f| MICROCODE_SOURCE.contains(f.as_str())) {
+        let outcome = check_microcode(
+            &root.join(MICROCODE_SOURCE),
+            &root.join(MICROCODE_GOLDEN),
+            bless,
+        );
+        cases.push((String::from(MICROCODE_SOURCE), outcome));
+    }
+
+    let mut failed = 0;
+    for (name, outcome) in &cases {
+        match outcome {
+            Outcome::Passed => println!("golden {} ... ok", name),
+            Outcome::Blessed => println!("golden {} ... blessed", name),
+            Outcome::Failed(report) => {
+                failed += 1;
+                println!("golden {} ... FAILED\n{}", name, report);
+            }
+        }
+    }
+
+    println!(
+        "\ngolden result: {}. {} checked, {} failed",
+        if failed == 0 { "ok" } else { "FAILED" },
+        cases.len(),
+        failed
+    );
+    if failed > 0 {
+        println!("If the changes are intended, run `cargo test --test golden -- --bless`.");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Returns all `.asm` files of a directory, sorted by name.
+fn sources_in(dir: &Path) -> Vec<PathBuf> {
+    let mut sources: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap_or_else(|e| panic!("cannot read '{}': {}", dir.display(), e))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "asm"))
+        .collect();
+    sources.sort();
+    sources
+}
+
+fn check_program(source: &Path, bless: bool) -> Outcome {
+    let golden = source.with_extension("bin");
+
+    let code = fs::read_to_string(source).unwrap();
+    let loader = FileLoader;
+    let options = AssembleOptions {
+        file_name: source.to_string_lossy().to_string(),
+        loader: Some(&loader),
+        ..Default::default()
+    };
+
+    let program = match assemble_str(&code, &options) {
+        Ok(program) => program,
+        Err(diagnostics) => return Outcome::Failed(indent(&diagnostics.to_string())),
+    };
+
+    compare(&golden, &program.bytes, bless, |address| {
+        attribute_program(&program.source_map, address)
+    })
+}
+
+fn check_microcode(source: &Path, golden: &Path, bless: bool) -> Outcome {
+    let code = fs::read_to_string(source).unwrap();
+    let options = MicroassembleOptions {
+        file_name: source.to_string_lossy().to_string(),
+        loader: Some(&FileLoader),
+        ..Default::default()
+    };
+
+    match microasm::asm::assemble_str(&code, &options) {
+        Ok(microcode) => compare(golden, &microcode.bytes, bless, attribute_microcode),
+        Err(e) => Outcome::Failed(indent(&e.to_string())),
+    }
+}
+
+/// Compares the output with the golden file, or overwrites the golden file when blessing.
+/// `attribute` describes where the byte at an address comes from.
+fn compare(
+    golden: &Path,
+    actual: &[u8],
+    bless: bool,
+    attribute: impl Fn(usize) -> String,
+) -> Outcome {
+    let expected = fs::read(golden).ok();
+
+    if expected.as_deref() == Some(actual) {
+        return Outcome::Passed;
+    }
+    if bless {
+        fs::write(golden, actual).unwrap();
+        return Outcome::Blessed;
+    }
+    let Some(expected) = expected else {
+        return Outcome::Failed(format!("  missing golden file '{}'", golden.display()));
+    };
+
+    let mut report = String::new();
+    if expected.len() != actual.len() {
+        report += &format!(
+            "  size differs: expected {} bytes, got {} bytes\n",
+            expected.len(),
+            actual.len()
+        );
+    }
+
+    let diffs: Vec<usize> = (0..expected.len().max(actual.len()))
+        .filter(|&i| expected.get(i) != actual.get(i))
+        .collect();
+    for &address in diffs.iter().take(MAX_REPORTED_DIFFS) {
+        report += &format!(
+            "  0x{:04x}: expected {}, got {}  {}\n",
+            address,
+            hex_byte(expected.get(address)),
+            hex_byte(actual.get(address)),
+            attribute(address)
+        );
+    }
+    if diffs.len() > MAX_REPORTED_DIFFS {
+        report += &format!(
+            "  ... and {} more differing byte(s)\n",
+            diffs.len() - MAX_REPORTED_DIFFS
+        );
+    }
+    Outcome::Failed(report.trim_end().to_string())
+}
+
+fn hex_byte(byte: Option<&u8>) -> String {
+    match byte {
+        Some(b) => format!("{:02x}", b),
+        None => String::from("--"),
+    }
+}
+
+/// Names the source line of the instruction which produced the byte at `address`.
+fn attribute_program(source_map: &[SourceMapEntry], address: usize) -> String {
+    let entry = source_map.iter().find(|e| {
+        let start = e.address as usize;
+        (start..start + e.size as usize).contains(&address)
+    });
+
+    match entry {
+        Some(e) => {
+            let line = fs::read_to_string(&e.file)
+                .ok()
+                .and_then(|code| code.lines().nth(e.line as usize - 1).map(str::to_string))
+                .unwrap_or_default();
+            format!("({}:{}: {})", file_name(&e.file), e.line, line.trim())
+        }
+        None => String::from("(not produced by any instruction)"),
+    }
+}
+
+/// Decodes the microcode ROM address the byte at `address` belongs to.
+fn attribute_microcode(address: usize) -> String {
+    let isa = isa();
+    let fields = &isa.address;
+    let word = address / isa.control_bytes();
+
+    let field = |shift: u32, bits: u32| (word >> shift) & ((1 << bits) - 1);
+    let step = field(0, fields.step_bits);
+    let flags = field(fields.step_bits, fields.flag_bits);
+    let mode = field(fields.step_bits + fields.flag_bits, fields.mode_bits);
+    let opcode = word >> (fields.step_bits + fields.flag_bits + fields.mode_bits);
+
+    let name = isa
+        .instruction_by_opcode(opcode as u32)
+        .map_or(format!("opcode 0x{:02x}", opcode), |ins| ins.name.clone());
+    let mode = tower_assembler::get_im_name(mode as u32)
+        .map_or(format!("mode {}", mode), |name| name.to_string());
+
+    format!(
+        "({} {}, flags {:0width$b}, step {})",
+        name,
+        mode,
+        flags,
+        step,
+        width = fields.flag_bits as usize
+    )
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map_or(path.to_string(), |n| n.to_string_lossy().to_string())
+}
+
+fn indent(text: &str) -> String {
+    text.lines()
+        .map(|l| format!("  {}", l))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -0,0 +1,133 @@
+//! Writes the control-flow graph and the call graph of programs and checks the DOT output.
+
+use tower_assembler::asm::graph::grapher;
+
+/// Returns the control-flow graph and the call graph of the source.
+fn graphs(name: &str, source: &str) -> (String, String) {
+    let dir = std::env::temp_dir();
+    let source_path = dir.join(format!("{}.asm", name));
+    let cfg_path = dir.join(format!("{}.dot", name));
+    let calls_path = dir.join(format!("{}_calls.dot", name));
+    std::fs::write(&source_path, source).unwrap();
+
+    grapher(
+        source_path.to_str().unwrap(),
+        cfg_path.to_str().unwrap(),
+        Some(calls_path.to_str().unwrap()),
+    )
+    .unwrap();
+
+    (
+        std::fs::read_to_string(&cfg_path).unwrap(),
+        std::fs::read_to_string(&calls_path).unwrap(),
+    )
+}
+
+#[test]
+fn test_control_flow_graph() {
+    let (cfg, _) = graphs(
+        "tower_graph_cfg",
+        "\tLDA #3 ; counter
+_loop:
+\tSUB #1
+\tJNZ _loop
+\tJSR _print
+\tHLT
+\tLDA #7 ; \"x\"
+_print:
+\tJW _wrapped
+\tRTS
+_wrapped:
+\tRTS
+",
+    );
+
+    assert_eq!(
+        cfg,
+        r##"digraph cfg {
+	node [shape=box, fontname="monospace"];
+	b0 [label="   1  LDA #3 ; counter\l"];
+	b1 [label="_loop:\l   3  SUB #1\l   4  JNZ _loop\l"];
+	b2 [label="   5  JSR _print\l"];
+	b3 [label="   6  HLT\l"];
+	b4 [label="   7  LDA #7 ; \"x\"\l", style=filled, fillcolor="#f4cccc", xlabel="unreachable"];
+	b5 [label="_print:\l   9  JW _wrapped\l"];
+	b6 [label="  10  RTS\l"];
+	b7 [label="_wrapped:\l  12  RTS\l"];
+	b0 -> b1;
+	b1 -> b1 [label="!ZERO"];
+	b1 -> b2 [label="ZERO"];
+	b2 -> b5 [label="call", style=dashed];
+	b2 -> b3 [label="after _print"];
+	b4 -> b5;
+	b5 -> b7 [label="WRAP"];
+	b5 -> b6 [label="!WRAP"];
+}
+"##
+    );
+}
+
+#[test]
+fn test_expansions_are_listed_once() {
+    let (cfg, calls) = graphs(
+        "tower_graph_expansion",
+        "\tINC16 *0x5000\n\tJSR _add16\n\tJSR @0x5000\n\tHLT\n#include <math16>\n",
+    );
+
+    // the pseudo-instruction expands to 8 instructions on one line
+    assert!(
+        cfg.contains("b0 [label=\"   1  INC16 *0x5000\\l   2  JSR _add16\\l\"];"),
+        "{}",
+        cfg
+    );
+    // lines of the standard library are read from the bundled files
+    assert!(cfg.contains("_add16:\\l"), "{}", cfg);
+    assert!(
+        cfg.contains("\tb1 -> b2 [label=\"after call\"];\n"),
+        "{}",
+        cfg
+    );
+
+    assert!(
+        calls.contains("\tr0 [label=\"(start)\\nstack: unknown\"];\n"),
+        "{}",
+        calls
+    );
+    assert!(
+        calls.contains("\tr1 [label=\"_add16\\nstack: 0 bytes\"];\n"),
+        "{}",
+        calls
+    );
+    assert!(
+        calls.contains("\tr0 -> r1;\n\tr0 -> indirect [style=dashed];\n"),
+        "{}",
+        calls
+    );
+    assert!(
+        calls.contains("\tindirect [label=\"(indirect)\", style=dashed];\n"),
+        "{}",
+        calls
+    );
+}
+
+#[test]
+fn test_call_graph() {
+    let (_, calls) = graphs(
+        "tower_graph_calls",
+        "JSR _a\nJSR _b\nHLT\n_a:\nJSR _b\nJSR _b\nRTS\n_b:\nPSA\nPOA\nRTS\n",
+    );
+
+    assert_eq!(
+        calls,
+        r#"digraph calls {
+	node [shape=box, fontname="monospace"];
+	r0 [label="(start)\nstack: 5 bytes"];
+	r1 [label="_a\nstack: 3 bytes"];
+	r2 [label="_b\nstack: 1 bytes"];
+	r0 -> r1;
+	r0 -> r2;
+	r1 -> r2;
+}
+"#
+    );
+}
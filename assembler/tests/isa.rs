@@ -0,0 +1,228 @@
+//! Loads ISA descriptions and checks invalid ones are refused.
+
+use std::process::Command;
+
+use tower_assembler::isa::{isa, Isa};
+use tower_assembler::{AssemblerError, IM_ABSOLUTE, IM_IMMEDIATE};
+
+/// Returns the message of the error `validate` finds after changing the Tower ISA.
+fn invalid(change: impl FnOnce(&mut Isa)) -> String {
+    let mut isa = Isa::tower();
+    change(&mut isa);
+    match isa.validate().unwrap_err() {
+        AssemblerError::Semantic(serr) => serr.message,
+        e => panic!("unexpected error: {:?}", e),
+    }
+}
+
+#[test]
+fn test_tower_isa_is_the_default() {
+    let tower = Isa::tower();
+    assert!(tower.validate().is_ok());
+    assert!(*isa() == tower);
+
+    let lda = tower.instruction_by_name("lda").unwrap();
+    assert_eq!(lda.opcode, 0x01);
+    assert_eq!(
+        lda.modes & (IM_IMMEDIATE | IM_ABSOLUTE),
+        IM_IMMEDIATE | IM_ABSOLUTE
+    );
+    assert_eq!(tower.instruction_by_opcode(0x01), Some(lda));
+    assert_eq!(
+        tower.signal_name(tower.signal_bit("PCO").unwrap()),
+        Some("PCO")
+    );
+    assert_eq!(tower.flag_value("zero"), Some(0b010));
+}
+
+#[test]
+fn test_descriptions_are_read() {
+    let description = r#"
+flags = ["CARRY"]
+
+[address]
+opcode_bits = 2
+mode_bits = 3
+flag_bits = 1
+step_bits = 2
+
+[[instructions]]
+name = "LDA"
+opcode = 0x01
+modes = ["immediate", "absolute"]
+
+[[signals]]
+name = "AI"
+bit = 9
+"#;
+    let isa = Isa::from_toml(description).unwrap();
+    assert_eq!(isa.instructions[0].modes, IM_IMMEDIATE | IM_ABSOLUTE);
+    assert_eq!(isa.control_bytes(), 2);
+    assert_eq!(isa.rom_size(), 1 << 8);
+
+    let json = r#"{
+        "flags": ["CARRY"],
+        "address": { "opcode_bits": 2, "mode_bits": 3, "flag_bits": 1, "step_bits": 2 },
+        "instructions": [{ "name": "LDA", "opcode": 1, "modes": ["immediate", "absolute"] }],
+        "signals": [{ "name": "AI", "bit": 9 }]
+    }"#;
+    assert!(Isa::from_json(json).unwrap() == isa);
+
+    // descriptions are validated
+    let e = Isa::from_toml(&description.replace("opcode = 0x01", "opcode = 0x04")).unwrap_err();
+    assert!(matches!(e, AssemblerError::Semantic(_)));
+    let e = Isa::from_toml(&description.replace("\"absolute\"", "\"relative\"")).unwrap_err();
+    assert!(
+        e.to_string()
+            .contains("unknown instruction mode 'relative'"),
+        "{}",
+        e
+    );
+}
+
+#[test]
+fn test_duplicates_are_refused() {
+    let first = &isa().signals[0];
+    assert_eq!(
+        invalid(|isa| {
+            let mut copy = isa.instructions[1].clone();
+            copy.name = copy.name.to_lowercase();
+            copy.opcode = 0x1F;
+            isa.instructions.push(copy);
+        }),
+        "Instruction 'lda' is defined more than once."
+    );
+    assert_eq!(
+        invalid(|isa| {
+            let mut copy = isa.instructions[1].clone();
+            copy.name = String::from("LDX");
+            isa.instructions.push(copy);
+        }),
+        "Opcode 0x01 is used by both 'LDA' and 'LDX'."
+    );
+    assert_eq!(
+        invalid(|isa| {
+            let mut copy = isa.signals[0].clone();
+            copy.bit = 63;
+            isa.signals.push(copy);
+        }),
+        format!("Control signal '{}' is defined more than once.", first.name)
+    );
+    assert_eq!(
+        invalid(|isa| {
+            let mut copy = isa.signals[0].clone();
+            copy.name = String::from("XO");
+            isa.signals.push(copy);
+        }),
+        format!(
+            "Bit {} is used by both '{}' and 'XO'.",
+            first.bit, first.name
+        )
+    );
+    assert_eq!(
+        invalid(|isa| isa.flags[2] = String::from("zero")),
+        "Flag 'zero' is defined more than once."
+    );
+}
+
+#[test]
+fn test_overflowing_fields_are_refused() {
+    assert_eq!(
+        invalid(|isa| isa.instructions[1].opcode = 0x20),
+        "Opcode 0x20 of 'LDA' does not fit into 5 opcode bits."
+    );
+    assert_eq!(
+        invalid(|isa| isa.address.opcode_bits = 4),
+        format!(
+            "Opcode 0x10 of '{}' does not fit into 4 opcode bits.",
+            isa().instruction_by_opcode(0x10).unwrap().name
+        )
+    );
+    assert_eq!(
+        invalid(|isa| isa.address.mode_bits = 2),
+        "2 mode bits cannot hold all 7 instruction modes."
+    );
+    assert_eq!(
+        invalid(|isa| isa.address.mode_bits = 4),
+        "5 opcode bits and 4 mode bits do not fit into the 8-bit instruction byte."
+    );
+    assert_eq!(
+        invalid(|isa| isa.address.step_bits = 0),
+        "At least one step bit is required."
+    );
+    assert_eq!(
+        invalid(|isa| isa.address.step_bits = 14),
+        "The microcode ROM address is 25 bits wide, at most 24 bits are supported."
+    );
+    assert_eq!(
+        invalid(|isa| isa.flags.push(String::from("CARRY"))),
+        "4 flags do not fit into 3 flag bits."
+    );
+    assert_eq!(
+        invalid(|isa| isa.signals[0].bit = 64),
+        format!(
+            "Bit 64 of '{}' does not fit into the control word, at most 64 bits are supported.",
+            isa().signals[0].name
+        )
+    );
+    assert_eq!(
+        invalid(|isa| isa.instructions[1].modes = 0),
+        "Instruction 'LDA' has no instruction modes."
+    );
+}
+
+#[test]
+fn test_instruction_byte_follows_the_address_fields() {
+    let dir = std::env::temp_dir();
+    let isa_path = dir.join("tower_isa_fields.toml");
+    let source_path = dir.join("tower_isa_fields.asm");
+    let out_path = dir.join("tower_isa_fields.bin");
+    std::fs::write(
+        &isa_path,
+        r#"
+flags = ["WRAP"]
+
+[address]
+opcode_bits = 4
+mode_bits = 4
+flag_bits = 1
+step_bits = 4
+
+[[instructions]]
+name = "LDA"
+opcode = 0x0c
+modes = ["immediate"]
+
+[[instructions]]
+name = "HLT"
+opcode = 0x0d
+modes = ["implied"]
+
+[[signals]]
+name = "HLT"
+bit = 0
+"#,
+    )
+    .unwrap();
+    std::fs::write(&source_path, "\tLDA #7\n\tHLT\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_assembler"))
+        .args([
+            "--isa",
+            isa_path.to_str().unwrap(),
+            "-i",
+            source_path.to_str().unwrap(),
+            "-o",
+            out_path.to_str().unwrap(),
+            "assemble",
+        ])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // the mode takes the lowest 4 bits: immediate is mode 1, implied mode 0
+    assert_eq!(std::fs::read(&out_path).unwrap(), vec![0xc1, 0x07, 0xd0]);
+}
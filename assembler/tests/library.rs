@@ -0,0 +1,140 @@
+//! Assembles programs from memory, with included files provided by a `MemoryLoader`.
+
+use tower_assembler::asm::asm::{assemble_str, AssembleOptions, AssembledProgram, Diagnostics};
+use tower_assembler::asm::loader::MemoryLoader;
+
+/// Assembles `main.asm` of the files.
+fn assemble_files(files: &[(&str, &str)]) -> Result<AssembledProgram, Diagnostics> {
+    let mut loader = MemoryLoader::new();
+    for (name, contents) in files {
+        loader.add(name, contents);
+    }
+    let main = files.iter().find(|(n, _)| *n == "main.asm").unwrap().1;
+    let options = AssembleOptions {
+        file_name: String::from("main.asm"),
+        loader: Some(&loader),
+        ..Default::default()
+    };
+    assemble_str(main, &options)
+}
+
+/// Returns the only error of a failed assembly as `file:line: message`.
+fn error(files: &[(&str, &str)]) -> String {
+    let diagnostics = assemble_files(files).unwrap_err();
+    assert_eq!(diagnostics.errors.len(), 1, "{}", diagnostics);
+    diagnostics.to_string()
+}
+
+#[test]
+fn test_program_is_assembled() {
+    let program = assemble_str(
+        "_start:\n\tLDA #1\n\tSTA &0x5000\n_end:\n\tHLT\n",
+        &AssembleOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(program.bytes.len(), 6);
+    let end = program.symbols.iter().find(|l| l.name == "_end").unwrap();
+    assert_eq!(end.address, 5);
+
+    let lines: Vec<(u32, u32, &str, u32)> = program
+        .source_map
+        .iter()
+        .map(|e| (e.address, e.size, e.file.as_str(), e.line))
+        .collect();
+    assert_eq!(
+        lines,
+        vec![
+            (0, 2, "<input>", 2),
+            (2, 3, "<input>", 3),
+            (5, 1, "<input>", 5)
+        ]
+    );
+    assert!(program.warnings.is_empty());
+}
+
+#[test]
+fn test_includes_are_loaded() {
+    let included = assemble_files(&[
+        (
+            "main.asm",
+            "#include \"lib/store.asm\"\n\tLDA #1\n\tJMP _store\n",
+        ),
+        (
+            "lib/store.asm",
+            "#include lib/halt.asm\n_store:\n\tSTA &0x5000\n",
+        ),
+        ("lib/halt.asm", "_halt:\n\tHLT\n"),
+    ])
+    .unwrap();
+    let single = assemble_str(
+        "_halt:\n\tHLT\n_store:\n\tSTA &0x5000\n\tLDA #1\n\tJMP _store\n",
+        &AssembleOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(included.bytes, single.bytes);
+
+    // the source map points into the included files
+    let files: Vec<(&str, u32)> = included
+        .source_map
+        .iter()
+        .map(|e| (e.file.as_str(), e.line))
+        .collect();
+    assert_eq!(
+        files,
+        vec![
+            ("lib/halt.asm", 2),
+            ("lib/store.asm", 3),
+            ("main.asm", 2),
+            ("main.asm", 3),
+        ]
+    );
+}
+
+#[test]
+fn test_include_errors() {
+    // errors point at the file they are in
+    assert_eq!(
+        error(&[
+            ("main.asm", "#include store.asm\n\tHLT\n"),
+            ("store.asm", "\tSTA &0x5000\n\tLDX #1\n"),
+        ]),
+        "store.asm:2: Unknown instruction 'LDX'."
+    );
+
+    assert_eq!(
+        error(&[("main.asm", "\tHLT\n#include missing.asm\n")]),
+        "missing.asm: file does not exist"
+    );
+    assert_eq!(
+        error(&[("main.asm", "#include\n")]),
+        "main.asm:1: Missing file to include."
+    );
+
+    assert_eq!(
+        error(&[
+            ("main.asm", "#include a.asm\n"),
+            ("a.asm", "#include b.asm\n"),
+            ("b.asm", "\tHLT\n#include a.asm\n"),
+        ]),
+        "b.asm:2: 'a.asm' is already being included, includes cannot be recursive."
+    );
+    assert_eq!(
+        error(&[("main.asm", "#include main.asm\n")]),
+        "main.asm:1: 'main.asm' is already being included, includes cannot be recursive."
+    );
+
+    // the same file can be included more than once, just not into itself
+    assert!(assemble_files(&[
+        ("main.asm", "#include a.asm\n#include a.asm\n\tHLT\n"),
+        ("a.asm", "\tNOP\n"),
+    ])
+    .is_ok());
+
+    // nothing can be included without a loader
+    let diagnostics = assemble_str("#include a.asm\n", &AssembleOptions::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.to_string(),
+        "<input>:1: Cannot include 'a.asm', no source loader is available."
+    );
+}
@@ -0,0 +1,315 @@
+//! Assembles modules to object files, links them and runs the result in the emulator.
+
+mod common;
+
+use tower_assembler::asm::asm::{assemble_str, AssembleOptions};
+use tower_assembler::asm::object::{assemble_object, ObjectFile, RelocationKind, Symbol};
+use tower_assembler::asm::Section;
+use tower_assembler::emu::Machine;
+use tower_assembler::link::{format_map, link, linker};
+use tower_assembler::DATA_START;
+
+const MAIN: &str = "
+#import _double, _result
+	LDA #21
+	JSR _double
+	INC16 *_counter
+	LDA #<_result
+	STA &0x5000
+	LDA #>_result
+	STA &0x5001
+	HLT
+
+#section data
+_counter:
+#reserve 2
+";
+
+const LIB: &str = "
+#export _double
+#export _result
+_double:
+	STA _tmp
+	ADD *_tmp
+	JMP _double_end
+_double_end:
+	STA _result
+	RTS
+
+#section data
+_result:
+#reserve 1
+_tmp:
+#reserve 1
+";
+
+fn object(source: &str, file_name: &str) -> ObjectFile {
+    let options = AssembleOptions {
+        file_name: file_name.to_string(),
+        loader: None,
+        ..Default::default()
+    };
+    assemble_object(source, &options).unwrap_or_else(|e| panic!("{}\n{}", e, source))
+}
+
+fn run(bytes: &[u8]) -> Machine {
+    common::run(bytes, 100_000)
+}
+
+fn link_error(sources: &[&str]) -> String {
+    let objects: Vec<ObjectFile> = sources
+        .iter()
+        .enumerate()
+        .map(|(idx, s)| object(s, &format!("module{}.asm", idx)))
+        .collect();
+    link(&objects).unwrap_err().to_string()
+}
+
+fn object_error(source: &str) -> String {
+    assemble_object(source, &AssembleOptions::default())
+        .unwrap_err()
+        .to_string()
+}
+
+#[test]
+fn test_linked_program_runs() {
+    let linked = link(&[object(MAIN, "main.asm"), object(LIB, "lib.asm")]).unwrap();
+    let machine = run(&linked.program.bytes);
+
+    // the data of lib.asm follows the data of main.asm
+    let result = DATA_START as usize + 2;
+    assert_eq!(machine.memory[result], 42);
+    assert_eq!(machine.memory[result + 1], 21);
+    assert_eq!(
+        &machine.memory[DATA_START as usize..DATA_START as usize + 2],
+        &[0, 1]
+    );
+    assert_eq!(&machine.memory[0x5000..0x5002], &[0x02, 0x42]);
+
+    let lib = &linked.modules[1];
+    assert_eq!(lib.code_start, linked.modules[0].code_size);
+    assert_eq!(lib.data_start, DATA_START as u32 + 2);
+}
+
+#[test]
+fn test_pseudo_instruction_jumps_are_relocated() {
+    let lib = "
+#export _check
+_check:
+	LDA #1
+	ADD #0xff
+	JNW _done
+	LDA #0xaa
+	STA &0x5000
+_done:
+	RTS
+";
+    let obj = object(lib, "lib.asm");
+    // `JW next` of `JNW`
+    assert_eq!(obj.code_addresses, vec![5]);
+
+    let linked = link(&[object("#import _check\nJSR _check\nHLT\n", "main.asm"), obj]).unwrap();
+    assert_eq!(run(&linked.program.bytes).memory[0x5000], 0xaa);
+}
+
+#[test]
+fn test_object_file() {
+    let obj = object(MAIN, "main.asm");
+
+    assert_eq!(obj.data_size, 2);
+    assert_eq!(obj.imports, vec!["_double", "_result"]);
+    assert_eq!(obj.symbols[0].name, "_counter");
+    assert_eq!(obj.symbols[0].section, Section::Data);
+    assert_eq!(obj.symbols[0].offset, 0);
+    assert!(!obj.symbols[0].exported);
+
+    let kinds: Vec<(String, RelocationKind, u32)> = obj
+        .relocations
+        .iter()
+        .map(|r| (r.symbol.clone(), r.kind, r.addend))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (String::from("_double"), RelocationKind::Abs16, 0),
+            // INC16 accesses the low byte after the high byte
+            (String::from("_counter"), RelocationKind::Abs16, 1),
+            (String::from("_counter"), RelocationKind::Abs16, 1),
+            (String::from("_counter"), RelocationKind::Abs16, 0),
+            (String::from("_counter"), RelocationKind::Abs16, 0),
+            (String::from("_result"), RelocationKind::Low8, 0),
+            (String::from("_result"), RelocationKind::High8, 0),
+        ]
+    );
+
+    // the placeholders of the addresses are zero
+    for r in &obj.relocations {
+        assert_eq!(obj.code[r.offset as usize], 0);
+    }
+    assert_eq!(obj.relocations[0].offset, 3);
+}
+
+#[test]
+fn test_object_files_are_linked_from_disk() {
+    let dir = std::env::temp_dir();
+    let paths: Vec<String> = ["tower_link_main.o", "tower_link_lib.o"]
+        .iter()
+        .map(|f| dir.join(f).to_str().unwrap().to_string())
+        .collect();
+    let out = dir.join("tower_link.bin");
+    let map = dir.join("tower_link.map");
+
+    object(MAIN, "main.asm").write(&paths[0]).unwrap();
+    object(LIB, "lib.asm").write(&paths[1]).unwrap();
+    assert_eq!(ObjectFile::read(&paths[1]).unwrap(), object(LIB, "lib.asm"));
+
+    linker(&paths, out.to_str().unwrap(), map.to_str().unwrap().into()).unwrap();
+    let bytes = std::fs::read(&out).unwrap();
+    assert_eq!(run(&bytes).memory[DATA_START as usize + 2], 42);
+
+    let map = std::fs::read_to_string(&map).unwrap();
+    assert!(
+        map.contains("data     0x4200  0x4201  0x0002  main.asm\n"),
+        "{}",
+        map
+    );
+    assert!(
+        map.contains("0x4202   data     _result                   lib.asm\n"),
+        "{}",
+        map
+    );
+}
+
+#[test]
+fn test_map() {
+    let linked = link(&[object(MAIN, "main.asm"), object(LIB, "lib.asm")]).unwrap();
+    let map = format_map(&linked);
+
+    let code_size = linked.modules[0].code_size;
+    assert!(
+        map.starts_with(&format!(
+            "SECTION  START   END     SIZE    MODULE\ncode     0x0000  0x{:04x}  0x{:04x}  main.asm\n",
+            code_size - 1,
+            code_size
+        )),
+        "{}",
+        map
+    );
+    assert!(
+        map.contains(&format!(
+            "0x{:04x}   code     _double                   lib.asm\n",
+            code_size
+        )),
+        "{}",
+        map
+    );
+}
+
+#[test]
+fn test_link_errors() {
+    assert!(link_error(&[MAIN, LIB, "#export _double\n_double:\nRTS\n"])
+        .contains("Label '_double' is exported by both 'module1.asm' and 'module2.asm'."));
+    assert!(link_error(&[MAIN]).contains(
+        "module0.asm:4: Label '_double' is not defined, it is not exported by any module."
+    ));
+    assert!(link_error(&["#import _nowhere\nHLT\n"])
+        .contains("Label '_nowhere' is imported, but it is not exported by any module."));
+
+    // labels which are not exported cannot be used by other modules
+    assert!(link_error(&["#import _lib\nJMP _lib\n", "_lib:\nRTS\n"])
+        .contains("Label '_lib' is not defined, it is not exported by any module."));
+}
+
+#[test]
+fn test_imported_labels_defined_locally() {
+    // the assembler refuses such objects, but they can still be written by other tools or edited
+    let main = object("#import _lib\n\tJSR _lib\n\tHLT\n", "main.asm");
+    let lib = object("#export _lib\n_lib:\n\tRTS\n", "lib.asm");
+    assert!(link(&[main.clone(), lib.clone()]).is_ok());
+
+    for exported in [false, true] {
+        let mut main = main.clone();
+        main.symbols.push(Symbol {
+            name: String::from("_lib"),
+            section: Section::Code,
+            offset: 4,
+            exported,
+        });
+        let e = link(&[main, lib.clone()]).unwrap_err().to_string();
+        assert_eq!(
+            e,
+            "main.asm: Label '_lib' is imported, but it is also defined in this module."
+        );
+    }
+}
+
+#[test]
+fn test_object_errors() {
+    assert!(object_error("JMP _lib\n").contains(
+        "Label '_lib' is not defined, labels of other modules have to be imported with '#import'."
+    ));
+    assert!(object_error("#export _lib\nHLT\n").contains("Exported label '_lib' is not defined."));
+    assert!(object_error("#import _lib\n_lib:\nHLT\n")
+        .contains("Label '_lib' is imported, but it is also defined in this module."));
+    assert!(object_error("#section data\nHLT\n")
+        .contains("Instruction 'HLT' cannot be placed in the data section"));
+    assert!(object_error("#reserve 4\nHLT\n")
+        .contains("Memory can only be reserved in the data section"));
+    assert!(object_error("#section bss\nHLT\n")
+        .contains("Invalid section 'bss', sections are 'code' and 'data'."));
+    assert!(object_error("_x:\nLDA #_x\n")
+        .contains("Label '_x' does not fit into an immediate value, use '#<_x' or '#>_x'"));
+    assert!(object_error("LDA #<5\n").contains("'<' and '>' can only be used with labels."));
+    assert!(
+        object_error("#section data\n#reserve 0xBCE1\n#section code\nHLT\n")
+            .contains("The data section does not fit into the RAM (0x4200-0xfedf).")
+    );
+}
+
+#[test]
+fn test_offsets_outside_of_the_code() {
+    let mut lib = object(LIB, "lib.asm");
+    lib.relocations[0].offset = 99;
+    let e = link(&[object(MAIN, "main.asm"), lib]).unwrap_err();
+    assert_eq!(e.code(), "E0003");
+    assert!(e
+        .to_string()
+        .contains("Invalid object file: the relocation of '_tmp' at offset 99 is outside of the"));
+
+    let mut main = object(MAIN, "main.asm");
+    let size = main.code.len() as u32;
+    main.code_addresses.push(size - 1);
+    let e = link(&[main, object(LIB, "lib.asm")]).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        format!(
+            "main.asm: Invalid object file: the code address at offset {} is outside of the {} bytes of code.",
+            size - 1,
+            size
+        )
+    );
+}
+
+#[test]
+fn test_data_section_in_single_module() {
+    let source = "
+	LDA #7
+	STA _value
+	LDA #<_value
+	STA &0x5000
+	HLT
+#section data
+_buffer:
+#reserve 16
+_value:
+#reserve 1
+";
+    let program = assemble_str(source, &AssembleOptions::default()).unwrap();
+    assert_eq!(run(&program.bytes).memory[DATA_START as usize + 16], 7);
+    assert_eq!(run(&program.bytes).memory[0x5000], 0x10);
+
+    let error = assemble_str("#import _lib\nJMP _lib\n", &AssembleOptions::default())
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("linked with tower-link"), "{}", error);
+}
@@ -0,0 +1,76 @@
+//! Parses numeric and character literals and checks that values have to fit their argument.
+
+mod common;
+
+use common::{assemble, error};
+use tower_assembler::asm::format::format_source;
+
+/// Returns the argument bytes of the only instruction of the source.
+fn argument(ins: &str) -> Vec<u8> {
+    assemble(ins).bytes[1..].to_vec()
+}
+
+#[test]
+fn test_numbers() {
+    assert_eq!(argument("LDA #10"), vec![10]);
+    assert_eq!(argument("LDA #0x1f"), vec![0x1f]);
+    assert_eq!(argument("LDA #0b1010_0101"), vec![0xa5]);
+    assert_eq!(argument("LDA #0o17"), vec![0o17]);
+    assert_eq!(argument("LDA #1_0"), vec![10]);
+    assert_eq!(argument("LDA *0xFF_00"), vec![0xff, 0x00]);
+
+    // two's complement of the size of the argument
+    assert_eq!(argument("LDA #-1"), vec![0xff]);
+    assert_eq!(argument("LDA #-128"), vec![0x80]);
+    assert_eq!(argument("LDA *-2"), vec![0xff, 0xfe]);
+    assert_eq!(argument("JMP -32768"), vec![0x80, 0x00]);
+}
+
+#[test]
+fn test_characters() {
+    assert_eq!(argument("LDA #'A'"), vec![0x41]);
+    assert_eq!(argument("LDA #'a'"), vec![0x61]);
+    assert_eq!(argument("LDA #' '"), vec![0x20]);
+    assert_eq!(argument("LDA #';' ; the comment starts here"), vec![0x3b]);
+    assert_eq!(argument("LDA #'\\n'"), vec![0x0a]);
+    assert_eq!(argument("LDA #'\\0'"), vec![0x00]);
+    assert_eq!(argument("LDA #'\\''"), vec![0x27]);
+    assert_eq!(argument("LDA #'\\\\'"), vec![0x5c]);
+    assert_eq!(argument("LDA #'\\x7f'"), vec![0x7f]);
+    assert_eq!(argument("LDA #-'a'"), vec![0x9f]);
+    assert_eq!(argument("LDA #'$'"), vec![b'$']);
+
+    // commas in literals do not separate the arguments of macros
+    let program = assemble("#macro pair\n\tLDA $1\n\tLDA $2\n#end\n\tpair #',', #'x'\n");
+    assert_eq!(program.bytes[1], b',');
+    assert_eq!(program.bytes[3], b'x');
+
+    // a `$` in a literal is no macro argument
+    let program = assemble("#macro dollar\n\tLDA #'$'\n\tADD $1\n#end\n\tdollar #1\n");
+    assert_eq!(program.bytes[1], b'$');
+    assert_eq!(program.bytes[3], 1);
+}
+
+#[test]
+fn test_invalid_literals() {
+    assert!(error("LDA #300").contains("<input>:1: Value 0x12c does not fit into 1 byte(s)."));
+    assert!(error("LDA #-129").contains("<input>:1: Value -129 does not fit into 1 byte(s)."));
+    assert!(error("LDA *0x10000").contains("<input>:1: Value 0x10000 does not fit into 2 byte(s)."));
+    assert!(error("JMP -32769").contains("<input>:1: Value -32769 does not fit into 2 byte(s)."));
+
+    assert!(error("LDA #'ab'")
+        .contains("Invalid character literal 'ab', it has to be one character in single quotes."));
+    assert!(error("LDA #'\\q'").contains("Invalid escape sequence '\\q' in '\\q'."));
+    assert!(error("LDA #'é'").contains("Character 'é' is not an ASCII character."));
+    assert!(error("LDA #0x").contains("Failed to parse value '0x'."));
+    assert!(error("LDA #0o8").contains("Failed to parse value '0o8'."));
+    assert!(error("LDA #-5000000000").contains("Failed to parse value '5000000000'."));
+}
+
+#[test]
+fn test_formatting_keeps_literals() {
+    assert_eq!(
+        format_source("lda #';' ;comment\nlda #','\n"),
+        "LDA #';' ;comment\nLDA #','\n"
+    );
+}
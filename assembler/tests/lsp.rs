@@ -0,0 +1,227 @@
+//! Checks the language server features on in-memory documents.
+
+use lsp_types::{HoverContents, Position, Url};
+use tower_assembler::lsp::features::{
+    completion, definition, diagnostics, document_symbols, hover, references, Workspace,
+};
+use tower_assembler::lsp::index::SymbolKind;
+use tower_assembler::lsp::{Document, SourceKind};
+
+const PROGRAM: &str = "; compute 17 * 6
+
+#macro MW
+    LDA $1
+    STA $2
+#end
+
+MW #17, &0xFE00
+
+_loop:
+    LDA *0xFE00
+    JZ _stop
+    JMP _loop
+
+_stop:
+    HLT
+";
+
+const MICROCODE: &str = "#macro FETCH_LOW
+\tPCO MO ARLI
+\tPCI
+
+#def LDA
+imm:
+\tPCO MO AI
+\tPCI
+zpage:
+\tFETCH_LOW
+\t#if zero
+\t\tAO
+\t#end
+";
+
+fn uri(name: &str) -> Url {
+    Url::parse(&format!("file:///tmp/{}", name)).unwrap()
+}
+
+fn open(text: &str, kind: SourceKind) -> (Url, Document, Workspace) {
+    let uri = uri("test.asm");
+    let doc = Document::new(text.to_string(), kind);
+    let mut workspace = Workspace::new();
+    workspace.insert(uri.clone(), doc.clone());
+    (uri, doc, workspace)
+}
+
+fn hover_text(text: &str, kind: SourceKind, line: u32, character: u32) -> Option<String> {
+    let (uri, doc, workspace) = open(text, kind);
+    hover(&uri, &doc, Position::new(line, character), &workspace).map(|h| match h.contents {
+        HoverContents::Markup(m) => m.value,
+        _ => panic!("hover is not markdown"),
+    })
+}
+
+#[test]
+fn test_detects_source_kind() {
+    assert_eq!(SourceKind::detect("", PROGRAM), SourceKind::Assembly);
+    assert_eq!(SourceKind::detect("", MICROCODE), SourceKind::Microassembly);
+    assert_eq!(
+        SourceKind::detect("tower-asm", MICROCODE),
+        SourceKind::Assembly
+    );
+}
+
+#[test]
+fn test_index_finds_labels_and_macros() {
+    let doc = Document::new(PROGRAM.to_string(), SourceKind::Assembly);
+
+    let stop: Vec<u32> = doc
+        .index
+        .occurrences_of("_stop", SymbolKind::Label)
+        .map(|o| o.line)
+        .collect();
+    assert_eq!(stop, vec![11, 14]);
+    assert_eq!(
+        doc.index
+            .definition("_loop", SymbolKind::Label)
+            .unwrap()
+            .line,
+        9
+    );
+
+    let block = doc.index.block("MW", SymbolKind::Macro).unwrap();
+    assert_eq!((block.start_line, block.end_line), (2, 5));
+    assert_eq!(doc.index.occurrences_of("mw", SymbolKind::Macro).count(), 2);
+}
+
+#[test]
+fn test_definition_and_references() {
+    let (uri, doc, workspace) = open(PROGRAM, SourceKind::Assembly);
+
+    // `_stop` in `JZ _stop`
+    let found = definition(&uri, &doc, Position::new(11, 8), &workspace);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].range.start, Position::new(14, 0));
+
+    let all = references(&uri, &doc, Position::new(12, 9), &workspace, true);
+    assert_eq!(all.len(), 2);
+    let uses = references(&uri, &doc, Position::new(12, 9), &workspace, false);
+    assert_eq!(uses.len(), 1);
+    assert_eq!(uses[0].range.start, Position::new(12, 8));
+}
+
+#[test]
+fn test_diagnostics() {
+    let (uri, doc, workspace) = open(PROGRAM, SourceKind::Assembly);
+    assert!(diagnostics(&uri, &doc, &workspace).is_empty());
+
+    let (uri, doc, workspace) = open("LDA #1\nFOO #2\n", SourceKind::Assembly);
+    let found = diagnostics(&uri, &doc, &workspace);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].range.start.line, 1);
+
+    let (uri, doc, workspace) = open("#def LDA\nimm:\n\tFOO\n", SourceKind::Microassembly);
+    let found = diagnostics(&uri, &doc, &workspace);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].range.start.line, 2);
+}
+
+#[test]
+fn test_microcode_include_diagnostics() {
+    let (main, doc, mut workspace) = open(
+        "; shared macros\n#include fetch.asm\n#def LDA\nimm:\n\tFETCH_LOW\n",
+        SourceKind::Microassembly,
+    );
+    workspace.insert(
+        uri("fetch.asm"),
+        Document::new(
+            "#macro FETCH_LOW\n\tPCO MO ARLI\n\tPCI\n".to_string(),
+            SourceKind::Microassembly,
+        ),
+    );
+    assert!(diagnostics(&main, &doc, &workspace).is_empty());
+
+    // `FETCH_LOW` in `#def LDA` is defined in the included file
+    let found = definition(&main, &doc, Position::new(4, 3), &workspace);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].uri, uri("fetch.asm"));
+
+    // problems in the included file are shown at the include
+    workspace.insert(
+        uri("fetch.asm"),
+        Document::new(
+            "#macro FETCH_LOW\n\tPCO MO ARLX\n".to_string(),
+            SourceKind::Microassembly,
+        ),
+    );
+    let found = diagnostics(&main, &doc, &workspace);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].range.start.line, 1);
+    assert!(
+        found[0]
+            .message
+            .ends_with("fetch.asm:2: Unknown identifier 'arlx'."),
+        "{}",
+        found[0].message
+    );
+}
+
+#[test]
+fn test_hover() {
+    let lda = hover_text(PROGRAM, SourceKind::Assembly, 10, 5).unwrap();
+    assert!(lda.contains("**LDA**"));
+    assert!(lda.contains("Modes: Immediate, Absolute"));
+
+    let jnw = hover_text("\tJNW 0x10\n", SourceKind::Assembly, 0, 2).unwrap();
+    assert!(jnw.contains("**JNW** (pseudo-instruction)"));
+    assert!(jnw.contains("Expands to `JW next; JMP x`"));
+
+    let mw = hover_text(PROGRAM, SourceKind::Assembly, 7, 1).unwrap();
+    assert!(mw.contains("STA $2"));
+
+    let signal = hover_text(MICROCODE, SourceKind::Microassembly, 1, 6).unwrap();
+    assert!(signal.starts_with("**MO**"));
+
+    let flag = hover_text(MICROCODE, SourceKind::Microassembly, 10, 7).unwrap();
+    assert!(flag.contains("ZERO"));
+}
+
+#[test]
+fn test_completion() {
+    let labels = |text: &str, kind, line, character| {
+        let (uri, doc, workspace) = open(text, kind);
+        completion(&uri, &doc, Position::new(line, character), &workspace)
+            .into_iter()
+            .map(|c| c.label)
+            .collect::<Vec<String>>()
+    };
+
+    let first_word = labels(PROGRAM, SourceKind::Assembly, 7, 0);
+    assert!(first_word.contains(&String::from("LDA")));
+    assert!(first_word.contains(&String::from("MW")));
+    assert!(first_word.contains(&String::from("JNW")));
+
+    let args = labels(PROGRAM, SourceKind::Assembly, 11, 7);
+    assert_eq!(args, vec!["_loop", "_stop"]);
+
+    let markers = labels("#", SourceKind::Assembly, 0, 1);
+    assert!(markers.contains(&String::from("include")));
+
+    let flags = labels(MICROCODE, SourceKind::Microassembly, 10, 5);
+    assert!(flags.contains(&String::from("!incwrap")));
+
+    let steps = labels(MICROCODE, SourceKind::Microassembly, 7, 1);
+    assert!(steps.contains(&String::from("PCO")));
+    assert!(steps.contains(&String::from("FETCH_LOW")));
+}
+
+#[test]
+fn test_document_symbols() {
+    let doc = Document::new(MICROCODE.to_string(), SourceKind::Microassembly);
+    let names: Vec<String> = document_symbols(&doc).into_iter().map(|s| s.name).collect();
+    assert_eq!(names, vec!["FETCH_LOW", "LDA"]);
+
+    let doc = Document::new(PROGRAM.to_string(), SourceKind::Assembly);
+    let symbols = document_symbols(&doc);
+    assert_eq!(symbols[0].name, "MW");
+    assert_eq!(symbols[0].range.end.line, 5);
+}
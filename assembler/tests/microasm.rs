@@ -0,0 +1,178 @@
+//! Assembles microcode with macros taking parameters and sources split into several files.
+
+mod common;
+
+use common::{microassemble as assemble, microassemble_error as error};
+use tower_assembler::asm::loader::MemoryLoader;
+use tower_assembler::microasm::asm::{assemble_str, MicroassembleOptions};
+
+#[test]
+fn test_parameters_are_substituted() {
+    let expanded = assemble(
+        "
+#macro FETCH_TO reg
+	PCO MO reg
+	PCI
+#macro LOAD_ABS reg
+	FETCH_TO ARHI
+	FETCH_TO ARLI
+	ARHLO MO reg
+#macro ALU_OP op
+	op ALUO AI
+#def ADD
+abs:
+	LOAD_ABS BI
+	ALU_OP OPADD
+	IEND
+#def SUB
+abs:
+	LOAD_ABS BI
+	PCI ALU_OP OPSUB
+	IEND
+",
+    );
+    let written_out = assemble(
+        "
+#def ADD
+abs:
+	PCO MO ARHI
+	PCI
+	PCO MO ARLI
+	PCI
+	ARHLO MO BI
+	OPADD ALUO AI
+	IEND
+#def SUB
+abs:
+	PCO MO ARHI
+	PCI
+	PCO MO ARLI
+	PCI
+	ARHLO MO BI
+	PCI OPSUB ALUO AI
+	IEND
+",
+    );
+    assert!(expanded == written_out);
+}
+
+#[test]
+fn test_arguments_are_checked() {
+    let source = "#macro ALU_OP op\n\top ALUO AI\n#def ADD\nimm:\n\tALU_OP\n";
+    assert!(error(source).contains("5: Macro 'alu_op' takes 1 argument(s) (op), 0 were given."));
+
+    let source = "#macro ALU_OP op\n\top ALUO AI\n#def ADD\nimm:\n\tALU_OP OPADD OPSUB\n";
+    assert!(error(source).contains("5: Macro 'alu_op' takes 1 argument(s) (op), 2 were given."));
+
+    let source = "#macro ALU_OP op op\n\top ALUO AI\n";
+    assert!(error(source).contains("1: Parameter 'op' of macro 'alu_op' is listed twice."));
+
+    let source = "#macro ALU_OP AI\n\tAI\n";
+    assert!(error(source).contains("1: The name 'ai' is already used."));
+
+    let source = "#macro ALU_OP op\n\top ALUO X\n";
+    assert!(error(source).contains("2: Unknown identifier 'x'."));
+}
+
+#[test]
+fn test_errors_show_the_expansion_trace() {
+    let source = "
+#macro ALU_OP op
+	op ALUO AI
+#macro LOAD_AND_RUN reg op
+	PCO MO reg
+	ALU_OP op
+#def ADD
+imm:
+	LOAD_AND_RUN BI OPFOO
+";
+    assert!(error(source)
+        .contains("9: Unknown identifier 'opfoo'. (macro trace: load_and_run->alu_op)"));
+
+    let source = "
+#macro TWICE m
+	m m
+#def ADD
+imm:
+	TWICE TWICE
+";
+    assert!(
+        error(source).contains("6: Macro 'twice' cannot use itself. (macro trace: twice)"),
+        "{}",
+        error(source)
+    );
+}
+
+/// Assembles `main.asm` of the files.
+fn assemble_files(files: &[(&str, &str)]) -> Result<Vec<u8>, String> {
+    let mut loader = MemoryLoader::new();
+    for (name, contents) in files {
+        loader.add(name, contents);
+    }
+    let main = files.iter().find(|(n, _)| *n == "main.asm").unwrap().1;
+    let options = MicroassembleOptions {
+        file_name: String::from("main.asm"),
+        loader: Some(&loader),
+        ..Default::default()
+    };
+    assemble_str(main, &options)
+        .map(|microcode| microcode.bytes)
+        .map_err(|e| e.to_string())
+}
+
+#[test]
+fn test_includes() {
+    let included = assemble_files(&[
+        (
+            "main.asm",
+            "#include fetch.asm\n#include \"Alu.asm\"\n#def HLT\n\tHLT\n",
+        ),
+        (
+            "fetch.asm",
+            "#pref\n\tPCO MO INI\n\tPCI\n#macro ALU_OP op\n\top ALUO AI\n",
+        ),
+        (
+            "Alu.asm",
+            "#def ADD\nimm:\n\tPCO MO BI\n\tPCI\n\tALU_OP OPADD\n",
+        ),
+    ])
+    .unwrap();
+    let single = assemble(
+        "#pref\n\tPCO MO INI\n\tPCI\n#def ADD\nimm:\n\tPCO MO BI\n\tPCI\n\tOPADD ALUO AI\n#def HLT\n\tHLT\n",
+    );
+    assert!(included == single);
+
+    // without a loader nothing can be included
+    assert!(error("#include fetch.asm\n")
+        .contains("<input>:1: Cannot include 'fetch.asm', no source loader is available."));
+}
+
+#[test]
+fn test_include_errors() {
+    // errors point at the file they are in
+    let e = assemble_files(&[
+        ("main.asm", "#include fetch.asm\n#def HLT\n\tHLT\n"),
+        ("fetch.asm", "#pref\n\tPCO MO INX\n"),
+    ])
+    .unwrap_err();
+    assert!(
+        e.contains("fetch.asm:2: Unknown identifier 'inx'."),
+        "{}",
+        e
+    );
+
+    let e = assemble_files(&[("main.asm", "#include missing.asm\n")]).unwrap_err();
+    assert!(e.contains("'missing.asm': file does not exist"), "{}", e);
+
+    let e = assemble_files(&[
+        ("main.asm", "#include a.asm\n"),
+        ("a.asm", "#include b.asm\n"),
+        ("b.asm", "#include a.asm\n"),
+    ])
+    .unwrap_err();
+    assert!(
+        e.contains("b.asm:1: 'a.asm' is already being included, includes cannot be recursive."),
+        "{}",
+        e
+    );
+}
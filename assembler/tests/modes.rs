@@ -0,0 +1,89 @@
+//! Infers the modes of arguments written without a mode identifier.
+
+mod common;
+
+use common::{assemble, error};
+
+/// Assembles the instruction with and without the mode identifier and checks the machine code is the same.
+fn assert_inferred(inferred: &str, explicit: &str) {
+    let source = |ins: &str| {
+        format!(
+            "_start:\n\t{}\n\tHLT\n#section data\n_data:\n#reserve 1\n",
+            ins
+        )
+    };
+    assert_eq!(
+        assemble(&source(inferred)).bytes,
+        assemble(&source(explicit)).bytes,
+        "{}",
+        inferred
+    );
+}
+
+#[test]
+fn test_inference_rules() {
+    // jumps and stores take the address itself
+    assert_inferred("JMP _start", "JMP &_start");
+    assert_inferred("JMP 0x0010", "JMP &0x0010");
+    assert_inferred("STA _data", "STA &_data");
+    assert_inferred("JNC _start", "JNC &_start");
+    // labels are read from their address
+    assert_inferred("LDA _data", "LDA *_data");
+    assert_inferred("CMP _data", "CMP *_data");
+    // the only mode which takes an argument
+    assert_inferred("INC 0x5000", "INC *0x5000");
+    assert_inferred("INC16 _data", "INC16 *_data");
+}
+
+#[test]
+fn test_inference_in_macros() {
+    let program = assemble(
+        "
+#macro load
+	LDA $1
+#end
+#macro move
+	load $1
+	STA $2
+#end
+_start:
+	move _value, _copy
+	load _start
+	HLT
+#section data
+_value:
+#reserve 1
+_copy:
+#reserve 1
+",
+    );
+    let sizes: Vec<u32> = program.source_map.iter().map(|e| e.size).collect();
+    assert_eq!(sizes, vec![3, 3, 3, 1]);
+
+    // a mode identifier in the macro is kept
+    let program = assemble("#zp _a\n#macro load\n\tLDA *$1\n#end\n\tload _a\n\tHLT\n");
+    assert_eq!(&program.bytes[1..3], &[0x40, 0x00]);
+}
+
+#[test]
+fn test_ambiguous_modes() {
+    assert!(error("LDA 5\nHLT\n").contains(
+        "<input>:1: Cannot infer the mode of argument '5' of instruction 'LDA', add a mode identifier: '#5' (Immediate), '*5' (Absolute), '@5' (Indirect)."
+    ));
+    assert!(error("RTS 5\n").contains("<input>:1: Instruction 'RTS' does not take an argument."));
+
+    let source = "
+#macro add_to
+	ADD $1
+#end
+#macro twice
+	add_to $1
+	add_to $1
+#end
+	twice 3
+	HLT
+";
+    assert!(error(source).contains(
+        "<input>:9: Cannot infer the mode of argument '3' of instruction 'ADD', add a mode identifier: '#3' (Immediate), '*3' (Absolute), '@3' (Indirect). (macro trace: twice->add_to)"
+    ), "{}", error(source));
+}
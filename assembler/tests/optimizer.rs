@@ -0,0 +1,127 @@
+//! Merges adjacent micro steps and checks only compatible steps are merged.
+
+use tower_assembler::isa::isa;
+use tower_assembler::microasm::asm::{
+    assemble_str, AssembledMicrocode, MicroassembleOptions, OptimizationReport,
+};
+use tower_assembler::{
+    get_instruction_by_name, IM_ABSOLUTE, IM_CONSTANT, IM_IMMEDIATE, IM_IMPLIED,
+};
+
+fn assemble(source: &str, optimize: bool) -> AssembledMicrocode {
+    let options = MicroassembleOptions {
+        optimize,
+        ..Default::default()
+    };
+    assemble_str(source, &options).unwrap_or_else(|e| panic!("{}\n{}", e, source))
+}
+
+/// Checks the optimized source assembles to the same microcode as the steps written out by hand.
+fn assert_merged(source: &str, merged: &str) {
+    let optimized = assemble(source, true).bytes;
+    assert!(optimized == assemble(merged, false).bytes, "{}", source);
+}
+
+#[test]
+fn test_independent_steps_are_merged() {
+    // PC is only latched at the end of the step, so it can be incremented while it is read
+    assert_merged(
+        "#def LDA\nimm:\n\tPCO MO AI\n\tPCI\n\tIEND\n",
+        "#def LDA\nimm:\n\tPCO MO AI PCI IEND\n",
+    );
+    // HO cannot join them, MO already drives the data bus
+    assert_merged(
+        "#def TAB\n\tPCI\n\tHLO MO BI\n\tHO LI\n",
+        "#def TAB\n\tPCI HLO MO BI\n\tHO LI\n",
+    );
+}
+
+#[test]
+fn test_dependent_steps_are_kept() {
+    let sources = [
+        // B is read after it is written
+        "#def ADD\nimm:\n\tPCO MO BI\n\tOPADD ALUO AI\n",
+        // two drivers on the address bus
+        "#def LDA\nabs:\n\tPCO MO AI\n\tHLO MO BI\n",
+        // two drivers on the data bus
+        "#def TAB\n\tBO AI\n\tALUO HI\n",
+        // the ALU operation changes the output of the ALU right away
+        "#def ADD\nimm:\n\tALUO HI\n\tOPSUB\n",
+        // nothing runs after the step counter is reset
+        "#def NOP\n\tIEND\n\tPCI\n",
+    ];
+    for source in sources {
+        assert_merged(source, source);
+    }
+}
+
+#[test]
+fn test_noopt_keeps_the_steps() {
+    let source = "#def LDA noopt\nimm:\n\tPCO MO AI\n\tPCI\n\tIEND\n";
+    assert_merged(source, &source.replace(" noopt", ""));
+
+    let microcode = assemble(source, true);
+    assert_eq!(microcode.report[0].before, microcode.report[0].after);
+}
+
+#[test]
+fn test_step_counts_are_reported() {
+    let source = "
+#pref
+	PCO MO INI
+	PCI
+#def LDA
+imm:
+	PCO MO AI
+	PCI
+	IEND
+abs:
+	PCO MO ARHI
+	PCI
+	PCO MO ARLI
+	PCI
+	ARHLO MO AI
+	IEND
+#def JZ
+const:
+	#if zero
+		PCO MO ARHI
+		PCI
+	#end
+	IEND
+#def HLT
+	HLT
+";
+    let report = assemble(source, true).report;
+
+    // ordered by opcode and instruction mode, min and max across the flag combinations
+    let steps = |name: &str, instruction_mode: u32| {
+        let r = report
+            .iter()
+            .find(|r| r.name == name && r.instruction_mode == instruction_mode)
+            .unwrap();
+        (r.before, r.after)
+    };
+    assert_eq!(steps("lda", IM_IMMEDIATE), ((5, 5), (3, 3)));
+    assert_eq!(steps("lda", IM_ABSOLUTE), ((8, 8), (5, 5)));
+    assert_eq!(steps("hlt", IM_IMPLIED), ((3, 3), (2, 2)));
+    assert!(report.windows(2).all(|w| {
+        let opcode = |r: &OptimizationReport| get_instruction_by_name(&r.name).unwrap().0;
+        (opcode(&w[0]), w[0].instruction_mode) < (opcode(&w[1]), w[1].instruction_mode)
+    }));
+
+    assert_eq!(steps("jz", IM_CONSTANT), ((3, 5), (2, 3)));
+    let jz = report.iter().find(|r| r.name == "jz").unwrap();
+    assert_eq!(jz.to_string(), "JZ    Constant        3-5 -> 2-3");
+
+    // without IEND the step counter runs through all of its steps, like in the timing table
+    let report = assemble("#def CMP\nimm:\n\tPCO MO BI\n\tPCI\n\tOPSUB\n", true).report;
+    let steps = isa().max_micro_steps();
+    assert_eq!(
+        (report[0].before, report[0].after),
+        ((steps, steps), (steps, steps))
+    );
+
+    // nothing is reported without the optimization
+    assert!(assemble(source, false).report.is_empty());
+}
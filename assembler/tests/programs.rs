@@ -0,0 +1,65 @@
+//! Runs the test programs under `software/tests` in the emulator.
+
+mod common;
+
+use tower_assembler::asm::asm::{assemble_str, AssembleOptions};
+use tower_assembler::emu::runner::{run_test, run_test_file, TestStatus, DEFAULT_MAX_CYCLES};
+use tower_assembler::emu::{Machine, StopReason};
+
+fn run_source(source: &str) -> TestStatus {
+    let program = assemble_str(source, &AssembleOptions::default()).unwrap();
+    run_test("<input>", &program, &common::microcode(), 10_000).status
+}
+
+#[test]
+fn test_programs_pass() {
+    let microcode = common::microcode();
+
+    for name in [
+        "full_test",
+        "indirect_access_test",
+        "random_test",
+        "simple_alu_test",
+        "stack_test",
+    ] {
+        let path = common::root().join(format!("software/tests/{}.asm", name));
+        let result = run_test_file(path.to_str().unwrap(), &microcode, DEFAULT_MAX_CYCLES);
+        assert_eq!(result.status, TestStatus::Passed, "{}", name);
+    }
+}
+
+#[test]
+fn halting_at_failed_label_fails() {
+    let status = run_source("LDA #7\nJMP _failed\nHLT\n_failed:\nHLT\n");
+    assert_eq!(
+        status,
+        TestStatus::Failed(String::from("halted at _failed (A = 7)"))
+    );
+}
+
+#[test]
+fn pass_label_is_required_when_defined() {
+    assert_eq!(run_source("JMP _pass\n_pass:\nHLT\n"), TestStatus::Passed);
+    assert!(matches!(
+        run_source("HLT\n_pass:\nHLT\n"),
+        TestStatus::Failed(_)
+    ));
+}
+
+#[test]
+fn endless_loop_hits_the_cycle_limit() {
+    assert!(matches!(
+        run_source("_loop:\nJMP _loop\n"),
+        TestStatus::Failed(_)
+    ));
+}
+
+#[test]
+fn fibonacci_sequence_is_computed() {
+    let program = std::fs::read(common::root().join("software/programs/fibonacci.bin")).unwrap();
+    let mut machine = Machine::new(common::microcode(), &program);
+    assert_eq!(machine.run(DEFAULT_MAX_CYCLES), StopReason::Halted);
+
+    let sequence = &machine.memory[0xFE00..0xFE0D];
+    assert_eq!(sequence, [1, 1, 2, 3, 5, 8, 13, 21, 34, 55, 89, 144, 233]);
+}
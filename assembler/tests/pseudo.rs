@@ -0,0 +1,193 @@
+//! Assembles programs using pseudo-instructions and runs them in the emulator.
+
+mod common;
+
+use tower_assembler::asm::asm::{assemble_str, AssembleOptions};
+use tower_assembler::asm::format::format_source;
+use tower_assembler::asm::listing::lister;
+use tower_assembler::emu::Machine;
+
+fn run(source: &str) -> Machine {
+    common::run_source(source, "", 100_000)
+}
+
+#[test]
+fn test_load_b_and_clear() {
+    let machine = run("
+        LDA #0xFF
+        STA &0x5002
+        LDA #7
+        CMP #7
+        LDB #9
+        CLR &0x5002
+        JNZ _failed
+        STA &0x5000
+        TBA
+        STA &0x5001
+        CLR %A
+        STA &0x5003
+        HLT
+    _failed:
+        HLT
+    ");
+
+    // A and the zero flag survive LDB and CLR
+    assert_eq!(&machine.memory[0x5000..0x5004], &[7, 9, 0, 0]);
+}
+
+#[test]
+fn test_word_increment_and_decrement() {
+    let machine = run("
+        LDA #0x12
+        STA &0x5000
+        LDA #0xFF
+        STA &0x5001
+        LDA #3
+        INC16 *0x5000
+        STA &0x5002
+        DEC16 *0x5004
+        DEC16 *0x5004
+        HLT
+    ");
+
+    assert_eq!(
+        &machine.memory[0x5000..0x5006],
+        &[0x13, 0x00, 3, 0, 0xFF, 0xFE]
+    );
+}
+
+#[test]
+fn test_push_and_pop_b() {
+    let machine = run("
+        LDB #4
+        PSB
+        LDB #0
+        POB
+        LDA #1
+        TBA
+        STA &0x5000
+        HLT
+    ");
+
+    assert_eq!(machine.memory[0x5000], 4);
+}
+
+#[test]
+fn test_conditional_jumps() {
+    // (jump, A, operand of CMP, taken)
+    let cases = [
+        ("JNW", 3, 5, false),
+        ("JNW", 5, 3, true),
+        ("JC", 3, 5, true),
+        ("JNC", 5, 5, true),
+        ("JLT", 3, 5, true),
+        ("JLT", 5, 5, false),
+        ("JGE", 5, 5, true),
+        ("JGE", 3, 5, false),
+        ("JGT", 6, 5, true),
+        ("JGT", 5, 5, false),
+        ("JGT", 4, 5, false),
+        ("JLE", 4, 5, true),
+        ("JLE", 5, 5, true),
+        ("JLE", 6, 5, false),
+    ];
+
+    for (jump, a, b, taken) in cases {
+        let source = format!(
+            "LDA #{}\nCMP #{}\n{} _taken\nLDA #1\nSTA &0x5000\nHLT\n_taken:\nLDA #2\nSTA &0x5000\nHLT\n",
+            a, b, jump
+        );
+        let expected = if taken { 2 } else { 1 };
+        assert_eq!(
+            run(&source).memory[0x5000],
+            expected,
+            "{} {} {}",
+            jump,
+            a,
+            b
+        );
+    }
+}
+
+#[test]
+fn test_pseudo_instructions_in_macros() {
+    let machine = run("
+    #macro BUMP
+        INC16 $1
+    #end
+        BUMP *0x5000
+        BUMP *0x5000
+        HLT
+    ");
+
+    assert_eq!(&machine.memory[0x5000..0x5002], &[0, 2]);
+
+    // macros take precedence over pseudo-instructions
+    let machine = run("
+    #macro CLR
+        LDA #5
+        STA $1
+    #end
+        CLR &0x5000
+        HLT
+    ");
+    assert_eq!(machine.memory[0x5000], 5);
+}
+
+#[test]
+fn test_listing_shows_expansion() {
+    let dir = std::env::temp_dir();
+    let source = dir.join("tower_pseudo_listing.asm");
+    let listing = dir.join("tower_pseudo_listing.lst");
+    std::fs::write(&source, "_loop:\n\tJNW _loop\n\tHLT\n").unwrap();
+
+    lister(
+        source.to_str().unwrap(),
+        listing.to_str().unwrap(),
+        None,
+        false,
+    )
+    .unwrap();
+    let output = std::fs::read_to_string(&listing).unwrap();
+
+    assert!(
+        output.contains("0000               2          JNW _loop\n"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("0000  5a 00 06     2            JW &0x0006\n"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("0003  52 00 00     2            JMP &0x0000\n"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("0006  e8           3          HLT\n"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_pseudo_instruction_errors() {
+    let error = |source: &str| {
+        assemble_str(source, &AssembleOptions::default())
+            .unwrap_err()
+            .to_string()
+    };
+
+    assert!(error("INC16 #5\n").contains("cannot take an argument in 'Immediate' instruction mode"));
+    assert!(error("PSB #5\n").contains("Available modes are: Implied"));
+    assert!(error("JGT _nowhere\n").contains("Label '_nowhere' is not defined."));
+    assert!(error("#macro BUMP\nINC16 $1\n#end\nBUMP #1\n")
+        .contains("cannot take an argument in 'Immediate' instruction mode"));
+}
+
+#[test]
+fn test_pseudo_instructions_are_formatted() {
+    assert_eq!(format_source("ldb #1\njnw 0x10\n"), "LDB #1\nJNW 0x10\n");
+}
@@ -0,0 +1,56 @@
+//! Register operands written with `%A` and `%B`.
+
+mod common;
+
+use common::{assemble, error};
+
+#[test]
+fn test_accumulator_operand() {
+    // the register is case insensitive and takes no argument bytes
+    let program = assemble("\tINC %A\n\tSL %a\n\tCLR %A\n\tHLT\n");
+    let sizes: Vec<u32> = program.source_map.iter().map(|e| e.size).collect();
+    assert_eq!(sizes, vec![1, 1, 2, 1]);
+}
+
+#[test]
+fn test_names_starting_with_a_are_labels() {
+    let program = assemble("again:\n\tJMP again\n\tJMP a\na:\n\tHLT\n");
+    assert_eq!(&program.bytes[1..3], &[0x00, 0x00]);
+    assert_eq!(&program.bytes[4..6], &[0x00, 0x06]);
+}
+
+#[test]
+fn test_invalid_registers() {
+    assert!(error("\tINC %B\n\tHLT\n").contains(
+        "<input>:1: Register B cannot be used as an argument, only register A ('%A') can. Copy B to A with 'TBA' first."
+    ));
+    assert!(error("\tINC %X\n\tHLT\n")
+        .contains("<input>:1: Invalid register '%X', the registers are '%A' and '%B'."));
+    assert!(error("\tLDA %A\n\tHLT\n").contains(
+        "<input>:1: Instruction 'LDA' cannot take register A as its argument. Available modes are: Immediate,Absolute,Indirect,Zeropage"
+    ));
+}
+
+#[test]
+fn test_registers_without_prefix() {
+    assert!(error("\tINC A\n\tHLT\n").contains(
+        "<input>:1: Label 'a' is not defined. Registers are prefixed with '%', use '%A' for register A."
+    ));
+    assert!(error("\tHLT\n\tdec b\n").contains(
+        "<input>:2: Label 'b' is not defined. Registers are prefixed with '%', but only register A ('%A') can be used as an argument."
+    ));
+    assert!(error("#macro shift\n\tSL $1\n#end\n\tshift A\n\tHLT\n")
+        .contains("<input>:4: Label 'a' is not defined. Registers are prefixed with '%'"));
+
+    // instructions which cannot take a register keep their errors
+    assert!(error("\tTAB B\n\tHLT\n")
+        .contains("<input>:1: Instruction 'TAB' does not take an argument."));
+    assert_eq!(
+        error("\tLDA a\n\tHLT\n"),
+        "<input>:1: Label 'a' is not defined."
+    );
+
+    // labels named like a register are still fine
+    let program = assemble("\tINC a\n\tHLT\na:\n");
+    assert_eq!(program.bytes, assemble("\tINC *a\n\tHLT\na:\n").bytes);
+}
@@ -0,0 +1,128 @@
+//! Checks the call graph, stack depths and stack warnings found by the stack analysis.
+
+use tower_assembler::asm::asm::{assemble_str, AssembleOptions};
+use tower_assembler::asm::stack::{analyze_file, format_stack_report, Depth, StackAnalysis};
+
+fn analyze(name: &str, source: &str) -> StackAnalysis {
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, source).unwrap();
+    analyze_file(path.to_str().unwrap()).unwrap()
+}
+
+fn warnings(source: &str) -> Vec<String> {
+    assemble_str(source, &AssembleOptions::default())
+        .unwrap_or_else(|e| panic!("{}\n{}", e, source))
+        .warnings
+        .iter()
+        .map(|w| w.to_string())
+        .collect()
+}
+
+#[test]
+fn test_depth_of_nested_calls() {
+    let analysis = analyze(
+        "tower_stack_nested.asm",
+        "
+	JSR _outer
+	HLT
+_outer:
+	PSA
+	JSR _inner
+	POA
+	RTS
+_inner:
+	PSA
+	PSF
+	POF
+	POA
+	JSR _inner_leaf
+	RTS
+_inner_leaf:
+	RTS
+",
+    );
+
+    assert!(analysis.warnings.is_empty(), "{:?}", analysis.warnings);
+    let depths: Vec<(&str, Depth)> = analysis
+        .routines
+        .iter()
+        .map(|r| (r.name.as_str(), r.depth))
+        .collect();
+    assert_eq!(
+        depths,
+        vec![
+            ("(start)", Depth::Bounded(7)),
+            ("_outer", Depth::Bounded(5)),
+            ("_inner", Depth::Bounded(2)),
+            ("_inner_leaf", Depth::Bounded(0)),
+        ]
+    );
+
+    assert_eq!(
+        format_stack_report(&analysis),
+        "ROUTINE                   ADDRESS  DEPTH      CALLS
+(start)                   0x0000   7          _outer
+_outer                    0x0004   5          _inner
+_inner                    0x000a   2          _inner_leaf
+_inner_leaf               0x0012   0
+"
+    );
+}
+
+#[test]
+fn test_unbalanced_pushes_and_pops() {
+    assert_eq!(
+        warnings("JSR _f\nHLT\n_f:\nPSA\nRTS\n"),
+        vec!["<input>:5: Routine '_f' returns with 1 pushed byte(s) left on the stack, 'RTS' takes them as the return address."]
+    );
+    assert_eq!(
+        warnings("JSR _f\nHLT\n_f:\nPOA\nRTS\n"),
+        vec!["<input>:4: 'POA' pops more bytes than routine '_f' pushed."]
+    );
+    assert_eq!(
+        warnings("LDA #3\n_loop:\nPSA\nSUB #1\nJNZ _loop\nHLT\n"),
+        vec!["<input>:3: Paths reach this instruction with 0 and 1 byte(s) on the stack."]
+    );
+
+    // a push on only one of two paths
+    assert_eq!(
+        warnings("JSR _f\nHLT\n_f:\nJZ _skip\nPSF\n_skip:\nRTS\n"),
+        vec![
+            "<input>:7: Routine '_f' returns with 1 pushed byte(s) left on the stack, 'RTS' takes them as the return address.",
+            "<input>:7: Paths reach this instruction with 0 and 1 byte(s) on the stack."
+        ]
+    );
+}
+
+#[test]
+fn test_recursion() {
+    assert_eq!(
+        warnings("JSR _a\nHLT\n_a:\nJSR _b\nRTS\n_b:\nJZ _done\nJSR _a\n_done:\nRTS\n"),
+        vec![
+            "<input>:8: Routine '_a' is recursive (_a -> _b -> _a), its stack depth is unbounded."
+        ]
+    );
+
+    let analysis = analyze(
+        "tower_stack_recursion.asm",
+        "JSR _a\nJSR @0x5000\nHLT\n_a:\nJSR _a\nRTS\n",
+    );
+    assert_eq!(analysis.routines[0].depth, Depth::Unbounded);
+    assert_eq!(analysis.routines[1].depth, Depth::Unbounded);
+    assert!(format_stack_report(&analysis).contains("unbounded  _a, (indirect)"));
+
+    let analysis = analyze("tower_stack_indirect.asm", "JSR @0x5000\nHLT\n");
+    assert_eq!(analysis.routines[0].depth, Depth::Unknown);
+}
+
+#[test]
+fn test_stack_overflow() {
+    let source = |depth| format!("{}{}HLT\n", "PSA\n".repeat(depth), "POA\n".repeat(depth));
+    assert_eq!(
+        warnings(&source(300)),
+        vec![
+            "<input>:1: The program can have 300 bytes on the stack, but the stack only holds 256."
+        ]
+    );
+    assert!(warnings(&source(256)).is_empty());
+}
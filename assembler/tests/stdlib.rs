@@ -0,0 +1,223 @@
+//! Calls the routines of the standard library in the emulator.
+
+mod common;
+
+use tower_assembler::asm::asm::{assemble_str, AssembleOptions};
+use tower_assembler::asm::format::format_source;
+use tower_assembler::asm::loader::MemoryLoader;
+use tower_assembler::asm::stdlib::LIBRARIES;
+use tower_assembler::emu::Machine;
+
+/// Registers of the calling convention.
+const R0: u16 = 0xFEE0;
+const R1: u16 = 0xFEE2;
+const R2: u16 = 0xFEE4;
+
+fn run(source: &str, input: &str) -> Machine {
+    common::run_source(source, input, 2_000_000)
+}
+
+/// Stores a 16-bit value, high byte first.
+fn set(address: u16, value: u16) -> String {
+    format!(
+        "\tLDA #{}\n\tSTA &{:#06X}\n\tLDA #{}\n\tSTA &{:#06X}\n",
+        value >> 8,
+        address,
+        value & 0xFF,
+        address + 1
+    )
+}
+
+fn word(machine: &Machine, address: u16) -> u16 {
+    let address = address as usize;
+    u16::from_be_bytes([machine.memory[address], machine.memory[address + 1]])
+}
+
+/// Calls `routine` with R0 and R1 set and returns R0 and R1 afterwards.
+fn call(library: &str, routine: &str, r0: u16, r1: u16) -> (u16, u16) {
+    let source = format!(
+        "{}{}\tJSR {}\n\tHLT\n#include <{}>\n",
+        set(R0, r0),
+        set(R1, r1),
+        routine,
+        library
+    );
+    let machine = run(&source, "");
+    (word(&machine, R0), word(&machine, R1))
+}
+
+/// Compares R0 with R1 and returns the ordering the flags describe.
+fn compare(routine: &str, r0: u16, r1: u16) -> std::cmp::Ordering {
+    let source = format!(
+        "{}{}\tJSR {}\n\tJZ _equal\n\tJW _less\n\tLDA #1\n\tJMP _store\n_equal:\n\tLDA #0\n\tJMP _store\n_less:\n\tLDA #2\n_store:\n\tSTA &0x5000\n\tHLT\n#include <math16>\n",
+        set(R0, r0),
+        set(R1, r1),
+        routine
+    );
+    match run(&source, "").memory[0x5000] {
+        0 => std::cmp::Ordering::Equal,
+        1 => std::cmp::Ordering::Greater,
+        _ => std::cmp::Ordering::Less,
+    }
+}
+
+/// Runs the program and returns what it printed.
+fn output(body: &str, libraries: &[&str], input: &str) -> String {
+    let includes: String = libraries
+        .iter()
+        .map(|l| format!("#include <{}>\n", l))
+        .collect();
+    run(&format!("{}\tHLT\n{}", body, includes), input).tty_output()
+}
+
+#[test]
+fn test_libraries_are_formatted() {
+    for (name, source) in LIBRARIES {
+        assert_eq!(format_source(source), source, "{}", name);
+    }
+}
+
+#[test]
+fn test_arithmetic() {
+    assert_eq!(call("math16", "_add16", 0x12FF, 0x0101).0, 0x1400);
+    assert_eq!(call("math16", "_add16", 0xFFFF, 0x0002).0, 0x0001);
+    assert_eq!(call("math16", "_sub16", 0x1400, 0x0101).0, 0x12FF);
+    assert_eq!(call("math16", "_sub16", 0x0001, 0x0002).0, 0xFFFF);
+    assert_eq!(call("math16", "_inc16", 0x00FF, 0).0, 0x0100);
+    assert_eq!(call("math16", "_dec16", 0x0100, 0).0, 0x00FF);
+    assert_eq!(call("math16", "_neg16", 0x0001, 0).0, 0xFFFF);
+    assert_eq!(call("math16", "_neg16", 0x1234, 0).0, 0xEDCC);
+}
+
+#[test]
+fn test_comparisons() {
+    use std::cmp::Ordering::*;
+
+    let cases = [
+        (0x1234, 0x1234, Equal, Equal),
+        (0x1233, 0x1234, Less, Less),
+        (0x1300, 0x12FF, Greater, Greater),
+        (0xFFFF, 0x0001, Greater, Less),
+        (0x7FFF, 0x8000, Less, Greater),
+        (0x8000, 0x8001, Less, Less),
+    ];
+
+    for (a, b, unsigned, signed) in cases {
+        assert_eq!(compare("_cmp16", a, b), unsigned, "{:#X} {:#X}", a, b);
+        assert_eq!(compare("_cmps16", a, b), signed, "{:#X} {:#X}", a, b);
+    }
+}
+
+#[test]
+fn test_shifts() {
+    assert_eq!(call("shift16", "_shl16", 0x81C3, 0).0, 0x0386);
+    assert_eq!(call("shift16", "_shr16", 0x81C3, 0).0, 0x40E1);
+    assert_eq!(call("shift16", "_sar16", 0x81C3, 0).0, 0xC0E1);
+    assert_eq!(call("shift16", "_sar16", 0x4101, 0).0, 0x2080);
+}
+
+#[test]
+fn test_multiplication_and_division() {
+    assert_eq!(call("mul", "_mul16", 123, 45).0, 5535);
+    assert_eq!(call("mul", "_mul16", 0x1234, 0).0, 0);
+    assert_eq!(
+        call("mul", "_mul16", 300, 300).0,
+        (300u32 * 300 % 0x10000) as u16
+    );
+
+    assert_eq!(call("div", "_div16", 5535, 45), (123, 0));
+    assert_eq!(call("div", "_div16", 1000, 7), (142, 6));
+    assert_eq!(call("div", "_div16", 0xFFFF, 0x8001), (1, 0x7FFE));
+    assert_eq!(call("div", "_div16", 3, 10), (0, 3));
+    assert_eq!(call("div", "_div16", 1234, 0), (0xFFFF, 1234));
+}
+
+#[test]
+fn test_number_formatting() {
+    let mut body = String::new();
+    for (routine, value) in [
+        ("_print_u16", 0),
+        ("_print_u16", 65535),
+        ("_print_u16", 1007),
+        ("_print_s16", 0xFB2E),
+        ("_print_s16", 42),
+        ("_print_hex16", 0xBEEF),
+    ] {
+        body += &set(R0, value);
+        body += &format!("\tJSR {}\n\tJSR _print_newline\n", routine);
+    }
+
+    assert_eq!(
+        output(&body, &["number", "tty"], ""),
+        "0\n65535\n1007\n-1234\n42\nBEEF\n"
+    );
+}
+
+#[test]
+fn test_reading_and_printing_lines() {
+    // macros have to be defined before they are used
+    let body = format!(
+        "#include <macros>\n{}\tJSR _read_line\n\tMW *0xFEE2, &0xFEE6\n\tMW *0xFEE3, &0xFEE7\n\tJSR _print_string\n\tJSR _print_newline\n\tMW *0xFEE6, &0xFEE0\n\tMW *0xFEE7, &0xFEE1\n\tJSR _print_u16\n\tJSR _poll_key\n\tJNZ _end\n\tLDA #33\n\tJSR _print_char\n_end:\n",
+        set(R0, 0x5000),
+    );
+
+    // the length is printed, then '!' as no key is left
+    assert_eq!(
+        output(&body, &["keyboard", "tty", "number"], "hello\n"),
+        "hello\n5!"
+    );
+}
+
+#[test]
+fn test_memory_routines() {
+    let body = format!(
+        "{}{}{}\tJSR _memset\n\tLDA #7\n\tSTA &0x5002\n{}{}{}\tJSR _memcpy\n",
+        set(R0, 0x5000),
+        set(R1, 0xAA),
+        set(R2, 4),
+        set(R0, 0x5100),
+        set(R1, 0x5000),
+        set(R2, 5),
+    );
+    let machine = run(&format!("{}\tHLT\n#include <mem>\n", body), "");
+
+    assert_eq!(&machine.memory[0x5000..0x5005], &[0xAA, 0xAA, 7, 0xAA, 0]);
+    assert_eq!(
+        &machine.memory[0x5100..0x5106],
+        &[0xAA, 0xAA, 7, 0xAA, 0, 0]
+    );
+}
+
+#[test]
+fn test_macros() {
+    let machine = run("#include <macros>\n\tMW #5, &0x5000\n\tHLT\n", "");
+    assert_eq!(machine.memory[0x5000], 5);
+}
+
+#[test]
+fn test_libraries_are_included_once() {
+    // the included file and the program both use the library
+    let mut loader = MemoryLoader::new();
+    loader.add(
+        "util.asm",
+        "_double:\n\tJSR _shl16\n\tRTS\n#include <shift16>\n",
+    );
+
+    let source = format!(
+        "{}\tJSR _double\n\tJSR _shr16\n\tHLT\n#include <SHIFT16>\n#include \"util.asm\"\n",
+        set(R0, 0x0123)
+    );
+    let options = AssembleOptions {
+        file_name: String::from("main.asm"),
+        loader: Some(&loader),
+        ..Default::default()
+    };
+    assert!(assemble_str(&source, &options).is_ok());
+
+    let error = assemble_str("#include <trig>\n", &AssembleOptions::default()).unwrap_err();
+    assert!(
+        error.to_string().contains("there is no library 'trig'"),
+        "{}",
+        error
+    );
+}
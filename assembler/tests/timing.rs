@@ -0,0 +1,124 @@
+//! Derives the clock cycles of every instruction from the microcode and annotates listings with them.
+
+use tower_assembler::asm::listing::lister;
+use tower_assembler::isa::isa;
+use tower_assembler::microasm::asm::{parse_source, MicroassembleOptions};
+use tower_assembler::microasm::timing::{find_timing, format_cycles, timing, InstructionTiming};
+use tower_assembler::{IM_ABSOLUTE, IM_CONSTANT, IM_IMMEDIATE, IM_IMPLIED};
+
+const MICROCODE: &str = "
+#def HLT
+	HLT
+#def ADD
+imm:
+	PCO MO BI
+	PCI
+	OPADD ALUO AI IEND
+abs:
+	PCO MO ARHI
+	PCI
+	PCO MO ARLI
+	PCI
+	ARHLO MO BI
+	OPADD ALUO AI IEND
+#def JZ
+const:
+	#if zero
+		PCO MO ARHI
+		PCI
+		PCO MO ARLI
+		ARHLO PCJ
+	#else
+		PCI
+		PCI
+	#end
+	IEND
+";
+
+fn timing_table(source: &str) -> Vec<InstructionTiming> {
+    let instruction_defs = parse_source(source, &MicroassembleOptions::default())
+        .unwrap_or_else(|e| panic!("{}\n{}", e, source));
+    timing(&instruction_defs)
+}
+
+#[test]
+fn test_cycles_are_counted() {
+    let table = timing_table(MICROCODE);
+
+    // ordered by opcode and instruction mode
+    let rows: Vec<(&str, u32, usize, usize)> = table
+        .iter()
+        .map(|t| (t.name.as_str(), t.instruction_mode, t.min, t.max))
+        .collect();
+    assert_eq!(
+        rows,
+        vec![
+            ("add", IM_IMMEDIATE, 3, 3),
+            ("add", IM_ABSOLUTE, 6, 6),
+            ("jz", IM_CONSTANT, 3, 5),
+            ("hlt", IM_IMPLIED, 1, 1),
+        ]
+    );
+
+    // the prefix steps are included
+    let table = timing_table("#pref\n\tPCO MO INI\n\tPCI\n#def NOP\n\tIEND\n");
+    assert_eq!((table[0].min, table[0].max), (3, 3));
+
+    // without IEND the step counter runs through all of its steps
+    let table = timing_table("#def NOP\n\tPCI\n");
+    let steps = isa().max_micro_steps();
+    assert_eq!((table[0].min, table[0].max), (steps, steps));
+}
+
+#[test]
+fn test_timing_is_looked_up_and_formatted() {
+    let table = timing_table(MICROCODE);
+
+    let jz = find_timing(&table, "JZ", IM_CONSTANT).unwrap();
+    assert_eq!(jz.to_string(), "JZ    Constant     3-5");
+    let add = find_timing(&table, "add", IM_IMMEDIATE).unwrap();
+    assert_eq!(add.to_string(), "ADD   Immediate    3");
+    assert!(find_timing(&table, "ADD", IM_CONSTANT).is_none());
+    assert!(find_timing(&table, "SUB", IM_IMMEDIATE).is_none());
+
+    assert_eq!(format_cycles(4, 4), "4");
+    assert_eq!(format_cycles(4, 6), "4-6");
+}
+
+#[test]
+fn test_listing_shows_cycles() {
+    let dir = std::env::temp_dir();
+    let microcode = dir.join("tower_timing_microcode.asm");
+    let source = dir.join("tower_timing.asm");
+    let listing = dir.join("tower_timing.lst");
+    std::fs::write(&microcode, MICROCODE).unwrap();
+    std::fs::write(
+        &source,
+        "\tADD #1\n\tADD *0x5000\n_loop:\n\tJZ _loop\n\tSUB #1\n\tHLT\n",
+    )
+    .unwrap();
+
+    lister(
+        source.to_str().unwrap(),
+        listing.to_str().unwrap(),
+        Some(microcode.to_str().unwrap()),
+        false,
+    )
+    .unwrap();
+    let output = std::fs::read_to_string(&listing).unwrap();
+
+    let expected = [
+        "0000  21 01        1       3  ADD #0x01\n",
+        "0002  23 50 00     2       6  ADD *0x5000\n",
+        "                                  ; block: 9 cycles\n",
+        "0005  62 00 05     4     3-5  JZ &0x0005\n",
+        "                                  ; block: 3-5 cycles\n",
+        // SUB is not defined in the microcode
+        "0008  31 01        5          SUB #0x01\n",
+        "000a  e8           6       1  HLT\n",
+        "                                  ; block: 1 + unknown cycles\n",
+    ];
+    for line in expected {
+        assert!(output.contains(line), "{}\n{}", line, output);
+    }
+}
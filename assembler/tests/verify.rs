@@ -0,0 +1,128 @@
+//! Checks micro steps for bus contention and conflicting control signals.
+
+mod common;
+
+use tower_assembler::isa::isa;
+use tower_assembler::microasm::asm::{parse_source, MicroassembleOptions};
+use tower_assembler::microasm::verify::{
+    check_step, classify_signal, verifier, verify, Bus, Conflict, ConflictKind,
+};
+use tower_assembler::{IM_ABSOLUTE, IM_IMMEDIATE};
+
+fn conflicts(source: &str) -> Vec<Conflict> {
+    let instruction_defs = parse_source(source, &MicroassembleOptions::default())
+        .unwrap_or_else(|e| panic!("{}\n{}", e, source));
+    verify(&instruction_defs)
+}
+
+#[test]
+fn test_signals_are_classified() {
+    assert_eq!(classify_signal("PCO").drives, Some(Bus::Address));
+    assert_eq!(classify_signal("ALUO").drives, Some(Bus::Data));
+    assert_eq!(classify_signal("AI").receives, &[Bus::Data]);
+    assert_eq!(classify_signal("PCJ").receives, &[Bus::Address]);
+    assert!(classify_signal("OPSUB").is_alu_op);
+
+    // memory needs an address, whether it is read or written
+    let mo = classify_signal("MO");
+    assert_eq!(
+        (mo.drives, mo.receives),
+        (Some(Bus::Data), &[Bus::Address][..])
+    );
+    assert_eq!(classify_signal("MI").receives, &[Bus::Data, Bus::Address]);
+
+    // signals which do not touch a bus
+    assert_eq!(classify_signal("IEND"), Default::default());
+    assert_eq!(classify_signal("PCI"), Default::default());
+}
+
+#[test]
+fn test_conflicts_in_a_step() {
+    assert!(check_step(&["PCO", "MO", "INI"]).is_empty());
+    assert!(check_step(&["OPADD", "ALUO", "AI"]).is_empty());
+
+    assert_eq!(
+        check_step(&["PCO", "ARHLO", "MO", "AI"]),
+        vec![ConflictKind::BusContention(
+            Bus::Address,
+            vec!["PCO", "ARHLO"]
+        )]
+    );
+    assert_eq!(
+        check_step(&["AI", "BO", "ALUO"]),
+        vec![ConflictKind::BusContention(Bus::Data, vec!["BO", "ALUO"])]
+    );
+    assert_eq!(
+        check_step(&["AI", "BI"]),
+        vec![ConflictKind::UndrivenBus(Bus::Data, vec!["AI", "BI"])]
+    );
+    assert_eq!(
+        check_step(&["OPADD", "OPSUB", "ALUO", "AI"]),
+        vec![ConflictKind::AluOpConflict(vec!["OPADD", "OPSUB"])]
+    );
+
+    // _RAMSTART supplies the address, _SPSTART only works together with it
+    assert!(check_step(&["_RAMSTART", "MO", "AI"]).is_empty());
+    assert!(check_step(&["_RAMSTART", "_SPSTART", "MO", "AI"]).is_empty());
+    assert_eq!(
+        check_step(&["_SPSTART", "ARHLO", "MO", "AI"]),
+        vec![ConflictKind::SpStartWithoutRamStart]
+    );
+}
+
+#[test]
+fn test_conflicts_are_located() {
+    let found = conflicts(
+        "
+#pref
+	PCO MO INI
+	PCI
+#def ADD
+imm:
+	PCO MO BI
+	PCI ARHLO PCO
+	IEND
+abs:
+	#if zero
+		OPADD OPSUB ALUO AI
+	#end
+	IEND
+",
+    );
+
+    // the prefix steps are counted, every flag combination is checked
+    let contention: Vec<&Conflict> = found
+        .iter()
+        .filter(|c| c.instruction_mode == IM_IMMEDIATE)
+        .collect();
+    assert_eq!(contention.len(), isa().flag_combinations());
+    assert!(contention.iter().all(|c| c.instruction == "add"
+        && c.step == 3
+        && c.kind == ConflictKind::BusContention(Bus::Address, vec!["ARHLO", "PCO"])));
+
+    // only the flag combinations taking the branch
+    let alu: Vec<&Conflict> = found
+        .iter()
+        .filter(|c| c.instruction_mode == IM_ABSOLUTE)
+        .collect();
+    assert_eq!(alu.len(), isa().flag_combinations() / 2);
+    assert!(alu
+        .iter()
+        .all(|c| c.step == 2 && c.kind == ConflictKind::AluOpConflict(vec!["OPADD", "OPSUB"])));
+
+    assert!(
+        alu[0].to_string().starts_with("ADD (Absolute, flags: ")
+            && alu[0]
+                .to_string()
+                .ends_with(") step 2: conflicting ALU operations: OPADD, OPSUB"),
+        "{}",
+        alu[0]
+    );
+}
+
+#[test]
+fn test_microcode_has_no_conflicts() {
+    let path = common::root().join("software/microcode/microcode.asm");
+    let found = verifier(path.to_str().unwrap()).unwrap();
+    assert!(found.is_empty(), "{:?}", found);
+}
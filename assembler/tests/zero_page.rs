@@ -0,0 +1,126 @@
+//! Selects the zero page mode for operands in the zero page and checks the programs still behave the same.
+
+mod common;
+
+use tower_assembler::asm::asm::{assemble_str, AssembleOptions, AssembledProgram};
+use tower_assembler::asm::object::{assemble_object, RelocationKind};
+use tower_assembler::emu::Machine;
+
+const PROGRAM: &str = "
+#zp _count
+#var _total
+	LDA #3
+	STA _count
+	LDA #0
+	STA _total
+	JMP _loop
+_loop:
+	LDA *_total
+	ADD #2
+	STA _total
+	LDA *_count
+	SUB #1
+	STA _count
+	JNZ _loop
+	LDA *0x4000
+	ADD #1
+	ADD #0xff
+	JNW _end
+	LDA #0xaa
+	STA &0x5000
+_end:
+	LDA *_total
+	STA &0x5001
+	HLT
+";
+
+fn assemble(source: &str, zero_page: bool) -> AssembledProgram {
+    let options = AssembleOptions {
+        zero_page,
+        ..Default::default()
+    };
+    assemble_str(source, &options).unwrap_or_else(|e| panic!("{}\n{}", e, source))
+}
+
+fn run(bytes: &[u8]) -> Machine {
+    common::run(bytes, 100_000)
+}
+
+#[test]
+fn test_program_behaves_the_same() {
+    let absolute = assemble(PROGRAM, false);
+    let zero_page = assemble(PROGRAM, true);
+
+    // `LDA *_count` and `LDA *0x4000`, `_total` lies outside of the zero page
+    assert_eq!(absolute.zero_page_saved, 0);
+    assert_eq!(zero_page.zero_page_saved, 2);
+    assert_eq!(zero_page.bytes.len() + 2, absolute.bytes.len());
+
+    // the labels after the shorter instructions moved
+    let end = |program: &AssembledProgram| {
+        program
+            .symbols
+            .iter()
+            .find(|l| l.name == "_end")
+            .unwrap()
+            .address
+    };
+    assert_eq!(end(&zero_page) + 2, end(&absolute));
+
+    let (absolute, zero_page) = (run(&absolute.bytes), run(&zero_page.bytes));
+    assert_eq!(&absolute.memory[0x5000..0x5002], &[0xaa, 6]);
+    assert_eq!(
+        &zero_page.memory[0x5000..0x5002],
+        &absolute.memory[0x5000..0x5002]
+    );
+}
+
+#[test]
+fn test_only_instructions_with_both_modes_change() {
+    let source = "
+	LDA *0x4005
+	INC *0x4005
+	STA &0x4005
+	LDA *0x40ff
+	LDA *0x4100
+	CMP *0x3fff
+	HLT
+";
+    let program = assemble(source, true);
+    let sizes: Vec<u32> = program.source_map.iter().map(|e| e.size).collect();
+    assert_eq!(sizes, vec![2, 3, 3, 2, 3, 3, 1]);
+    assert_eq!(program.zero_page_saved, 2);
+    assert_eq!(program.bytes[1], 0x05);
+    assert_eq!(program.bytes[9], 0xff);
+}
+
+#[test]
+fn test_object_files_use_the_low_byte() {
+    let source = "
+#import _other
+#zp _local
+	LDA *_local
+	LDA *_other
+	HLT
+";
+    let options = AssembleOptions {
+        zero_page: true,
+        ..Default::default()
+    };
+    let obj = assemble_object(source, &options).unwrap();
+
+    // the address of an imported label is not known before linking
+    let kinds: Vec<(&str, RelocationKind)> = obj
+        .relocations
+        .iter()
+        .map(|r| (r.symbol.as_str(), r.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            ("_local", RelocationKind::Low8),
+            ("_other", RelocationKind::Abs16)
+        ]
+    );
+    assert_eq!(obj.code.len(), 2 + 3 + 1);
+}
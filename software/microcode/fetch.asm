@@ -0,0 +1,45 @@
+; Fetch the instruction.
+#pref
+	PCO MO INI
+	PCI
+
+; Reset step counter after every instruction ends.
+#suf
+	IEND
+
+
+; Fetches the next word from memory and stores it in the register.
+#macro FETCH_TO reg
+	PCO MO reg
+	PCI
+
+#macro FETCH_LOW
+	FETCH_TO ARLI
+
+#macro FETCH_HIGH
+	FETCH_TO ARHI
+
+
+; Fetches next two words from memory and stores them in ARH and ARL respectively.
+#macro FETCH_ARGS
+	FETCH_HIGH
+	FETCH_LOW
+
+
+; Fetches two words starting at the address specified at the next two locations in memory and stores them in H and L respectively.
+#macro FETCH_INDIRECTLY
+	FETCH_ARGS
+
+	ARHLO MO HI
+
+	ARLO INCI
+	INCE
+	INCO ARLI
+
+	#if incwrap
+		ARHO INCI
+		INCE
+		INCO ARHI
+	#end
+
+	ARHLO MO LI
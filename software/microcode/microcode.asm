@@ -1,45 +1,6 @@
-; Fetch the instruction.
-#pref
-	PCO MO INI
-	PCI
-
-; Reset step counter after every instruction ends.
-#suf
-	IEND
-
-
-#macro FETCH_LOW
-	PCO MO ARLI
-	PCI
-
-#macro FETCH_HIGH
-	PCO MO ARHI
-	PCI
-
-
-; Fetches next two words from memory and stores them in ARH and ARL respectively.
-#macro FETCH_ARGS
-	FETCH_HIGH
-	FETCH_LOW
-
-
-; Fetches two words starting at the address specified at the next two locations in memory and stores them in H and L respectively.
-#macro FETCH_INDIRECTLY
-	FETCH_ARGS
-
-	ARHLO MO HI
-
-	ARLO INCI
-	INCE
-	INCO ARLI
-
-	#if incwrap 
-		ARHO INCI
-		INCE
-		INCO ARHI
-	#end
-
-	ARHLO MO LI
+; The fetch cycle and the macros shared by the instructions.
+#include fetch.asm
+#include operands.asm
 
 
 ; ========== NOP ==========
@@ -50,20 +11,13 @@
 ; ========== LDA ==========
 #def LDA
 imm:
-	PCO MO AI
-	PCI
+	FETCH_TO AI
 abs:
-	FETCH_ARGS
-
-	ARHLO MO AI
+	LOAD_ABS AI
 zpage:
-	FETCH_LOW
-
-	_RAMSTART ARHLO MO AI
+	LOAD_ZPAGE AI
 ind:
-	FETCH_INDIRECTLY
-
-	HLO MO AI
+	LOAD_IND AI
 
 ; ========== STA ==========
 #def STA
@@ -87,61 +41,46 @@ ind:
 #def ADC
 imm:
 	; get value from memory and store in B
-	PCO MO BI
-	PCI
+	FETCH_TO BI
 
 	OPADD ALUFI ALUO AI
 	IEND
 abs:
-	FETCH_ARGS
-
-	ARHLO MO BI
+	LOAD_ABS BI
 
 	OPADD ALUFI ALUO AI
 	IEND
 zpage:
-	FETCH_LOW
-
-	_RAMSTART ARHLO MO BI
+	LOAD_ZPAGE BI
 
 	OPADD ALUFI ALUO AI
 	IEND
 ind:
-	FETCH_INDIRECTLY
-
-	HLO MO BI
+	LOAD_IND BI
 	OPADD ALUFI ALUO AI
 
 
-
 ; ========== ADD ==========
 #def ADD
 imm:
 	; get value from memory and store in B
-	PCO MO BI
-	PCI
+	FETCH_TO BI
 
-	OPADD ALUO AI
+	ALU_OP OPADD
 	IEND
 abs:
-	FETCH_ARGS
-
-	ARHLO MO BI
+	LOAD_ABS BI
 
-	OPADD ALUO AI
+	ALU_OP OPADD
 	IEND
 zpage:
-	FETCH_LOW
-
-	_RAMSTART ARHLO MO BI
+	LOAD_ZPAGE BI
 
-	OPADD ALUO AI
+	ALU_OP OPADD
 	IEND
 ind:
-	FETCH_INDIRECTLY
-
-	HLO MO BI
-	OPADD ALUO AI
+	LOAD_IND BI
+	ALU_OP OPADD
 
 #suf
 	IEND
@@ -154,62 +93,46 @@ ind:
 #def SBB
 imm:
 	; get value from memory and store in B
-	PCO MO BI
-	PCI
+	FETCH_TO BI
 
 	OPSUB ALUFI ALUO AI
 	IEND
 abs:
-	FETCH_ARGS
-
-	ARHLO MO BI
+	LOAD_ABS BI
 
 	OPSUB ALUFI ALUO AI
 	IEND
 zpage:
-	FETCH_LOW
-
-	_RAMSTART ARHLO MO BI
+	LOAD_ZPAGE BI
 
 	OPSUB ALUFI ALUO AI
 	IEND
 ind:
-	FETCH_INDIRECTLY
-
-	HLO MO BI
+	LOAD_IND BI
 	OPSUB ALUFI ALUO AI
 
 
-
-
 ; ========== SUB ==========
 #def SUB
 imm:
 	; get value from memory and store in B
-	PCO MO BI
-	PCI
+	FETCH_TO BI
 
-	OPSUB ALUO AI
+	ALU_OP OPSUB
 	IEND
 abs:
-	FETCH_ARGS
-
-	ARHLO MO BI
+	LOAD_ABS BI
 
-	OPSUB ALUO AI
+	ALU_OP OPSUB
 	IEND
 zpage:
-	FETCH_LOW
+	LOAD_ZPAGE BI
 
-	_RAMSTART ARHLO MO BI
-
-	OPSUB ALUO AI
+	ALU_OP OPSUB
 	IEND
 ind:
-	FETCH_INDIRECTLY
-
-	HLO MO BI
-	OPSUB ALUO AI
+	LOAD_IND BI
+	ALU_OP OPSUB
 
 #suf
 	IEND
@@ -220,11 +143,11 @@ abs:
 	FETCH_ARGS
 
 	ARHLO MO INCI
-	INCE 
+	INCE
 	ARHLO INCO MI
 accumulator:
 	ALUO INCI
-	INCE 
+	INCE
 	INCO AI
 
 ; ========== DEC ==========
@@ -233,7 +156,7 @@ abs:
 	FETCH_ARGS
 
 	ARHLO MO INCI
-	DEC INCE 
+	DEC INCE
 	ARHLO INCO MI
 accumulator:
 	ALUO INCI
@@ -247,26 +170,19 @@ accumulator:
 #def CMP
 imm:
 	; get value from memory and store in B
-	PCO MO BI
-	PCI
+	FETCH_TO BI
 
 	OPSUB
 abs:
-	FETCH_ARGS
-
-	ARHLO MO BI
+	LOAD_ABS BI
 
 	OPSUB
 zpage:
-	FETCH_LOW
-
-	_RAMSTART ARHLO MO BI
+	LOAD_ZPAGE BI
 
 	OPSUB
 ind:
-	FETCH_INDIRECTLY
-
-	HLO MO BI
+	LOAD_IND BI
 	OPSUB
 
 #suf
@@ -333,7 +249,7 @@ const:
 		PCI
 	#end
 ind:
-	#if !zero 
+	#if !zero
 		FETCH_INDIRECTLY
 		HLO PCJ
 	#else
@@ -352,27 +268,20 @@ accumulator:
 #def NAND
 imm:
 	; get value from memory and store in B
-	PCO MO BI
-	PCI
+	FETCH_TO BI
 
-	OPNAND ALUO AI
+	ALU_OP OPNAND
 abs:
-	FETCH_ARGS
-
-	ARHLO MO BI
+	LOAD_ABS BI
 
-	OPNAND ALUO AI
+	ALU_OP OPNAND
 zpage:
-	FETCH_LOW
-
-	_RAMSTART ARHLO MO BI
+	LOAD_ZPAGE BI
 
-	OPNAND ALUO AI
+	ALU_OP OPNAND
 ind:
-	FETCH_INDIRECTLY
-
-	HLO MO BI
-	OPNAND ALUO AI
+	LOAD_IND BI
+	ALU_OP OPNAND
 #suf
 	IEND
 
@@ -385,18 +294,18 @@ accumulator:
 #def SL
 accumulator:
 	ALUO BI
-	OPADD ALUO AI
+	ALU_OP OPADD
 
 
 ; remove the suffix to save a step
 ;#suf
-; nothing 
+; nothing
 
 ; ========== JSR ==========
 #def JSR
-const:	
+const:
 	FETCH_ARGS
-	
+
 	; save current PC value
 	PCO HLI
 
@@ -409,13 +318,13 @@ const:
 
 	; store high byte on stack
 	_RAMSTART _SPSTART SPOA HO MI
-	
+
 	SPO INCI
 	INCE INCO SPI
 
 	; store low byte on stack
 	_RAMSTART _SPSTART SPOA LO MI
-	
+
 	SPO INCI
 	INCE INCO SPI
 
@@ -424,72 +333,72 @@ const:
 
 ; ========== RTS ==========
 #def RTS
-imp:	
+imp:
 	SPO INCI
 	DEC INCE INCO SPI
-	
+
 	_RAMSTART _SPSTART SPOA MO LI
-	
+
 	SPO INCI
 	DEC INCE INCO SPI
-	
+
 	_RAMSTART _SPSTART SPOA MO HI
-	
+
 	HLO PCJ
 
 ; ========== PSA ==========
 #def PSA
-imp:	
+imp:
 	_RAMSTART _SPSTART SPOA ALUO MI
-	
+
 	SPO INCI
 	INCE INCO SPI
 
 ; ========== PSF ==========
 #def PSF
-imp:	
+imp:
 	_RAMSTART _SPSTART SPOA FO MI
-	
+
 	SPO INCI
 	INCE INCO SPI
 
 ; ========== POA ==========
 #def POA
-imp:	
+imp:
 	SPO INCI
 	DEC INCE INCO SPI
 
 	_RAMSTART _SPSTART SPOA MO AI
-	
+
 
 ; ========== POF ==========
 #def POF
-imp:	
+imp:
 	SPO INCI
 	DEC INCE INCO SPI
 
 	_RAMSTART _SPSTART SPOA MO FI
-	
+
 ; ========== TBA ==========
-#def TBA 
-imp:	
+#def TBA
+imp:
 	BO AI
 
 ; ========== TAB ==========
-#def TAB 
-imp:	
+#def TAB
+imp:
 	ALUO BI
 
 ; ========== TFA ==========
-#def TFA 
-imp:	
+#def TFA
+imp:
 	FO AI
 
 ; ========== TAF ==========
-#def TAF 
-imp:	
+#def TAF
+imp:
 	ALUO FI
 
 ; ========== HLT ==========
 #def HLT
-HLT
\ No newline at end of file
+	HLT
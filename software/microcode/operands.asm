@@ -0,0 +1,20 @@
+; Loads the argument of the instruction into the register, one macro for every instruction mode.
+#macro LOAD_ABS reg
+	FETCH_ARGS
+
+	ARHLO MO reg
+
+#macro LOAD_ZPAGE reg
+	FETCH_LOW
+
+	_RAMSTART ARHLO MO reg
+
+#macro LOAD_IND reg
+	FETCH_INDIRECTLY
+
+	HLO MO reg
+
+
+; Runs the ALU operation on A and B and stores the result in A.
+#macro ALU_OP op
+	op ALUO AI
@@ -3,8 +3,8 @@
 
 
 #macro MW
-    LDA $1
-    STA $2
+	LDA $1
+	STA $2
 #end
 
 
@@ -22,34 +22,34 @@ JSR _compute
 HLT
 
 _compute:
-    ; compute next number
-    LDA *0xFD02
-    ADD *0xFD03
-
-    JW _return
-    JMP _continue
-
-    _return:
-        ; final number reached
-        RTS
-    _continue:
-        ; update arguments
-        PSA
-        LDA *0xFD03
-        STA &0xFD02
-        POA
-        STA &0xFD03
-
-        ; store result
-        STA @0xFD00
-        
-        ; increment page offset
-        LDA *0xFD01
-        ADD #1
-        STA &0xFD01
-
-        ; recurse
-        JSR _compute
-    
-    ; return up the stack
-    RTS
\ No newline at end of file
+	; compute next number
+	LDA *0xFD02
+	ADD *0xFD03
+
+	JW _return
+	JMP _continue
+
+	_return:
+		; final number reached
+		RTS
+	_continue:
+		; update arguments
+		PSA
+		LDA *0xFD03
+		STA &0xFD02
+		POA
+		STA &0xFD03
+
+		; store result
+		STA @0xFD00
+
+		; increment page offset
+		LDA *0xFD01
+		ADD #1
+		STA &0xFD01
+
+		; recurse
+		JSR _compute
+
+	; return up the stack
+		RTS
@@ -11,4 +11,4 @@ _loop:
 _break:
 
 _hlt:
-	hlt
\ No newline at end of file
+	HLT
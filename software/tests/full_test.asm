@@ -1,4 +1,3 @@
-
 ; Full test for the Tower architecture.
 ; Tests every instruction in every instruction mode.
 ; Error code will be stored at 0xFEFF.
@@ -11,68 +10,67 @@
 #end
 
 ; ========== TESTS ==========
-	
-	
-
-	MW #0xFF, &0xFE32
-
-	; test 1 - ADDING, INC, DEC, indirect access
-	INC *0xFE00
-	LDA *0xFE00
-	CMP #1
-	JNZ _failed
-	DEC *0xFE00
-	LDA *0xFE00
-	CMP #0
-	JNZ _failed
 
-	LDA #50
-	STA &0xFE01
-	LDA *0xFE01
-	DEC A
-	DEC A
-	ADD #5
-	INC A
-	ADD #200
-	STA &0xFE00
-	LDA @0xFE00
-	SUB #50
-	CMP #205
-	;LDA #01 
-	JNZ _failed
-	
-
-	; test 2 - NAND and SR
-	LDA #0
-	NOT A
-	SR A
-	NAND #0b00000001
-	CMP #254
-	LDA #02
-	JNZ _failed
 
-
-	; test 3 - flags
-	LDA #0
-	TAF
-	LDA #03
-	JW _failed
-	LDA #255
-	ADD #10
-	JW _continue1
-	LDA #04
-	JMP _failed
-	_continue1:
+MW #0xFF, &0xFE32
+
+; test 1 - ADDING, INC, DEC, indirect access
+INC *0xFE00
+LDA *0xFE00
+CMP #1
+JNZ _failed
+DEC *0xFE00
+LDA *0xFE00
+CMP #0
+JNZ _failed
+
+LDA #50
+STA &0xFE01
+LDA *0xFE01
+DEC %A
+DEC %A
+ADD #5
+INC %A
+ADD #200
+STA &0xFE00
+LDA @0xFE00
+SUB #50
+CMP #205
+;LDA #01
+JNZ _failed
+
+
+; test 2 - NAND and SR
+LDA #0
+NOT %A
+SR %A
+NAND #0b00000001
+CMP #254
+LDA #02
+JNZ _failed
+
+
+; test 3 - flags
+LDA #0
+TAF
+LDA #03
+JW _failed
+LDA #255
+ADD #10
+JW _continue1
+LDA #04
+JMP _failed
+_continue1:
 	ADC #10
 	SUB #20
 	JZ _continue2
 	LDA #05
 	JMP _failed
-	
-	_continue2:
 
-	; test 4 - stack
-	; test pushing/poping
+_continue2:
+
+; test 4 - stack
+; test pushing/poping
 	LDA #20
 	PSA
 	LDA #200
@@ -92,24 +90,24 @@
 	JNZ _failed
 
 
-	; test recursion
+; test recursion
 	LDA #10
 	JSR recursive_function
 	JMP _continue3
 
-	recursive_function:
-		ADD #5
-		JW _break
-		JSR recursive_function
+recursive_function:
+	ADD #5
+	JW _break
+	JSR recursive_function
+	RTS
+	_break:
 		RTS
-		_break:
-			RTS
 
-	_continue3:
-	
+_continue3:
+
 	LDA #123
 	HLT
 
 _failed:
 	STA &0xFEFF
-	hlt
+	HLT
@@ -5,14 +5,14 @@
 
 MW #0xFF, &0x4000
 
-lda #0
+LDA #0
 _loop:
-	jw _stop
-	add #1
-	sta &0x4001
+	JW _stop
+	ADD #1
+	STA &0x4001
 
-	sta @0x4000
-	jmp _loop
+	STA @0x4000
+	JMP _loop
 
 _stop:
-	hlt
\ No newline at end of file
+	HLT
@@ -15,4 +15,4 @@ _char_loop:
 	CMP #127
 	JNZ _char_loop
 
-HLT
\ No newline at end of file
+	HLT
@@ -1,10 +1,10 @@
-INC &0xFE00
+INC *0xFE00
 JZ _end
-; DEC &0xFE00
-; JNZ _end 
+; DEC *0xFE00
+; JNZ _end
 
 LDA #123
 HLT
 
 _end:
-	LDA #111
\ No newline at end of file
+	LDA #111